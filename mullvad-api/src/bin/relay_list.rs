@@ -1,23 +1,31 @@
 //! Fetches and prints the full relay list in JSON.
 //! Used by the installer artifact packer to bundle the latest available
 //! relay list at the time of creating the installer.
+//!
+//! With `--signed`, the relay list is printed as it was served by the API along with its
+//! signature. Such a bundle can be loaded with `mullvad relay import` when the API is unreachable.
 
-use mullvad_api::{proxy::ApiConnectionMode, rest::Error as RestError, RelayListProxy};
+use mullvad_api::{
+    proxy::ApiConnectionMode, rest::Error as RestError, RelayListProxy, RelayListSigningKeys,
+};
 use std::process;
 use talpid_types::ErrorExt;
 
 #[tokio::main]
 async fn main() {
+    let signed = std::env::args().skip(1).any(|arg| arg == "--signed");
+
     let runtime = mullvad_api::Runtime::new(tokio::runtime::Handle::current())
         .expect("Failed to load runtime");
 
     let relay_list_request =
         RelayListProxy::new(runtime.mullvad_rest_handle(ApiConnectionMode::Direct.into_provider()))
-            .relay_list(None)
+            .signed_relay_list(None)
             .await;
 
-    let relay_list = match relay_list_request {
-        Ok(relay_list) => relay_list,
+    let signed_relay_list = match relay_list_request {
+        Ok(Some(relay_list)) => relay_list,
+        Ok(None) => unreachable!("no etag was given"),
         Err(RestError::TimeoutError) => {
             eprintln!("Request timed out");
             process::exit(2);
@@ -34,5 +42,28 @@ async fn main() {
             process::exit(1);
         }
     };
+
+    if signed {
+        if signed_relay_list.signature.is_none() {
+            eprintln!("The relay list is not signed");
+            process::exit(4);
+        }
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&signed_relay_list).unwrap()
+        );
+        return;
+    }
+
+    let relay_list = match signed_relay_list.verify(&RelayListSigningKeys::pinned()) {
+        Ok(relay_list) => relay_list,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                e.display_chain_with_msg("Failed to verify relay list")
+            );
+            process::exit(3);
+        }
+    };
     println!("{}", serde_json::to_string_pretty(&relay_list).unwrap());
 }
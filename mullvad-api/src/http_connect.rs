@@ -0,0 +1,226 @@
+//! Tunnel a stream through an HTTP proxy using the `CONNECT` method, optionally over TLS to the
+//! proxy itself.
+use crate::tls_stream;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+    net::SocketAddr,
+};
+use talpid_types::net::proxy::{HttpAuth, HttpConnect};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_rustls::{rustls::pki_types::ServerName, TlsConnector};
+
+/// Maximum size of the response headers sent by the proxy.
+const MAX_RESPONSE_SIZE: usize = 8 * 1024;
+
+/// Stream returned by [`connect`]. It is either the original stream or a TLS stream wrapping it.
+pub trait ProxyStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ProxyStream for T {}
+
+/// Ask the proxy reachable over `stream` to open a tunnel to `target`. If the proxy uses TLS,
+/// the handshake with the proxy is done first.
+pub async fn connect<S>(
+    config: &HttpConnect,
+    stream: S,
+    target: SocketAddr,
+) -> io::Result<Box<dyn ProxyStream>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    match config.tls_server_name {
+        Some(ref server_name) => {
+            let stream = connect_tls(stream, server_name).await?;
+            Ok(Box::new(
+                handshake(stream, target, config.auth.as_ref()).await?,
+            ))
+        }
+        None => Ok(Box::new(
+            handshake(stream, target, config.auth.as_ref()).await?,
+        )),
+    }
+}
+
+async fn connect_tls<S>(
+    stream: S,
+    server_name: &str,
+) -> io::Result<tokio_rustls::client::TlsStream<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let server_name = ServerName::try_from(server_name.to_owned()).map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("invalid proxy hostname \"{server_name}\""),
+        )
+    })?;
+    TlsConnector::from(tls_stream::public_tls_config())
+        .connect(server_name, stream)
+        .await
+}
+
+/// Ask the HTTP server at the other end of `stream` to open a tunnel to `authority`, e.g.
+/// `api.mullvad.net:443`. Unlike [`connect`], the target is named rather than given as an address,
+/// so that a server that routes on the name, such as a CDN edge, can forward it.
+pub async fn tunnel<S>(stream: S, authority: &str) -> io::Result<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    handshake(stream, authority, None).await
+}
+
+/// Send a `CONNECT` request and wait for a successful response. On success, the stream is
+/// connected to `target`.
+async fn handshake<S>(
+    mut stream: S,
+    target: impl fmt::Display,
+    auth: Option<&HttpAuth>,
+) -> io::Result<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream
+        .write_all(connect_request(target, auth).as_bytes())
+        .await?;
+    stream.flush().await?;
+
+    let response = read_response_head(&mut stream).await?;
+    let status = parse_status(&response)?;
+    if !(200..300).contains(&status) {
+        return Err(io::Error::new(
+            ErrorKind::ConnectionRefused,
+            format!("HTTP proxy refused the connection with status {status}"),
+        ));
+    }
+    Ok(stream)
+}
+
+fn connect_request(target: impl fmt::Display, auth: Option<&HttpAuth>) -> String {
+    let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+    if let Some(auth) = auth {
+        let credentials =
+            BASE64_STANDARD.encode(format!("{}:{}", auth.username(), auth.password()));
+        request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+    }
+    request.push_str("\r\n");
+    request
+}
+
+/// Read the response up to and including the empty line that ends the headers. This reads one
+/// byte at a time, so that no data sent by the target after the headers is consumed.
+async fn read_response_head<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<String> {
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_RESPONSE_SIZE {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "HTTP proxy response is too large",
+            ));
+        }
+        response.push(stream.read_u8().await?);
+    }
+    String::from_utf8(response)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid HTTP proxy response"))
+}
+
+/// Parse the status code from a status line like `HTTP/1.1 200 Connection established`.
+fn parse_status(response: &str) -> io::Result<u16> {
+    let status_line = response.lines().next().unwrap_or_default();
+    let mut parts = status_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/1.") => status
+            .parse()
+            .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid HTTP proxy status")),
+        _ => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Invalid HTTP proxy response",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::{
+        io::{AsyncBufReadExt, BufReader},
+        net::{TcpListener, TcpStream},
+    };
+
+    /// Accept a single connection, expect a `CONNECT` request with the given credentials and
+    /// echo everything sent after a successful handshake.
+    async fn spawn_proxy(expected_auth: Option<&'static str>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut authorization = None;
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.strip_prefix("Proxy-Authorization: Basic ") {
+                    authorization = Some(value.trim_end().to_owned());
+                }
+            }
+            let expected = expected_auth.map(|auth| BASE64_STANDARD.encode(auth));
+            if authorization != expected {
+                stream
+                    .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                    .await
+                    .unwrap();
+                return;
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .await
+                .unwrap();
+            let (mut reader, mut writer) = tokio::io::split(stream);
+            let _ = tokio::io::copy(&mut reader, &mut writer).await;
+        });
+        addr
+    }
+
+    fn target() -> SocketAddr {
+        "192.0.2.1:443".parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_auth() {
+        let proxy = spawn_proxy(Some("user:pass")).await;
+        let auth = HttpAuth::new("user".to_owned(), "pass".to_owned()).unwrap();
+        let config = HttpConnect::new(proxy, None, Some(auth));
+
+        let stream = TcpStream::connect(proxy).await.unwrap();
+        let mut stream = connect(&config, stream, target()).await.unwrap();
+
+        stream.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejected() {
+        let proxy = spawn_proxy(Some("user:pass")).await;
+        let config = HttpConnect::new(proxy, None, None);
+
+        let stream = TcpStream::connect(proxy).await.unwrap();
+        let error = connect(&config, stream, target()).await.err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(
+            parse_status("HTTP/1.1 200 Connection established\r\n\r\n").unwrap(),
+            200
+        );
+        assert_eq!(parse_status("HTTP/1.0 407 Denied\r\n\r\n").unwrap(), 407);
+        assert_eq!(parse_status("HTTP/1.1 200\r\n\r\n").unwrap(), 200);
+        assert!(parse_status("SSH-2.0-OpenSSH\r\n\r\n").is_err());
+    }
+}
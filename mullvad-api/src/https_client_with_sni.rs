@@ -1,6 +1,7 @@
 use crate::{
     abortable_stream::{AbortableStream, AbortableStreamHandle},
-    proxy::{ApiConnection, ApiConnectionMode, ProxyConfig},
+    http_connect,
+    proxy::{ApiConnection, ApiConnectionMode, DomainFront, ProxyConfig},
     tls_stream::TlsStream,
     AddressCache,
 };
@@ -81,9 +82,17 @@ enum InnerConnectionMode {
     Shadowsocks(ShadowsocksConfig),
     /// Connect to the destination via a Socks proxy.
     Socks5(SocksConfig),
+    /// Connect to the destination via an HTTP proxy, using the `CONNECT` method.
+    HttpConnect(proxy::HttpConnect),
     /// Connect to the destination via Mullvad Encrypted DNS proxy.
     /// See [`mullvad-encrypted-dns-proxy`] for how the proxy works.
     EncryptedDnsProxy(EncryptedDNSConfig),
+    /// Connect to a CDN edge, using a different TLS server name than the API hostname, and
+    /// tunnel to the API through it.
+    DomainFronting(DomainFront),
+    /// Connect to the destination through the Tor network.
+    #[cfg(feature = "tor")]
+    Tor(crate::proxy::TorConfig),
 }
 
 impl InnerConnectionMode {
@@ -159,6 +168,20 @@ impl InnerConnectionMode {
                 )
                 .await
             }
+            // Set up a tunnel through an HTTP proxy.
+            InnerConnectionMode::HttpConnect(http) => {
+                let first_hop = http.endpoint;
+                let make_proxy_stream =
+                    |tcp_stream| async { http_connect::connect(&http, tcp_stream, *addr).await };
+                Self::connect_proxied(
+                    first_hop,
+                    hostname,
+                    make_proxy_stream,
+                    #[cfg(target_os = "android")]
+                    socket_bypass_tx,
+                )
+                .await
+            }
             InnerConnectionMode::EncryptedDnsProxy(proxy_config) => {
                 let first_hop = SocketAddr::V4(proxy_config.addr);
                 let make_proxy_stream = |tcp_stream| async {
@@ -173,6 +196,39 @@ impl InnerConnectionMode {
                 )
                 .await
             }
+            // Set up a TLS connection to the CDN and ask it to open a tunnel to the API host. The
+            // front's certificate must chain to the same pinned roots as the API's. The TLS
+            // connection to the API itself is set up inside the tunnel, so that the CDN can
+            // neither read nor modify the traffic.
+            InnerConnectionMode::DomainFronting(front) => {
+                let first_hop = front.endpoint;
+                let authority = format!("{hostname}:{}", addr.port());
+                let make_proxy_stream = move |tcp_stream| async move {
+                    let stream = TlsStream::connect_https(tcp_stream, &front.front).await?;
+                    http_connect::tunnel(stream, &authority).await
+                };
+                Self::connect_proxied(
+                    first_hop,
+                    hostname,
+                    make_proxy_stream,
+                    #[cfg(target_os = "android")]
+                    socket_bypass_tx,
+                )
+                .await
+            }
+            // Set up a stream through the Tor network. The API certificate is verified as usual.
+            #[cfg(feature = "tor")]
+            InnerConnectionMode::Tor(config) => {
+                let stream = crate::tor::connect(&config, hostname, addr.port()).await?;
+
+                #[cfg(feature = "api-override")]
+                if API.disable_tls {
+                    return Ok(ApiConnection::new(Box::new(ConnectionDecorator(stream))));
+                }
+
+                let tls_stream = TlsStream::connect_https(stream, hostname).await?;
+                Ok(ApiConnection::new(Box::new(tls_stream)))
+            }
         }
     }
 
@@ -247,6 +303,9 @@ struct SocksConfig {
 enum ProxyConfigError {
     #[error("Unrecognized cipher selected: {0}")]
     InvalidCipher(String),
+    #[cfg(not(feature = "tor"))]
+    #[error("Tor support was not enabled at build time")]
+    TorUnsupported,
 }
 
 impl TryFrom<ApiConnectionMode> for InnerConnectionMode {
@@ -276,9 +335,15 @@ impl TryFrom<ApiConnectionMode> for InnerConnectionMode {
                     peer: config.endpoint,
                     authentication: config.auth,
                 }),
+                ProxyConfig::HttpConnect(config) => InnerConnectionMode::HttpConnect(config),
                 ProxyConfig::EncryptedDnsProxy(config) => {
                     InnerConnectionMode::EncryptedDnsProxy(config)
                 }
+                ProxyConfig::DomainFronting(front) => InnerConnectionMode::DomainFronting(front),
+                #[cfg(feature = "tor")]
+                ProxyConfig::Tor(config) => InnerConnectionMode::Tor(config),
+                #[cfg(not(feature = "tor"))]
+                ProxyConfig::Tor(_) => return Err(ProxyConfigError::TorUnsupported),
             },
         })
     }
@@ -502,3 +567,187 @@ impl Service<Uri> for HttpsConnectorWithSni {
         Box::pin(fut)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::API;
+    use http_body_util::Empty;
+    use hyper::body::Bytes;
+    use hyper_util::{client::legacy::Client, rt::TokioExecutor};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+    use tokio_rustls::{
+        rustls::{self, ServerConfig},
+        TlsAcceptor,
+    };
+
+    /// Front with a certificate that chains to the test root that the API is pinned to in tests.
+    /// The certificate is valid for the API host as well, so the API is served with it too.
+    const PINNED_FRONT: &str = "localhost";
+    const PINNED_FRONT_CERT: &[u8] = include_bytes!("../test-data/localhost.pem");
+    const PINNED_FRONT_KEY: &[u8] = include_bytes!("../test-data/localhost.key");
+
+    /// Front with a certificate from a CA that the API is not pinned to.
+    const UNPINNED_FRONT: &str = "front.example.com";
+    const UNPINNED_FRONT_CERT: &[u8] = include_bytes!("../test-data/front.example.com.pem");
+    const UNPINNED_FRONT_KEY: &[u8] = include_bytes!("../test-data/front.example.com.key");
+
+    /// What a front observed while tunneling a request to the API.
+    #[derive(Debug)]
+    struct FrontedRequest {
+        /// Server name of the TLS connection to the front.
+        front_sni: Option<String>,
+        /// Target of the `CONNECT` request.
+        tunnel_target: String,
+        /// Server name of the TLS connection inside the tunnel.
+        api_sni: Option<String>,
+        /// `Host` header of the request sent inside the tunnel.
+        api_host: Option<String>,
+    }
+
+    fn tls_acceptor(cert: &[u8], key: &[u8]) -> TlsAcceptor {
+        let certs = rustls_pemfile::certs(&mut &cert[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = rustls_pemfile::private_key(&mut &key[..]).unwrap().unwrap();
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .unwrap();
+        TlsAcceptor::from(Arc::new(config))
+    }
+
+    /// Read request headers up to the empty line. Returns the request line and the `Host` header.
+    async fn read_request_head(
+        stream: &mut (impl AsyncBufReadExt + Unpin),
+    ) -> (String, Option<String>) {
+        let mut request_line = String::new();
+        stream.read_line(&mut request_line).await.unwrap();
+        let mut host = None;
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            let (name, value) = line.split_once(':').unwrap_or((&line, ""));
+            if name.eq_ignore_ascii_case("host") {
+                host = Some(value.trim().to_owned());
+            }
+        }
+        (request_line, host)
+    }
+
+    /// Accept a single TLS connection using the given certificate, open the requested tunnel and
+    /// serve a request inside it as the API would.
+    async fn spawn_front(
+        cert: &[u8],
+        key: &[u8],
+    ) -> (SocketAddr, tokio::task::JoinHandle<FrontedRequest>) {
+        let front_acceptor = tls_acceptor(cert, key);
+        let api_acceptor = tls_acceptor(PINNED_FRONT_CERT, PINNED_FRONT_KEY);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let stream = front_acceptor.accept(stream).await.unwrap();
+            let front_sni = stream.get_ref().1.server_name().map(str::to_owned);
+
+            let mut stream = BufReader::new(stream);
+            let (request_line, _) = read_request_head(&mut stream).await;
+            let tunnel_target = request_line
+                .strip_prefix("CONNECT ")
+                .and_then(|rest| rest.split_whitespace().next())
+                .unwrap()
+                .to_owned();
+            stream
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .await
+                .unwrap();
+
+            let stream = api_acceptor.accept(stream.into_inner()).await.unwrap();
+            let api_sni = stream.get_ref().1.server_name().map(str::to_owned);
+            let mut stream = BufReader::new(stream);
+            let (_, api_host) = read_request_head(&mut stream).await;
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .await
+                .unwrap();
+
+            FrontedRequest {
+                front_sni,
+                tunnel_target,
+                api_sni,
+                api_host,
+            }
+        });
+        (addr, server)
+    }
+
+    /// Create a connector that uses the domain fronting access method with `front`.
+    async fn fronting_connector(front: &str, endpoint: SocketAddr) -> HttpsConnectorWithSni {
+        // The API address is never connected to, since the connector goes through the front
+        let address_cache = AddressCache::with_static_addr("192.0.2.1:443".parse().unwrap());
+        let (connector, handle) = HttpsConnectorWithSni::new(
+            None,
+            address_cache,
+            #[cfg(target_os = "android")]
+            None,
+        );
+        handle.set_connection_mode(ApiConnectionMode::Proxied(ProxyConfig::DomainFronting(
+            DomainFront {
+                front: front.to_owned(),
+                endpoint,
+            },
+        )));
+        // Wait for the connector to switch to the new connection mode
+        while !matches!(
+            connector.inner.lock().unwrap().proxy_config,
+            InnerConnectionMode::DomainFronting(_)
+        ) {
+            tokio::task::yield_now().await;
+        }
+        connector
+    }
+
+    fn api_uri() -> Uri {
+        format!("https://{}/app/v1/api-addrs", API.host())
+            .parse()
+            .unwrap()
+    }
+
+    /// The front is used for the outer TLS connection, while the tunnel, the inner TLS connection
+    /// and the request itself name the API.
+    #[tokio::test]
+    async fn test_domain_fronting_tunnels_to_api() {
+        let (addr, server) = spawn_front(PINNED_FRONT_CERT, PINNED_FRONT_KEY).await;
+        let connector = fronting_connector(PINNED_FRONT, addr).await;
+
+        let client = Client::builder(TokioExecutor::new()).build::<_, Empty<Bytes>>(connector);
+        let response = client.get(api_uri()).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::NO_CONTENT);
+
+        let request = server.await.unwrap();
+        assert_eq!(request.front_sni.as_deref(), Some(PINNED_FRONT));
+        assert_eq!(request.tunnel_target, format!("{}:443", API.host()));
+        assert_eq!(request.api_sni.as_deref(), Some(API.host()));
+        assert_eq!(request.api_host.as_deref(), Some(API.host()));
+    }
+
+    /// The front's certificate must chain to the roots that the API is pinned to. A certificate
+    /// that is only valid in the web PKI is not enough.
+    #[tokio::test]
+    async fn test_domain_fronting_requires_pinned_root() {
+        let (addr, _server) = spawn_front(UNPINNED_FRONT_CERT, UNPINNED_FRONT_KEY).await;
+        let mut connector = fronting_connector(UNPINNED_FRONT, addr).await;
+
+        assert!(connector.call(api_uri()).await.is_err());
+    }
+}
@@ -24,9 +24,12 @@ use availability::ApiAvailability;
 pub mod rest;
 
 mod abortable_stream;
+mod http_connect;
 mod https_client_with_sni;
 pub mod proxy;
 mod tls_stream;
+#[cfg(feature = "tor")]
+mod tor;
 #[cfg(target_os = "android")]
 pub use crate::https_client_with_sni::SocketBypassRequest;
 
@@ -41,7 +44,11 @@ pub mod ffi;
 pub use address_cache::AddressCache;
 pub use device::DevicesProxy;
 pub use hyper::StatusCode;
-pub use relay_list::RelayListProxy;
+pub use relay_list::{
+    RelayListProxy, RelayListSigningKeys, SignatureError as RelayListSignatureError,
+    SignedRelayList,
+};
+pub use tls_stream::{public_tls_config, TlsStream};
 
 /// Error code returned by the Mullvad API if the voucher has alreaby been used.
 pub const VOUCHER_USED: &str = "VOUCHER_USED";
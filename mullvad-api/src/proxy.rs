@@ -1,11 +1,13 @@
 use hyper_util::client::legacy::connect::{Connected, Connection};
+use mullvad_types::access_method::{TorSettings, TorTransport};
 use serde::{Deserialize, Serialize};
 use std::{
     io,
     net::SocketAddr,
-    path::Path,
+    path::{Path, PathBuf},
     pin::Pin,
     task::{self, Poll},
+    time::Duration,
 };
 use talpid_types::{
     net::{proxy, Endpoint, TransportProtocol},
@@ -27,6 +29,9 @@ pub trait ConnectionModeProvider: Send {
 
     /// Receive changes to the connection mode, announced by the provider
     fn receive(&mut self) -> impl std::future::Future<Output = Option<ApiConnectionMode>> + Send;
+
+    /// Report that a request made using `connection_mode` reached the API after `latency`
+    fn report_success(&self, _connection_mode: ApiConnectionMode, _latency: Duration) {}
 }
 
 pub struct StaticConnectionModeProvider {
@@ -66,13 +71,38 @@ pub enum ProxyConfig {
     Shadowsocks(proxy::Shadowsocks),
     Socks5Local(proxy::Socks5Local),
     Socks5Remote(proxy::Socks5Remote),
+    HttpConnect(proxy::HttpConnect),
     EncryptedDnsProxy(mullvad_encrypted_dns_proxy::config::ProxyConfig),
+    DomainFronting(DomainFront),
+    Tor(TorConfig),
+}
+
+/// Reach the API through a CDN by connecting to `endpoint` and presenting `front` as the TLS
+/// server name. The CDN is asked to open a tunnel to the API host, which it routes on, and the TLS
+/// connection to the API is made inside the tunnel.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct DomainFront {
+    /// Hostname sent in the TLS handshake. The certificate must be valid for this name.
+    pub front: String,
+    /// Address of the CDN edge serving `front`.
+    pub endpoint: SocketAddr,
+}
+
+/// Reach the API through the Tor network. Requires the `tor` feature.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TorConfig {
+    pub settings: TorSettings,
+    /// Pluggable transports that the Tor client may run to reach the bridge.
+    pub transports: Vec<TorTransport>,
+    /// Directory where the Tor client keeps its state and directory cache.
+    pub state_dir: PathBuf,
 }
 
 impl ProxyConfig {
-    /// Returns the remote endpoint describing how to reach the proxy.
-    fn get_endpoint(&self) -> Endpoint {
-        match self {
+    /// Returns the remote endpoint describing how to reach the proxy, or `None` if it is not
+    /// known in advance.
+    fn get_endpoint(&self) -> Option<Endpoint> {
+        let endpoint = match self {
             ProxyConfig::Shadowsocks(shadowsocks) => {
                 Endpoint::from_socket_address(shadowsocks.endpoint, TransportProtocol::Tcp)
             }
@@ -80,11 +110,24 @@ impl ProxyConfig {
             ProxyConfig::Socks5Remote(remote) => {
                 Endpoint::from_socket_address(remote.endpoint, TransportProtocol::Tcp)
             }
+            ProxyConfig::HttpConnect(http) => {
+                Endpoint::from_socket_address(http.endpoint, TransportProtocol::Tcp)
+            }
             ProxyConfig::EncryptedDnsProxy(proxy) => {
                 let addr = SocketAddr::V4(proxy.addr);
                 Endpoint::from_socket_address(addr, TransportProtocol::Tcp)
             }
-        }
+            ProxyConfig::DomainFronting(front) => {
+                Endpoint::from_socket_address(front.endpoint, TransportProtocol::Tcp)
+            }
+            // The daemon only uses Tor with a bridge. Without one, the Tor client would connect to
+            // any of the guard relays
+            ProxyConfig::Tor(tor) => {
+                let bridge = tor.settings.bridge_addr()?;
+                Endpoint::from_socket_address(bridge, TransportProtocol::Tcp)
+            }
+        };
+        Some(endpoint)
     }
 }
 
@@ -94,6 +137,7 @@ impl From<proxy::CustomProxy> for ProxyConfig {
             proxy::CustomProxy::Shadowsocks(shadowsocks) => ProxyConfig::Shadowsocks(shadowsocks),
             proxy::CustomProxy::Socks5Local(socks) => ProxyConfig::Socks5Local(socks),
             proxy::CustomProxy::Socks5Remote(socks) => ProxyConfig::Socks5Remote(socks),
+            proxy::CustomProxy::HttpConnect(http) => ProxyConfig::HttpConnect(http),
         }
     }
 }
@@ -159,11 +203,11 @@ impl ApiConnectionMode {
     }
 
     /// Returns the remote endpoint required to reach the API, or `None` for
-    /// `ApiConnectionMode::Direct`.
+    /// `ApiConnectionMode::Direct` and proxies whose endpoint is not known in advance.
     pub fn get_endpoint(&self) -> Option<Endpoint> {
         match self {
             ApiConnectionMode::Direct => None,
-            ApiConnectionMode::Proxied(proxy_config) => Some(proxy_config.get_endpoint()),
+            ApiConnectionMode::Proxied(proxy_config) => proxy_config.get_endpoint(),
         }
     }
 
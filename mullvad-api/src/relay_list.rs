@@ -1,9 +1,16 @@
 //! A module dedicated to retrieving the relay list from the Mullvad API.
+//!
+//! Relay lists are signed with Ed25519. The signature is sent in [SIGNATURE_HEADER] and covers the
+//! response body exactly as it was sent. A relay list and its signature can be stored together as a
+//! [SignedRelayList], which lets the list be passed around out-of-band and verified later.
 
 use crate::rest;
 
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{DateTime, Utc};
 use hyper::{header, StatusCode};
 use mullvad_types::{location, relay_list};
+use ring::signature::{UnparsedPublicKey, ED25519};
 use talpid_types::net::wireguard;
 
 use std::{
@@ -11,6 +18,8 @@ use std::{
     future::Future,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     ops::RangeInclusive,
+    str::FromStr,
+    sync::Arc,
     time::Duration,
 };
 
@@ -22,17 +31,147 @@ pub struct RelayListProxy {
 
 const RELAY_LIST_TIMEOUT: Duration = Duration::from_secs(15);
 
+/// Response header containing the base64-encoded signature of the relay list.
+const SIGNATURE_HEADER: &str = "x-relay-list-signature";
+
+/// Public keys that relay lists must be signed with. They are compiled in, so that they cannot be
+/// removed or replaced after installation. Each line holds one base64-encoded Ed25519 public key.
+/// Empty lines and lines starting with `#` are ignored.
+const PINNED_SIGNING_KEYS: &str = include_str!("../relay-list-signing-keys");
+
+const PUBLIC_KEY_LEN: usize = 32;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SignatureError {
+    #[error("Invalid relay list signing key on line {0}")]
+    InvalidKey(usize),
+
+    #[error("No relay list signing keys are pinned")]
+    NoKeys,
+
+    #[error("The relay list is not signed")]
+    Unsigned,
+
+    #[error("Invalid signature encoding")]
+    InvalidSignature,
+
+    #[error("The relay list is not signed by a trusted key")]
+    Untrusted,
+
+    #[error("Failed to parse relay list")]
+    Parse(#[source] serde_json::Error),
+}
+
+/// Public keys that relay lists may be signed with.
+#[derive(Debug, Default, Clone)]
+pub struct RelayListSigningKeys(Vec<[u8; PUBLIC_KEY_LEN]>);
+
+impl RelayListSigningKeys {
+    /// Returns the keys that are compiled into the app.
+    pub fn pinned() -> Self {
+        PINNED_SIGNING_KEYS
+            .parse()
+            .expect("pinned relay list signing keys must be valid")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromStr for RelayListSigningKeys {
+    type Err = SignatureError;
+
+    fn from_str(contents: &str) -> Result<Self, SignatureError> {
+        let mut keys = vec![];
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let key = BASE64_STANDARD
+                .decode(line)
+                .ok()
+                .and_then(|key| <[u8; PUBLIC_KEY_LEN]>::try_from(key).ok())
+                .ok_or(SignatureError::InvalidKey(index + 1))?;
+            keys.push(key);
+        }
+        Ok(Self(keys))
+    }
+}
+
+/// A relay list, in the format served by the API, along with its signature.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignedRelayList {
+    /// The relay list, exactly as it was signed.
+    pub relay_list: String,
+    /// Base64-encoded Ed25519 signature of `relay_list`.
+    pub signature: Option<String>,
+    /// The ETag of the API response, if the list was downloaded.
+    #[serde(skip)]
+    pub etag: Option<String>,
+}
+
+impl SignedRelayList {
+    /// Returns the relay list if it is signed by any of `keys`. If no keys are pinned, every list
+    /// is rejected, unless the API can be overridden. Such builds talk to APIs that may not sign
+    /// their relay lists, so they skip the check instead.
+    pub fn verify(
+        &self,
+        keys: &RelayListSigningKeys,
+    ) -> Result<relay_list::RelayList, SignatureError> {
+        if keys.is_empty() {
+            if !cfg!(feature = "api-override") {
+                return Err(SignatureError::NoKeys);
+            }
+        } else {
+            let signature = self.signature.as_ref().ok_or(SignatureError::Unsigned)?;
+            let signature = BASE64_STANDARD
+                .decode(signature)
+                .map_err(|_| SignatureError::InvalidSignature)?;
+            let trusted = keys.0.iter().any(|key| {
+                UnparsedPublicKey::new(&ED25519, key)
+                    .verify(self.relay_list.as_bytes(), &signature)
+                    .is_ok()
+            });
+            if !trusted {
+                return Err(SignatureError::Untrusted);
+            }
+        }
+        let relay_list: ServerRelayList =
+            serde_json::from_str(&self.relay_list).map_err(SignatureError::Parse)?;
+        Ok(relay_list.into_relay_list(self.etag.clone()))
+    }
+}
+
 impl RelayListProxy {
     /// Construct a new relay list rest client
     pub fn new(handle: rest::MullvadRestHandle) -> Self {
         Self { handle }
     }
 
-    /// Fetch the relay list
+    /// Fetch the relay list without checking its signature.
     pub fn relay_list(
         &self,
         etag: Option<String>,
     ) -> impl Future<Output = Result<Option<relay_list::RelayList>, rest::Error>> {
+        let request = self.signed_relay_list(etag);
+        async move {
+            let Some(signed) = request.await? else {
+                return Ok(None);
+            };
+            let relay_list: ServerRelayList =
+                serde_json::from_str(&signed.relay_list).map_err(Arc::new)?;
+            Ok(Some(relay_list.into_relay_list(signed.etag)))
+        }
+    }
+
+    /// Fetch the relay list along with its signature. Returns `None` if the list matches `etag`.
+    pub fn signed_relay_list(
+        &self,
+        etag: Option<String>,
+    ) -> impl Future<Output = Result<Option<SignedRelayList>, rest::Error>> {
         let service = self.handle.service.clone();
         let request = self.handle.factory.get("app/v1/relays");
 
@@ -61,14 +200,30 @@ impl RelayListProxy {
                     }
                 });
 
-            let relay_list: ServerRelayList = response.deserialize().await?;
-            Ok(Some(relay_list.into_relay_list(etag)))
+            let signature = response
+                .headers()
+                .get(SIGNATURE_HEADER)
+                .and_then(|signature| signature.to_str().ok())
+                .map(str::to_owned);
+
+            // A body that is not UTF-8 fails to verify and to parse later on
+            let body = response.body().await?;
+            let relay_list = String::from_utf8_lossy(&body).into_owned();
+            Ok(Some(SignedRelayList {
+                relay_list,
+                signature,
+                etag,
+            }))
         }
     }
 }
 
 #[derive(Debug, serde::Deserialize)]
 struct ServerRelayList {
+    /// When the API created the list. It is covered by the signature, so it can be used to reject
+    /// signed lists that are older than the current one.
+    #[serde(default)]
+    created: Option<DateTime<Utc>>,
     locations: BTreeMap<String, Location>,
     openvpn: OpenVpn,
     wireguard: Wireguard,
@@ -79,6 +234,7 @@ impl ServerRelayList {
     fn into_relay_list(self, etag: Option<String>) -> relay_list::RelayList {
         let mut countries = BTreeMap::new();
         let Self {
+            created,
             locations,
             openvpn,
             wireguard,
@@ -109,6 +265,7 @@ impl ServerRelayList {
                 }
                 tag
             }),
+            created,
             openvpn: openvpn.extract_relays(&mut countries),
             wireguard: wireguard.extract_relays(&mut countries),
             bridge: bridge.extract_relays(&mut countries),
@@ -347,6 +504,8 @@ impl WireGuardRelay {
 struct Bridges {
     shadowsocks: Vec<relay_list::ShadowsocksEndpointData>,
     relays: Vec<Relay>,
+    #[serde(default)]
+    domain_fronts: Vec<String>,
 }
 
 impl Bridges {
@@ -382,6 +541,114 @@ impl Bridges {
 
         relay_list::BridgeEndpointData {
             shadowsocks: self.shadowsocks,
+            domain_fronts: self.domain_fronts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    const RELAY_LIST: &str = r#"{
+        "created": "2026-10-01T12:00:00Z",
+        "locations": {
+            "se-got": { "city": "Gothenburg", "country": "Sweden", "latitude": 57.7, "longitude": 11.9 }
+        },
+        "openvpn": { "ports": [], "relays": [] },
+        "wireguard": {
+            "port_ranges": [[51820, 51820]],
+            "ipv4_gateway": "10.64.0.1",
+            "ipv6_gateway": "fc00:bbbb:bbbb:bb01::1",
+            "relays": []
+        },
+        "bridge": { "shadowsocks": [], "relays": [] }
+    }"#;
+
+    fn sign(key_pair: &Ed25519KeyPair, relay_list: &str) -> SignedRelayList {
+        SignedRelayList {
+            relay_list: relay_list.to_owned(),
+            signature: Some(BASE64_STANDARD.encode(key_pair.sign(relay_list.as_bytes()))),
+            etag: None,
         }
     }
+
+    fn signing_keys(key_pair: &Ed25519KeyPair) -> RelayListSigningKeys {
+        format!(
+            "# Relay list key\n{}\n",
+            BASE64_STANDARD.encode(key_pair.public_key())
+        )
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_verify_signed_relay_list() {
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+        let other_key_pair = Ed25519KeyPair::from_seed_unchecked(&[2; 32]).unwrap();
+        let keys = signing_keys(&key_pair);
+
+        let relay_list = sign(&key_pair, RELAY_LIST).verify(&keys).unwrap();
+        assert_eq!(relay_list.countries[0].code, "se");
+        assert_eq!(
+            relay_list.created,
+            Some("2026-10-01T12:00:00Z".parse().unwrap())
+        );
+
+        assert!(matches!(
+            sign(&other_key_pair, RELAY_LIST).verify(&keys),
+            Err(SignatureError::Untrusted)
+        ));
+
+        let mut tampered = sign(&key_pair, RELAY_LIST);
+        tampered.relay_list = tampered.relay_list.replace("Gothenburg", "Malmö");
+        assert!(matches!(
+            tampered.verify(&keys),
+            Err(SignatureError::Untrusted)
+        ));
+
+        let mut unsigned = sign(&key_pair, RELAY_LIST);
+        unsigned.signature = None;
+        assert!(matches!(
+            unsigned.verify(&keys),
+            Err(SignatureError::Unsigned)
+        ));
+
+        let result = sign(&key_pair, RELAY_LIST).verify(&RelayListSigningKeys::default());
+        if cfg!(feature = "api-override") {
+            result.expect("signatures are not checked if no keys are pinned");
+        } else {
+            assert!(matches!(result, Err(SignatureError::NoKeys)));
+        }
+    }
+
+    #[test]
+    fn test_pinned_signing_keys() {
+        RelayListSigningKeys::pinned();
+    }
+
+    /// A list signed by a key in the pinned keys file must be accepted whether or not the API can
+    /// be overridden, since the signature is checked as soon as any key is pinned.
+    #[test]
+    fn test_verify_with_pinned_keys_file() {
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+        let keys: RelayListSigningKeys = format!(
+            "{PINNED_SIGNING_KEYS}{}\n",
+            BASE64_STANDARD.encode(key_pair.public_key())
+        )
+        .parse()
+        .unwrap();
+
+        let relay_list = sign(&key_pair, RELAY_LIST).verify(&keys).unwrap();
+        assert_eq!(relay_list.countries[0].code, "se");
+    }
+
+    #[test]
+    fn test_invalid_signing_key() {
+        assert!(matches!(
+            "# comment\n\nnot a key\n".parse::<RelayListSigningKeys>(),
+            Err(SignatureError::InvalidKey(3))
+        ));
+    }
 }
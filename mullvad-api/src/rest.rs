@@ -5,7 +5,7 @@ use crate::{
     address_cache::AddressCache,
     availability::ApiAvailability,
     https_client_with_sni::{HttpsConnectorWithSni, HttpsConnectorWithSniHandle},
-    proxy::ConnectionModeProvider,
+    proxy::{ApiConnectionMode, ConnectionModeProvider},
 };
 use futures::{
     channel::{mpsc, oneshot},
@@ -25,7 +25,7 @@ use std::{
     error::Error as StdError,
     str::FromStr,
     sync::{Arc, Weak},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use talpid_types::ErrorExt;
 
@@ -143,6 +143,7 @@ pub(crate) struct RequestService<T: ConnectionModeProvider> {
     connector_handle: HttpsConnectorWithSniHandle,
     client: RequestClient,
     connection_mode_provider: T,
+    connection_mode: ApiConnectionMode,
     connection_mode_generation: usize,
     api_availability: ApiAvailability,
 }
@@ -163,7 +164,8 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
             socket_bypass_tx.clone(),
         );
 
-        connector_handle.set_connection_mode(connection_mode_provider.initial());
+        let connection_mode = connection_mode_provider.initial();
+        connector_handle.set_connection_mode(connection_mode.clone());
 
         let (command_tx, command_rx) = mpsc::unbounded();
         let client =
@@ -178,6 +180,7 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
             connector_handle,
             client,
             connection_mode_provider,
+            connection_mode,
             connection_mode_generation: 0,
             api_availability,
         };
@@ -193,6 +196,7 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
                     let Some(new_mode) = new_mode else {
                         break;
                     };
+                    self.connection_mode = new_mode.clone();
                     self.connector_handle.set_connection_mode(new_mode);
                 }
                 command = self.command_rx.next() => {
@@ -222,6 +226,10 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
                     self.connection_mode_provider.rotate().await;
                 }
             }
+            RequestCommand::RequestSucceeded(connection_mode, latency) => {
+                self.connection_mode_provider
+                    .report_success(connection_mode, latency);
+            }
         }
     }
 
@@ -238,10 +246,22 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
             .into_future(self.client.clone(), api_availability.clone());
 
         let connection_mode_generation = self.connection_mode_generation;
+        let connection_mode = self.connection_mode.clone();
 
         tokio::spawn(async move {
+            let start = Instant::now();
             let response = request_future.await.map_err(|error| error.map_aborted());
 
+            // Any response means that the API was reached using `connection_mode`
+            if response.is_ok() {
+                if let Some(tx) = &tx {
+                    let _ = tx.unbounded_send(RequestCommand::RequestSucceeded(
+                        connection_mode,
+                        start.elapsed(),
+                    ));
+                }
+            }
+
             // Switch API endpoint if the request failed due to a network error
             if let Err(err) = &response {
                 if err.is_network_error() && !api_availability.is_offline() {
@@ -295,6 +315,7 @@ pub(crate) enum RequestCommand {
     ),
     Reset,
     NextApiConfig(usize),
+    RequestSucceeded(ApiConnectionMode, Duration),
 }
 
 /// A REST request that is sent to the RequestService to be executed.
@@ -512,6 +533,14 @@ where
     pub async fn deserialize<T: serde::de::DeserializeOwned>(self) -> Result<T> {
         deserialize_body_inner(self.response).await
     }
+
+    /// Returns the raw response body.
+    pub async fn body(self) -> Result<Bytes> {
+        use http_body_util::BodyExt;
+
+        let collected = BodyExt::collect(self.response).await?;
+        Ok(collected.to_bytes())
+    }
 }
 
 #[derive(serde::Deserialize)]
@@ -733,3 +762,94 @@ impl_into_arc_err!(hyper_util::client::legacy::Error);
 impl_into_arc_err!(serde_json::Error);
 impl_into_arc_err!(http::Error);
 impl_into_arc_err!(http::uri::InvalidUri);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::SocketAddr;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+    use tokio_rustls::{
+        rustls::{self, ServerConfig},
+        TlsAcceptor,
+    };
+
+    const LOCALHOST_CERT: &[u8] = include_bytes!("../test-data/localhost.pem");
+    const LOCALHOST_KEY: &[u8] = include_bytes!("../test-data/localhost.key");
+
+    /// Serve `body` to a single HTTPS client as `localhost`.
+    async fn serve_https(body: &'static str) -> SocketAddr {
+        let certs = rustls_pemfile::certs(&mut &LOCALHOST_CERT[..])
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        let key = rustls_pemfile::private_key(&mut &LOCALHOST_KEY[..])
+            .unwrap()
+            .unwrap();
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .unwrap();
+
+        let listener = TcpListener::bind("localhost:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let stream = TlsAcceptor::from(Arc::new(config))
+                .accept(stream)
+                .await
+                .unwrap();
+            let mut stream = BufReader::new(stream);
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.flush().await.unwrap();
+        });
+        addr
+    }
+
+    /// Documents that are not hosted by the API, such as managed profiles, are fetched from their
+    /// own host, but only over HTTPS.
+    #[tokio::test]
+    async fn test_get_over_https() {
+        let body = r#"{ "signed_patch": "{}", "signature": "c2lnbmF0dXJl" }"#;
+        let addr = serve_https(body).await;
+        let service = RequestService::spawn(
+            None,
+            ApiAvailability::default(),
+            AddressCache::with_static_addr(addr),
+            ApiConnectionMode::Direct.into_provider(),
+            #[cfg(target_os = "android")]
+            None,
+        );
+
+        let request = get(&format!("https://localhost:{}/profile.json", addr.port())).unwrap();
+        let response: serde_json::Value = service
+            .request(request)
+            .await
+            .unwrap()
+            .deserialize()
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            serde_json::from_str::<serde_json::Value>(body).unwrap()
+        );
+
+        let request = get(&format!("http://localhost:{}/profile.json", addr.port())).unwrap();
+        assert!(service.request(request).await.is_err());
+    }
+}
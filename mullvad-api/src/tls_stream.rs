@@ -1,4 +1,5 @@
-//! Provides a TLS 1.3 stream with SNI and LE root cert only.
+//! Provides a TLS 1.3 stream with SNI and LE root cert only. Streams to servers other than the
+//! API, such as proxies, may instead use [`public_tls_config`].
 use std::{
     io::{self, ErrorKind},
     pin::Pin,
@@ -8,6 +9,7 @@ use std::{
 
 use hyper_util::client::legacy::connect::{Connected, Connection};
 use std::sync::LazyLock;
+pub use talpid_net::tls::public_tls_config;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio_rustls::{
     rustls::{self, pki_types::ServerName, ClientConfig},
@@ -31,12 +33,22 @@ where
             ))
             .with_protocol_versions(&[&rustls::version::TLS13])
             .expect("ring crypt-prover should support TLS 1.3")
-            .with_root_certificates(read_cert_store())
+            .with_root_certificates(api_cert_store())
             .with_no_client_auth();
             Arc::new(config)
         });
 
-        let connector = TlsConnector::from(TLS_CONFIG.clone());
+        Self::connect_with_config(stream, domain, TLS_CONFIG.clone()).await
+    }
+
+    /// Connect to `domain` using the given TLS configuration instead of the one pinned to the
+    /// API's root certificate.
+    pub async fn connect_with_config(
+        stream: S,
+        domain: &str,
+        config: Arc<ClientConfig>,
+    ) -> io::Result<TlsStream<S>> {
+        let connector = TlsConnector::from(config);
 
         let host = match ServerName::try_from(domain.to_owned()) {
             Ok(n) => n,
@@ -54,6 +66,26 @@ where
     }
 }
 
+/// Root certificates that the API is pinned to.
+#[cfg(not(test))]
+fn api_cert_store() -> rustls::RootCertStore {
+    read_cert_store()
+}
+
+/// Tests additionally trust a local CA, so that a local server can stand in for the API.
+#[cfg(test)]
+fn api_cert_store() -> rustls::RootCertStore {
+    const TEST_CA_CERT: &[u8] = include_bytes!("../test-data/api-ca.pem");
+
+    let mut cert_store = read_cert_store();
+    for cert in rustls_pemfile::certs(&mut &TEST_CA_CERT[..]) {
+        cert_store
+            .add(cert.expect("Failed to parse test CA"))
+            .expect("Failed to add test CA");
+    }
+    cert_store
+}
+
 fn read_cert_store() -> rustls::RootCertStore {
     let mut cert_store = rustls::RootCertStore::empty();
 
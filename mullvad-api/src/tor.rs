@@ -0,0 +1,177 @@
+//! Reach the API through the Tor network, using an embedded [Arti](https://arti.torproject.org)
+//! client. Only available with the `tor` feature.
+//!
+//! A single Tor client is kept for the whole process, and replaced whenever the configuration
+//! changes. Bootstrapping runs in the background, so that it is not cancelled if a connection
+//! attempt times out. Connections made before bootstrapping has finished wait for it.
+
+use crate::proxy::TorConfig;
+use arti_client::{
+    config::{pt::TransportConfigBuilder, BridgeConfigBuilder, CfgPath, TorClientConfigBuilder},
+    DataStream, TorClient, TorClientConfig,
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+use talpid_types::ErrorExt;
+use tokio::sync::Mutex;
+use tor_rtcompat::PreferredRuntime;
+
+static CLIENT: LazyLock<Mutex<Option<(TorConfig, TorClient<PreferredRuntime>)>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid bridge line: {0}")]
+    InvalidBridge(String),
+    #[error("Invalid pluggable transport name: {0}")]
+    InvalidTransport(String),
+    #[error("Pluggable transport path is not absolute: {0}")]
+    RelativeTransportPath(PathBuf),
+    #[error("Invalid Tor client configuration")]
+    Config(#[source] arti_client::config::ConfigBuildError),
+    #[error("Failed to create Tor client")]
+    Client(#[source] arti_client::Error),
+}
+
+/// Open a stream to `hostname:port` through the Tor network. The exit relay resolves
+/// `hostname`, so no DNS lookup is made locally.
+pub async fn connect(config: &TorConfig, hostname: &str, port: u16) -> io::Result<DataStream> {
+    let client = client(config).await?;
+    client
+        .connect((hostname, port))
+        .await
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+}
+
+/// Return the Tor client for `config`, creating it and starting to bootstrap it if needed.
+async fn client(config: &TorConfig) -> io::Result<TorClient<PreferredRuntime>> {
+    let mut current = CLIENT.lock().await;
+    if let Some((current_config, client)) = &*current {
+        if current_config == config {
+            return Ok(client.clone());
+        }
+    }
+
+    let client = create_client(config).map_err(|error| {
+        log::error!("{}", error.display_chain());
+        io::Error::new(io::ErrorKind::Other, error)
+    })?;
+
+    let bootstrapping_client = client.clone();
+    tokio::spawn(async move {
+        log::debug!("Bootstrapping Tor client");
+        match bootstrapping_client.bootstrap().await {
+            Ok(()) => log::info!("Bootstrapped Tor client"),
+            Err(error) => log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to bootstrap Tor client")
+            ),
+        }
+    });
+
+    *current = Some((config.clone(), client.clone()));
+    Ok(client)
+}
+
+fn create_client(config: &TorConfig) -> Result<TorClient<PreferredRuntime>, Error> {
+    TorClient::builder()
+        .config(client_config(config)?)
+        .create_unbootstrapped()
+        .map_err(Error::Client)
+}
+
+fn client_config(config: &TorConfig) -> Result<TorClientConfig, Error> {
+    let mut builder = TorClientConfigBuilder::from_directories(
+        config.state_dir.join("state"),
+        config.state_dir.join("cache"),
+    );
+
+    if let Some(line) = &config.settings.bridge {
+        let bridge: BridgeConfigBuilder = line
+            .parse()
+            .map_err(|_| Error::InvalidBridge(line.clone()))?;
+        builder.bridges().bridges().push(bridge);
+    }
+
+    for transport in &config.transports {
+        let protocols = transport
+            .protocols
+            .iter()
+            .map(|protocol| {
+                protocol
+                    .parse()
+                    .map_err(|_| Error::InvalidTransport(protocol.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut transport_builder = TransportConfigBuilder::default();
+        transport_builder
+            .protocols(protocols)
+            .path(transport_path(&transport.path)?)
+            .arguments(transport.arguments.clone());
+        builder.bridges().transports().push(transport_builder);
+    }
+
+    builder.build().map_err(Error::Config)
+}
+
+/// Transports are configured by root and checked by the daemon before they get here. Their paths
+/// are still never expanded or resolved against the working directory, so that the binary that is
+/// run is the one that was checked.
+fn transport_path(path: &Path) -> Result<CfgPath, Error> {
+    if !path.is_absolute() {
+        return Err(Error::RelativeTransportPath(path.to_owned()));
+    }
+    Ok(CfgPath::new_literal(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::access_method::{TorSettings, TorTransport};
+
+    const FINGERPRINT: &str = "0123456789ABCDEF0123456789ABCDEF01234567";
+
+    fn config(bridge: &str, transports: Vec<TorTransport>) -> TorConfig {
+        TorConfig {
+            settings: TorSettings {
+                bridge: Some(bridge.to_owned()),
+            },
+            transports,
+            state_dir: PathBuf::from("/nonexistent/tor"),
+        }
+    }
+
+    fn lyrebird(path: &str) -> TorTransport {
+        TorTransport {
+            protocols: vec!["obfs4".to_owned()],
+            path: PathBuf::from(path),
+            arguments: vec![],
+        }
+    }
+
+    #[test]
+    fn test_client_config_with_bridge() {
+        let bridge = format!("obfs4 192.0.2.2:443 {FINGERPRINT} cert=AAAA iat-mode=0");
+        client_config(&config(&bridge, vec![lyrebird("/usr/bin/lyrebird")])).unwrap();
+    }
+
+    #[test]
+    fn test_client_config_rejects_invalid_bridge() {
+        assert!(matches!(
+            client_config(&config("not a bridge", vec![])),
+            Err(Error::InvalidBridge(_))
+        ));
+    }
+
+    #[test]
+    fn test_client_config_rejects_relative_transport() {
+        let bridge = format!("obfs4 192.0.2.2:443 {FINGERPRINT} cert=AAAA iat-mode=0");
+        assert!(matches!(
+            client_config(&config(&bridge, vec![lyrebird("lyrebird")])),
+            Err(Error::RelativeTransportPath(_))
+        ));
+    }
+}
@@ -1,13 +1,43 @@
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use clap::Subcommand;
 use itertools::Itertools;
 use mullvad_management_interface::MullvadProxyClient;
-use mullvad_types::{account::AccountNumber, device::DeviceState};
-use std::io::{self, Write};
+use mullvad_types::{
+    account::{AccountData, AccountNumber},
+    device::{Device, DeviceId, DeviceLabel, DeviceState},
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal, Write},
+    time::Duration,
+};
+
+use crate::{cmds::receive_confirmation, format};
 
 const NOT_LOGGED_IN_MESSAGE: &str = "Not logged in on any account";
 const REVOKED_MESSAGE: &str = "The current device has been revoked";
 
+/// JSON output of `mullvad account get`.
+#[derive(Serialize)]
+struct AccountOutput {
+    device_state: DeviceState,
+    /// Only present when logged in.
+    account_data: Option<AccountData>,
+}
+
+/// JSON output of `mullvad account list-devices`.
+#[derive(Serialize)]
+struct DeviceOutput {
+    #[serde(flatten)]
+    device: Device,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Account {
     /// Create and log in on a new account
@@ -43,8 +73,39 @@ pub enum Account {
     /// Revoke a device associated with an account
     RevokeDevice {
         /// Name or UID of the device to revoke
+        #[arg(required_unless_present = "prune_older_than")]
+        device: Option<String>,
+
+        /// Revoke all devices created before this date (YYYY-MM-DD), except this one
+        #[arg(long, value_name = "DATE", conflicts_with = "device")]
+        prune_older_than: Option<NaiveDate>,
+
+        /// Revoke the devices without asking for confirmation
+        #[arg(long, short = 'y', requires = "prune_older_than")]
+        confirm: bool,
+
+        /// Mullvad account number (current account if not specified)
+        #[arg(long, short = 'a')]
+        account: Option<String>,
+    },
+
+    /// Set a label or note for a device. These are only stored on this computer
+    LabelDevice {
+        /// Name or UID of the device
         device: String,
 
+        /// Label to show next to the device name
+        #[arg(required_unless_present_any = ["note", "clear"])]
+        label: Option<String>,
+
+        /// Note to show in the verbose device list
+        #[arg(long)]
+        note: Option<String>,
+
+        /// Remove the label and note
+        #[arg(long, conflicts_with_all = ["label", "note"])]
+        clear: bool,
+
         /// Mullvad account number (current account if not specified)
         #[arg(long, short = 'a')]
         account: Option<String>,
@@ -55,6 +116,30 @@ pub enum Account {
         /// Voucher code to submit
         voucher: String,
     },
+
+    /// List vouchers redeemed on this device
+    VoucherHistory,
+
+    /// Manage warnings about the account running out of time
+    #[clap(subcommand)]
+    ExpiryWarnings(ExpiryWarningCommands),
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ExpiryWarningCommands {
+    /// Show when warnings are emitted
+    Get,
+
+    /// Set when warnings are emitted
+    Set {
+        /// Number of hours before the account expires to emit a warning at, at most 8760 (365
+        /// days)
+        #[arg(required = true, value_parser = clap::value_parser!(u32).range(1..=8760))]
+        hours: Vec<u32>,
+    },
+
+    /// Do not emit any warnings
+    Disable,
 }
 
 impl Account {
@@ -74,10 +159,28 @@ impl Account {
             Account::ListDevices { account, verbose } => {
                 Self::list_devices(&mut rpc, account, verbose).await
             }
-            Account::RevokeDevice { device, account } => {
-                Self::revoke_device(&mut rpc, device, account).await
-            }
+            Account::RevokeDevice {
+                device,
+                prune_older_than,
+                confirm,
+                account,
+            } => match (device, prune_older_than) {
+                (Some(device), _) => Self::revoke_device(&mut rpc, device, account).await,
+                (None, Some(older_than)) => {
+                    Self::prune_devices(&mut rpc, older_than, confirm, account).await
+                }
+                (None, None) => unreachable!("clap requires a device or a date"),
+            },
+            Account::LabelDevice {
+                device,
+                label,
+                note,
+                clear,
+                account,
+            } => Self::label_device(&mut rpc, device, label, note, clear, account).await,
             Account::Redeem { voucher } => Self::redeem_voucher(&mut rpc, voucher).await,
+            Account::VoucherHistory => Self::voucher_history(&mut rpc).await,
+            Account::ExpiryWarnings(cmd) => Self::expiry_warnings(&mut rpc, cmd).await,
         }
     }
 
@@ -88,11 +191,52 @@ impl Account {
     }
 
     async fn login(rpc: &mut MullvadProxyClient, account_number: AccountNumber) -> Result<()> {
-        rpc.login_account(account_number.clone()).await?;
+        match rpc.login_account(account_number.clone()).await {
+            Err(mullvad_management_interface::Error::TooManyDevices)
+                if io::stdin().is_terminal() =>
+            {
+                Self::make_room(rpc, &account_number).await?;
+                rpc.login_account(account_number.clone()).await?;
+            }
+            result => result?,
+        }
         println!("Mullvad account \"{account_number}\" set");
         Ok(())
     }
 
+    /// Let the user pick a device to revoke when the account already has the maximum number of
+    /// devices.
+    async fn make_room(rpc: &mut MullvadProxyClient, account_number: &AccountNumber) -> Result<()> {
+        let mut devices = rpc.list_devices(account_number.clone()).await?;
+        devices.sort_unstable_by_key(|dev| dev.created.timestamp());
+        let labels = get_labels(rpc).await?;
+
+        println!("There are too many devices on the account. Revoke one of them to log in:");
+        for (index, device) in devices.iter().enumerate() {
+            println!(
+                "{:>3}. {}, created {}",
+                index + 1,
+                device_name(device, &labels),
+                device.created.with_timezone(&chrono::Local).date_naive()
+            );
+        }
+
+        let choice = from_stdin_async("Device to revoke (leave empty to cancel): ").await;
+        if choice.is_empty() {
+            return Err(mullvad_management_interface::Error::TooManyDevices.into());
+        }
+        let device = choice
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| devices.get(number.checked_sub(1)?))
+            .ok_or_else(|| anyhow!("Invalid device number: {choice}"))?;
+
+        rpc.remove_device(account_number.clone(), device.id.clone())
+            .await?;
+        println!("Removed device {}", device.pretty_name());
+        Ok(())
+    }
+
     async fn logout(rpc: &mut MullvadProxyClient) -> Result<()> {
         rpc.logout_account().await?;
         println!("Removed device from Mullvad account");
@@ -104,6 +248,19 @@ impl Account {
 
         let state = rpc.get_device().await?;
 
+        if format::is_json() {
+            let account_data = match &state {
+                DeviceState::LoggedIn(device) => {
+                    Some(rpc.get_account_data(device.account_number.clone()).await?)
+                }
+                DeviceState::LoggedOut | DeviceState::Revoked => None,
+            };
+            return format::print_json(&AccountOutput {
+                device_state: state,
+                account_data,
+            });
+        }
+
         match state {
             DeviceState::LoggedIn(device) => {
                 println!("{:<20}{}", "Mullvad account:", device.account_number);
@@ -114,6 +271,14 @@ impl Account {
                     "Expires at:",
                     data.expiry.with_timezone(&chrono::Local)
                 );
+                println!("{:<20}{}", "Time left:", format_time_left(data.expiry));
+                if let Some(cached_at) = data.cached_at {
+                    println!(
+                        "{:<20}{} (the API could not be reached)",
+                        "Last updated:",
+                        cached_at.with_timezone(&chrono::Local)
+                    );
+                }
                 if verbose {
                     println!("{:<20}{}", "Account id:", data.id);
                 }
@@ -146,13 +311,36 @@ impl Account {
     ) -> Result<()> {
         let account_number = account_else_current(rpc, account).await?;
         let mut device_list = rpc.list_devices(account_number).await?;
+        device_list.sort_unstable_by_key(|dev| dev.created.timestamp());
+        let mut labels = get_labels(rpc).await?;
+
+        if format::is_json() {
+            let output = device_list
+                .into_iter()
+                .map(|device| {
+                    let label = labels.remove(&device.id).unwrap_or_default();
+                    DeviceOutput {
+                        device,
+                        label: label.label,
+                        note: label.note,
+                    }
+                })
+                .collect::<Vec<_>>();
+            return format::print_json(&output);
+        }
 
         println!("Devices on the account:");
-        device_list.sort_unstable_by_key(|dev| dev.created.timestamp());
         for device in device_list {
             if verbose {
+                let label = labels.remove(&device.id).unwrap_or_default();
                 println!();
                 println!("Name      : {}", device.pretty_name());
+                if let Some(label) = label.label {
+                    println!("Label     : {label}");
+                }
+                if let Some(note) = label.note {
+                    println!("Note      : {note}");
+                }
                 println!("Id        : {}", device.id);
                 println!("Public key: {}", device.pubkey);
                 println!(
@@ -160,7 +348,7 @@ impl Account {
                     device.created.with_timezone(&chrono::Local)
                 );
             } else {
-                println!("{}", device.pretty_name());
+                println!("{}", device_name(&device, &labels));
             }
         }
 
@@ -173,18 +361,82 @@ impl Account {
         account: Option<String>,
     ) -> Result<()> {
         let account_number = account_else_current(rpc, account).await?;
+        let device = find_device(rpc, account_number.clone(), &device).await?;
 
-        let device_list = rpc.list_devices(account_number.clone()).await?;
-        let device_id = device_list
+        rpc.remove_device(account_number, device.id).await?;
+        println!("Removed device");
+        Ok(())
+    }
+
+    async fn prune_devices(
+        rpc: &mut MullvadProxyClient,
+        older_than: NaiveDate,
+        confirm: bool,
+        account: Option<String>,
+    ) -> Result<()> {
+        let account_number = account_else_current(rpc, account).await?;
+        // The API does not say when a device was last used, so go by when it was created
+        let cutoff = older_than.and_time(NaiveTime::default()).and_utc();
+        let current_device = rpc.get_device().await?.logged_in().map(|dev| dev.device.id);
+
+        let mut stale_devices = rpc
+            .list_devices(account_number.clone())
+            .await?
             .into_iter()
-            .find(|dev| {
-                dev.name.eq_ignore_ascii_case(&device) || dev.id.eq_ignore_ascii_case(&device)
-            })
-            .map(|dev| dev.id)
-            .ok_or(mullvad_management_interface::Error::DeviceNotFound)?;
+            .filter(|dev| dev.created < cutoff && Some(&dev.id) != current_device.as_ref())
+            .collect::<Vec<_>>();
+        stale_devices.sort_unstable_by_key(|dev| dev.created.timestamp());
 
-        rpc.remove_device(account_number, device_id).await?;
-        println!("Removed device");
+        if stale_devices.is_empty() {
+            println!("No devices were created before {older_than}");
+            return Ok(());
+        }
+        println!("Devices created before {older_than}:");
+        for device in &stale_devices {
+            println!("{}", device.pretty_name());
+        }
+        if !confirm && !receive_confirmation("Revoke these devices?", false).await {
+            return Ok(());
+        }
+
+        for device in stale_devices {
+            rpc.remove_device(account_number.clone(), device.id).await?;
+            println!("Removed device {}", device.pretty_name());
+        }
+        Ok(())
+    }
+
+    async fn label_device(
+        rpc: &mut MullvadProxyClient,
+        device: String,
+        label: Option<String>,
+        note: Option<String>,
+        clear: bool,
+        account: Option<String>,
+    ) -> Result<()> {
+        let account_number = account_else_current(rpc, account).await?;
+        let device = find_device(rpc, account_number, &device).await?;
+
+        let mut device_label = get_labels(rpc)
+            .await?
+            .remove(&device.id)
+            .unwrap_or_else(|| DeviceLabel {
+                device_id: device.id.clone(),
+                ..Default::default()
+            });
+        if clear {
+            device_label.label = None;
+            device_label.note = None;
+        }
+        if let Some(label) = label {
+            device_label.label = Some(label);
+        }
+        if let Some(note) = note {
+            device_label.note = Some(note);
+        }
+
+        rpc.set_device_label(device_label).await?;
+        println!("Updated label of device {}", device.pretty_name());
         Ok(())
     }
 
@@ -202,6 +454,63 @@ impl Account {
         );
         Ok(())
     }
+
+    async fn voucher_history(rpc: &mut MullvadProxyClient) -> Result<()> {
+        let vouchers = rpc.get_voucher_history().await?;
+
+        if format::is_json() {
+            return format::print_json(&vouchers);
+        }
+
+        if vouchers.is_empty() {
+            println!("No vouchers have been redeemed on this device");
+        }
+        for voucher in vouchers {
+            println!(
+                "{}: added {}, new expiry date: {}",
+                voucher.redeemed.with_timezone(&chrono::Local),
+                format_duration(voucher.time_added),
+                voucher.new_expiry.with_timezone(&chrono::Local),
+            );
+        }
+        Ok(())
+    }
+
+    async fn expiry_warnings(
+        rpc: &mut MullvadProxyClient,
+        cmd: ExpiryWarningCommands,
+    ) -> Result<()> {
+        const HOUR: Duration = Duration::from_secs(60 * 60);
+
+        let mut expiry_warnings = rpc.get_settings().await?.expiry_warnings;
+        match cmd {
+            ExpiryWarningCommands::Get => {
+                if format::is_json() {
+                    return format::print_json(&expiry_warnings);
+                }
+                if expiry_warnings.thresholds.is_empty() {
+                    println!("Expiry warnings are disabled");
+                }
+                for threshold in expiry_warnings.thresholds {
+                    println!(
+                        "{} before the account expires",
+                        format_duration(threshold.as_secs())
+                    );
+                }
+                return Ok(());
+            }
+            ExpiryWarningCommands::Set { hours } => {
+                expiry_warnings.thresholds = hours.into_iter().map(|hours| HOUR * hours).collect();
+            }
+            ExpiryWarningCommands::Disable => {
+                expiry_warnings.thresholds.clear();
+            }
+        }
+
+        rpc.set_expiry_warning_settings(expiry_warnings).await?;
+        println!("Updated expiry warnings");
+        Ok(())
+    }
 }
 
 async fn account_else_current(
@@ -220,16 +529,55 @@ async fn account_else_current(
     }
 }
 
-async fn unwrap_or_from_stdin(val: Option<String>, prompt_str: &'static str) -> String {
-    if let Some(val) = val {
-        return val;
+/// Find a device by name or ID.
+async fn find_device(
+    rpc: &mut MullvadProxyClient,
+    account_number: AccountNumber,
+    device: &str,
+) -> Result<Device> {
+    let device = rpc
+        .list_devices(account_number)
+        .await?
+        .into_iter()
+        .find(|dev| dev.name.eq_ignore_ascii_case(device) || dev.id.eq_ignore_ascii_case(device))
+        .ok_or(mullvad_management_interface::Error::DeviceNotFound)?;
+    Ok(device)
+}
+
+async fn get_labels(rpc: &mut MullvadProxyClient) -> Result<HashMap<DeviceId, DeviceLabel>> {
+    Ok(rpc
+        .get_device_labels()
+        .await?
+        .into_iter()
+        .map(|label| (label.device_id.clone(), label))
+        .collect())
+}
+
+/// Return the device name, followed by its label if it has one.
+fn device_name(device: &Device, labels: &HashMap<DeviceId, DeviceLabel>) -> String {
+    match labels
+        .get(&device.id)
+        .and_then(|label| label.label.as_ref())
+    {
+        Some(label) => format!("{} ({label})", device.pretty_name()),
+        None => device.pretty_name(),
     }
+}
 
+async fn from_stdin_async(prompt_str: &'static str) -> String {
     tokio::task::spawn_blocking(|| from_stdin(prompt_str))
         .await
         .unwrap()
 }
 
+async fn unwrap_or_from_stdin(val: Option<String>, prompt_str: &'static str) -> String {
+    if let Some(val) = val {
+        return val;
+    }
+
+    from_stdin_async(prompt_str).await
+}
+
 fn from_stdin(prompt_str: &'static str) -> String {
     let mut val = String::new();
     io::stdout()
@@ -242,6 +590,23 @@ fn from_stdin(prompt_str: &'static str) -> String {
     val.split_whitespace().join("")
 }
 
+fn format_time_left(expiry: DateTime<Utc>) -> String {
+    let left = expiry.signed_duration_since(Utc::now());
+    if left <= chrono::TimeDelta::zero() {
+        "expired".to_owned()
+    } else if left.num_days() > 0 {
+        format!("{} days, {} hours", left.num_days(), left.num_hours() % 24)
+    } else if left.num_hours() > 0 {
+        format!(
+            "{} hours, {} minutes",
+            left.num_hours(),
+            left.num_minutes() % 60
+        )
+    } else {
+        format!("{} minutes", left.num_minutes().max(1))
+    }
+}
+
 fn format_duration(seconds: u64) -> String {
     let dur = chrono::Duration::seconds(seconds as i64);
     if dur.num_days() > 0 {
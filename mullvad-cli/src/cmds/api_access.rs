@@ -1,11 +1,15 @@
 use anyhow::{anyhow, Result};
 use mullvad_management_interface::MullvadProxyClient;
-use mullvad_types::access_method::{AccessMethod, AccessMethodSetting};
+use mullvad_types::access_method::{bridge_addr, AccessMethod, AccessMethodSetting, DohResolver};
+use std::net::IpAddr;
 use talpid_types::net::proxy::CustomProxy;
 
 use clap::{Args, Subcommand};
 
-use super::proxies::{ProxyEditParams, ShadowsocksAdd, Socks5LocalAdd, Socks5RemoteAdd};
+use super::proxies::{
+    HttpConnectAdd, ProxyEditParams, ShadowsocksAdd, Socks5LocalAdd, Socks5RemoteAdd,
+};
+use crate::format;
 
 #[derive(Subcommand, Debug, Clone)]
 pub enum ApiAccess {
@@ -36,6 +40,69 @@ pub enum ApiAccess {
     Use(SelectItem),
     /// Try to reach the Mullvad API using a specific access method
     Test(SelectItem),
+    /// Show how well each API access method has worked on the current network
+    ///
+    /// Enabled access methods are listed in the order that they will be tried in.
+    Status,
+    /// Manage the PAC file used by the "System proxy" access method
+    ///
+    /// If no PAC file URL is set, the PAC file is located using WPAD.
+    #[clap(subcommand)]
+    PacUrl(PacUrlCommands),
+    /// Manage the "Encrypted DNS proxy" access method
+    ///
+    /// Proxy configurations are fetched using DoH resolvers and cached, so that they can be used
+    /// when no resolver can be reached.
+    #[clap(subcommand)]
+    EncryptedDnsProxy(EncryptedDnsProxyCommands),
+    /// Manage the bridge used by the "Tor" access method
+    ///
+    /// The Tor access method is only available in builds with Tor support. Pluggable transports
+    /// can only be configured by root, in tor-transports.json in the settings directory.
+    #[clap(subcommand)]
+    Tor(TorCommands),
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum TorCommands {
+    /// Display the configured bridge
+    Get,
+    /// Connect to the Tor network through a bridge, replacing the current one
+    SetBridge {
+        /// Bridge line, e.g. "obfs4 192.0.2.1:443 <fingerprint> cert=<cert> iat-mode=0"
+        line: String,
+    },
+    /// Remove the bridge, which stops the Tor access method from being used
+    ClearBridge,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum EncryptedDnsProxyCommands {
+    /// Display the configured DoH resolvers and the cached proxy configurations
+    Get,
+    /// Fetch new proxy configurations
+    Refresh,
+    /// Use a DoH resolver in addition to the default ones. A resolver with the same name is
+    /// replaced
+    AddResolver {
+        /// Hostname that the resolver's certificate is valid for
+        name: String,
+        /// Addresses of the resolver. Port 443 is always used
+        #[arg(required = true)]
+        addrs: Vec<IpAddr>,
+    },
+    /// Stop using a DoH resolver that was added
+    RemoveResolver { name: String },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum PacUrlCommands {
+    /// Display the current PAC file URL
+    Get,
+    /// Read proxies from the PAC file at this URL. Only https:// URLs are supported
+    Set { url: String },
+    /// Locate the PAC file using WPAD
+    Unset,
 }
 
 impl ApiAccess {
@@ -64,14 +131,119 @@ impl ApiAccess {
             ApiAccess::Get => {
                 Self::get().await?;
             }
+            ApiAccess::Status => Self::status().await?,
+            ApiAccess::PacUrl(cmd) => Self::pac_url(cmd).await?,
+            ApiAccess::EncryptedDnsProxy(cmd) => Self::encrypted_dns_proxy(cmd).await?,
+            ApiAccess::Tor(cmd) => Self::tor(cmd).await?,
         };
         Ok(())
     }
 
+    /// Get or set the PAC file URL used by the system proxy access method.
+    async fn pac_url(cmd: PacUrlCommands) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match cmd {
+            PacUrlCommands::Get => {
+                let settings = rpc.get_settings().await?;
+                let pac_url = settings.api_access_methods.pac_url();
+                if format::is_json() {
+                    return format::print_json(&pac_url);
+                }
+                println!("PAC file URL: {}", pac_url.unwrap_or("unset"));
+            }
+            PacUrlCommands::Set { url } => rpc.set_system_proxy_pac_url(Some(url)).await?,
+            PacUrlCommands::Unset => rpc.set_system_proxy_pac_url(None).await?,
+        }
+        Ok(())
+    }
+
+    /// Inspect and configure the Encrypted DNS proxy access method.
+    async fn encrypted_dns_proxy(cmd: EncryptedDnsProxyCommands) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match cmd {
+            EncryptedDnsProxyCommands::Get => {
+                let settings = rpc.get_settings().await?;
+                let resolvers = settings.api_access_methods.doh_resolvers();
+                let configs = rpc.get_encrypted_dns_proxy_configs().await?;
+                if format::is_json() {
+                    return format::print_json(&serde_json::json!({
+                        "resolvers": resolvers,
+                        "configs": configs,
+                    }));
+                }
+                pp::print_doh_resolvers(resolvers);
+                pp::print_encrypted_dns_proxy_configs(&configs);
+            }
+            EncryptedDnsProxyCommands::Refresh => {
+                let configs = rpc.refresh_encrypted_dns_proxy_configs().await?;
+                pp::print_encrypted_dns_proxy_configs(&configs);
+            }
+            EncryptedDnsProxyCommands::AddResolver { name, addrs } => {
+                let mut resolvers = rpc
+                    .get_settings()
+                    .await?
+                    .api_access_methods
+                    .doh_resolvers()
+                    .to_vec();
+                resolvers.retain(|resolver| resolver.name != name);
+                resolvers.push(DohResolver { name, addrs });
+                rpc.set_encrypted_dns_proxy_resolvers(resolvers).await?;
+            }
+            EncryptedDnsProxyCommands::RemoveResolver { name } => {
+                let mut resolvers = rpc
+                    .get_settings()
+                    .await?
+                    .api_access_methods
+                    .doh_resolvers()
+                    .to_vec();
+                let count = resolvers.len();
+                resolvers.retain(|resolver| resolver.name != name);
+                if resolvers.len() == count {
+                    return Err(anyhow!("No DoH resolver named {name} has been added"));
+                }
+                rpc.set_encrypted_dns_proxy_resolvers(resolvers).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Inspect and configure the Tor access method.
+    async fn tor(cmd: TorCommands) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut tor_settings = rpc
+            .get_settings()
+            .await?
+            .api_access_methods
+            .tor_settings()
+            .clone();
+        match cmd {
+            TorCommands::Get => {
+                if format::is_json() {
+                    return format::print_json(&tor_settings);
+                }
+                pp::print_tor_settings(&tor_settings);
+                return Ok(());
+            }
+            TorCommands::SetBridge { line } => {
+                if bridge_addr(&line).is_none() {
+                    return Err(anyhow!("Invalid bridge line: {line}"));
+                }
+                tor_settings.bridge = Some(line);
+            }
+            TorCommands::ClearBridge => tor_settings.bridge = None,
+        }
+        rpc.set_tor_settings(tor_settings).await?;
+        Ok(())
+    }
+
     /// Show all API access methods.
     async fn list() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        for (index, api_access_method) in rpc.get_api_access_methods().await?.iter().enumerate() {
+        let access_methods = rpc.get_api_access_methods().await?;
+        if format::is_json() {
+            return format::print_json(&access_methods);
+        }
+        for (index, api_access_method) in access_methods.iter().enumerate() {
             println!(
                 "{}. {}",
                 index + 1,
@@ -153,6 +325,9 @@ impl ApiAccess {
                         }
                     })
                 }
+                CustomProxy::HttpConnect(http) => {
+                    AccessMethod::from(cmd.params.merge_http_connect(&http)?)
+                }
             },
         };
 
@@ -238,6 +413,19 @@ impl ApiAccess {
         Ok(())
     }
 
+    /// Show the statistics of all API access methods.
+    async fn status() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let statuses = rpc.get_api_access_method_statistics().await?;
+        if format::is_json() {
+            return format::print_json(&statuses);
+        }
+        for (index, status) in statuses.iter().enumerate() {
+            pp::print_status(index + 1, status);
+        }
+        Ok(())
+    }
+
     async fn get_access_method(
         rpc: &mut MullvadProxyClient,
         item: &SelectItem,
@@ -266,6 +454,17 @@ pub enum AddCustomCommands {
         #[clap(flatten)]
         add: ShadowsocksAdd,
     },
+    /// Configure an HTTP proxy that supports the CONNECT method
+    HttpConnect {
+        /// An easy to remember name for this custom proxy
+        name: String,
+        /// Disable the use of this custom access method. It has to be manually
+        /// enabled at a later stage to be used when accessing the Mullvad API.
+        #[arg(default_value_t = false, short, long)]
+        disabled: bool,
+        #[clap(flatten)]
+        add: HttpConnectAdd,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -298,6 +497,7 @@ impl AddCustomCommands {
     fn name(&self) -> &str {
         match self {
             AddCustomCommands::Shadowsocks { name, .. }
+            | AddCustomCommands::HttpConnect { name, .. }
             | AddCustomCommands::Socks5(AddSocks5Commands::Remote { name, .. })
             | AddCustomCommands::Socks5(AddSocks5Commands::Local { name, .. }) => name,
         }
@@ -306,6 +506,7 @@ impl AddCustomCommands {
     fn enabled(&self) -> bool {
         match self {
             AddCustomCommands::Shadowsocks { disabled, .. }
+            | AddCustomCommands::HttpConnect { disabled, .. }
             | AddCustomCommands::Socks5(AddSocks5Commands::Remote { disabled, .. })
             | AddCustomCommands::Socks5(AddSocks5Commands::Local { disabled, .. }) => !disabled,
         }
@@ -401,6 +602,9 @@ mod conversions {
                         add.password,
                     ),
                 )),
+                AddCustomCommands::HttpConnect { add, .. } => Ok(daemon_types::AccessMethod::from(
+                    talpid_types::HttpConnect::try_from(add)?,
+                )),
             }
         }
     }
@@ -408,8 +612,75 @@ mod conversions {
 
 /// Pretty printing of [`AccessMethodSetting`]s
 mod pp {
-    use crate::cmds::proxies::pp::CustomProxyFormatter;
-    use mullvad_types::access_method::{AccessMethod, AccessMethodSetting};
+    use crate::{cmds::proxies::pp::CustomProxyFormatter, print_option};
+    use mullvad_types::access_method::{
+        AccessMethod, AccessMethodSetting, AccessMethodStatus, DohResolver,
+        EncryptedDnsProxyConfigInfo, TorSettings,
+    };
+
+    pub fn print_tor_settings(tor_settings: &TorSettings) {
+        println!("Bridge:");
+        match &tor_settings.bridge {
+            Some(line) => print_option!(line),
+            None => print_option!("None (the Tor access method is not used)"),
+        }
+    }
+
+    pub fn print_doh_resolvers(resolvers: &[DohResolver]) {
+        println!("DoH resolvers (in addition to the default ones):");
+        if resolvers.is_empty() {
+            print_option!("None");
+        }
+        for resolver in resolvers {
+            let addrs: Vec<_> = resolver.addrs.iter().map(|addr| addr.to_string()).collect();
+            print_option!(resolver.name, addrs.join(", "));
+        }
+    }
+
+    pub fn print_encrypted_dns_proxy_configs(configs: &[EncryptedDnsProxyConfigInfo]) {
+        println!("Cached proxy configurations:");
+        if configs.is_empty() {
+            print_option!("None");
+        }
+        for config in configs {
+            println!("{}", config.endpoint);
+            print_option!(
+                "Obfuscation",
+                config.obfuscation.as_deref().unwrap_or("none")
+            );
+            print_option!("Fetched", config.fetched.with_timezone(&chrono::Local));
+            if let Some(last_success) = config.last_success {
+                print_option!("Last success", last_success.with_timezone(&chrono::Local));
+            }
+            print_option!("Expires", config.expires.with_timezone(&chrono::Local));
+        }
+    }
+
+    /// Print the statistics of an access method, prefixed by its position in the list.
+    pub fn print_status(position: usize, status: &AccessMethodStatus) {
+        let enabled = if status.enabled { " *" } else { "" };
+        let active = if status.active { " (in use)" } else { "" };
+        println!("{position}. {}{enabled}{active}", status.name);
+
+        let statistics = &status.statistics;
+        print_option!("Successes", statistics.successes);
+        print_option!(
+            "Failures",
+            format!(
+                "{} ({} since last success)",
+                statistics.failures, statistics.consecutive_failures
+            )
+        );
+        if let Some(last_success) = statistics.last_success {
+            print_option!("Last success", last_success.with_timezone(&chrono::Local));
+        }
+        if let Some(last_failure) = statistics.last_failure {
+            print_option!("Last failure", last_failure.with_timezone(&chrono::Local));
+        }
+        if let Some(latency) = statistics.latency {
+            print_option!("Request time", format!("{} ms", latency.as_millis()));
+        }
+    }
 
     pub struct ApiAccessMethodFormatter<'a> {
         api_access_method: &'a AccessMethodSetting,
@@ -1,4 +1,5 @@
 use super::{relay::resolve_location_constraint, relay_constraints::LocationArgs};
+use crate::format;
 use anyhow::{anyhow, bail, Result};
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
@@ -86,8 +87,12 @@ impl CustomList {
     /// Print all custom lists.
     async fn list() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
+        let custom_lists = rpc.get_settings().await?.custom_lists;
+        if format::is_json() {
+            return format::print_json(&*custom_lists);
+        }
         let cache = rpc.get_relay_locations().await?;
-        for custom_list in rpc.get_settings().await?.custom_lists {
+        for custom_list in custom_lists {
             Self::print_custom_list(&custom_list, &cache)
         }
         Ok(())
@@ -4,11 +4,24 @@ use mullvad_types::{
     constraints::Constraint,
     relay_constraints::{RelayConstraints, RelaySettings},
 };
+use std::time::Duration;
 
 #[derive(clap::Subcommand, Debug)]
 pub enum DebugCommands {
     /// Block all internet connection by setting an invalid relay constraint.
     BlockConnection,
+
+    /// Measure latency, jitter and throughput through the tunnel.
+    /// Relays that perform well are preferred when selecting relays.
+    Speedtest {
+        /// Speed test server to measure against, as `host:port`
+        #[arg(long)]
+        endpoint: String,
+
+        /// How many seconds to measure throughput in each direction
+        #[arg(long, default_value_t = 5)]
+        duration: u64,
+    },
 }
 
 impl DebugCommands {
@@ -41,6 +54,31 @@ impl DebugCommands {
                 eprintln!("WARNING: ENTERED BLOCKED MODE");
                 Ok(())
             }
+            DebugCommands::Speedtest { endpoint, duration } => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                println!("Running speed test...");
+                let result = rpc
+                    .run_speed_test(endpoint, Duration::from_secs(duration))
+                    .await?;
+
+                if let Some(relay) = &result.relay {
+                    println!("{:<12}{relay}", "Relay:");
+                }
+                println!("{:<12}{}", "Server:", result.endpoint);
+                println!("{:<12}{} ms", "Latency:", result.latency.as_millis());
+                println!("{:<12}{} ms", "Jitter:", result.jitter.as_millis());
+                println!(
+                    "{:<12}{:.2} Mbit/s",
+                    "Download:",
+                    result.download_bps as f64 / 1_000_000.0
+                );
+                println!(
+                    "{:<12}{:.2} Mbit/s",
+                    "Upload:",
+                    result.upload_bps as f64 / 1_000_000.0
+                );
+                Ok(())
+            }
         }
     }
 }
@@ -0,0 +1,29 @@
+use anyhow::Result;
+use mullvad_management_interface::MullvadProxyClient;
+
+/// Print the hooks that the daemon runs when the tunnel state, device or settings change.
+pub async fn print() -> Result<()> {
+    let mut rpc = MullvadProxyClient::new().await?;
+    let hooks = rpc.get_event_hooks().await?;
+
+    if hooks.is_empty() {
+        println!("No event hooks are configured");
+        return Ok(());
+    }
+
+    for hook in hooks {
+        let events = if hook.events.is_empty() {
+            "all".to_owned()
+        } else {
+            hook.events
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        println!("{}", hook.path.display());
+        println!("{:<4}events: {events}", " ");
+        println!("{:<4}timeout: {} s", " ", hook.timeout_secs);
+    }
+    Ok(())
+}
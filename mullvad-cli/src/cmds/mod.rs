@@ -9,14 +9,19 @@ pub mod bridge;
 pub mod custom_list;
 pub mod debug;
 pub mod dns;
+pub mod hooks;
 pub mod lan;
 pub mod lockdown;
+pub mod network_rules;
 pub mod obfuscation;
 pub mod patch;
+pub mod policy;
+pub mod profile;
 pub mod proxies;
 pub mod relay;
 pub mod relay_constraints;
 pub mod reset;
+pub mod settings;
 pub mod split_tunnel;
 pub mod status;
 pub mod tunnel;
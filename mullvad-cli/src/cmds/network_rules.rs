@@ -0,0 +1,238 @@
+use anyhow::{anyhow, Result};
+use clap::{Args, Subcommand};
+use ipnetwork::IpNetwork;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::network_rules::{
+    NetworkAction, NetworkMatcher, NetworkRule, NetworkRulesSettings,
+};
+
+use super::{receive_confirmation, BooleanOption};
+
+#[derive(Subcommand, Debug)]
+pub enum NetworkRules {
+    /// Display the current network rules
+    Get,
+    /// Display the network that this device is currently connected to
+    Current,
+    /// Add a rule for a network. Rules are matched in the order they were added
+    Add {
+        #[clap(flatten)]
+        matcher: MatcherArgs,
+        /// What to do when connected to the network
+        action: NetworkAction,
+    },
+    /// Remove a rule, given its index as shown by 'get'
+    Remove { index: usize },
+    /// Save a network as known, without adding a rule for it
+    Save {
+        #[clap(flatten)]
+        matcher: MatcherArgs,
+    },
+    /// Forget a known network, given its index as shown by 'get'
+    Forget { index: usize },
+    /// Set what to do on known networks that no rule matches. Omit the action to do nothing
+    SetOther { action: Option<NetworkAction> },
+    /// Set what to do on networks that have not been saved and that no rule matches. Omit the
+    /// action to do nothing
+    SetUnknown { action: Option<NetworkAction> },
+    /// Allow rules to disconnect the tunnel. Networks are identified by properties that any
+    /// network can copy, so a hostile network can pose as one that disconnects the tunnel.
+    /// Disconnecting is not allowed by default
+    AllowDisconnect { policy: BooleanOption },
+    /// Remove all rules and actions
+    Clear {
+        /// Clear without asking for confirmation
+        #[arg(long, short = 'y', default_value_t = false)]
+        confirm: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+#[group(required = true, multiple = false)]
+pub struct MatcherArgs {
+    /// UUID of a NetworkManager connection
+    #[arg(long)]
+    connection_id: Option<String>,
+    /// SSID of a Wi-Fi network
+    #[arg(long)]
+    ssid: Option<String>,
+    /// Hardware address of the default gateway
+    #[arg(long)]
+    gateway_mac: Option<String>,
+    /// Subnet that the default gateway is in
+    #[arg(long)]
+    subnet: Option<IpNetwork>,
+}
+
+impl From<MatcherArgs> for NetworkMatcher {
+    fn from(args: MatcherArgs) -> Self {
+        if let Some(id) = args.connection_id {
+            NetworkMatcher::ConnectionId(id)
+        } else if let Some(ssid) = args.ssid {
+            NetworkMatcher::Ssid(ssid)
+        } else if let Some(mac) = args.gateway_mac {
+            NetworkMatcher::GatewayMac(mac)
+        } else if let Some(subnet) = args.subnet {
+            NetworkMatcher::Subnet(subnet)
+        } else {
+            unreachable!("clap requires exactly one matcher")
+        }
+    }
+}
+
+impl NetworkRules {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            NetworkRules::Get => Self::get().await,
+            NetworkRules::Current => Self::current().await,
+            NetworkRules::Clear { confirm } => {
+                if confirm
+                    || receive_confirmation(
+                        "Are you sure you want to clear all network rules?",
+                        true,
+                    )
+                    .await
+                {
+                    Self::update(|settings| *settings = NetworkRulesSettings::default()).await?;
+                    println!("Cleared all network rules");
+                }
+                Ok(())
+            }
+            NetworkRules::Add { matcher, action } => {
+                let rule = NetworkRule {
+                    matcher: NetworkMatcher::from(matcher),
+                    action,
+                };
+                let mut allow_disconnect = false;
+                Self::update(|settings| {
+                    settings.rules.push(rule.clone());
+                    allow_disconnect = settings.allow_disconnect;
+                })
+                .await?;
+                println!("Added rule: {rule}");
+                Self::warn_if_ignored(action, allow_disconnect);
+                Ok(())
+            }
+            NetworkRules::Remove { index } => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                let mut network_rules = rpc.get_settings().await?.network_rules;
+                if index >= network_rules.rules.len() {
+                    return Err(anyhow!("There is no rule with index {index}"));
+                }
+                let rule = network_rules.rules.remove(index);
+                rpc.set_network_rules(network_rules).await?;
+                println!("Removed rule: {rule}");
+                Ok(())
+            }
+            NetworkRules::Save { matcher } => {
+                let matcher = NetworkMatcher::from(matcher);
+                Self::update(|settings| settings.known_networks.push(matcher.clone())).await?;
+                println!("Saved known network: {matcher}");
+                Ok(())
+            }
+            NetworkRules::Forget { index } => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                let mut network_rules = rpc.get_settings().await?.network_rules;
+                if index >= network_rules.known_networks.len() {
+                    return Err(anyhow!("There is no known network with index {index}"));
+                }
+                let matcher = network_rules.known_networks.remove(index);
+                rpc.set_network_rules(network_rules).await?;
+                println!("Forgot known network: {matcher}");
+                Ok(())
+            }
+            NetworkRules::SetOther { action } => {
+                let mut allow_disconnect = false;
+                Self::update(|settings| {
+                    settings.other_networks = action;
+                    allow_disconnect = settings.allow_disconnect;
+                })
+                .await?;
+                println!("Updated action for other networks");
+                if let Some(action) = action {
+                    Self::warn_if_ignored(action, allow_disconnect);
+                }
+                Ok(())
+            }
+            NetworkRules::SetUnknown { action } => {
+                let mut allow_disconnect = false;
+                Self::update(|settings| {
+                    settings.unknown_networks = action;
+                    allow_disconnect = settings.allow_disconnect;
+                })
+                .await?;
+                println!("Updated action for unknown networks");
+                if let Some(action) = action {
+                    Self::warn_if_ignored(action, allow_disconnect);
+                }
+                Ok(())
+            }
+            NetworkRules::AllowDisconnect { policy } => {
+                Self::update(|settings| settings.allow_disconnect = *policy).await?;
+                println!("Changed whether network rules may disconnect the tunnel");
+                Ok(())
+            }
+        }
+    }
+
+    fn warn_if_ignored(action: NetworkAction, allow_disconnect: bool) {
+        if action == NetworkAction::Disconnect && !allow_disconnect {
+            eprintln!(
+                "Warning: Disconnecting is not allowed, so this action is ignored. See \
+                 'mullvad network-rules allow-disconnect --help'"
+            );
+        }
+    }
+
+    async fn update(update_fn: impl FnOnce(&mut NetworkRulesSettings)) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut network_rules = rpc.get_settings().await?.network_rules;
+        update_fn(&mut network_rules);
+        rpc.set_network_rules(network_rules).await?;
+        Ok(())
+    }
+
+    async fn get() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let network_rules = rpc.get_settings().await?.network_rules;
+
+        println!("Rules:");
+        if network_rules.rules.is_empty() {
+            println!("\tnone");
+        }
+        for (index, rule) in network_rules.rules.iter().enumerate() {
+            println!("\t{index}: {rule}");
+        }
+        println!("Known networks:");
+        if network_rules.known_networks.is_empty() {
+            println!("\tnone");
+        }
+        for (index, matcher) in network_rules.known_networks.iter().enumerate() {
+            println!("\t{index}: {matcher}");
+        }
+        let format_action = |action: Option<NetworkAction>| match action {
+            Some(action) => action.to_string(),
+            None => "nothing".to_string(),
+        };
+        println!(
+            "Other networks: {}",
+            format_action(network_rules.other_networks)
+        );
+        println!(
+            "Unknown networks: {}",
+            format_action(network_rules.unknown_networks)
+        );
+        println!(
+            "Allow disconnect: {}",
+            BooleanOption::from(network_rules.allow_disconnect)
+        );
+        Ok(())
+    }
+
+    async fn current() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let network = rpc.get_current_network().await?;
+        println!("Current network: {network}");
+        Ok(())
+    }
+}
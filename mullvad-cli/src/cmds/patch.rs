@@ -10,7 +10,10 @@ use std::{
 ///
 /// * If `source` is "-", read the patch from standard input
 /// * Otherwise, interpret `source` as a filepath and read from the provided file
-pub async fn import(source: String) -> Result<()> {
+///
+/// If `dry_run` is set, the patch is only validated and the settings it would change are
+/// printed.
+pub async fn import(source: String, dry_run: bool) -> Result<()> {
     let json_blob = tokio::task::spawn_blocking(move || match source.as_str() {
         "-" => read_to_string(BufReader::new(stdin())).context("Failed to read from stdin"),
         _ => read_to_string(File::open(&source)?)
@@ -20,6 +23,21 @@ pub async fn import(source: String) -> Result<()> {
     .unwrap()?;
 
     let mut rpc = MullvadProxyClient::new().await?;
+
+    if dry_run {
+        let changes = rpc
+            .diff_json_settings(json_blob)
+            .await
+            .context("Error validating patch")?;
+        if changes.is_empty() {
+            println!("No settings would change");
+        }
+        for change in changes {
+            println!("{change}");
+        }
+        return Ok(());
+    }
+
     rpc.apply_json_settings(json_blob)
         .await
         .context("Error applying patch")?;
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::{policy::LockedSetting, settings::DnsState};
+
+/// Print the settings that are locked by the administrator policy, and their values.
+pub async fn print() -> Result<()> {
+    let mut rpc = MullvadProxyClient::new().await?;
+    let policy = rpc.get_settings_policy().await?;
+
+    let locked_settings = policy.locked_settings();
+    if locked_settings.is_empty() {
+        println!("No settings are locked by the administrator policy");
+        return Ok(());
+    }
+
+    println!("Settings locked by the administrator policy:");
+    for setting in locked_settings {
+        let value = match setting {
+            LockedSetting::LockdownMode => {
+                on_off(policy.block_when_disconnected.unwrap_or_default()).to_owned()
+            }
+            LockedSetting::AllowLan => {
+                if policy.allow_lan.unwrap_or_default() {
+                    "allow".to_owned()
+                } else {
+                    "block".to_owned()
+                }
+            }
+            LockedSetting::Dns => match policy.dns_options.as_ref().map(|dns| &dns.state) {
+                Some(DnsState::Custom) => "custom".to_owned(),
+                _ => "default".to_owned(),
+            },
+            LockedSetting::RelayLocation => policy
+                .allowed_locations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; "),
+        };
+        println!("{:22}: {value}", setting.to_string());
+    }
+    Ok(())
+}
+
+fn on_off(state: bool) -> &'static str {
+    if state {
+        "on"
+    } else {
+        "off"
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::Result;
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::profile::{ProfileField, SettingsProfile};
+
+#[derive(Subcommand, Debug)]
+pub enum Profile {
+    /// List all settings profiles
+    List,
+    /// Save the current settings as a new profile
+    Create {
+        /// Name of the profile
+        name: String,
+        /// Settings to include in the profile. By default, all settings that a profile can
+        /// contain are included
+        #[arg(long, value_delimiter = ',')]
+        include: Vec<ProfileField>,
+    },
+    /// Apply the settings in a profile
+    Use {
+        /// Name of the profile
+        name: String,
+    },
+    /// Delete a profile
+    Delete {
+        /// Name of the profile
+        name: String,
+    },
+}
+
+impl Profile {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            Profile::List => Self::list().await,
+            Profile::Create { name, include } => Self::create(name, include).await,
+            Profile::Use { name } => Self::use_profile(name).await,
+            Profile::Delete { name } => Self::delete(name).await,
+        }
+    }
+
+    async fn list() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let profiles = rpc.get_settings_profiles().await?;
+        if profiles.is_empty() {
+            println!("No profiles");
+        }
+        for profile in profiles {
+            println!("{profile}");
+        }
+        Ok(())
+    }
+
+    async fn create(name: String, include: Vec<ProfileField>) -> Result<()> {
+        let fields = if include.is_empty() {
+            ProfileField::ALL.to_vec()
+        } else {
+            include
+        };
+        let mut rpc = MullvadProxyClient::new().await?;
+        let settings = rpc.get_settings().await?;
+        let profile = SettingsProfile::capture(name, &settings, &fields);
+        let description = profile.to_string();
+        rpc.create_settings_profile(profile).await?;
+        println!("Created profile {description}");
+        Ok(())
+    }
+
+    async fn use_profile(name: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.apply_settings_profile(name.clone()).await?;
+        println!("Applied profile {name}");
+        Ok(())
+    }
+
+    async fn delete(name: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.delete_settings_profile(name.clone()).await?;
+        println!("Deleted profile {name}");
+        Ok(())
+    }
+}
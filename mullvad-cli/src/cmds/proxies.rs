@@ -1,7 +1,10 @@
 use clap::Args;
 use std::net::{IpAddr, SocketAddr};
 use talpid_types::net::{
-    proxy::{Shadowsocks, Socks5Local, Socks5Remote, SocksAuth, SHADOWSOCKS_CIPHERS},
+    proxy::{
+        HttpAuth, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote, SocksAuth,
+        SHADOWSOCKS_CIPHERS,
+    },
     Endpoint, TransportProtocol,
 };
 
@@ -89,6 +92,46 @@ impl From<ShadowsocksAdd> for Shadowsocks {
     }
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct HttpConnectAdd {
+    /// The IP of the remote HTTP proxy
+    pub remote_ip: IpAddr,
+    /// The port of the remote HTTP proxy
+    pub remote_port: u16,
+    /// Connect to the proxy over TLS, and verify its certificate against this name
+    #[arg(long)]
+    pub tls_server_name: Option<String>,
+
+    #[clap(flatten)]
+    pub authentication: Option<HttpAuthentication>,
+}
+
+impl TryFrom<HttpConnectAdd> for HttpConnect {
+    type Error = Error;
+    fn try_from(add: HttpConnectAdd) -> Result<Self, Self::Error> {
+        let auth = add
+            .authentication
+            .map(|auth| HttpAuth::new(auth.username, auth.password))
+            .transpose()?;
+        Ok(HttpConnect::new(
+            (add.remote_ip, add.remote_port),
+            add.tls_server_name,
+            auth,
+        ))
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+#[group(requires_all = ["username", "password"])] // https://github.com/clap-rs/clap/issues/5092
+pub struct HttpAuthentication {
+    /// Username for Basic authentication against a remote HTTP proxy
+    #[arg(short, long, required = false)]
+    pub username: String,
+    /// Password for Basic authentication against a remote HTTP proxy
+    #[arg(short, long, required = false)]
+    pub password: String,
+}
+
 #[derive(Args, Debug, Clone)]
 #[group(requires_all = ["username", "password"])] // https://github.com/clap-rs/clap/issues/5092
 pub struct SocksAuthentication {
@@ -102,19 +145,19 @@ pub struct SocksAuthentication {
 
 #[derive(Args, Debug, Clone)]
 pub struct ProxyEditParams {
-    /// Username for authentication \[Socks5 (Remote proxy)\]
+    /// Username for authentication \[Socks5 (Remote proxy), HTTP\]
     #[arg(long)]
     pub username: Option<String>,
-    /// Password for authentication \[Socks5 (Remote proxy), Shadowsocks\]
+    /// Password for authentication \[Socks5 (Remote proxy), Shadowsocks, HTTP\]
     #[arg(long)]
     pub password: Option<String>,
     /// Cipher to use \[Shadowsocks\]
     #[arg(value_parser = SHADOWSOCKS_CIPHERS, long)]
     pub cipher: Option<String>,
-    /// The IP of the remote proxy server \[Socks5 (Local & Remote proxy), Shadowsocks\]
+    /// The IP of the remote proxy server \[Socks5 (Local & Remote proxy), Shadowsocks, HTTP\]
     #[arg(long)]
     pub ip: Option<IpAddr>,
-    /// The port of the remote proxy server \[Socks5 (Local & Remote proxy), Shadowsocks\]
+    /// The port of the remote proxy server \[Socks5 (Local & Remote proxy), Shadowsocks, HTTP\]
     #[arg(long)]
     pub port: Option<u16>,
     /// The port that the server on localhost is listening on \[Socks5 (Local proxy)\]
@@ -123,6 +166,9 @@ pub struct ProxyEditParams {
     /// The transport protocol used by the remote proxy \[Socks5 (Local proxy)\]
     #[arg(long)]
     pub transport_protocol: Option<TransportProtocol>,
+    /// The name to verify the certificate of a TLS proxy against \[HTTP\]
+    #[arg(long)]
+    pub tls_server_name: Option<String>,
 }
 
 impl ProxyEditParams {
@@ -172,6 +218,28 @@ impl ProxyEditParams {
         let cipher = self.cipher.unwrap_or(shadowsocks.cipher.to_owned());
         Shadowsocks::new((ip, port), cipher, password)
     }
+
+    pub fn merge_http_connect(self, http: &HttpConnect) -> Result<HttpConnect, Error> {
+        let ip = self.ip.unwrap_or(http.endpoint.ip());
+        let port = self.port.unwrap_or(http.endpoint.port());
+        let tls_server_name = self.tls_server_name.or(http.tls_server_name.clone());
+        let auth = match &http.auth {
+            None => match (self.username, self.password) {
+                (Some(username), Some(password)) => Some(HttpAuth::new(username, password)?),
+                (None, None) => None,
+                _ => {
+                    println!("HTTP proxy does not have a username and password set already, so you must provide both or neither when you edit.");
+                    None
+                }
+            },
+            Some(credentials) => {
+                let username = self.username.unwrap_or(credentials.username().to_string());
+                let password = self.password.unwrap_or(credentials.password().to_string());
+                Some(HttpAuth::new(username, password)?)
+            }
+        };
+        Ok(HttpConnect::new((ip, port), tls_server_name, auth))
+    }
 }
 
 pub mod pp {
@@ -212,6 +280,20 @@ pub mod pp {
                     print_option!("Local port", local.local_port);
                     Ok(())
                 }
+                CustomProxy::HttpConnect(http) => {
+                    match &http.tls_server_name {
+                        Some(server_name) => {
+                            print_option!("Protocol", format!("HTTP CONNECT [TLS, {server_name}]"))
+                        }
+                        None => print_option!("Protocol", "HTTP CONNECT"),
+                    }
+                    print_option!("Peer", http.endpoint);
+                    if let Some(credentials) = &http.auth {
+                        print_option!("Username", credentials.username());
+                        print_option!("Password", credentials.password());
+                    }
+                    Ok(())
+                }
             }
         }
     }
@@ -17,13 +17,14 @@ use std::{
     collections::HashMap,
     io::BufRead,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
 };
 use talpid_types::net::{
     all_of_the_internet, openvpn, wireguard, Endpoint, IpVersion, TransportProtocol, TunnelType,
 };
 
 use super::{relay_constraints::LocationArgs, BooleanOption};
-use crate::{cmds::receive_confirmation, print_option};
+use crate::{cmds::receive_confirmation, format, print_option};
 
 #[derive(Subcommand, Debug)]
 pub enum Relay {
@@ -40,9 +41,20 @@ pub enum Relay {
     /// Update the relay list
     Update,
 
+    /// Load a signed relay list from a file, e.g. when the API cannot be reached. The list must be
+    /// signed by a key that is trusted by the app
+    Import {
+        /// Path to a signed relay list, or "-" to read from standard input
+        source: String,
+    },
+
     /// Override options for individual relays/servers
     #[clap(subcommand)]
     Override(OverrideCommands),
+
+    /// Automatically reconnect to a different exit relay after some time or amount of traffic
+    #[clap(subcommand)]
+    Rotation(RotationCommands),
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -258,14 +270,37 @@ pub enum OverrideUnsetCommands {
     Ipv6 { hostname: String },
 }
 
+#[derive(Subcommand, Debug, Clone)]
+pub enum RotationCommands {
+    /// Show the current relay rotation policy
+    Get,
+    /// Change the relay rotation policy. Rotation happens when either limit is reached
+    #[clap(arg_required_else_help = true)]
+    Set {
+        /// Rotate after being connected to the same relay for this many minutes, or 'any'
+        #[arg(long)]
+        interval: Option<Constraint<u32>>,
+        /// Rotate after this many gigabytes have been transferred through the tunnel, or 'any'
+        #[arg(long)]
+        traffic: Option<Constraint<u32>>,
+        /// Require the new exit relay to be in a different city
+        #[arg(long)]
+        change_city: Option<BooleanOption>,
+    },
+    /// Disable relay rotation
+    Disable,
+}
+
 impl Relay {
     pub async fn handle(self) -> Result<()> {
         match self {
             Relay::Get => Self::get().await,
             Relay::List => Self::list().await,
             Relay::Update => Self::update().await,
+            Relay::Import { source } => Self::import(source).await,
             Relay::Set(subcmd) => Self::set(subcmd).await,
             Relay::Override(subcmd) => Self::r#override(subcmd).await,
+            Relay::Rotation(subcmd) => Self::rotation(subcmd).await,
         }
     }
 
@@ -274,6 +309,10 @@ impl Relay {
         let settings = rpc.get_settings().await?;
         let relay_settings = settings.relay_settings;
 
+        if format::is_json() {
+            return format::print_json(&relay_settings);
+        }
+
         match relay_settings {
             RelaySettings::CustomTunnelEndpoint(endpoint) => {
                 println!("Custom endpoint: {endpoint}")
@@ -345,14 +384,23 @@ impl Relay {
     async fn list() -> Result<()> {
         let mut countries = get_active_relays().await?;
         countries.sort_by(|c1, c2| natord::compare_ignore_case(&c1.name, &c2.name));
-        for mut country in countries {
+        for country in &mut countries {
             country
                 .cities
                 .sort_by(|c1, c2| natord::compare_ignore_case(&c1.name, &c2.name));
-            println!("{} ({})", country.name, country.code);
-            for mut city in country.cities {
+            for city in &mut country.cities {
                 city.relays
                     .sort_by(|r1, r2| natord::compare_ignore_case(&r1.hostname, &r2.hostname));
+            }
+        }
+
+        if format::is_json() {
+            return format::print_json(&countries);
+        }
+
+        for country in countries {
+            println!("{} ({})", country.name, country.code);
+            for city in country.cities {
                 println!(
                     "\t{} ({}) @ {:.5}°N, {:.5}°W",
                     city.name, city.code, city.latitude, city.longitude
@@ -395,6 +443,24 @@ impl Relay {
         Ok(())
     }
 
+    async fn import(source: String) -> Result<()> {
+        let signed_relay_list = tokio::task::spawn_blocking(move || match source.as_str() {
+            "-" => std::io::read_to_string(std::io::stdin()).context("Failed to read from stdin"),
+            _ => std::fs::read_to_string(&source)
+                .context(format!("Failed to read from path: {source}")),
+        })
+        .await
+        .unwrap()?;
+
+        MullvadProxyClient::new()
+            .await?
+            .import_relay_list(signed_relay_list)
+            .await
+            .context("Failed to import relay list")?;
+        println!("Imported relay list");
+        Ok(())
+    }
+
     /// Get active relays which are not bridges.
 
     async fn update_constraints(update_fn: impl FnOnce(&mut RelayConstraints)) -> Result<()> {
@@ -890,6 +956,73 @@ impl Relay {
         }
         Ok(())
     }
+
+    async fn rotation(subcmd: RotationCommands) -> Result<()> {
+        const BYTES_PER_GIGABYTE: u64 = 1_000_000_000;
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut relay_rotation = rpc.get_settings().await?.relay_rotation;
+
+        match subcmd {
+            RotationCommands::Get => {
+                print_option!(
+                    "Interval",
+                    match relay_rotation.interval {
+                        Some(interval) => format!("{} minutes", interval.as_secs() / 60),
+                        None => "any".to_string(),
+                    },
+                );
+                print_option!(
+                    "Traffic",
+                    match relay_rotation.traffic_limit {
+                        Some(limit) => format!("{} GB", limit / BYTES_PER_GIGABYTE),
+                        None => "any".to_string(),
+                    },
+                );
+                print_option!(
+                    "Change city",
+                    if relay_rotation.change_city {
+                        "on"
+                    } else {
+                        "off"
+                    },
+                );
+                return Ok(());
+            }
+            RotationCommands::Set {
+                interval,
+                traffic,
+                change_city,
+            } => {
+                if let Some(interval) = interval {
+                    relay_rotation.interval = interval
+                        .option()
+                        .map(|minutes| Duration::from_secs(u64::from(minutes) * 60));
+                }
+                if let Some(traffic) = traffic {
+                    relay_rotation.traffic_limit = traffic
+                        .option()
+                        .map(|gigabytes| u64::from(gigabytes) * BYTES_PER_GIGABYTE);
+                }
+                if let Some(change_city) = change_city {
+                    relay_rotation.change_city = *change_city;
+                }
+                if relay_rotation.interval == Some(Duration::ZERO)
+                    || relay_rotation.traffic_limit == Some(0)
+                {
+                    bail!("Rotation limits must be positive");
+                }
+            }
+            RotationCommands::Disable => {
+                relay_rotation.interval = None;
+                relay_rotation.traffic_limit = None;
+            }
+        }
+
+        rpc.set_relay_rotation_settings(relay_rotation).await?;
+        println!("Updated relay rotation policy");
+        Ok(())
+    }
 }
 
 fn parse_transport_port(
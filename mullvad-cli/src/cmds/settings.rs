@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+use std::{
+    fs::File,
+    io::{read_to_string, stdin, BufReader},
+};
+
+#[derive(Subcommand, Debug)]
+pub enum Settings {
+    /// Export a backup of all settings
+    #[clap(arg_required_else_help = true)]
+    Export {
+        /// File to write to. If this is "-", write to standard output
+        file: String,
+    },
+
+    /// Replace all settings with the ones in a backup created by 'settings export'
+    #[clap(arg_required_else_help = true)]
+    Import {
+        /// File to read from. If this is "-", read from standard input
+        file: String,
+    },
+}
+
+impl Settings {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            Settings::Export { file } => Self::export(file).await,
+            Settings::Import { file } => Self::import(file).await,
+        }
+    }
+
+    async fn export(dest: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let blob = rpc
+            .export_settings_backup()
+            .await
+            .context("Error exporting settings")?;
+
+        match dest.as_str() {
+            "-" => {
+                println!("{blob}");
+                Ok(())
+            }
+            _ => tokio::fs::write(&dest, blob)
+                .await
+                .context(format!("Failed to write to path {dest}")),
+        }
+    }
+
+    async fn import(source: String) -> Result<()> {
+        let blob = tokio::task::spawn_blocking(move || match source.as_str() {
+            "-" => read_to_string(BufReader::new(stdin())).context("Failed to read from stdin"),
+            _ => read_to_string(File::open(&source)?)
+                .context(format!("Failed to read from path: {source}")),
+        })
+        .await
+        .unwrap()?;
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.import_settings_backup(blob)
+            .await
+            .context("Error importing settings")?;
+
+        println!("Settings restored");
+        Ok(())
+    }
+}
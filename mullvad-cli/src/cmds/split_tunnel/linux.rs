@@ -2,6 +2,8 @@ use anyhow::Result;
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 
+use crate::format;
+
 /// Manage split tunneling. To launch applications outside the tunnel, use the program
 /// 'mullvad-exclude' instead of this command
 #[derive(Subcommand, Debug)]
@@ -25,6 +27,10 @@ impl SplitTunnel {
                     .get_split_tunnel_processes()
                     .await?;
 
+                if format::is_json() {
+                    return format::print_json(&pids);
+                }
+
                 println!("Excluded PIDs:");
                 for pid in &pids {
                     println!("{pid}");
@@ -5,6 +5,7 @@ use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 
 use super::super::BooleanOption;
+use crate::format;
 
 /// Set options for applications to exclude from the tunnel.
 #[derive(Subcommand, Debug)]
@@ -34,6 +35,10 @@ impl SplitTunnel {
                 let mut rpc = MullvadProxyClient::new().await?;
                 let settings = rpc.get_settings().await?.split_tunnel;
 
+                if format::is_json() {
+                    return format::print_json(&settings);
+                }
+
                 let enable_exclusions = BooleanOption::from(settings.enable_exclusions);
 
                 println!("Split tunneling state: {enable_exclusions}");
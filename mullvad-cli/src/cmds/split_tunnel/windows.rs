@@ -6,8 +6,22 @@ use std::{
 
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::settings::SplitTunnelSettings;
+use serde::Serialize;
+use talpid_types::split_tunnel::ExcludedProcess;
 
 use super::super::BooleanOption;
+use crate::format;
+
+/// JSON output of `mullvad split-tunnel get`.
+#[derive(Serialize)]
+struct SplitTunnelOutput {
+    #[serde(flatten)]
+    settings: SplitTunnelSettings,
+    /// Only present if `--list-processes` is given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    excluded_processes: Option<Vec<ExcludedProcess>>,
+}
 
 /// Set options for applications to exclude from the tunnel.
 #[derive(Subcommand, Debug)]
@@ -43,6 +57,18 @@ impl SplitTunnel {
                 let mut rpc = MullvadProxyClient::new().await?;
                 let settings = rpc.get_settings().await?.split_tunnel;
 
+                if format::is_json() {
+                    let excluded_processes = if list_processes {
+                        Some(rpc.get_excluded_processes().await?)
+                    } else {
+                        None
+                    };
+                    return format::print_json(&SplitTunnelOutput {
+                        settings,
+                        excluded_processes,
+                    });
+                }
+
                 let enable_exclusions = BooleanOption::from(settings.enable_exclusions);
 
                 println!("Split tunneling state: {enable_exclusions}");
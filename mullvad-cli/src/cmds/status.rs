@@ -6,7 +6,7 @@ use mullvad_types::{device::DeviceState, states::TunnelState};
 use serde::Serialize;
 use std::fmt::Debug;
 
-use crate::format;
+use crate::format::{self, OutputFormat};
 
 #[derive(Subcommand, Debug, PartialEq)]
 pub enum Status {
@@ -29,6 +29,33 @@ pub struct StatusArgs {
     json: bool,
 }
 
+impl StatusArgs {
+    /// Resolve `--json` against the global `--format` flag. `--json` is the same as
+    /// `--format json`, so both conflict with `--verbose` and `--debug`.
+    pub fn resolve_format(
+        &mut self,
+        format: Option<OutputFormat>,
+    ) -> Result<OutputFormat, clap::Error> {
+        let conflict = |argument: &str, other: &str| {
+            clap::Error::raw(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!("the argument '{argument}' cannot be used with '{other}'\n"),
+            )
+        };
+        match format {
+            Some(OutputFormat::Text) if self.json => Err(conflict("--json", "--format text")),
+            Some(OutputFormat::Json) if self.debug => Err(conflict("--format json", "--debug")),
+            Some(OutputFormat::Json) if self.verbose => Err(conflict("--format json", "--verbose")),
+            Some(OutputFormat::Json) => {
+                self.json = true;
+                Ok(OutputFormat::Json)
+            }
+            _ if self.json => Ok(OutputFormat::Json),
+            _ => Ok(format.unwrap_or_default()),
+        }
+    }
+}
+
 impl Status {
     pub async fn listen(
         mut rpc: MullvadProxyClient,
@@ -68,6 +95,9 @@ impl Status {
                 DaemonEvent::NewAccessMethod(access_method) => {
                     print_debug_or_json(&args, "New access method", &access_method)?;
                 }
+                DaemonEvent::AccountExpiryWarning(warning) => {
+                    print_debug_or_json(&args, "Account expiry warning", &warning)?;
+                }
             }
         }
         Ok(())
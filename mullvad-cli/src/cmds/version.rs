@@ -1,8 +1,22 @@
 use anyhow::{Context, Result};
 use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::version::AppVersionInfo;
+use serde::Serialize;
+
+use crate::format;
+
+/// JSON output of `mullvad version`.
+#[derive(Serialize)]
+struct VersionOutput {
+    current_version: &'static str,
+    daemon_version: String,
+    version_info: AppVersionInfo,
+}
 
 pub async fn print() -> Result<()> {
-    println!("{:22}: {}", "Current version", mullvad_version::VERSION);
+    if !format::is_json() {
+        println!("{:22}: {}", "Current version", mullvad_version::VERSION);
+    }
 
     let mut rpc = MullvadProxyClient::new()
         .await
@@ -13,14 +27,23 @@ pub async fn print() -> Result<()> {
         .await
         .context("Failed to get current mullvad-daemon version")?;
 
-    if daemon_version != mullvad_version::VERSION {
-        println!("{:22}: {}", "mullvad-daemon version", daemon_version);
-    };
-
     let version_info = rpc
         .get_version_info()
         .await
         .context("Failed to get version info")?;
+
+    if format::is_json() {
+        return format::print_json(&VersionOutput {
+            current_version: mullvad_version::VERSION,
+            daemon_version,
+            version_info,
+        });
+    }
+
+    if daemon_version != mullvad_version::VERSION {
+        println!("{:22}: {}", "mullvad-daemon version", daemon_version);
+    };
+
     println!("{:22}: {}", "Is supported", version_info.supported);
 
     if let Some(suggested_upgrade) = version_info.suggested_upgrade {
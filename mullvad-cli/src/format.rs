@@ -1,15 +1,77 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::OnceLock};
 
+use anyhow::Context;
 use itertools::Itertools;
 use mullvad_types::{
     auth_failed::AuthFailed, features::FeatureIndicators, location::GeoIpLocation,
     states::TunnelState,
 };
+use serde::Serialize;
 use talpid_types::{
     net::{Endpoint, TunnelEndpoint},
     tunnel::{ActionAfterDisconnect, ErrorState},
 };
 
+/// Output format of commands, selected with the global `--format` flag.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// A single JSON document. See `docs/cli-json-output.md` for the schema
+    Json,
+}
+
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+pub fn set_output_format(format: OutputFormat) {
+    let _ = OUTPUT_FORMAT.set(format);
+}
+
+/// Returns whether the user asked for JSON output instead of text.
+pub fn is_json() -> bool {
+    OUTPUT_FORMAT.get() == Some(&OutputFormat::Json)
+}
+
+/// Commands that can print JSON, given as the names of their subcommands. Keep this in sync with
+/// `docs/cli-json-output.md`.
+const JSON_COMMANDS: &[&str] = &[
+    "status",
+    "status listen",
+    "relay get",
+    "relay list",
+    "account get",
+    "account list-devices",
+    "account voucher-history",
+    "account expiry-warnings get",
+    "api-access list",
+    "api-access status",
+    "api-access pac-url get",
+    "api-access encrypted-dns-proxy get",
+    "api-access tor get",
+    "custom-list list",
+    #[cfg(target_os = "linux")]
+    "split-tunnel list",
+    #[cfg(any(windows, target_os = "macos"))]
+    "split-tunnel get",
+    "dns get",
+    "obfuscation get",
+    "version",
+];
+
+/// Returns whether the command with the given subcommand names, e.g. `["relay", "get"]`, can
+/// print JSON.
+pub fn supports_json(command: &[&str]) -> bool {
+    JSON_COMMANDS.contains(&command.join(" ").as_str())
+}
+
+/// Print `value` as JSON on a single line.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> anyhow::Result<()> {
+    let json = serde_json::to_string(value).context("Failed to format output as JSON")?;
+    println!("{json}");
+    Ok(())
+}
+
 #[macro_export]
 macro_rules! print_option {
     ($value:expr $(,)?) => {{
@@ -169,6 +231,10 @@ fn connection_information(
         .filter(|_| verbose)
         .map(|endpoint| endpoint.tunnel_type.to_string());
     info.insert("Tunnel type", tunnel_type_fmt);
+    let ip_version_fmt = endpoint
+        .filter(|_| verbose)
+        .map(|endpoint| endpoint.ip_version.to_string());
+    info.insert("IP version", ip_version_fmt);
 
     info.insert("Visible location", location.map(format_location));
     let features_fmt = feature_indicators
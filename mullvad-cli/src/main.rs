@@ -1,5 +1,5 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{error::ErrorKind, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 
 mod cmds;
 mod format;
@@ -10,7 +10,18 @@ pub const BIN_NAME: &str = env!("CARGO_BIN_NAME");
 #[derive(Debug, Parser)]
 #[command(author, version = mullvad_version::VERSION, about, long_about = None)]
 #[command(propagate_version = true)]
-enum Cli {
+struct Cli {
+    /// Output format, text by default. Commands that do not support JSON output refuse
+    /// `--format json`
+    #[arg(long, global = true, value_enum)]
+    format: Option<format::OutputFormat>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
     /// Control and display information about your Mullvad account
     #[clap(subcommand)]
     Account(account::Account),
@@ -42,6 +53,11 @@ enum Cli {
     #[clap(subcommand)]
     Lan(lan::Lan),
 
+    /// Automatically connect, disconnect or block depending on the network that this device is
+    /// connected to. Networks can currently only be identified on Linux
+    #[clap(subcommand)]
+    NetworkRules(network_rules::NetworkRules),
+
     /// Connect to a VPN relay
     Connect {
         /// Wait until connected before exiting
@@ -140,6 +156,10 @@ enum Cli {
     ImportSettings {
         /// File to read from. If this is "-", read from standard input
         file: String,
+
+        /// Print the settings that would change without applying the patch
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Export a JSON patch based on the current settings
@@ -148,38 +168,93 @@ enum Cli {
         /// File to write to. If this is "-", write to standard output
         file: String,
     },
+
+    /// Back up or restore all settings
+    #[clap(subcommand)]
+    Settings(settings::Settings),
+
+    /// Manage named settings profiles
+    #[clap(subcommand)]
+    Profile(profile::Profile),
+
+    /// Show the settings that are locked by the administrator policy
+    Policy,
+
+    /// List the hooks that are run when the tunnel state, device or settings change
+    Hooks,
+}
+
+impl Cli {
+    /// Resolve the output format from `--format` and command-specific flags, and reject
+    /// `--format json` for commands that can only print text.
+    fn output_format(&mut self, matches: &ArgMatches) -> Result<format::OutputFormat, clap::Error> {
+        let output_format = match &mut self.command {
+            Command::Status { args, .. } => args.resolve_format(self.format)?,
+            _ => self.format.unwrap_or_default(),
+        };
+
+        let mut command = vec![];
+        let mut matches = matches;
+        while let Some((name, subcommand_matches)) = matches.subcommand() {
+            command.push(name);
+            matches = subcommand_matches;
+        }
+        if output_format == format::OutputFormat::Json && !format::supports_json(&command) {
+            return Err(clap::Error::raw(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "'{BIN_NAME} {}' does not support '--format json'\n",
+                    command.join(" ")
+                ),
+            ));
+        }
+        Ok(output_format)
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    match Cli::parse() {
-        Cli::Account(cmd) => cmd.handle().await,
-        Cli::Bridge(cmd) => cmd.handle().await,
-        Cli::Connect { wait } => tunnel_state::connect(wait).await,
-        Cli::Reconnect { wait } => tunnel_state::reconnect(wait).await,
-        Cli::Debug(cmd) => cmd.handle().await,
-        Cli::Disconnect { wait } => tunnel_state::disconnect(wait).await,
-        Cli::AutoConnect(cmd) => cmd.handle().await,
-        Cli::BetaProgram(cmd) => cmd.handle().await,
-        Cli::LockdownMode(cmd) => cmd.handle().await,
-        Cli::Dns(cmd) => cmd.handle().await,
-        Cli::Lan(cmd) => cmd.handle().await,
-        Cli::Obfuscation(cmd) => cmd.handle().await,
-        Cli::ApiAccess(cmd) => cmd.handle().await,
-        Cli::Version => version::print().await,
-        Cli::FactoryReset => reset::handle().await,
-        Cli::Relay(cmd) => cmd.handle().await,
-        Cli::Tunnel(cmd) => cmd.handle().await,
-        Cli::SplitTunnel(cmd) => cmd.handle().await,
-        Cli::Status { cmd, args } => status::handle(cmd, args).await,
-        Cli::CustomList(cmd) => cmd.handle().await,
-        Cli::ImportSettings { file } => patch::import(file).await,
-        Cli::ExportSettings { file } => patch::export(file).await,
+    let mut cli_command = Cli::command();
+    let matches = cli_command.get_matches_mut();
+    let mut cli = Cli::from_arg_matches(&matches)
+        .unwrap_or_else(|error| error.format(&mut cli_command).exit());
+    let output_format = cli
+        .output_format(&matches)
+        .unwrap_or_else(|error| error.format(&mut cli_command).exit());
+    format::set_output_format(output_format);
+
+    match cli.command {
+        Command::Account(cmd) => cmd.handle().await,
+        Command::Bridge(cmd) => cmd.handle().await,
+        Command::Connect { wait } => tunnel_state::connect(wait).await,
+        Command::Reconnect { wait } => tunnel_state::reconnect(wait).await,
+        Command::Debug(cmd) => cmd.handle().await,
+        Command::Disconnect { wait } => tunnel_state::disconnect(wait).await,
+        Command::AutoConnect(cmd) => cmd.handle().await,
+        Command::BetaProgram(cmd) => cmd.handle().await,
+        Command::LockdownMode(cmd) => cmd.handle().await,
+        Command::Dns(cmd) => cmd.handle().await,
+        Command::Lan(cmd) => cmd.handle().await,
+        Command::NetworkRules(cmd) => cmd.handle().await,
+        Command::Obfuscation(cmd) => cmd.handle().await,
+        Command::ApiAccess(cmd) => cmd.handle().await,
+        Command::Version => version::print().await,
+        Command::FactoryReset => reset::handle().await,
+        Command::Relay(cmd) => cmd.handle().await,
+        Command::Tunnel(cmd) => cmd.handle().await,
+        Command::SplitTunnel(cmd) => cmd.handle().await,
+        Command::Status { cmd, args } => status::handle(cmd, args).await,
+        Command::CustomList(cmd) => cmd.handle().await,
+        Command::ImportSettings { file, dry_run } => patch::import(file, dry_run).await,
+        Command::ExportSettings { file } => patch::export(file).await,
+        Command::Settings(cmd) => cmd.handle().await,
+        Command::Profile(cmd) => cmd.handle().await,
+        Command::Policy => policy::print().await,
+        Command::Hooks => hooks::print().await,
 
         #[cfg(all(unix, not(target_os = "android")))]
-        Cli::ShellCompletions { shell, dir } => {
+        Command::ShellCompletions { shell, dir } => {
             use anyhow::Context;
-            use clap::CommandFactory;
 
             // FIXME: The shell completions include hidden commands (including "shell-completions")
             println!("Generating shell completions to {}", dir.display());
@@ -1,4 +1,4 @@
-use crate::{api, settings, Daemon};
+use crate::{api, settings, system_proxy, Daemon};
 use mullvad_api::{proxy::ApiConnectionMode, rest, ApiProxy};
 use mullvad_types::{
     access_method::{self, AccessMethod, AccessMethodSetting},
@@ -26,6 +26,15 @@ pub enum Error {
     /// Access methods settings error
     #[error("Settings error")]
     Settings(#[from] settings::Error),
+    /// The PAC file URL can not be used
+    #[error("Invalid PAC file URL: {0}")]
+    InvalidPacUrl(String),
+    /// A DoH resolver is missing a name or addresses
+    #[error("Invalid DoH resolver: {0}")]
+    InvalidDohResolver(String),
+    /// A Tor bridge line does not contain an address
+    #[error("Invalid Tor bridge: {0}")]
+    InvalidTorBridge(String),
 }
 
 impl Daemon {
@@ -136,6 +145,63 @@ impl Daemon {
         Ok(())
     }
 
+    /// Set or clear the URL of the PAC file used by the
+    /// [`access_method::BuiltInAccessMethod::SystemProxy`] access method.
+    pub async fn set_system_proxy_pac_url(&mut self, pac_url: Option<String>) -> Result<(), Error> {
+        if let Some(ref url) = pac_url {
+            system_proxy::validate_pac_url(url).map_err(|_| Error::InvalidPacUrl(url.clone()))?;
+        }
+        self.settings
+            .update(|settings: &mut Settings| {
+                settings.api_access_methods.set_pac_url(pac_url);
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Set the DoH resolvers used by the
+    /// [`access_method::BuiltInAccessMethod::EncryptedDnsProxy`] access method, in addition to
+    /// the default ones.
+    pub async fn set_encrypted_dns_proxy_resolvers(
+        &mut self,
+        resolvers: Vec<access_method::DohResolver>,
+    ) -> Result<(), Error> {
+        if let Some(resolver) = resolvers
+            .iter()
+            .find(|resolver| resolver.name.trim().is_empty() || resolver.addrs.is_empty())
+        {
+            return Err(Error::InvalidDohResolver(resolver.name.clone()));
+        }
+        self.settings
+            .update(|settings: &mut Settings| {
+                settings.api_access_methods.set_doh_resolvers(resolvers);
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Set the bridge used by the [`access_method::BuiltInAccessMethod::Tor`] access method.
+    /// Pluggable transports are not part of these settings, see [`crate::tor_transports`].
+    pub async fn set_tor_settings(
+        &mut self,
+        tor_settings: access_method::TorSettings,
+    ) -> Result<(), Error> {
+        if let Some(bridge) = &tor_settings.bridge {
+            if access_method::bridge_addr(bridge).is_none() {
+                return Err(Error::InvalidTorBridge(bridge.clone()));
+            }
+        }
+        self.settings
+            .update(|settings: &mut Settings| {
+                settings.api_access_methods.set_tor_settings(tor_settings);
+            })
+            .await?;
+
+        Ok(())
+    }
+
     /// Return the [`AccessMethodSetting`] which is currently used to access the
     /// Mullvad API.
     pub async fn get_current_access_method(&self) -> Result<AccessMethodSetting, Error> {
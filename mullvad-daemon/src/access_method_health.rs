@@ -0,0 +1,329 @@
+//! Tracks how well each API access method works on each network, so that the access methods
+//! that are likely to work are tried first. The statistics are kept in the cache directory.
+//!
+//! A network is identified by the key of the network identity that the offline monitor reports,
+//! which does not depend on the tunnel. Networks that cannot be identified share a single entry.
+use chrono::{TimeDelta, Utc};
+use mullvad_types::access_method::{AccessMethodSetting, AccessMethodStatistics, Id};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use talpid_types::ErrorExt;
+use tokio::io::AsyncWriteExt;
+
+const HEALTH_FILENAME: &str = "api-access-health.json";
+
+/// Maximum number of networks to keep statistics for. The least recently used network is
+/// forgotten first.
+const MAX_NETWORKS: usize = 16;
+
+/// Failures older than this do not lower the rank of an access method, so that it is eventually
+/// tried again.
+const FAILURE_EXPIRY: TimeDelta = TimeDelta::hours(1);
+
+/// Minimum time between saving statistics that do not change the ranking.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Network used when the network cannot be identified.
+pub const UNKNOWN_NETWORK: &str = "unknown";
+
+/// Statistics of all access methods on all known networks.
+pub struct HealthTracker {
+    path: PathBuf,
+    store: Store,
+    last_save: Option<Instant>,
+    /// Whether there are unsaved changes.
+    changed: bool,
+    /// Whether there are unsaved changes that affect the ranking.
+    rank_changed: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Store {
+    /// Networks ordered from least to most recently used.
+    networks: Vec<NetworkStatistics>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NetworkStatistics {
+    network: String,
+    methods: Vec<MethodStatistics>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MethodStatistics {
+    id: Id,
+    #[serde(flatten)]
+    statistics: AccessMethodStatistics,
+}
+
+/// How likely an access method is to work. Lower is better.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    /// The last attempt succeeded. More recent successes are better.
+    Working(Reverse<chrono::DateTime<Utc>>),
+    /// The access method has not been used recently.
+    Untried,
+    /// The last attempts failed. Fewer failures are better.
+    Failing(u32),
+}
+
+impl HealthTracker {
+    /// Load the statistics from `cache_dir`. If they can not be read, no statistics are used.
+    pub async fn load(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(HEALTH_FILENAME);
+        let store = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+                log::warn!("Failed to parse API access method statistics: {error}");
+                Store::default()
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Store::default(),
+            Err(error) => {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read API access method statistics")
+                );
+                Store::default()
+            }
+        };
+        Self {
+            path,
+            store,
+            last_save: None,
+            changed: false,
+            rank_changed: false,
+        }
+    }
+
+    /// Return the statistics of `id` on `network`.
+    pub fn statistics(&self, network: &str, id: &Id) -> AccessMethodStatistics {
+        self.store
+            .networks
+            .iter()
+            .find(|stats| stats.network == network)
+            .and_then(|stats| stats.methods.iter().find(|method| method.id == *id))
+            .map(|method| method.statistics.clone())
+            .unwrap_or_default()
+    }
+
+    /// Record that `id` reached the API on `network` after `latency`.
+    pub fn record_success(&mut self, network: &str, id: &Id, latency: Duration) {
+        let statistics = self.statistics_mut(network, id);
+        let was_working = statistics.consecutive_failures == 0 && statistics.successes > 0;
+        statistics.successes = statistics.successes.saturating_add(1);
+        statistics.consecutive_failures = 0;
+        statistics.last_success = Some(Utc::now());
+        statistics.latency = Some(match statistics.latency {
+            Some(previous) => (previous * 3 + latency) / 4,
+            None => latency,
+        });
+        self.changed = true;
+        self.rank_changed |= !was_working;
+    }
+
+    /// Record that `id` failed to reach the API on `network`.
+    pub fn record_failure(&mut self, network: &str, id: &Id) {
+        let statistics = self.statistics_mut(network, id);
+        statistics.failures = statistics.failures.saturating_add(1);
+        statistics.consecutive_failures = statistics.consecutive_failures.saturating_add(1);
+        statistics.last_failure = Some(Utc::now());
+        self.changed = true;
+        self.rank_changed = true;
+    }
+
+    /// Forget the statistics of access methods that are not in `methods`.
+    pub fn retain<'a>(&mut self, methods: impl Iterator<Item = &'a AccessMethodSetting>) {
+        let ids: Vec<Id> = methods.map(|method| method.get_id()).collect();
+        for network in &mut self.store.networks {
+            let before = network.methods.len();
+            network.methods.retain(|method| ids.contains(&method.id));
+            if network.methods.len() != before {
+                self.changed = true;
+                self.rank_changed = true;
+            }
+        }
+    }
+
+    /// Sort `methods` by how likely they are to work on `network`, best first. Access methods
+    /// that are equally likely to work keep their order.
+    pub fn rank<'a>(
+        &self,
+        network: &str,
+        methods: impl Iterator<Item = &'a AccessMethodSetting>,
+    ) -> Vec<&'a AccessMethodSetting> {
+        let now = Utc::now();
+        let mut ranked: Vec<_> = methods
+            .map(|method| {
+                let rank = rank(&self.statistics(network, &method.get_id()), now);
+                (rank, method)
+            })
+            .collect();
+        ranked.sort_by(|(a, _), (b, _)| a.cmp(b));
+        ranked.into_iter().map(|(_, method)| method).collect()
+    }
+
+    fn statistics_mut(&mut self, network: &str, id: &Id) -> &mut AccessMethodStatistics {
+        let networks = &mut self.store.networks;
+        // Move the network to the end, since it is now the most recently used
+        let stats = match networks.iter().position(|stats| stats.network == network) {
+            Some(index) => networks.remove(index),
+            None => NetworkStatistics {
+                network: network.to_owned(),
+                methods: vec![],
+            },
+        };
+        networks.push(stats);
+        if networks.len() > MAX_NETWORKS {
+            networks.remove(0);
+        }
+
+        let methods = &mut networks.last_mut().unwrap().methods;
+        let index = match methods.iter().position(|method| method.id == *id) {
+            Some(index) => index,
+            None => {
+                methods.push(MethodStatistics {
+                    id: id.clone(),
+                    statistics: AccessMethodStatistics::default(),
+                });
+                methods.len() - 1
+            }
+        };
+        &mut methods[index].statistics
+    }
+
+    /// Write any changes to the cache directory. Changes that do not affect the ranking, such as
+    /// new latency measurements, are only written if nothing has been saved recently.
+    pub fn save(&mut self) {
+        let recently_saved = self
+            .last_save
+            .is_some_and(|last_save| last_save.elapsed() < SAVE_INTERVAL);
+        if !self.changed || (!self.rank_changed && recently_saved) {
+            return;
+        }
+        self.last_save = Some(Instant::now());
+        self.changed = false;
+        self.rank_changed = false;
+
+        let json = match serde_json::to_string_pretty(&self.store) {
+            Ok(json) => json,
+            Err(error) => {
+                log::error!("Failed to serialize API access method statistics: {error}");
+                return;
+            }
+        };
+        let path = self.path.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let mut file = mullvad_fs::AtomicFile::new(&path).await?;
+                file.write_all(json.as_bytes()).await?;
+                file.write_all(b"\n").await?;
+                file.finalize().await
+            }
+            .await;
+            if let Err(error) = result {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to save API access method statistics")
+                );
+            }
+        });
+    }
+}
+
+fn rank(statistics: &AccessMethodStatistics, now: chrono::DateTime<Utc>) -> Rank {
+    match (statistics.last_success, statistics.last_failure) {
+        (Some(last_success), _) if statistics.consecutive_failures == 0 => {
+            Rank::Working(Reverse(last_success))
+        }
+        (_, Some(last_failure)) if now - last_failure < FAILURE_EXPIRY => {
+            Rank::Failing(statistics.consecutive_failures)
+        }
+        _ => Rank::Untried,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::access_method::{AccessMethod, BuiltInAccessMethod};
+
+    fn method(name: &str) -> AccessMethodSetting {
+        AccessMethodSetting::new(
+            name.to_owned(),
+            true,
+            AccessMethod::from(BuiltInAccessMethod::Direct),
+        )
+    }
+
+    fn tracker() -> HealthTracker {
+        HealthTracker {
+            path: PathBuf::new(),
+            store: Store::default(),
+            last_save: None,
+            changed: false,
+            rank_changed: false,
+        }
+    }
+
+    #[test]
+    fn test_rank() {
+        let now = Utc::now();
+        let untried = AccessMethodStatistics::default();
+        let working = AccessMethodStatistics {
+            successes: 1,
+            last_success: Some(now),
+            ..Default::default()
+        };
+        let failing = AccessMethodStatistics {
+            failures: 2,
+            consecutive_failures: 2,
+            last_failure: Some(now),
+            ..Default::default()
+        };
+        let expired = AccessMethodStatistics {
+            last_failure: Some(now - FAILURE_EXPIRY - TimeDelta::seconds(1)),
+            ..failing.clone()
+        };
+
+        assert!(rank(&working, now) < rank(&untried, now));
+        assert!(rank(&untried, now) < rank(&failing, now));
+        assert_eq!(rank(&expired, now), Rank::Untried);
+    }
+
+    #[test]
+    fn test_failing_method_is_tried_last() {
+        let (first, second, third) = (method("first"), method("second"), method("third"));
+        let methods = [first.clone(), second.clone(), third.clone()];
+        let mut tracker = tracker();
+
+        tracker.record_failure("a", &first.get_id());
+        tracker.record_success("a", &third.get_id(), Duration::from_millis(100));
+
+        let ranked = tracker.rank("a", methods.iter());
+        assert_eq!(ranked, vec![&third, &second, &first]);
+
+        // Other networks are not affected
+        let ranked = tracker.rank("b", methods.iter());
+        assert_eq!(ranked, vec![&first, &second, &third]);
+    }
+
+    #[test]
+    fn test_statistics() {
+        let id = method("method").get_id();
+        let mut tracker = tracker();
+
+        tracker.record_success("a", &id, Duration::from_millis(100));
+        tracker.record_failure("a", &id);
+        tracker.record_success("a", &id, Duration::from_millis(500));
+
+        let statistics = tracker.statistics("a", &id);
+        assert_eq!(statistics.successes, 2);
+        assert_eq!(statistics.failures, 1);
+        assert_eq!(statistics.consecutive_failures, 0);
+        assert_eq!(statistics.latency, Some(Duration::from_millis(200)));
+    }
+}
@@ -0,0 +1,258 @@
+//! Keeps the last known account data and device list in the settings directory, along with the
+//! vouchers redeemed on this device, so that they can be shown when the API cannot be reached.
+//! Only data for the most recently used account is kept.
+use chrono::{DateTime, Utc};
+use mullvad_types::{
+    account::{AccountData, AccountNumber, RedeemedVoucher, VoucherSubmission},
+    device::Device,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use talpid_types::ErrorExt;
+use tokio::{io::AsyncWriteExt, sync::Mutex};
+
+const CACHE_FILENAME: &str = "account-data.json";
+
+/// Last known account data. Cloning returns a handle to the same cache.
+#[derive(Clone)]
+pub struct AccountDataCache {
+    path: PathBuf,
+    contents: Arc<Mutex<CacheContents>>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct CacheContents {
+    account_number: Option<AccountNumber>,
+    account_data: Option<AccountData>,
+    /// Time when `account_data` was fetched.
+    account_data_fetched: Option<DateTime<Utc>>,
+    devices: Option<Vec<Device>>,
+    /// Time when `devices` was fetched.
+    devices_fetched: Option<DateTime<Utc>>,
+    #[serde(default)]
+    vouchers: Vec<RedeemedVoucher>,
+}
+
+impl CacheContents {
+    /// Return the contents for `account_number`, replacing any data for another account.
+    fn for_account(&mut self, account_number: &AccountNumber) -> &mut Self {
+        if self.account_number.as_ref() != Some(account_number) {
+            *self = CacheContents {
+                account_number: Some(account_number.clone()),
+                ..Default::default()
+            };
+        }
+        self
+    }
+
+    fn matches(&self, account_number: &AccountNumber) -> bool {
+        self.account_number.as_ref() == Some(account_number)
+    }
+}
+
+impl AccountDataCache {
+    /// Load the cached account data from `settings_dir`.
+    pub async fn load(settings_dir: &Path) -> Self {
+        let path = settings_dir.join(CACHE_FILENAME);
+        let contents = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+                log::warn!("Failed to parse cached account data: {error}");
+                CacheContents::default()
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => CacheContents::default(),
+            Err(error) => {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read cached account data")
+                );
+                CacheContents::default()
+            }
+        };
+        Self {
+            path,
+            contents: Arc::new(Mutex::new(contents)),
+        }
+    }
+
+    /// Store account data that was just fetched from the API.
+    pub async fn store_account_data(&self, account_number: &AccountNumber, data: &AccountData) {
+        let mut contents = self.contents.lock().await;
+        let entry = contents.for_account(account_number);
+        entry.account_data = Some(AccountData {
+            cached_at: None,
+            ..data.clone()
+        });
+        entry.account_data_fetched = Some(Utc::now());
+        self.save(&contents).await;
+    }
+
+    /// Return the last known account data, with `cached_at` set to when it was fetched.
+    pub async fn account_data(&self, account_number: &AccountNumber) -> Option<AccountData> {
+        let contents = self.contents.lock().await;
+        if !contents.matches(account_number) {
+            return None;
+        }
+        let data = contents.account_data.clone()?;
+        Some(AccountData {
+            cached_at: contents.account_data_fetched,
+            ..data
+        })
+    }
+
+    /// Store a device list that was just fetched from the API.
+    pub async fn store_devices(&self, account_number: &AccountNumber, devices: &[Device]) {
+        let mut contents = self.contents.lock().await;
+        let entry = contents.for_account(account_number);
+        entry.devices = Some(devices.to_vec());
+        entry.devices_fetched = Some(Utc::now());
+        self.save(&contents).await;
+    }
+
+    /// Return the last known device list and when it was fetched.
+    pub async fn devices(
+        &self,
+        account_number: &AccountNumber,
+    ) -> Option<(Vec<Device>, DateTime<Utc>)> {
+        let contents = self.contents.lock().await;
+        if !contents.matches(account_number) {
+            return None;
+        }
+        Some((contents.devices.clone()?, contents.devices_fetched?))
+    }
+
+    /// Record a voucher that was redeemed on this device. The new expiry also replaces the
+    /// cached one.
+    pub async fn record_voucher(
+        &self,
+        account_number: &AccountNumber,
+        submission: &VoucherSubmission,
+    ) {
+        let now = Utc::now();
+        let mut contents = self.contents.lock().await;
+        let entry = contents.for_account(account_number);
+        entry.vouchers.push(RedeemedVoucher {
+            redeemed: now,
+            time_added: submission.time_added,
+            new_expiry: submission.new_expiry,
+        });
+        if let Some(data) = &mut entry.account_data {
+            data.expiry = submission.new_expiry;
+            entry.account_data_fetched = Some(now);
+        }
+        self.save(&contents).await;
+    }
+
+    /// Return the vouchers redeemed on this device for `account_number`, oldest first.
+    pub async fn vouchers(&self, account_number: &AccountNumber) -> Vec<RedeemedVoucher> {
+        let contents = self.contents.lock().await;
+        if !contents.matches(account_number) {
+            return vec![];
+        }
+        contents.vouchers.clone()
+    }
+
+    /// Remove all cached data.
+    pub async fn clear(&self) {
+        let mut contents = self.contents.lock().await;
+        *contents = CacheContents::default();
+        if let Err(error) = tokio::fs::remove_file(&self.path).await {
+            if error.kind() != io::ErrorKind::NotFound {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to remove cached account data")
+                );
+            }
+        }
+    }
+
+    async fn save(&self, contents: &CacheContents) {
+        let json = match serde_json::to_string_pretty(contents) {
+            Ok(json) => json,
+            Err(error) => {
+                log::error!("Failed to serialize account data: {error}");
+                return;
+            }
+        };
+        let result = async {
+            let mut file = mullvad_fs::AtomicFile::new(&self.path).await?;
+            file.write_all(json.as_bytes()).await?;
+            file.write_all(b"\n").await?;
+            file.finalize().await
+        }
+        .await;
+        if let Err(error) = result {
+            log::warn!(
+                "{}",
+                error.display_chain_with_msg("Failed to save cached account data")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Saving to this directory fails, which is only logged.
+    const DUMMY_SETTINGS_DIR: &str = "/nonexistent/account-data-cache-test";
+
+    fn account_data(expiry: DateTime<Utc>) -> AccountData {
+        AccountData {
+            id: "id".to_owned(),
+            expiry,
+            cached_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_data_is_kept_for_one_account() {
+        let cache = AccountDataCache::load(Path::new(DUMMY_SETTINGS_DIR)).await;
+        let first = "1111".to_owned();
+        let second = "2222".to_owned();
+        let expiry = Utc::now();
+
+        cache
+            .store_account_data(&first, &account_data(expiry))
+            .await;
+        let cached = cache.account_data(&first).await.unwrap();
+        assert_eq!(cached.expiry, expiry);
+        assert!(cached.cached_at.is_some());
+        assert!(cache.account_data(&second).await.is_none());
+
+        cache.store_devices(&second, &[]).await;
+        assert!(cache.account_data(&first).await.is_none());
+        assert!(cache.devices(&second).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_voucher_updates_expiry() {
+        let cache = AccountDataCache::load(Path::new(DUMMY_SETTINGS_DIR)).await;
+        let account = "1111".to_owned();
+        let new_expiry = Utc::now() + chrono::TimeDelta::days(30);
+
+        cache
+            .store_account_data(&account, &account_data(Utc::now()))
+            .await;
+        cache
+            .record_voucher(
+                &account,
+                &VoucherSubmission {
+                    time_added: 30 * 24 * 60 * 60,
+                    new_expiry,
+                },
+            )
+            .await;
+
+        assert_eq!(
+            cache.account_data(&account).await.unwrap().expiry,
+            new_expiry
+        );
+        let vouchers = cache.vouchers(&account).await;
+        assert_eq!(vouchers.len(), 1);
+        assert_eq!(vouchers[0].new_expiry, new_expiry);
+    }
+}
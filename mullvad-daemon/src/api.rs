@@ -5,26 +5,38 @@
 //! establishing connections when performing API requests.
 #[cfg(target_os = "android")]
 use crate::DaemonCommand;
-use crate::DaemonEventSender;
+use crate::{
+    access_method_health::{self, HealthTracker},
+    domain_fronting::DomainFrontState,
+    encrypted_dns_proxy::{self, EncryptedDnsProxyCache},
+    system_proxy::SystemProxyState,
+    DaemonEventSender,
+};
 use futures::{
     channel::{mpsc, oneshot},
-    StreamExt,
+    future, Future, StreamExt,
 };
 use mullvad_api::{
     availability::ApiAvailability,
-    proxy::{ApiConnectionMode, ConnectionModeProvider, ProxyConfig},
+    proxy::{ApiConnectionMode, ConnectionModeProvider, ProxyConfig, TorConfig},
     AddressCache,
 };
-use mullvad_encrypted_dns_proxy::state::EncryptedDnsProxyState;
+use mullvad_encrypted_dns_proxy::config_resolver::{self, Nameserver};
 use mullvad_relay_selector::RelaySelector;
 use mullvad_types::access_method::{
-    AccessMethod, AccessMethodSetting, BuiltInAccessMethod, Id, Settings,
+    AccessMethod, AccessMethodSetting, AccessMethodStatus, BuiltInAccessMethod,
+    EncryptedDnsProxyConfigInfo, Id, Settings, TorTransport,
+};
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
-use std::{net::SocketAddr, path::PathBuf};
 use talpid_core::mpsc::Sender;
 use talpid_types::net::{
     AllowedClients, AllowedEndpoint, Connectivity, Endpoint, TransportProtocol,
 };
+use tokio::sync::watch;
 
 pub enum Message {
     Get(ResponseTx<ResolvedConnectionMode>),
@@ -32,8 +44,26 @@ pub enum Message {
     Rotate(ResponseTx<ApiConnectionMode>),
     Update(ResponseTx<()>, Settings),
     Resolve(ResponseTx<ResolvedConnectionMode>, AccessMethodSetting),
+    /// A request using the given connection mode reached the API.
+    Success(ApiConnectionMode, Duration),
+    Statistics(ResponseTx<Vec<AccessMethodStatus>>),
+    EncryptedDnsProxyConfigs(ResponseTx<Vec<EncryptedDnsProxyConfigInfo>>),
+    /// Fetch new Encrypted DNS proxy configurations.
+    RefreshEncryptedDnsProxyConfigs(ResponseTx<Vec<EncryptedDnsProxyConfigInfo>>),
+    /// Whether the firewall lets the daemon reach any endpoint, so that several access methods
+    /// may be probed at once.
+    AllowParallelProbing(bool),
+    /// The host moved to another network. `None` means that the network cannot be identified.
+    NetworkChanged(Option<String>),
 }
 
+/// Maximum number of access methods to probe at once.
+const MAX_PARALLEL_PROBES: usize = 4;
+/// How long to wait for probed access methods to respond.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+/// Directory in the cache directory where the embedded Tor client keeps its state.
+const TOR_STATE_DIR: &str = "tor";
+
 /// Calling [`AccessMethodEvent::send`] will cause a
 /// [`crate::InternalDaemonEvent::AccessMethodEvent`] being sent to the daemon,
 /// which in turn will handle updating the firewall and notifying clients as
@@ -78,6 +108,88 @@ impl AccessMethodEvent {
     }
 }
 
+/// Lets tasks reach endpoints other than the API while they look up how to reach the API, such as
+/// a PAC file or a DoH resolver. The firewall is told to allow one such endpoint at a time, so the
+/// current API endpoint is blocked until the task is done with it.
+///
+/// This must not be awaited by the [`AccessModeSelector`] itself, since the daemon may be waiting
+/// for the selector while the selector would be waiting for the daemon to update the firewall.
+#[derive(Clone)]
+pub(crate) struct EndpointAccess {
+    daemon_event_sender: DaemonEventSender<(AccessMethodEvent, oneshot::Sender<()>)>,
+    /// Endpoint of the current access method, which is allowed again afterwards.
+    current: watch::Receiver<AllowedEndpoint>,
+}
+
+impl EndpointAccess {
+    pub fn new(
+        daemon_event_sender: DaemonEventSender<(AccessMethodEvent, oneshot::Sender<()>)>,
+        current: watch::Receiver<AllowedEndpoint>,
+    ) -> Self {
+        Self {
+            daemon_event_sender,
+            current,
+        }
+    }
+
+    /// Run `f` while the firewall lets the daemon reach `endpoint`.
+    pub async fn with_endpoint<T>(
+        &self,
+        endpoint: Endpoint,
+        f: impl Future<Output = T>,
+    ) -> Result<T> {
+        let endpoint = AllowedEndpoint {
+            endpoint,
+            clients: allowed_clients(&ApiConnectionMode::Direct),
+        };
+        AccessMethodEvent::Allow { endpoint }
+            .send(self.daemon_event_sender.clone())
+            .await?;
+        let output = f.await;
+        let endpoint = self.current.borrow().clone();
+        AccessMethodEvent::Allow { endpoint }
+            .send(self.daemon_event_sender.clone())
+            .await?;
+        Ok(output)
+    }
+
+    /// Resolve `hostname` using the DoH `resolvers`, one resolver address at a time. The system
+    /// resolver is not used, since the firewall blocks DNS while the tunnel is not up.
+    pub async fn lookup_host(&self, hostname: &str, resolvers: &[Nameserver]) -> Option<IpAddr> {
+        if let Ok(ip) = hostname.parse() {
+            return Some(ip);
+        }
+        for resolver in resolvers {
+            for &address in &resolver.addr {
+                let nameserver = Nameserver {
+                    name: resolver.name.clone(),
+                    addr: vec![address],
+                };
+                let endpoint = Endpoint::new(
+                    address,
+                    config_resolver::RESOLVER_PORT,
+                    TransportProtocol::Tcp,
+                );
+                let lookup = config_resolver::resolve_hostname(&[nameserver], hostname);
+                match self.with_endpoint(endpoint, lookup).await {
+                    Ok(Ok(ips)) => match ips.first() {
+                        Some(ip) => return Some(*ip),
+                        None => log::debug!("{hostname} did not resolve to any address"),
+                    },
+                    Ok(Err(error)) => {
+                        log::debug!("Failed to resolve {hostname} using {address}: {error}")
+                    }
+                    Err(error) => {
+                        log::debug!("Failed to allow {address} in the firewall: {error}");
+                        return None;
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
 /// This struct represent a concrete API endpoint (in the form of an
 /// [`ApiConnectionMode`] and [`AllowedEndpoint`]) which has been derived from
 /// some [`AccessMethodSetting`] (most likely the currently active access
@@ -108,6 +220,8 @@ pub enum Error {
     OneshotSendFailed,
     #[error("AccessModeSelector is not responding.")]
     NotRunning(#[from] oneshot::Canceled),
+    #[error("Failed to fetch Encrypted DNS proxy configurations")]
+    FetchEncryptedDnsProxyConfigs,
 }
 
 impl std::fmt::Display for Message {
@@ -118,6 +232,14 @@ impl std::fmt::Display for Message {
             Message::Rotate(_) => f.write_str("Rotate"),
             Message::Update(..) => f.write_str("Update"),
             Message::Resolve(..) => f.write_str("Resolve"),
+            Message::Success(..) => f.write_str("Success"),
+            Message::Statistics(_) => f.write_str("Statistics"),
+            Message::EncryptedDnsProxyConfigs(_) => f.write_str("EncryptedDnsProxyConfigs"),
+            Message::RefreshEncryptedDnsProxyConfigs(_) => {
+                f.write_str("RefreshEncryptedDnsProxyConfigs")
+            }
+            Message::AllowParallelProbing(_) => f.write_str("AllowParallelProbing"),
+            Message::NetworkChanged(_) => f.write_str("NetworkChanged"),
         }
     }
 }
@@ -188,6 +310,57 @@ impl AccessModeSelectorHandle {
             log::debug!("Failed while getting the next access method");
         })
     }
+
+    /// Return the statistics of all access methods, in the order that they will be tried.
+    pub async fn statistics(&self) -> Result<Vec<AccessMethodStatus>> {
+        self.send_command(Message::Statistics)
+            .await
+            .inspect_err(|_| {
+                log::debug!("Failed to get access method statistics");
+            })
+    }
+
+    /// Return the cached Encrypted DNS proxy configurations.
+    pub async fn encrypted_dns_proxy_configs(&self) -> Result<Vec<EncryptedDnsProxyConfigInfo>> {
+        self.send_command(Message::EncryptedDnsProxyConfigs)
+            .await
+            .inspect_err(|_| {
+                log::debug!("Failed to get Encrypted DNS proxy configurations");
+            })
+    }
+
+    /// Fetch new Encrypted DNS proxy configurations and return them.
+    pub async fn refresh_encrypted_dns_proxy_configs(
+        &self,
+    ) -> Result<Vec<EncryptedDnsProxyConfigInfo>> {
+        self.send_command(Message::RefreshEncryptedDnsProxyConfigs)
+            .await
+            .inspect_err(|_| {
+                log::debug!("Failed to refresh Encrypted DNS proxy configurations");
+            })
+    }
+
+    /// Report that a request made using `connection_mode` reached the API.
+    pub fn report_success(&self, connection_mode: ApiConnectionMode, latency: Duration) {
+        let _ = self
+            .cmd_tx
+            .unbounded_send(Message::Success(connection_mode, latency));
+    }
+
+    /// Set whether several access methods may be probed at once when the current one fails.
+    /// This should only be allowed when the firewall does not restrict which endpoints the
+    /// daemon can reach.
+    pub fn allow_parallel_probing(&self, allow: bool) {
+        let _ = self
+            .cmd_tx
+            .unbounded_send(Message::AllowParallelProbing(allow));
+    }
+
+    /// Set the key of the network that the host is connected to, which the statistics of the
+    /// access methods are kept for. `None` means that the network cannot be identified.
+    pub fn set_network(&self, network: Option<String>) {
+        let _ = self.cmd_tx.unbounded_send(Message::NetworkChanged(network));
+    }
 }
 
 pub struct AccessModeConnectionModeProvider {
@@ -225,6 +398,10 @@ impl ConnectionModeProvider for AccessModeConnectionModeProvider {
             handle.rotate().await.ok();
         }
     }
+
+    fn report_success(&self, connection_mode: ApiConnectionMode, latency: Duration) {
+        self.handle.report_success(connection_mode, latency);
+    }
 }
 
 /// A small actor which takes care of handling the logic around rotating
@@ -235,20 +412,39 @@ impl ConnectionModeProvider for AccessModeConnectionModeProvider {
 /// [`ApiConnectionMode::Direct`]) via a bridge ([`ApiConnectionMode::Proxied`])
 /// or via any supported custom proxy protocol
 /// ([`talpid_types::net::proxy::CustomProxy`]).
+///
+/// The enabled access methods are tried in the order given by [`HealthTracker::rank`], so that
+/// access methods that have recently worked on the current network are tried first.
 pub struct AccessModeSelector {
     cmd_rx: mpsc::UnboundedReceiver<Message>,
     cache_dir: PathBuf,
     /// Used for selecting a Bridge when the `Mullvad Bridges` access method is used.
     relay_selector: RelaySelector,
     /// Used for selecting a config for the 'Encrypted DNS proxy' access method.
-    encrypted_dns_proxy_cache: EncryptedDnsProxyState,
+    encrypted_dns_proxy_cache: EncryptedDnsProxyCache,
+    /// Used for selecting a proxy for the 'System proxy' access method.
+    system_proxies: SystemProxyState,
+    /// Used for selecting a front for the 'Domain fronting' access method.
+    domain_fronts: DomainFrontState,
     access_method_settings: Settings,
+    /// Pluggable transports used by the 'Tor' access method, read from a root-only file.
+    tor_transports: Vec<TorTransport>,
     address_cache: AddressCache,
     access_method_event_sender: DaemonEventSender<(AccessMethodEvent, oneshot::Sender<()>)>,
     connection_mode_provider_sender: mpsc::UnboundedSender<ApiConnectionMode>,
     current: ResolvedConnectionMode,
-    /// `index` is used to keep track of the [`AccessMethodSetting`] to use.
-    index: usize,
+    /// Success and failure statistics of each access method, used to pick the next one.
+    health: HealthTracker,
+    /// Access methods that have failed since an access method last reached the API.
+    attempted: Vec<Id>,
+    /// Whether several access methods may be probed at once. See
+    /// [`AccessModeSelectorHandle::allow_parallel_probing`].
+    parallel_probing: bool,
+    /// Network that the statistics in `health` are recorded for. See
+    /// [`AccessModeSelectorHandle::set_network`].
+    network: String,
+    /// Publishes the endpoint of the current access method to [`EndpointAccess`].
+    current_endpoint: watch::Sender<AllowedEndpoint>,
 }
 
 impl AccessModeSelector {
@@ -257,6 +453,7 @@ impl AccessModeSelector {
         relay_selector: RelaySelector,
         #[cfg_attr(not(feature = "api-override"), allow(unused_mut))]
         mut access_method_settings: Settings,
+        tor_transports: Vec<TorTransport>,
         access_method_event_sender: DaemonEventSender<(AccessMethodEvent, oneshot::Sender<()>)>,
         address_cache: AddressCache,
     ) -> Result<(AccessModeSelectorHandle, AccessModeConnectionModeProvider)> {
@@ -271,14 +468,45 @@ impl AccessModeSelector {
         }
 
         // Initialize the Encrypted DNS cache
-        let mut encrypted_dns_proxy_cache = EncryptedDnsProxyState::default();
+        let mut encrypted_dns_proxy_cache =
+            EncryptedDnsProxyCache::load(&cache_dir, access_method_settings.doh_resolvers()).await;
+        let (current_endpoint, current_endpoint_rx) = watch::channel(resolve_allowed_endpoint(
+            &ApiConnectionMode::Direct,
+            address_cache.get_address().await,
+        ));
+        let endpoint_access =
+            EndpointAccess::new(access_method_event_sender.clone(), current_endpoint_rx);
+        let mut system_proxies = SystemProxyState::new(
+            access_method_settings.pac_url().map(str::to_owned),
+            encrypted_dns_proxy::nameservers(access_method_settings.doh_resolvers()),
+            endpoint_access.clone(),
+        );
+        let mut domain_fronts = DomainFrontState::new(
+            access_method_settings.domain_fronts().to_vec(),
+            encrypted_dns_proxy::nameservers(access_method_settings.doh_resolvers()),
+            endpoint_access,
+        );
 
-        // Always start looking from the position of `Direct`.
-        let (index, next) = Self::find_next_active(0, &access_method_settings);
+        // Start with the access method that is most likely to work on the current network
+        let health = HealthTracker::load(&cache_dir).await;
+        let network = access_method_health::UNKNOWN_NETWORK.to_owned();
+        let next = Self::ranked_candidates(&health, &network, &access_method_settings, &[])
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| access_method_settings.direct().clone());
+        #[cfg(feature = "api-override")]
+        let next = if mullvad_api::API.force_direct {
+            access_method_settings.direct().clone()
+        } else {
+            next
+        };
         let initial_connection_mode = Self::resolve_inner(
             next,
             &relay_selector,
             &mut encrypted_dns_proxy_cache,
+            &mut system_proxies,
+            &mut domain_fronts,
+            tor_config(&access_method_settings, &tor_transports, &cache_dir),
             &address_cache,
         )
         .await;
@@ -286,18 +514,26 @@ impl AccessModeSelector {
         let (change_tx, change_rx) = mpsc::unbounded();
 
         let api_connection_mode = initial_connection_mode.connection_mode.clone();
+        current_endpoint.send_replace(initial_connection_mode.endpoint.clone());
 
         let selector = AccessModeSelector {
             cmd_rx,
             cache_dir,
             relay_selector,
             encrypted_dns_proxy_cache,
+            system_proxies,
+            domain_fronts,
             access_method_settings,
+            tor_transports,
             address_cache,
             access_method_event_sender,
             connection_mode_provider_sender: change_tx,
             current: initial_connection_mode,
-            index,
+            health,
+            attempted: vec![],
+            parallel_probing: false,
+            network,
+            current_endpoint,
         };
 
         tokio::spawn(selector.into_future());
@@ -319,6 +555,26 @@ impl AccessModeSelector {
                 Message::Rotate(tx) => self.on_next_connection_mode(tx).await,
                 Message::Update(tx, values) => self.on_update_access_methods(tx, values).await,
                 Message::Resolve(tx, setting) => self.on_resolve_access_method(tx, setting).await,
+                Message::Success(connection_mode, latency) => {
+                    self.on_success(connection_mode, latency).await;
+                    Ok(())
+                }
+                Message::Statistics(tx) => self.on_statistics(tx).await,
+                Message::EncryptedDnsProxyConfigs(tx) => {
+                    let configs = self.encrypted_dns_proxy_cache.configs();
+                    self.reply(tx, configs)
+                }
+                Message::RefreshEncryptedDnsProxyConfigs(tx) => {
+                    self.on_refresh_encrypted_dns_proxy_configs(tx).await
+                }
+                Message::AllowParallelProbing(allow) => {
+                    self.parallel_probing = allow;
+                    Ok(())
+                }
+                Message::NetworkChanged(network) => {
+                    self.on_network_changed(network);
+                    Ok(())
+                }
             };
             match execution {
                 Ok(_) => (),
@@ -357,24 +613,98 @@ impl AccessModeSelector {
             }
         }
 
-        let Some((index, method)) = self
+        let Some(method) = self
             .access_method_settings
             .iter()
-            .enumerate()
-            .find(|(_, access_method)| access_method.get_id() == id)
+            .find(|access_method| access_method.get_id() == id)
         else {
             return;
         };
 
-        self.index = index;
         self.set_current(method.to_owned()).await;
     }
 
     async fn on_next_connection_mode(&mut self, tx: ResponseTx<ApiConnectionMode>) -> Result<()> {
+        // The current access method failed to reach the API
+        let failed = self.current.setting.get_id();
+        self.health.record_failure(&self.network, &failed);
+        self.health.save();
+        if !self.attempted.contains(&failed) {
+            self.attempted.push(failed);
+        }
+
         let next = self.next_connection_mode().await?;
         self.reply(tx, next)
     }
 
+    async fn on_success(&mut self, connection_mode: ApiConnectionMode, latency: Duration) {
+        // Ignore requests that were made using a previous access method
+        if connection_mode != self.current.connection_mode {
+            return;
+        }
+        self.health
+            .record_success(&self.network, &self.current.setting.get_id(), latency);
+        self.health.save();
+        self.attempted.clear();
+
+        if let ApiConnectionMode::Proxied(ProxyConfig::EncryptedDnsProxy(config)) =
+            &self.current.connection_mode
+        {
+            self.encrypted_dns_proxy_cache.record_success(config);
+        }
+    }
+
+    async fn on_refresh_encrypted_dns_proxy_configs(
+        &mut self,
+        tx: ResponseTx<Vec<EncryptedDnsProxyConfigInfo>>,
+    ) -> Result<()> {
+        let result = match self.encrypted_dns_proxy_cache.refresh().await {
+            Ok(()) => Ok(self.encrypted_dns_proxy_cache.configs()),
+            Err(error) => {
+                log::debug!("{error:#?}");
+                Err(Error::FetchEncryptedDnsProxyConfigs)
+            }
+        };
+        tx.send(result).map_err(|_| Error::OneshotSendFailed)
+    }
+
+    async fn on_statistics(&mut self, tx: ResponseTx<Vec<AccessMethodStatus>>) -> Result<()> {
+        let network = &self.network;
+        let current = self.current.setting.get_id();
+        let enabled = self.health.rank(
+            network,
+            self.access_method_settings.iter().filter(|m| m.enabled()),
+        );
+        let disabled = self
+            .access_method_settings
+            .iter()
+            .filter(|method| !method.enabled());
+        let statistics = enabled
+            .into_iter()
+            .chain(disabled)
+            .map(|method| AccessMethodStatus {
+                id: method.get_id(),
+                name: method.name.clone(),
+                enabled: method.enabled(),
+                active: method.get_id() == current,
+                statistics: self.health.statistics(network, &method.get_id()),
+            })
+            .collect();
+        self.reply(tx, statistics)
+    }
+
+    fn on_network_changed(&mut self, network: Option<String>) {
+        let network = network.unwrap_or_else(|| access_method_health::UNKNOWN_NETWORK.to_owned());
+        if network != self.network {
+            // Failures on the previous network say nothing about this one, and neither do the
+            // proxies that it advertised or the addresses that the fronts resolved to
+            self.attempted.clear();
+            self.system_proxies.clear();
+            self.domain_fronts.clear();
+            self.network = network;
+        }
+    }
+
     async fn next_connection_mode(&mut self) -> Result<ApiConnectionMode> {
         #[cfg(feature = "api-override")]
         {
@@ -389,16 +719,91 @@ impl AccessModeSelector {
             );
         }
 
-        let (next_index, next) =
-            Self::find_next_active(self.index + 1, &self.access_method_settings);
-        self.index = next_index;
-        self.set_current(next).await;
+        let network = self.network.clone();
+        let mut candidates = Self::ranked_candidates(
+            &self.health,
+            &network,
+            &self.access_method_settings,
+            &self.attempted,
+        );
+        if candidates.is_empty() {
+            // Every access method has been tried. Start over, but try the others first.
+            self.attempted.clear();
+            self.attempted.push(self.current.setting.get_id());
+            candidates = Self::ranked_candidates(
+                &self.health,
+                &network,
+                &self.access_method_settings,
+                &self.attempted,
+            );
+        }
+        if candidates.is_empty() {
+            // The current access method is the only one that is enabled
+            candidates =
+                Self::ranked_candidates(&self.health, &network, &self.access_method_settings, &[]);
+        }
+
+        let resolved = if self.parallel_probing && candidates.len() > 1 {
+            self.probe_candidates(&network, candidates).await
+        } else {
+            let next = candidates
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| self.access_method_settings.direct().clone());
+            self.resolve(next).await
+        };
+        self.announce(resolved);
         Ok(self.current.connection_mode.clone())
     }
 
+    /// Resolve the best ranked `candidates` and try to connect to them at the same time. The
+    /// best ranked candidate that could be connected to is returned. Candidates that could not
+    /// be connected to are recorded as failures.
+    async fn probe_candidates(
+        &mut self,
+        network: &str,
+        candidates: Vec<AccessMethodSetting>,
+    ) -> ResolvedConnectionMode {
+        let mut resolved = Vec::with_capacity(MAX_PARALLEL_PROBES);
+        for candidate in candidates.into_iter().take(MAX_PARALLEL_PROBES) {
+            resolved.push(self.resolve(candidate).await);
+        }
+
+        log::debug!("Probing {} API access methods", resolved.len());
+        let results = future::join_all(resolved.iter().map(probe_candidate)).await;
+
+        let mut selected = None;
+        for (candidate, result) in resolved.iter().zip(results) {
+            match result {
+                Probe::Unreachable => {
+                    log::debug!(
+                        "API access method {} is unreachable",
+                        candidate.setting.name
+                    );
+                    let id = candidate.setting.get_id();
+                    self.health.record_failure(network, &id);
+                    if !self.attempted.contains(&id) {
+                        self.attempted.push(id);
+                    }
+                }
+                Probe::Reachable | Probe::Unknown => {
+                    selected.get_or_insert_with(|| candidate.clone());
+                }
+            }
+        }
+        self.health.save();
+
+        // If nothing could be reached, fall back to the best ranked candidate
+        selected.unwrap_or_else(|| resolved.swap_remove(0))
+    }
+
     async fn set_current(&mut self, access_method: AccessMethodSetting) {
         let resolved = self.resolve(access_method).await;
+        self.announce(resolved);
+    }
 
+    /// Set and announce `resolved` as the current connection mode.
+    fn announce(&mut self, resolved: ResolvedConnectionMode) {
         // Note: If the daemon is busy waiting for a call to this function
         // to complete while we wait for the daemon to fully handle this
         // `NewAccessMethodEvent`, then we find ourselves in a deadlock.
@@ -424,6 +829,9 @@ impl AccessModeSelector {
             }
         });
 
+        self.current_endpoint
+            .send_replace(resolved.endpoint.clone());
+
         // Notify REST client
         let _ = self
             .connection_mode_provider_sender
@@ -437,20 +845,23 @@ impl AccessModeSelector {
         );
     }
 
-    /// Find the next access method to use.
-    ///
-    /// * `start`: From which point in `access_methods` to start the search.
-    /// * `access_methods`: The search space.
-    fn find_next_active(start: usize, access_methods: &Settings) -> (usize, AccessMethodSetting) {
-        access_methods
+    /// Return the enabled access methods that are not in `exclude`, ordered by how likely they
+    /// are to work on `network`.
+    fn ranked_candidates(
+        health: &HealthTracker,
+        network: &str,
+        access_methods: &Settings,
+        exclude: &[Id],
+    ) -> Vec<AccessMethodSetting> {
+        let candidates = access_methods
             .iter()
+            .filter(|access_method| access_method.enabled())
+            .filter(|access_method| !exclude.contains(&access_method.get_id()));
+        health
+            .rank(network, candidates)
+            .into_iter()
             .cloned()
-            .enumerate()
-            .cycle()
-            .skip(start)
-            .take(access_methods.cardinality())
-            .find(|(_index, access_method)| access_method.enabled())
-            .unwrap_or_else(|| (0, access_methods.direct().clone()))
+            .collect()
     }
 
     async fn on_update_access_methods(
@@ -463,19 +874,40 @@ impl AccessModeSelector {
     }
 
     async fn update_access_methods(&mut self, access_methods: Settings) -> Result<()> {
+        self.system_proxies
+            .set_pac_url(access_methods.pac_url().map(str::to_owned));
+        self.system_proxies
+            .set_resolvers(encrypted_dns_proxy::nameservers(
+                access_methods.doh_resolvers(),
+            ));
+        self.domain_fronts
+            .set_fronts(access_methods.domain_fronts().to_vec());
+        self.domain_fronts
+            .set_resolvers(encrypted_dns_proxy::nameservers(
+                access_methods.doh_resolvers(),
+            ));
+        self.encrypted_dns_proxy_cache
+            .set_resolvers(access_methods.doh_resolvers());
+        let tor_settings_changed =
+            self.access_method_settings.tor_settings() != access_methods.tor_settings();
         self.access_method_settings = access_methods;
+        self.health.retain(self.access_method_settings.iter());
+        self.health.save();
 
         let new_current = self
             .access_method_settings
             .iter()
-            .enumerate()
-            .find(|(_, access_method)| access_method.get_id() == self.current.setting.get_id());
+            .find(|access_method| access_method.get_id() == self.current.setting.get_id());
 
         match new_current {
-            Some((index, new_current)) => {
-                // If the current method was modified, announce changes
-                self.index = index;
-                if self.current.setting != *new_current {
+            Some(new_current) => {
+                // If the current method was modified, announce changes. The Tor client must also
+                // be replaced if its bridge was changed.
+                let uses_tor = matches!(
+                    self.current.connection_mode,
+                    ApiConnectionMode::Proxied(ProxyConfig::Tor(_))
+                );
+                if self.current.setting != *new_current || (uses_tor && tor_settings_changed) {
                     if new_current.enabled() {
                         self.set_current(new_current.to_owned()).await;
                     } else {
@@ -484,11 +916,7 @@ impl AccessModeSelector {
                 }
             }
             None => {
-                // Current method was removed: A new access method will suddenly have the same index
-                // as the one we are removing, but we want it to still be a
-                // candidate. A minor hack to achieve this is to simply decrement
-                // the current index.
-                self.index = self.index.saturating_sub(1);
+                // Current method was removed
                 self.next_connection_mode().await?;
             }
         }
@@ -509,6 +937,13 @@ impl AccessModeSelector {
             access_method,
             &self.relay_selector,
             &mut self.encrypted_dns_proxy_cache,
+            &mut self.system_proxies,
+            &mut self.domain_fronts,
+            tor_config(
+                &self.access_method_settings,
+                &self.tor_transports,
+                &self.cache_dir,
+            ),
             &self.address_cache,
         )
         .await
@@ -517,7 +952,10 @@ impl AccessModeSelector {
     async fn resolve_inner(
         access_method: AccessMethodSetting,
         relay_selector: &RelaySelector,
-        encrypted_dns_proxy_cache: &mut EncryptedDnsProxyState,
+        encrypted_dns_proxy_cache: &mut EncryptedDnsProxyCache,
+        system_proxies: &mut SystemProxyState,
+        domain_fronts: &mut DomainFrontState,
+        tor: TorConfig,
         address_cache: &AddressCache,
     ) -> ResolvedConnectionMode {
         let connection_mode = {
@@ -535,20 +973,35 @@ impl AccessModeSelector {
                         log::debug!("Defaulting to direct API connection");
                         ApiConnectionMode::Direct
                     }),
-                AccessMethod::BuiltIn(BuiltInAccessMethod::EncryptedDnsProxy) => {
-                    if let Err(error) = encrypted_dns_proxy_cache.fetch_configs().await {
-                        log::warn!("Failed to fetch new Encrypted DNS Proxy configurations");
-                        log::debug!("{error:#?}");
-                    }
-                    encrypted_dns_proxy_cache
+                AccessMethod::BuiltIn(BuiltInAccessMethod::EncryptedDnsProxy) => encrypted_dns_proxy_cache
                     .next_configuration()
+                    .await
                     .map(ProxyConfig::EncryptedDnsProxy)
                     .map(ApiConnectionMode::Proxied)
                     .unwrap_or_else(|| {
                         log::warn!("Could not select next Encrypted DNS proxy config");
                         log::debug!("Defaulting to direct API connection");
                         ApiConnectionMode::Direct
-                    })},
+                    }),
+                AccessMethod::BuiltIn(BuiltInAccessMethod::SystemProxy) => system_proxies
+                    .next_candidate()
+                    .map(ProxyConfig::from)
+                    .map(ApiConnectionMode::Proxied)
+                    .unwrap_or_else(|| {
+                        log::warn!("Could not find any system proxy");
+                        log::debug!("Defaulting to direct API connection");
+                        ApiConnectionMode::Direct
+                    }),
+                AccessMethod::BuiltIn(BuiltInAccessMethod::DomainFronting) => domain_fronts
+                    .next_candidate(relay_selector.get_domain_fronts())
+                    .map(ProxyConfig::DomainFronting)
+                    .map(ApiConnectionMode::Proxied)
+                    .unwrap_or_else(|| {
+                        log::warn!("Could not find any domain front");
+                        log::debug!("Defaulting to direct API connection");
+                        ApiConnectionMode::Direct
+                    }),
+                AccessMethod::BuiltIn(BuiltInAccessMethod::Tor) => tor_connection_mode(tor),
                 AccessMethod::Custom(config) => ApiConnectionMode::Proxied(ProxyConfig::from(config)),
             }
         };
@@ -562,6 +1015,69 @@ impl AccessModeSelector {
     }
 }
 
+/// Result of probing the endpoint of an access method.
+enum Probe {
+    Reachable,
+    Unreachable,
+    /// The endpoint could not be probed, e.g. because it is not reached over TCP.
+    Unknown,
+}
+
+/// Probe the first hop of `candidate`, if it is known in advance.
+async fn probe_candidate(candidate: &ResolvedConnectionMode) -> Probe {
+    let connection_mode = &candidate.connection_mode;
+    if connection_mode.is_proxy() && connection_mode.get_endpoint().is_none() {
+        return Probe::Unknown;
+    }
+    probe(candidate.endpoint.endpoint).await
+}
+
+/// Try to open a TCP connection to `endpoint`. This only tells whether the first hop of an
+/// access method is reachable, not whether the API can be reached through it.
+async fn probe(endpoint: Endpoint) -> Probe {
+    if endpoint.protocol != TransportProtocol::Tcp {
+        return Probe::Unknown;
+    }
+    let start = Instant::now();
+    match tokio::time::timeout(
+        PROBE_TIMEOUT,
+        tokio::net::TcpStream::connect(endpoint.address),
+    )
+    .await
+    {
+        Ok(Ok(_)) => {
+            log::trace!("Connected to {} in {:?}", endpoint.address, start.elapsed());
+            Probe::Reachable
+        }
+        Ok(Err(_)) | Err(_) => Probe::Unreachable,
+    }
+}
+
+fn tor_config(settings: &Settings, transports: &[TorTransport], cache_dir: &Path) -> TorConfig {
+    TorConfig {
+        settings: settings.tor_settings().clone(),
+        transports: transports.to_vec(),
+        state_dir: cache_dir.join(TOR_STATE_DIR),
+    }
+}
+
+/// Returns the connection mode of the Tor access method. A bridge is required, since the firewall
+/// can only allow one endpoint. The addresses of the guard relays that the Tor client would
+/// otherwise connect to are not known in advance, so they would be blocked.
+fn tor_connection_mode(tor: TorConfig) -> ApiConnectionMode {
+    if !cfg!(feature = "tor") {
+        log::warn!("Tor support was not enabled at build time");
+        log::debug!("Defaulting to direct API connection");
+        return ApiConnectionMode::Direct;
+    }
+    if tor.settings.bridge_addr().is_none() {
+        log::warn!("The Tor access method requires a bridge, but none has been added");
+        log::debug!("Defaulting to direct API connection");
+        return ApiConnectionMode::Direct;
+    }
+    ApiConnectionMode::Proxied(ProxyConfig::Tor(tor))
+}
+
 pub fn resolve_allowed_endpoint(
     connection_mode: &ApiConnectionMode,
     fallback: SocketAddr,
@@ -574,6 +1090,8 @@ pub fn resolve_allowed_endpoint(
     AllowedEndpoint { endpoint, clients }
 }
 
+/// Pluggable transports used by the Tor access method are started by the daemon and run as root,
+/// so they are allowed along with the daemon.
 #[cfg(unix)]
 pub fn allowed_clients(connection_mode: &ApiConnectionMode) -> AllowedClients {
     match connection_mode {
@@ -588,14 +1106,22 @@ pub fn allowed_clients(connection_mode: &ApiConnectionMode) -> AllowedClients {
         ApiConnectionMode::Proxied(ProxyConfig::Socks5Local(_)) => AllowedClients::all(),
         ApiConnectionMode::Direct | ApiConnectionMode::Proxied(_) => {
             let daemon_exe = std::env::current_exe().expect("failed to obtain executable path");
-            vec![
+            let mut clients = vec![
                 daemon_exe
                     .parent()
                     .expect("missing executable parent directory")
                     .join("mullvad-problem-report.exe"),
                 daemon_exe,
-            ]
-            .into()
+            ];
+            // The pluggable transport connects to the Tor bridge on behalf of the daemon
+            if let ApiConnectionMode::Proxied(ProxyConfig::Tor(tor)) = connection_mode {
+                clients.extend(
+                    tor.transports
+                        .iter()
+                        .map(|transport| transport.path.clone()),
+                );
+            }
+            clients.into()
         }
     }
 }
@@ -643,3 +1169,52 @@ pub(crate) fn create_bypass_tx(
     });
     Some(bypass_tx)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::access_method::TorSettings;
+
+    fn tor_config(bridge: Option<&str>) -> TorConfig {
+        TorConfig {
+            settings: TorSettings {
+                bridge: bridge.map(str::to_owned),
+            },
+            transports: vec![],
+            state_dir: PathBuf::new(),
+        }
+    }
+
+    /// The firewall must allow the bridge that the Tor client connects to. Without a bridge, the
+    /// Tor access method is not used, so the API is reached directly at the fallback address.
+    #[test]
+    fn test_tor_allowed_endpoint() {
+        let fallback: SocketAddr = "192.0.2.10:443".parse().unwrap();
+        let bridge: SocketAddr = "192.0.2.1:443".parse().unwrap();
+        let bridge_line =
+            format!("obfs4 {bridge} 0123456789ABCDEF0123456789ABCDEF01234567 iat-mode=0");
+
+        let connection_mode = tor_connection_mode(tor_config(Some(&bridge_line)));
+        let allowed = resolve_allowed_endpoint(&connection_mode, fallback);
+        if cfg!(feature = "tor") {
+            assert!(matches!(
+                connection_mode,
+                ApiConnectionMode::Proxied(ProxyConfig::Tor(_))
+            ));
+            assert_eq!(
+                allowed.endpoint,
+                Endpoint::from_socket_address(bridge, TransportProtocol::Tcp)
+            );
+        } else {
+            assert_eq!(connection_mode, ApiConnectionMode::Direct);
+        }
+
+        let connection_mode = tor_connection_mode(tor_config(None));
+        assert_eq!(connection_mode, ApiConnectionMode::Direct);
+        let allowed = resolve_allowed_endpoint(&connection_mode, fallback);
+        assert_eq!(
+            allowed.endpoint,
+            Endpoint::from_socket_address(fallback, TransportProtocol::Tcp)
+        );
+    }
+}
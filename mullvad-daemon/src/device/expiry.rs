@@ -0,0 +1,213 @@
+//! Tracks the known account expiry and decides when to warn that the account is running out of
+//! time.
+use chrono::{DateTime, TimeDelta, Utc};
+use mullvad_types::{account::AccountExpiryWarning, settings::ExpiryWarningSettings};
+use std::time::Duration;
+
+/// How often to fetch the account expiry while warnings are enabled, so that time added
+/// elsewhere (e.g. on the website) is noticed.
+const EXPIRY_CHECK_INTERVAL: TimeDelta = TimeDelta::hours(6);
+
+pub(super) struct ExpiryWarnings {
+    /// Thresholds in descending order.
+    thresholds: Vec<Duration>,
+    expiry: Option<DateTime<Utc>>,
+    /// Smallest threshold that a warning has been emitted for.
+    warned: Option<Duration>,
+    /// When the expiry was last fetched, or an attempt was made to.
+    last_check: Option<DateTime<Utc>>,
+}
+
+impl ExpiryWarnings {
+    pub fn new(thresholds: Vec<Duration>) -> Self {
+        let mut warnings = Self {
+            thresholds: vec![],
+            expiry: None,
+            warned: None,
+            last_check: None,
+        };
+        warnings.set_thresholds(thresholds);
+        warnings
+    }
+
+    pub fn set_thresholds(&mut self, mut thresholds: Vec<Duration>) {
+        thresholds.retain(|threshold| ExpiryWarningSettings::is_valid_threshold(*threshold));
+        thresholds.sort_unstable_by(|a, b| b.cmp(a));
+        thresholds.dedup();
+        self.thresholds = thresholds;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.thresholds.is_empty()
+    }
+
+    /// Update the known expiry. Warnings are emitted again if time was added.
+    pub fn set_expiry(&mut self, expiry: DateTime<Utc>, now: DateTime<Utc>) {
+        if self.expiry.is_some_and(|old_expiry| expiry > old_expiry) {
+            self.warned = None;
+        }
+        self.expiry = Some(expiry);
+        self.last_check = Some(now);
+    }
+
+    /// Forget everything about the current account.
+    pub fn reset(&mut self) {
+        self.expiry = None;
+        self.warned = None;
+        self.last_check = None;
+    }
+
+    /// Record that the expiry is being fetched.
+    pub fn check_started(&mut self, now: DateTime<Utc>) {
+        self.last_check = Some(now);
+    }
+
+    /// Return whether the expiry should be fetched.
+    pub fn needs_check(&self, now: DateTime<Utc>) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+        match self.last_check {
+            Some(last_check) => now >= last_check + EXPIRY_CHECK_INTERVAL,
+            None => true,
+        }
+    }
+
+    /// Return the warning to emit at `now`, if any. Only the smallest threshold that has been
+    /// crossed is reported.
+    pub fn take_warning(&mut self, now: DateTime<Utc>) -> Option<AccountExpiryWarning> {
+        let expiry = self.expiry?;
+        let time_left = expiry.signed_duration_since(now).to_std().ok()?;
+        if time_left.is_zero() {
+            return None;
+        }
+        let threshold = self
+            .pending_thresholds()
+            .filter(|threshold| time_left <= *threshold)
+            .last()?;
+        self.warned = Some(threshold);
+        Some(AccountExpiryWarning { expiry, threshold })
+    }
+
+    /// Return when something needs to be done next: either a warning is due or the expiry
+    /// should be fetched. `None` means that warnings are disabled.
+    pub fn next_deadline(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let next_check = self
+            .last_check
+            .map_or(now, |last_check| last_check + EXPIRY_CHECK_INTERVAL);
+        let next_warning = self
+            .expiry
+            .filter(|expiry| *expiry > now)
+            .and_then(|expiry| {
+                let threshold = self.pending_thresholds().next()?;
+                expiry.checked_sub_signed(TimeDelta::from_std(threshold).ok()?)
+            });
+        Some(next_warning.map_or(next_check, |warning| warning.min(next_check)))
+    }
+
+    /// Thresholds that no warning has been emitted for yet, in descending order.
+    fn pending_thresholds(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.thresholds
+            .iter()
+            .copied()
+            .filter(|threshold| *threshold < self.warned.unwrap_or(Duration::MAX))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn warnings() -> ExpiryWarnings {
+        ExpiryWarnings::new(vec![HOUR, 7 * 24 * HOUR, 24 * HOUR])
+    }
+
+    #[test]
+    fn test_warns_once_per_threshold() {
+        let now = Utc::now();
+        let mut warnings = warnings();
+        warnings.set_expiry(now + TimeDelta::days(3), now);
+
+        let warning = warnings.take_warning(now).unwrap();
+        assert_eq!(warning.threshold, 7 * 24 * HOUR);
+        assert!(warnings.take_warning(now).is_none());
+
+        // The next warning is due when one day is left
+        assert_eq!(
+            warnings.next_deadline(now),
+            Some(now + TimeDelta::days(2)).min(Some(now + EXPIRY_CHECK_INTERVAL))
+        );
+        let later = now + TimeDelta::days(2) + TimeDelta::minutes(30);
+        assert_eq!(warnings.take_warning(later).unwrap().threshold, 24 * HOUR);
+    }
+
+    #[test]
+    fn test_only_smallest_crossed_threshold_is_reported() {
+        let now = Utc::now();
+        let mut warnings = warnings();
+        warnings.set_expiry(now + TimeDelta::minutes(30), now);
+
+        assert_eq!(warnings.take_warning(now).unwrap().threshold, HOUR);
+        assert!(warnings.take_warning(now).is_none());
+    }
+
+    #[test]
+    fn test_no_warning_when_expired() {
+        let now = Utc::now();
+        let mut warnings = warnings();
+        warnings.set_expiry(now - TimeDelta::minutes(1), now);
+        assert!(warnings.take_warning(now).is_none());
+        // Only the periodic check is pending
+        assert_eq!(
+            warnings.next_deadline(now),
+            Some(now + EXPIRY_CHECK_INTERVAL)
+        );
+    }
+
+    #[test]
+    fn test_added_time_resets_warnings() {
+        let now = Utc::now();
+        let mut warnings = warnings();
+        warnings.set_expiry(now + TimeDelta::minutes(30), now);
+        warnings.take_warning(now).unwrap();
+
+        warnings.set_expiry(now + TimeDelta::days(30), now);
+        assert!(warnings.take_warning(now).is_none());
+        assert_eq!(
+            warnings
+                .take_warning(now + TimeDelta::days(25))
+                .unwrap()
+                .threshold,
+            7 * 24 * HOUR
+        );
+    }
+
+    #[test]
+    fn test_disabled_without_thresholds() {
+        let now = Utc::now();
+        let mut warnings = ExpiryWarnings::new(vec![]);
+        warnings.set_expiry(now + TimeDelta::minutes(30), now);
+        assert!(!warnings.needs_check(now));
+        assert!(warnings.next_deadline(now).is_none());
+        assert!(warnings.take_warning(now).is_none());
+    }
+
+    #[test]
+    fn test_huge_thresholds_are_ignored() {
+        let now = Utc::now();
+        let huge = TimeDelta::MAX.to_std().unwrap();
+        let mut warnings = ExpiryWarnings::new(vec![huge, Duration::MAX, HOUR]);
+        warnings.set_expiry(now + TimeDelta::days(30), now);
+
+        assert!(warnings.take_warning(now).is_none());
+        assert_eq!(
+            warnings.next_deadline(now),
+            Some(now + EXPIRY_CHECK_INTERVAL)
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use futures::{
     channel::{mpsc, oneshot},
+    future::FutureExt,
     stream::StreamExt,
 };
 
@@ -8,7 +9,7 @@ use mullvad_api::rest;
 #[cfg(target_os = "android")]
 use mullvad_types::account::{PlayPurchase, PlayPurchasePaymentToken};
 use mullvad_types::{
-    account::{AccountNumber, VoucherSubmission},
+    account::{AccountExpiryWarning, AccountNumber, VoucherSubmission},
     device::{
         AccountAndDevice, Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceName, DeviceState,
     },
@@ -36,6 +37,7 @@ use tokio::{
 };
 
 mod api;
+mod expiry;
 mod service;
 pub(crate) use service::{AccountService, DeviceService};
 
@@ -243,6 +245,8 @@ pub(crate) enum AccountEvent {
     Device(PrivateDeviceEvent),
     /// Emitted when the account expiry is fetched.
     Expiry(DateTime<Utc>),
+    /// Emitted when the time left on the account drops below a warning threshold.
+    ExpiryWarning(AccountExpiryWarning),
 }
 
 #[derive(Clone)]
@@ -305,6 +309,7 @@ enum AccountManagerCommand {
     GetDataAfterLogin(ResponseTx<PrivateDeviceState>),
     RotateKey(ResponseTx<()>),
     SetRotationInterval(RotationInterval, ResponseTx<()>),
+    SetExpiryWarningThresholds(Vec<Duration>, ResponseTx<()>),
     ValidateDevice(ResponseTx<()>),
     SubmitVoucher(String, ResponseTx<VoucherSubmission>),
     #[cfg(target_os = "android")]
@@ -355,6 +360,14 @@ impl AccountManagerHandle {
             .await
     }
 
+    pub async fn set_expiry_warning_thresholds(
+        &self,
+        thresholds: Vec<Duration>,
+    ) -> Result<(), Error> {
+        self.send_command(|tx| AccountManagerCommand::SetExpiryWarningThresholds(thresholds, tx))
+            .await
+    }
+
     pub async fn validate_device(&self) -> Result<(), Error> {
         self.send_command(AccountManagerCommand::ValidateDevice)
             .await
@@ -407,6 +420,7 @@ pub(crate) struct AccountManager {
     device_service: DeviceService,
     data: PrivateDeviceState,
     rotation_interval: RotationInterval,
+    expiry_warnings: expiry::ExpiryWarnings,
     listeners: Vec<Box<dyn Sender<AccountEvent> + Send>>,
     last_validation: Option<SystemTime>,
     validation_requests: Vec<ResponseTx<()>>,
@@ -422,6 +436,7 @@ impl AccountManager {
         rest_handle: rest::MullvadRestHandle,
         settings_dir: &Path,
         initial_rotation_interval: RotationInterval,
+        expiry_warning_thresholds: Vec<Duration>,
         listener_tx: impl Sender<AccountEvent> + Send + 'static,
     ) -> Result<(AccountManagerHandle, PrivateDeviceState), Error> {
         let (cacher, data) = DeviceCacher::new(settings_dir).await?;
@@ -439,6 +454,7 @@ impl AccountManager {
             device_service: device_service.clone(),
             data: data.clone(),
             rotation_interval: initial_rotation_interval,
+            expiry_warnings: expiry::ExpiryWarnings::new(expiry_warning_thresholds),
             listeners: vec![Box::new(listener_tx)],
             last_validation: None,
             validation_requests: vec![],
@@ -467,11 +483,18 @@ impl AccountManager {
                 }
             }
 
+            let expiry_warning_timer = self.expiry_warning_timer().fuse();
+            futures::pin_mut!(expiry_warning_timer);
+
             futures::select! {
                 api_result = current_api_call => {
                     self.consume_api_result(api_result, &mut current_api_call).await;
                 }
 
+                () = expiry_warning_timer => {
+                    self.on_expiry_warning_timer(&mut current_api_call);
+                }
+
                 cmd = cmd_rx.next() => {
                     match cmd {
                         Some(AccountManagerCommand::Shutdown(tx)) => {
@@ -526,6 +549,10 @@ impl AccountManager {
                             }
                             let _ = tx.send(Ok(()));
                         }
+                        Some(AccountManagerCommand::SetExpiryWarningThresholds(thresholds, tx)) => {
+                            self.expiry_warnings.set_thresholds(thresholds);
+                            let _ = tx.send(Ok(()));
+                        }
                         Some(AccountManagerCommand::ValidateDevice(tx)) => {
                             self.handle_validation_request(tx, &mut current_api_call);
                         },
@@ -754,6 +781,7 @@ impl AccountManager {
                 let event = AccountEvent::Expiry(submission.new_expiry);
                 self.listeners
                     .retain(|listener| listener.send(event.clone()).is_ok());
+                self.update_expiry_warnings(submission.new_expiry);
             }
             Err(Error::InvalidAccount) => {
                 self.revoke_device(|| Error::InvalidAccount).await;
@@ -779,6 +807,7 @@ impl AccountManager {
                 let event = AccountEvent::Expiry(expiry);
                 self.listeners
                     .retain(|listener| listener.send(event.clone()).is_ok());
+                self.update_expiry_warnings(expiry);
 
                 Self::drain_requests(&mut self.expiry_requests, || Ok(expiry));
             }
@@ -971,6 +1000,7 @@ impl AccountManager {
             );
         }
         self.data.revoke();
+        self.expiry_warnings.reset();
 
         Self::drain_requests(&mut self.validation_requests, || Err(err_constructor()));
         Self::drain_requests(&mut self.rotation_requests, || Err(err_constructor()));
@@ -995,6 +1025,7 @@ impl AccountManager {
         }
 
         let old_config = self.data.logout();
+        self.expiry_warnings.reset();
 
         self.listeners.retain(|listener| {
             listener
@@ -1047,6 +1078,9 @@ impl AccountManager {
         }
 
         self.data = device_state;
+        if matches!(event, PrivateDeviceEvent::Login(_)) {
+            self.expiry_warnings.reset();
+        }
 
         let event = AccountEvent::Device(event);
         self.listeners
@@ -1055,6 +1089,60 @@ impl AccountManager {
         Ok(())
     }
 
+    /// Return a future that completes when an expiry warning is due or the expiry should be
+    /// fetched again. It never completes if there is nothing to do.
+    fn expiry_warning_timer(&self) -> impl Future<Output = ()> + Send + 'static {
+        let now = Utc::now();
+        let deadline = if self.data.logged_in() {
+            self.expiry_warnings.next_deadline(now)
+        } else {
+            None
+        };
+
+        async move {
+            match deadline {
+                Some(deadline) => {
+                    let delay = deadline
+                        .signed_duration_since(now)
+                        .to_std()
+                        .unwrap_or_default();
+                    talpid_time::sleep(delay).await
+                }
+                None => futures::future::pending::<()>().await,
+            }
+        }
+    }
+
+    fn on_expiry_warning_timer(&mut self, current_api_call: &mut api::CurrentApiCall) {
+        let now = Utc::now();
+        if self.expiry_warnings.needs_check(now) {
+            self.expiry_warnings.check_started(now);
+            // Do not interrupt calls that someone is waiting for
+            if current_api_call.is_idle() || current_api_call.is_running_timed_totation() {
+                if let Ok(call) = self.expiry_call() {
+                    log::debug!("Checking account expiry");
+                    current_api_call.set_expiry_check(Box::pin(call));
+                }
+            }
+        }
+        self.emit_expiry_warning(now);
+    }
+
+    fn update_expiry_warnings(&mut self, expiry: DateTime<Utc>) {
+        let now = Utc::now();
+        self.expiry_warnings.set_expiry(expiry, now);
+        self.emit_expiry_warning(now);
+    }
+
+    fn emit_expiry_warning(&mut self, now: DateTime<Utc>) {
+        if let Some(warning) = self.expiry_warnings.take_warning(now) {
+            log::info!("Account is about to expire at {}", warning.expiry);
+            let event = AccountEvent::ExpiryWarning(warning);
+            self.listeners
+                .retain(|listener| listener.send(event.clone()).is_ok());
+        }
+    }
+
     fn initiate_key_rotation(
         &self,
     ) -> Result<impl Future<Output = Result<WireguardData, Error>>, Error> {
@@ -0,0 +1,162 @@
+//! Labels and notes that users have given to their devices. They are only stored locally, in the
+//! settings directory, since the API does not support them.
+use mullvad_types::device::{DeviceId, DeviceLabel};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use talpid_types::ErrorExt;
+use tokio::{io::AsyncWriteExt, sync::Mutex};
+
+const LABELS_FILENAME: &str = "device-labels.json";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to serialize device labels")]
+    Serialize(#[source] serde_json::Error),
+
+    #[error("Failed to save device labels")]
+    Write(#[source] io::Error),
+}
+
+/// Device labels, ordered by device ID. Cloning returns a handle to the same labels.
+#[derive(Clone)]
+pub struct DeviceLabels {
+    path: PathBuf,
+    labels: Arc<Mutex<Vec<DeviceLabel>>>,
+}
+
+impl DeviceLabels {
+    /// Load the device labels from `settings_dir`.
+    pub async fn load(settings_dir: &Path) -> Self {
+        let path = settings_dir.join(LABELS_FILENAME);
+        let labels = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+                log::warn!("Failed to parse device labels: {error}");
+                vec![]
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(error) => {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read device labels")
+                );
+                vec![]
+            }
+        };
+        Self {
+            path,
+            labels: Arc::new(Mutex::new(labels)),
+        }
+    }
+
+    pub async fn get_all(&self) -> Vec<DeviceLabel> {
+        self.labels.lock().await.clone()
+    }
+
+    /// Set the label and note of a device. The device's entry is removed if both are empty.
+    pub async fn set(&self, mut label: DeviceLabel) -> Result<(), Error> {
+        label.label = normalize(label.label);
+        label.note = normalize(label.note);
+
+        let mut labels = self.labels.lock().await;
+        let mut new_labels = labels.clone();
+        match new_labels.binary_search_by(|existing| existing.device_id.cmp(&label.device_id)) {
+            Ok(index) if label.is_empty() => {
+                new_labels.remove(index);
+            }
+            Ok(index) => new_labels[index] = label,
+            Err(_) if label.is_empty() => return Ok(()),
+            Err(index) => new_labels.insert(index, label),
+        }
+        self.save(&new_labels).await?;
+        *labels = new_labels;
+        Ok(())
+    }
+
+    /// Remove the label of a device that no longer exists.
+    pub async fn remove(&self, device_id: &DeviceId) {
+        let result = self
+            .set(DeviceLabel {
+                device_id: device_id.clone(),
+                ..Default::default()
+            })
+            .await;
+        if let Err(error) = result {
+            log::warn!("{}", error.display_chain());
+        }
+    }
+
+    /// Remove all labels.
+    pub async fn clear(&self) {
+        let mut labels = self.labels.lock().await;
+        labels.clear();
+        if let Err(error) = tokio::fs::remove_file(&self.path).await {
+            if error.kind() != io::ErrorKind::NotFound {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to remove device labels")
+                );
+            }
+        }
+    }
+
+    async fn save(&self, labels: &[DeviceLabel]) -> Result<(), Error> {
+        let json = serde_json::to_string_pretty(labels).map_err(Error::Serialize)?;
+        let mut file = mullvad_fs::AtomicFile::new(&self.path)
+            .await
+            .map_err(Error::Write)?;
+        file.write_all(json.as_bytes())
+            .await
+            .map_err(Error::Write)?;
+        file.write_all(b"\n").await.map_err(Error::Write)?;
+        file.finalize().await.map_err(Error::Write)
+    }
+}
+
+/// Treat blank strings as unset.
+fn normalize(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn label(device_id: &str, label: Option<&str>) -> DeviceLabel {
+        DeviceLabel {
+            device_id: device_id.to_owned(),
+            label: label.map(str::to_owned),
+            note: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_and_remove() {
+        let dir = std::env::temp_dir().join(format!("device-labels-test-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let labels = DeviceLabels::load(&dir).await;
+
+        labels.set(label("b", Some("Laptop"))).await.unwrap();
+        labels.set(label("a", Some("  Phone "))).await.unwrap();
+        assert_eq!(
+            labels.get_all().await,
+            vec![label("a", Some("Phone")), label("b", Some("Laptop"))]
+        );
+
+        // Labels are persisted
+        let reloaded = DeviceLabels::load(&dir).await;
+        assert_eq!(reloaded.get_all().await, labels.get_all().await);
+
+        // A blank label removes the entry
+        labels.set(label("a", Some(" "))).await.unwrap();
+        labels.remove(&"b".to_owned()).await;
+        assert!(labels.get_all().await.is_empty());
+
+        labels.clear().await;
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
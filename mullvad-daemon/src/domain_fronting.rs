@@ -0,0 +1,193 @@
+//! Selection of CDN fronts for the [`BuiltInAccessMethod::DomainFronting`] access method. Fronts
+//! are hostnames served by a CDN that also serves the API. They come from the settings, which only
+//! signed patches may change, and from the signed relay list. The TLS connection to the API is
+//! made through the CDN, so a front cannot read or modify API traffic. See
+//! `docs/domain-fronting.md`.
+//!
+//! [`BuiltInAccessMethod::DomainFronting`]: mullvad_types::access_method::BuiltInAccessMethod::DomainFronting
+
+use crate::api::EndpointAccess;
+use futures::FutureExt;
+use mullvad_api::proxy::DomainFront;
+use mullvad_encrypted_dns_proxy::config_resolver::Nameserver;
+use std::{collections::VecDeque, net::SocketAddr};
+use tokio::task::JoinHandle;
+
+/// Port that CDN fronts are reached on.
+const FRONT_PORT: u16 = 443;
+
+/// Keeps the fronts that remain to be tried and hands them out one at a time, so that each of
+/// them is tried before the list is rebuilt. Fronts are resolved in the background, so that the
+/// [`AccessModeSelector`] is not blocked while it waits for the network.
+///
+/// [`AccessModeSelector`]: crate::api::AccessModeSelector
+pub struct DomainFrontState {
+    /// Fronts from the settings, which are tried before the ones in the relay list.
+    fronts: Vec<String>,
+    /// DoH resolvers used to look up the fronts.
+    resolvers: Vec<Nameserver>,
+    access: EndpointAccess,
+    candidates: VecDeque<DomainFront>,
+    resolution: Option<JoinHandle<Vec<DomainFront>>>,
+}
+
+impl DomainFrontState {
+    pub fn new(fronts: Vec<String>, resolvers: Vec<Nameserver>, access: EndpointAccess) -> Self {
+        Self {
+            fronts,
+            resolvers,
+            access,
+            candidates: VecDeque::new(),
+            resolution: None,
+        }
+    }
+
+    /// Set the fronts from the settings. Previously resolved fronts are discarded if they changed.
+    pub fn set_fronts(&mut self, fronts: Vec<String>) {
+        if self.fronts != fronts {
+            self.fronts = fronts;
+            self.clear();
+        }
+    }
+
+    /// Set the DoH resolvers used to look up the fronts.
+    pub fn set_resolvers(&mut self, resolvers: Vec<Nameserver>) {
+        self.resolvers = resolvers;
+    }
+
+    /// Discard the resolved fronts, e.g. because the host moved to another network.
+    pub fn clear(&mut self) {
+        self.candidates.clear();
+        if let Some(resolution) = self.resolution.take() {
+            resolution.abort();
+        }
+    }
+
+    /// Return the next front to try. `None` is returned if no front could be resolved, or if the
+    /// fronts are still being resolved. `relay_fronts` are the fronts advertised by the relay list.
+    pub fn next_candidate(&mut self, relay_fronts: Vec<String>) -> Option<DomainFront> {
+        if self.candidates.is_empty() {
+            match self.resolution.take() {
+                Some(resolution) if resolution.is_finished() => {
+                    self.candidates = resolution.now_or_never()?.unwrap_or_default().into();
+                }
+                Some(resolution) => {
+                    log::debug!("Still resolving domain fronts");
+                    self.resolution = Some(resolution);
+                    return None;
+                }
+                None => {
+                    log::debug!("Resolving domain fronts");
+                    let fronts = normalize_fronts(self.fronts.iter().cloned().chain(relay_fronts));
+                    self.resolution = Some(tokio::spawn(resolve(
+                        fronts,
+                        self.resolvers.clone(),
+                        self.access.clone(),
+                    )));
+                    return None;
+                }
+            }
+        }
+        self.candidates.pop_front()
+    }
+}
+
+/// Resolve `fronts` using the DoH `resolvers`, skipping the ones that do not resolve.
+async fn resolve(
+    fronts: Vec<String>,
+    resolvers: Vec<Nameserver>,
+    access: EndpointAccess,
+) -> Vec<DomainFront> {
+    let mut resolved = vec![];
+    for front in fronts {
+        match access.lookup_host(&front, &resolvers).await {
+            Some(ip) => resolved.push(DomainFront {
+                front,
+                endpoint: SocketAddr::new(ip, FRONT_PORT),
+            }),
+            None => log::warn!("Failed to resolve domain front {front}"),
+        }
+    }
+    resolved
+}
+
+/// Lowercase the fronts and drop empty and duplicate ones, keeping their order.
+fn normalize_fronts(fronts: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut normalized = vec![];
+    for front in fronts {
+        let front = front.trim().to_ascii_lowercase();
+        if !front.is_empty() && !normalized.contains(&front) {
+            normalized.push(front);
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{api, DaemonEventSender};
+    use mullvad_api::proxy::ApiConnectionMode;
+    use std::{sync::Weak, time::Duration};
+    use tokio::sync::watch;
+
+    /// Access that is never used, since IP addresses are not looked up.
+    fn unused_access() -> EndpointAccess {
+        let (_tx, current) = watch::channel(api::resolve_allowed_endpoint(
+            &ApiConnectionMode::Direct,
+            "10.0.0.2:443".parse().unwrap(),
+        ));
+        EndpointAccess::new(
+            DaemonEventSender::new(Weak::new()).to_specialized_sender(),
+            current,
+        )
+    }
+
+    #[test]
+    fn test_normalize_fronts() {
+        let fronts = vec![
+            "cdn.example.com".to_owned(),
+            "".to_owned(),
+            "front.example.net".to_owned(),
+            "CDN.example.com".to_owned(),
+        ];
+        assert_eq!(
+            normalize_fronts(fronts),
+            ["cdn.example.com".to_owned(), "front.example.net".to_owned()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_next_candidate_resolves_in_background() {
+        let mut state = DomainFrontState::new(vec!["10.0.0.1".to_owned()], vec![], unused_access());
+        let relay_fronts = || vec!["10.0.0.3".to_owned(), "10.0.0.1".to_owned()];
+
+        // Nothing is returned until the fronts have been resolved
+        assert_eq!(state.next_candidate(relay_fronts()), None);
+        let mut first = None;
+        for _ in 0..100 {
+            first = state.next_candidate(relay_fronts());
+            if first.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Fronts from the settings come first
+        assert_eq!(
+            first,
+            Some(DomainFront {
+                front: "10.0.0.1".to_owned(),
+                endpoint: "10.0.0.1:443".parse().unwrap(),
+            })
+        );
+        assert_eq!(
+            state.next_candidate(relay_fronts()).map(|f| f.front),
+            Some("10.0.0.3".to_owned())
+        );
+
+        // Changing the fronts discards the resolved ones
+        state.set_fronts(vec![]);
+        assert_eq!(state.next_candidate(relay_fronts()), None);
+    }
+}
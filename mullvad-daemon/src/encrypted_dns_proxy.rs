@@ -0,0 +1,254 @@
+//! Keeps the configurations used by the [`BuiltInAccessMethod::EncryptedDnsProxy`] access method
+//! in the cache directory, so that the access method can be used after a restart even if no DoH
+//! resolver can be reached. See `docs/encrypted-dns-proxy.md`.
+//!
+//! [`BuiltInAccessMethod::EncryptedDnsProxy`]: mullvad_types::access_method::BuiltInAccessMethod::EncryptedDnsProxy
+use chrono::{DateTime, TimeDelta, Utc};
+use mullvad_encrypted_dns_proxy::{
+    config::{ObfuscationConfig, ProxyConfig},
+    config_resolver::{self, Nameserver},
+    state::{EncryptedDnsProxyState, FetchConfigError},
+};
+use mullvad_types::access_method::{DohResolver, EncryptedDnsProxyConfigInfo};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+use talpid_types::ErrorExt;
+use tokio::io::AsyncWriteExt;
+
+const CACHE_FILENAME: &str = "encrypted-dns-proxy-configs.json";
+
+/// Cached configurations that have been neither fetched nor used for this long are removed.
+const CONFIG_EXPIRY: TimeDelta = TimeDelta::days(30);
+
+/// Minimum time between saving new successes of a configuration that has already worked.
+const SUCCESS_SAVE_INTERVAL: TimeDelta = TimeDelta::hours(1);
+
+/// Encrypted DNS proxy configurations, both fetched and cached.
+pub struct EncryptedDnsProxyCache {
+    state: EncryptedDnsProxyState,
+    path: PathBuf,
+    entries: Vec<CacheEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    config: ProxyConfig,
+    fetched: DateTime<Utc>,
+    last_success: Option<DateTime<Utc>>,
+}
+
+impl CacheEntry {
+    fn expires(&self) -> DateTime<Utc> {
+        self.last_success.unwrap_or(self.fetched).max(self.fetched) + CONFIG_EXPIRY
+    }
+}
+
+impl EncryptedDnsProxyCache {
+    /// Load the cached configurations from `cache_dir`. Expired configurations are ignored.
+    pub async fn load(cache_dir: &Path, resolvers: &[DohResolver]) -> Self {
+        let path = cache_dir.join(CACHE_FILENAME);
+        let entries: Vec<CacheEntry> = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+                log::warn!("Failed to parse cached Encrypted DNS proxy configs: {error}");
+                vec![]
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(error) => {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg(
+                        "Failed to read cached Encrypted DNS proxy configs"
+                    )
+                );
+                vec![]
+            }
+        };
+        Self::from_entries(path, entries, resolvers, Utc::now())
+    }
+
+    fn from_entries(
+        path: PathBuf,
+        mut entries: Vec<CacheEntry>,
+        resolvers: &[DohResolver],
+        now: DateTime<Utc>,
+    ) -> Self {
+        entries.retain(|entry| entry.expires() > now);
+        let mut state = EncryptedDnsProxyState::default();
+        state.add_configs(entries.iter().map(|entry| entry.config.clone()));
+        state.set_resolvers(resolvers.iter().map(nameserver).collect());
+        Self {
+            state,
+            path,
+            entries,
+        }
+    }
+
+    /// Set the DoH resolvers to use in addition to the default ones.
+    pub fn set_resolvers(&mut self, resolvers: &[DohResolver]) {
+        self.state
+            .set_resolvers(resolvers.iter().map(nameserver).collect());
+    }
+
+    /// Try to fetch new configurations and return the next one to use. If no DoH resolver can be
+    /// reached, the cached configurations are used.
+    pub async fn next_configuration(&mut self) -> Option<ProxyConfig> {
+        if let Err(error) = self.refresh().await {
+            log::warn!("Failed to fetch new Encrypted DNS Proxy configurations");
+            log::debug!("{error:#?}");
+        }
+        self.state.next_configuration()
+    }
+
+    /// Fetch new configurations, replacing the cached ones.
+    pub async fn refresh(&mut self) -> Result<(), FetchConfigError> {
+        let configs = self.state.refresh_configs().await?;
+        let now = Utc::now();
+        self.entries = configs
+            .into_iter()
+            .map(|config| {
+                let last_success = self
+                    .entries
+                    .iter()
+                    .find(|entry| entry.config == config)
+                    .and_then(|entry| entry.last_success);
+                CacheEntry {
+                    config,
+                    fetched: now,
+                    last_success,
+                }
+            })
+            .collect();
+        self.save();
+        Ok(())
+    }
+
+    /// Record that the API was reached using `config`.
+    pub fn record_success(&mut self, config: &ProxyConfig) {
+        let now = Utc::now();
+        let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.config == *config)
+        else {
+            return;
+        };
+        let recently_saved = entry
+            .last_success
+            .is_some_and(|last_success| now - last_success < SUCCESS_SAVE_INTERVAL);
+        if !recently_saved {
+            entry.last_success = Some(now);
+            self.save();
+        }
+    }
+
+    /// Return information about all cached configurations.
+    pub fn configs(&self) -> Vec<EncryptedDnsProxyConfigInfo> {
+        self.entries
+            .iter()
+            .map(|entry| EncryptedDnsProxyConfigInfo {
+                endpoint: SocketAddr::V4(entry.config.addr),
+                obfuscation: entry
+                    .config
+                    .obfuscation
+                    .as_ref()
+                    .map(|obfuscation| obfuscation_name(obfuscation).to_owned()),
+                fetched: entry.fetched,
+                last_success: entry.last_success,
+                expires: entry.expires(),
+            })
+            .collect()
+    }
+
+    fn save(&self) {
+        let json = match serde_json::to_string_pretty(&self.entries) {
+            Ok(json) => json,
+            Err(error) => {
+                log::error!("Failed to serialize Encrypted DNS proxy configs: {error}");
+                return;
+            }
+        };
+        let path = self.path.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let mut file = mullvad_fs::AtomicFile::new(&path).await?;
+                file.write_all(json.as_bytes()).await?;
+                file.write_all(b"\n").await?;
+                file.finalize().await
+            }
+            .await;
+            if let Err(error) = result {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to save Encrypted DNS proxy configs")
+                );
+            }
+        });
+    }
+}
+
+/// Return the nameservers of `resolvers`, followed by the default DoH resolvers.
+pub fn nameservers(resolvers: &[DohResolver]) -> Vec<Nameserver> {
+    resolvers
+        .iter()
+        .map(nameserver)
+        .chain(config_resolver::default_resolvers())
+        .collect()
+}
+
+fn nameserver(resolver: &DohResolver) -> Nameserver {
+    Nameserver {
+        name: resolver.name.clone(),
+        addr: resolver.addrs.clone(),
+    }
+}
+
+fn obfuscation_name(obfuscation: &ObfuscationConfig) -> &'static str {
+    match obfuscation {
+        ObfuscationConfig::XorV2(_) => "XOR",
+        ObfuscationConfig::ChaCha20(_) => "ChaCha20",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(port: u16, fetched: DateTime<Utc>) -> CacheEntry {
+        CacheEntry {
+            config: ProxyConfig {
+                addr: std::net::SocketAddrV4::new([192, 0, 2, 1].into(), port),
+                obfuscation: None,
+            },
+            fetched,
+            last_success: None,
+        }
+    }
+
+    #[test]
+    fn test_expired_configs_are_ignored() {
+        let now = Utc::now();
+        let fresh = entry(443, now - TimeDelta::days(1));
+        let expired = entry(444, now - CONFIG_EXPIRY - TimeDelta::days(1));
+        let used = CacheEntry {
+            last_success: Some(now - TimeDelta::days(1)),
+            ..entry(445, now - CONFIG_EXPIRY - TimeDelta::days(1))
+        };
+
+        let mut cache = EncryptedDnsProxyCache::from_entries(
+            PathBuf::new(),
+            vec![fresh.clone(), expired, used.clone()],
+            &[],
+            now,
+        );
+
+        let ports: Vec<_> = cache.configs().iter().map(|c| c.endpoint.port()).collect();
+        assert_eq!(ports, [443, 445]);
+        assert!(cache.state.next_configuration().is_some());
+        assert!(cache.state.next_configuration().is_some());
+        assert_eq!(cache.state.configurations().count(), 2);
+    }
+}
@@ -0,0 +1,337 @@
+//! Event hooks are executables that the daemon runs when the tunnel state changes, when the device
+//! changes (login, logout, revocation or key rotation), or when the settings change. This lets
+//! administrators react to these events without polling the management interface.
+//!
+//! Hooks are configured in [CONFIG_FILE] in the settings directory, which must only be writable by
+//! root:
+//!
+//! ```json
+//! {
+//!     "hooks": [
+//!         { "path": "/usr/local/bin/mount-shares", "events": ["tunnel_state"], "timeout_secs": 5 }
+//!     ]
+//! }
+//! ```
+//!
+//! Each hook is run with the event written to its standard input as JSON, e.g.
+//! `{ "event": "tunnel_state", "data": { "state": "connected", "details": { ... } } }`. The kind
+//! of event is also passed in the [EVENT_ENV_VAR] environment variable.
+//!
+//! Hooks are run in the background after the event has happened. The daemon never waits for them,
+//! so they cannot delay or prevent tunnel state transitions, including changes to the firewall.
+//! Events are delivered to the hooks in order, and a hook that runs for longer than its timeout is
+//! killed.
+
+use crate::settings::signed_patch;
+use futures::future::join_all;
+use mullvad_types::hooks::{EventHook, HookEvent};
+use serde::Serialize;
+use std::{path::Path, process::Stdio, sync::Arc};
+use talpid_types::ErrorExt;
+use tokio::{fs, io::AsyncWriteExt, process::Command, sync::mpsc};
+
+/// Root-only file in the settings directory that configures the event hooks.
+pub const CONFIG_FILE: &str = "hooks.json";
+
+/// Environment variable that holds the kind of event that a hook is run for.
+pub const EVENT_ENV_VAR: &str = "MULLVAD_HOOK_EVENT";
+
+/// Maximum number of events that may wait for the hooks to finish. Events beyond this are
+/// dropped.
+const EVENT_QUEUE_SIZE: usize = 32;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to read event hook configuration")]
+    ReadConfig(#[source] signed_patch::Error),
+
+    #[error("Invalid event hook configuration")]
+    ParseConfig(#[source] serde_json::Error),
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    hooks: Vec<EventHook>,
+}
+
+/// Read the hooks from the settings directory. Hooks whose executables may be modified by users
+/// other than root are skipped.
+pub async fn load(settings_dir: &Path) -> Result<Vec<EventHook>, Error> {
+    let Some(contents) = signed_patch::read_root_only(&settings_dir.join(CONFIG_FILE))
+        .await
+        .map_err(Error::ReadConfig)?
+    else {
+        return Ok(vec![]);
+    };
+    let config: Config = serde_json::from_str(&contents).map_err(Error::ParseConfig)?;
+
+    let mut hooks = Vec::with_capacity(config.hooks.len());
+    for hook in config.hooks {
+        match check_executable(&hook.path).await {
+            Ok(()) => hooks.push(hook),
+            Err(reason) => log::error!("Ignoring event hook {}: {reason}", hook.path.display()),
+        }
+    }
+    Ok(hooks)
+}
+
+/// Hooks are run as the same user as the daemon, so only executables that cannot be replaced by
+/// unprivileged users are accepted. This also applies to the Tor pluggable transports.
+pub(crate) async fn check_executable(path: &Path) -> Result<(), &'static str> {
+    if !path.is_absolute() {
+        return Err("the path is not absolute");
+    }
+    let metadata = fs::metadata(path)
+        .await
+        .map_err(|_| "the file cannot be read")?;
+    if !metadata.is_file() {
+        return Err("not a file");
+    }
+
+    // On Windows, it is up to the administrator to pick an executable in a protected location
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if metadata.uid() != 0 || metadata.mode() & 0o022 != 0 {
+            return Err("the file must be owned by root and not writable by other users");
+        }
+    }
+    Ok(())
+}
+
+/// The JSON document that is written to the standard input of a hook.
+#[derive(Serialize)]
+struct Payload<'a, T> {
+    event: HookEvent,
+    data: &'a T,
+}
+
+/// Handle used to run the configured hooks. Cloning it is cheap.
+#[derive(Clone)]
+pub struct HookRunner {
+    hooks: Arc<[EventHook]>,
+    tx: Option<mpsc::Sender<(HookEvent, Arc<[u8]>)>>,
+}
+
+impl HookRunner {
+    /// Start running `hooks` for the events passed to [HookRunner::notify]. If there are no
+    /// hooks, nothing is spawned.
+    pub fn spawn(hooks: Vec<EventHook>) -> Self {
+        let hooks: Arc<[EventHook]> = hooks.into();
+        if hooks.is_empty() {
+            return Self { hooks, tx: None };
+        }
+
+        log::info!("Running {} event hook(s)", hooks.len());
+        let (tx, mut rx) = mpsc::channel::<(HookEvent, Arc<[u8]>)>(EVENT_QUEUE_SIZE);
+        let task_hooks = hooks.clone();
+        tokio::spawn(async move {
+            while let Some((event, payload)) = rx.recv().await {
+                let runs = task_hooks
+                    .iter()
+                    .filter(|hook| hook.handles(event))
+                    .map(|hook| run_hook(hook, event, &payload));
+                join_all(runs).await;
+            }
+        });
+
+        Self {
+            hooks,
+            tx: Some(tx),
+        }
+    }
+
+    pub fn hooks(&self) -> &[EventHook] {
+        &self.hooks
+    }
+
+    /// Run the hooks that handle `event` in the background, passing `data` to them.
+    pub fn notify(&self, event: HookEvent, data: &impl Serialize) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        if !self.hooks.iter().any(|hook| hook.handles(event)) {
+            return;
+        }
+        let payload = match serde_json::to_vec(&Payload { event, data }) {
+            Ok(payload) => payload,
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to serialize event for hooks")
+                );
+                return;
+            }
+        };
+        if tx.try_send((event, payload.into())).is_err() {
+            log::warn!("Event hooks are not keeping up, dropping {event} event");
+        }
+    }
+}
+
+async fn run_hook(hook: &EventHook, event: HookEvent, payload: &[u8]) {
+    let path = hook.path.display();
+    let mut child = match Command::new(&hook.path)
+        .env(EVENT_ENV_VAR, event.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(error) => {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg(&format!("Failed to start event hook {path}"))
+            );
+            return;
+        }
+    };
+
+    let result = tokio::time::timeout(hook.timeout(), async {
+        if let Some(mut stdin) = child.stdin.take() {
+            // The hook is free to ignore its input, so a closed pipe is not an error
+            let _ = stdin.write_all(payload).await;
+        }
+        child.wait().await
+    })
+    .await;
+
+    match result {
+        Ok(Ok(status)) if status.success() => (),
+        Ok(Ok(status)) => log::warn!("Event hook {path} failed: {status}"),
+        Ok(Err(error)) => log::error!(
+            "{}",
+            error.display_chain_with_msg(&format!("Failed to wait for event hook {path}"))
+        ),
+        Err(_) => {
+            log::warn!(
+                "Event hook {path} did not finish within {} seconds, killing it",
+                hook.timeout_secs
+            );
+            let _ = child.kill().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "hooks": [
+                    { "path": "/usr/local/bin/on-connect", "events": ["tunnel_state"] },
+                    { "path": "/usr/local/bin/log-all", "timeout_secs": 2 }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let [on_connect, log_all] = &config.hooks[..] else {
+            panic!("expected two hooks");
+        };
+        assert!(on_connect.handles(HookEvent::TunnelState));
+        assert!(!on_connect.handles(HookEvent::Settings));
+        assert_eq!(on_connect.timeout(), Duration::from_secs(10));
+        assert!(log_all.handles(HookEvent::Device));
+        assert_eq!(log_all.timeout(), Duration::from_secs(2));
+
+        serde_json::from_str::<Config>(r#"{ "hooks": [{ "path": "/bin/true", "args": [] }] }"#)
+            .unwrap_err();
+        serde_json::from_str::<Config>(
+            r#"{ "hooks": [{ "path": "/bin/true", "events": ["x"] }] }"#,
+        )
+        .unwrap_err();
+    }
+
+    /// Write an executable shell script to `dir` and return a hook that runs it.
+    #[cfg(unix)]
+    fn script_hook(dir: &Path, script: &str, timeout_secs: u64) -> EventHook {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("hook.sh");
+        std::fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        EventHook {
+            path,
+            events: vec![HookEvent::TunnelState],
+            timeout_secs,
+        }
+    }
+
+    /// The hook receives the event on its standard input and in its environment.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hook_payload_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("output");
+        let hook = script_hook(
+            dir.path(),
+            &format!(
+                "{{ echo \"${EVENT_ENV_VAR}\"; cat; }} > {}.tmp && mv {0}.tmp {0}",
+                output.display()
+            ),
+            10,
+        );
+
+        let runner = HookRunner::spawn(vec![hook]);
+        // Events that the hook does not handle are not delivered
+        runner.notify(HookEvent::Settings, &"ignored");
+        runner.notify(
+            HookEvent::TunnelState,
+            &serde_json::json!({ "state": "connected" }),
+        );
+
+        let contents = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Ok(contents) = fs::read_to_string(&output).await {
+                    return contents;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("the hook was not run");
+
+        let (event, payload) = contents.split_once('\n').unwrap();
+        assert_eq!(event, "tunnel_state");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(payload).unwrap(),
+            serde_json::json!({ "event": "tunnel_state", "data": { "state": "connected" } })
+        );
+    }
+
+    /// A hook that runs for longer than its timeout is killed.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hook_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let hook = script_hook(
+            dir.path(),
+            &format!("echo $$ > {}\nexec sleep 60", pid_file.display()),
+            1,
+        );
+
+        let started = std::time::Instant::now();
+        run_hook(&hook, HookEvent::TunnelState, b"{}").await;
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let pid: i32 = std::fs::read_to_string(&pid_file)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        assert_eq!(
+            nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid), None),
+            Err(nix::errno::Errno::ESRCH),
+            "the hook is still running"
+        );
+    }
+}
@@ -2,6 +2,8 @@
 #![allow(rustdoc::private_intra_doc_links)]
 
 mod access_method;
+mod access_method_health;
+mod account_data_cache;
 pub mod account_history;
 mod api;
 mod api_address_updater;
@@ -9,24 +11,34 @@ mod api_address_updater;
 mod cleanup;
 mod custom_list;
 pub mod device;
+mod device_labels;
 mod dns;
+mod domain_fronting;
+mod encrypted_dns_proxy;
 pub mod exception_logging;
 mod geoip;
+mod hooks;
 pub mod logging;
 #[cfg(target_os = "macos")]
 mod macos;
+mod managed_profile;
 pub mod management_interface;
 mod migrations;
-mod relay_list;
+pub mod relay_list;
+mod relay_rotation;
 #[cfg(not(target_os = "android"))]
 pub mod rpc_uniqueness_check;
 pub mod runtime;
 pub mod settings;
 pub mod shutdown;
+pub mod speed_test;
+mod system_proxy;
 mod target_state;
+mod tor_transports;
 mod tunnel;
 pub mod version;
 mod version_check;
+mod wireguard_ip_versions;
 
 use crate::target_state::PersistentTargetState;
 use api::AccessMethodEvent;
@@ -48,18 +60,31 @@ use mullvad_types::settings::SplitApp;
 #[cfg(daita)]
 use mullvad_types::wireguard::DaitaSettings;
 use mullvad_types::{
-    access_method::{AccessMethod, AccessMethodSetting},
-    account::{AccountData, AccountNumber, VoucherSubmission},
+    access_method::{
+        AccessMethod, AccessMethodSetting, AccessMethodStatus, DohResolver,
+        EncryptedDnsProxyConfigInfo, TorSettings,
+    },
+    account::{AccountData, AccountNumber, RedeemedVoucher, VoucherSubmission},
     auth_failed::AuthFailed,
     custom_list::CustomList,
-    device::{Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceState, RemoveDeviceEvent},
+    device::{
+        Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceLabel, DeviceState,
+        RemoveDeviceEvent,
+    },
     features::{compute_feature_indicators, FeatureIndicator, FeatureIndicators},
+    hooks::{EventHook, HookEvent},
     location::{GeoIpLocation, LocationEventData},
+    network_rules::{NetworkAction, NetworkRulesSettings},
+    policy::SettingsPolicy,
+    profile::SettingsProfile,
     relay_constraints::{
         BridgeSettings, BridgeState, BridgeType, ObfuscationSettings, RelayOverride, RelaySettings,
     },
     relay_list::RelayList,
-    settings::{DnsOptions, Settings},
+    settings::{
+        DnsOptions, ExpiryWarningSettings, RelayRotationSettings, Settings, SettingsPatchChange,
+    },
+    speed_test::SpeedTestResult,
     states::{Secured, TargetState, TargetStateStrict, TunnelState},
     version::{AppVersion, AppVersionInfo},
     wireguard::{PublicKey, QuantumResistantState, RotationInterval},
@@ -87,7 +112,7 @@ use talpid_types::android::AndroidContext;
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 use talpid_types::{
-    net::{IpVersion, TunnelType},
+    net::{IpVersion, NetworkIdentity, TunnelType},
     tunnel::{ErrorStateCause, TunnelStateTransition},
     ErrorExt,
 };
@@ -171,6 +196,9 @@ pub enum Error {
     #[error("Account history error")]
     AccountHistory(#[source] account_history::Error),
 
+    #[error("Device label error")]
+    DeviceLabels(#[source] device_labels::Error),
+
     #[cfg(not(target_os = "android"))]
     #[error("Factory reset partially failed: {0}")]
     FactoryResetError(&'static str),
@@ -187,6 +215,7 @@ pub enum Error {
 
     #[error("API connection mode error")]
     ApiConnectionModeError(#[source] api::Error),
+
     #[error("No custom bridge has been specified")]
     NoCustomProxySaved,
 
@@ -225,11 +254,15 @@ pub enum DaemonCommand {
     GetAccountHistory(oneshot::Sender<Option<AccountNumber>>),
     /// Remove the last used account, if there is one
     ClearAccountHistory(ResponseTx<(), Error>),
+    /// Get the vouchers redeemed on this device for the last used account
+    GetVoucherHistory(oneshot::Sender<Vec<RedeemedVoucher>>),
     /// Get the list of countries and cities where there are relays.
     GetRelayLocations(oneshot::Sender<RelayList>),
     /// Trigger an asynchronous relay list update. This returns before the relay list is actually
     /// updated.
     UpdateRelayLocations,
+    /// Replace the relay list with a signed relay list obtained out-of-band.
+    ImportRelayList(ResponseTx<(), relay_list::Error>, String),
     /// Log in with a given account and create a new device.
     LoginAccount(ResponseTx<(), Error>, AccountNumber),
     /// Log out of the current account and remove the device, if they exist.
@@ -242,6 +275,10 @@ pub enum DaemonCommand {
     ListDevices(ResponseTx<Vec<Device>, Error>, AccountNumber),
     /// Remove device from a given account.
     RemoveDevice(ResponseTx<(), Error>, AccountNumber, DeviceId),
+    /// Set or clear the local label and note of a device
+    SetDeviceLabel(ResponseTx<(), Error>, DeviceLabel),
+    /// Get the local labels of all devices
+    GetDeviceLabels(oneshot::Sender<Vec<DeviceLabel>>),
     /// Place constraints on the type of tunnel and relay
     SetRelaySettings(ResponseTx<(), settings::Error>, RelaySettings),
     /// Set the allow LAN setting.
@@ -275,6 +312,14 @@ pub enum DaemonCommand {
     SetRelayOverride(ResponseTx<(), settings::Error>, RelayOverride),
     /// Remove all relay override options
     ClearAllRelayOverrides(ResponseTx<(), settings::Error>),
+    /// Set when to automatically reconnect to a different exit relay
+    SetRelayRotationSettings(ResponseTx<(), settings::Error>, RelayRotationSettings),
+    /// Set what to do when joining specific networks
+    SetNetworkRules(ResponseTx<(), settings::Error>, NetworkRulesSettings),
+    /// Set when to warn that the account is about to expire
+    SetExpiryWarningSettings(ResponseTx<(), settings::Error>, ExpiryWarningSettings),
+    /// Get the identity of the network that the host is currently connected to
+    GetCurrentNetwork(oneshot::Sender<NetworkIdentity>),
     /// Toggle macOS network check leak
     /// Set MTU for wireguard tunnels
     SetWireguardMtu(ResponseTx<(), settings::Error>, Option<u16>),
@@ -313,6 +358,8 @@ pub enum DaemonCommand {
     ClearCustomApiAccessMethods(ResponseTx<(), Error>),
     /// Get the currently used API access method
     GetCurrentAccessMethod(ResponseTx<AccessMethodSetting, Error>),
+    /// Get the statistics of all API access methods, in the order they will be tried in
+    GetApiAccessMethodStatistics(ResponseTx<Vec<AccessMethodStatus>, Error>),
     /// Test an API access method
     TestApiAccessMethodById(ResponseTx<bool, Error>, mullvad_types::access_method::Id),
     /// Test a custom API access method
@@ -320,6 +367,16 @@ pub enum DaemonCommand {
         ResponseTx<bool, Error>,
         talpid_types::net::proxy::CustomProxy,
     ),
+    /// Set or clear the URL of the PAC file used by the system proxy access method
+    SetSystemProxyPacUrl(ResponseTx<(), Error>, Option<String>),
+    /// Set the DoH resolvers used by the Encrypted DNS proxy access method
+    SetEncryptedDnsProxyResolvers(ResponseTx<(), Error>, Vec<DohResolver>),
+    /// Get the cached Encrypted DNS proxy configurations
+    GetEncryptedDnsProxyConfigs(ResponseTx<Vec<EncryptedDnsProxyConfigInfo>, Error>),
+    /// Fetch new Encrypted DNS proxy configurations
+    RefreshEncryptedDnsProxyConfigs(ResponseTx<Vec<EncryptedDnsProxyConfigInfo>, Error>),
+    /// Set the bridges and pluggable transports used by the Tor access method
+    SetTorSettings(ResponseTx<(), Error>, TorSettings),
     /// Get information about the currently running and latest app versions
     GetVersionInfo(oneshot::Sender<Result<AppVersionInfo, Error>>),
     /// Return whether the daemon is performing post-upgrade tasks
@@ -376,10 +433,37 @@ pub enum DaemonCommand {
     VerifyPlayPurchase(ResponseTx<(), Error>, PlayPurchase),
     /// Patch the settings using a JSON patch
     ApplyJsonSettings(ResponseTx<(), settings::patch::Error>, String),
+    /// Return the changes that applying a JSON patch would make, without applying it
+    DiffJsonSettings(
+        ResponseTx<Vec<SettingsPatchChange>, settings::patch::Error>,
+        String,
+    ),
     /// Return a JSON blob containing all overridable settings, if there are any
     ExportJsonSettings(ResponseTx<String, settings::patch::Error>),
+    /// Return a versioned backup of all settings
+    ExportSettingsBackup(ResponseTx<String, settings::backup::Error>),
+    /// Replace all settings with the ones in a backup
+    ImportSettingsBackup(ResponseTx<(), settings::backup::Error>, String),
+    /// Save a new settings profile
+    CreateSettingsProfile(ResponseTx<(), settings::profiles::Error>, SettingsProfile),
+    /// Return all settings profiles
+    GetSettingsProfiles(oneshot::Sender<Vec<SettingsProfile>>),
+    /// Apply the settings profile with the given name
+    ApplySettingsProfile(ResponseTx<(), settings::profiles::Error>, String),
+    /// Delete the settings profile with the given name
+    DeleteSettingsProfile(ResponseTx<(), settings::profiles::Error>, String),
+    /// Return the settings that are locked by the administrator policy
+    GetSettingsPolicy(oneshot::Sender<SettingsPolicy>),
+    /// Return the configured event hooks
+    GetEventHooks(oneshot::Sender<Vec<EventHook>>),
     /// Request the current feature indicators.
     GetFeatureIndicators(oneshot::Sender<FeatureIndicators>),
+    /// Measure latency and throughput through the tunnel, against the given endpoint.
+    RunSpeedTest(
+        ResponseTx<SpeedTestResult, speed_test::Error>,
+        String,
+        Duration,
+    ),
 }
 
 /// All events that can happen in the daemon. Sent from various threads and exposed interfaces.
@@ -406,6 +490,11 @@ pub(crate) enum InternalDaemonEvent {
     LocationEvent(LocationEventData),
     /// A generic event for when any settings change.
     SettingsChanged,
+    /// The host has joined a different network, or left the current one.
+    #[cfg(not(target_os = "android"))]
+    NetworkChanged(NetworkIdentity),
+    /// A verified settings patch was fetched from the managed profile.
+    ManagedProfileUpdate(String),
     /// The split tunnel paths or state were updated.
     #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     ExcludedPathsEvent(ExcludedPathsUpdate, oneshot::Sender<Result<(), Error>>),
@@ -565,10 +654,18 @@ pub struct Daemon {
     rx: mpsc::UnboundedReceiver<InternalDaemonEvent>,
     tx: DaemonEventSender,
     reconnection_job: Option<AbortHandle>,
+    relay_rotation_job: Option<AbortHandle>,
+    network_identity: NetworkIdentity,
+    /// Action of the network rule that currently overrides the target state, if any.
+    network_rule_action: Option<NetworkAction>,
     management_interface: ManagementInterfaceServer,
     migration_complete: migrations::MigrationComplete,
     settings: SettingsPersister,
+    profiles: settings::profiles::ProfileStore,
+    event_hooks: hooks::HookRunner,
     account_history: account_history::AccountHistory,
+    account_data_cache: account_data_cache::AccountDataCache,
+    device_labels: device_labels::DeviceLabels,
     device_checker: device::TunnelStateChangeHandler,
     account_manager: device::AccountManagerHandle,
     access_mode_handler: api::AccessModeSelectorHandle,
@@ -583,6 +680,8 @@ pub struct Daemon {
     #[cfg(target_os = "windows")]
     volume_update_tx: mpsc::UnboundedSender<()>,
     location_handler: GeoIpHandler,
+    settings_dir: PathBuf,
+    cache_dir: PathBuf,
 }
 
 impl Daemon {
@@ -630,17 +729,55 @@ impl Daemon {
 
         let settings_event_listener = management_interface.notifier().clone();
         let mut settings = SettingsPersister::load(&settings_dir).await;
+
+        let managed_profile = managed_profile::Config::load(&settings_dir)
+            .await
+            .unwrap_or_else(|error| {
+                log::error!("{}", error.display_chain());
+                None
+            });
+        if managed_profile.is_some() {
+            // Apply the last known profile until a new one has been fetched
+            match managed_profile::load_cached_patch(&settings_dir, &cache_dir).await {
+                Ok(Some(patch)) => {
+                    if let Err(error) =
+                        settings::patch::apply_managed_patch(&mut settings, &patch).await
+                    {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to apply cached managed profile")
+                        );
+                    }
+                }
+                Ok(None) => (),
+                Err(error) => log::error!("{}", error.display_chain()),
+            }
+        }
+
+        let profiles = settings::profiles::ProfileStore::load(&settings_dir).await;
+
         settings.register_change_listener(move |settings| {
             // Notify management interface server of changes to the settings
             settings_event_listener.notify_settings(settings.to_owned());
         });
 
+        let event_hooks =
+            hooks::HookRunner::spawn(hooks::load(&settings_dir).await.unwrap_or_else(|error| {
+                log::error!("{}", error.display_chain());
+                vec![]
+            }));
+        let settings_hooks = event_hooks.clone();
+        settings.register_change_listener(move |settings| {
+            settings_hooks.notify(HookEvent::Settings, settings);
+        });
+
         let initial_selector_config = new_selector_config(&settings);
         let relay_selector = RelaySelector::new(
             initial_selector_config,
             resource_dir.join(RELAYS_FILENAME),
             cache_dir.join(RELAYS_FILENAME),
         );
+        relay_selector.set_latency_cache(speed_test::load_latency_cache(&cache_dir));
 
         let settings_relay_selector = relay_selector.clone();
         settings.register_change_listener(move |settings| {
@@ -650,10 +787,17 @@ impl Daemon {
                 .set_config(new_selector_config(settings));
         });
 
+        let tor_transports = tor_transports::load(&settings_dir)
+            .await
+            .unwrap_or_else(|error| {
+                log::error!("{}", error.display_chain());
+                vec![]
+            });
         let (access_mode_handler, access_mode_provider) = api::AccessModeSelector::spawn(
             cache_dir.clone(),
             relay_selector.clone(),
             settings.api_access_methods.clone(),
+            tor_transports,
             internal_event_tx.to_specialized_sender(),
             api_runtime.address_cache().clone(),
         )
@@ -695,6 +839,7 @@ impl Daemon {
                 .wireguard
                 .rotation_interval
                 .unwrap_or_default(),
+            settings.expiry_warnings.thresholds.clone(),
             internal_event_tx.to_specialized_sender(),
         )
         .await
@@ -706,6 +851,8 @@ impl Daemon {
         )
         .await
         .map_err(Error::LoadAccountHistory)?;
+        let account_data_cache = account_data_cache::AccountDataCache::load(&settings_dir).await;
+        let device_labels = device_labels::DeviceLabels::load(&settings_dir).await;
 
         let target_state = if settings.auto_connect {
             log::info!("Automatically connecting since auto-connect is turned on");
@@ -731,6 +878,7 @@ impl Daemon {
             account_manager.clone(),
             relay_selector.clone(),
             settings.tunnel_options.clone(),
+            wireguard_ip_versions::WorkingIpVersions::load(&cache_dir).await,
         );
 
         let param_gen = parameters_generator.clone();
@@ -752,6 +900,17 @@ impl Daemon {
         });
 
         let (offline_state_tx, offline_state_rx) = mpsc::unbounded();
+        #[cfg(not(target_os = "android"))]
+        let (network_identity_tx, mut network_identity_rx) = mpsc::unbounded();
+        #[cfg(not(target_os = "android"))]
+        {
+            let network_event_sender = internal_event_tx.clone();
+            tokio::spawn(async move {
+                while let Some(network) = network_identity_rx.next().await {
+                    let _ = network_event_sender.send(InternalDaemonEvent::NetworkChanged(network));
+                }
+            });
+        }
         #[cfg(target_os = "windows")]
         let (volume_update_tx, volume_update_rx) = mpsc::unbounded();
         let tunnel_state_machine_handle = tunnel_state_machine::spawn(
@@ -773,6 +932,8 @@ impl Daemon {
             resource_dir.clone(),
             internal_event_tx.to_specialized_sender(),
             offline_state_tx,
+            #[cfg(not(target_os = "android"))]
+            network_identity_tx,
             #[cfg(target_os = "windows")]
             volume_update_rx,
             #[cfg(target_os = "android")]
@@ -796,6 +957,7 @@ impl Daemon {
         let mut relay_list_updater = RelayListUpdater::spawn(
             relay_selector.clone(),
             api_handle.clone(),
+            mullvad_api::RelayListSigningKeys::pinned(),
             &cache_dir,
             on_relay_list_update,
         );
@@ -812,6 +974,16 @@ impl Daemon {
         // Attempt to download a fresh relay list
         relay_list_updater.update().await;
 
+        if let Some(config) = managed_profile {
+            managed_profile::spawn(
+                config,
+                settings_dir.clone(),
+                cache_dir.clone(),
+                api_runtime.rest_handle(),
+                internal_event_tx.clone(),
+            );
+        }
+
         let location_handler = GeoIpHandler::new(
             api_runtime.rest_handle(),
             internal_event_tx.clone().to_specialized_sender(),
@@ -828,10 +1000,17 @@ impl Daemon {
             rx: internal_event_rx,
             tx: internal_event_tx,
             reconnection_job: None,
+            relay_rotation_job: None,
+            network_identity: NetworkIdentity::default(),
+            network_rule_action: None,
             management_interface,
             migration_complete,
             settings,
+            profiles,
+            event_hooks,
             account_history,
+            account_data_cache,
+            device_labels,
             device_checker: device::TunnelStateChangeHandler::new(account_manager.clone()),
             account_manager,
             access_mode_handler,
@@ -846,6 +1025,8 @@ impl Daemon {
             #[cfg(target_os = "windows")]
             volume_update_tx,
             location_handler,
+            settings_dir,
+            cache_dir,
         };
 
         api_availability.unsuspend();
@@ -964,6 +1145,9 @@ impl Daemon {
             SettingsChanged => {
                 self.update_feature_indicators_on_settings_changed();
             }
+            #[cfg(not(target_os = "android"))]
+            NetworkChanged(network) => self.handle_network_changed(network).await,
+            ManagedProfileUpdate(patch) => self.handle_managed_profile_update(patch).await,
             #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             ExcludedPathsEvent(update, tx) => self.handle_new_excluded_paths(update, tx).await,
         }
@@ -996,6 +1180,11 @@ impl Daemon {
                 }
             }
             TunnelStateTransition::Connected(endpoint) => {
+                if endpoint.tunnel_type == TunnelType::Wireguard {
+                    self.parameters_generator
+                        .record_working_ip_version(endpoint.ip_version)
+                        .await;
+                }
                 let feature_indicators = compute_feature_indicators(
                     &self.settings.to_settings(),
                     &endpoint,
@@ -1020,6 +1209,12 @@ impl Daemon {
             self.unschedule_reconnect();
         }
 
+        if tunnel_state.is_connected() {
+            self.start_relay_rotation();
+        } else {
+            self.stop_relay_rotation();
+        }
+
         if self.tunnel_state.is_disconnected() && !tunnel_state.is_disconnected() {
             // Enable background API requests when leaving the disconnected state.
             self.api_handle.availability.resume_background();
@@ -1062,7 +1257,18 @@ impl Daemon {
             _ => {}
         }
 
+        // Several access methods can only be probed at once if the firewall does not block them
+        self.access_mode_handler.allow_parallel_probing(matches!(
+            tunnel_state,
+            TunnelState::Disconnected {
+                locked_down: false,
+                ..
+            } | TunnelState::Connected { .. }
+        ));
+
         self.tunnel_state = tunnel_state.clone();
+        self.event_hooks
+            .notify(HookEvent::TunnelState, &tunnel_state);
         self.management_interface
             .notifier()
             .notify_new_state(tunnel_state);
@@ -1214,6 +1420,98 @@ impl Daemon {
         }
     }
 
+    /// Start watching the connected tunnel for when to rotate the exit relay, if enabled.
+    fn start_relay_rotation(&mut self) {
+        self.stop_relay_rotation();
+
+        let settings = self.settings.relay_rotation.clone();
+        if !settings.is_enabled() {
+            return;
+        }
+        self.relay_rotation_job = Some(relay_rotation::spawn(
+            settings,
+            Arc::downgrade(self.tunnel_state_machine_handle.command_tx()),
+            self.parameters_generator.clone(),
+            self.tx.to_specialized_sender(),
+        ));
+    }
+
+    fn stop_relay_rotation(&mut self) {
+        if let Some(job) = self.relay_rotation_job.take() {
+            job.abort();
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn handle_network_changed(&mut self, network: NetworkIdentity) {
+        log::debug!("Current network: {network}");
+        let key = network.key().map(str::to_owned);
+        self.parameters_generator
+            .set_current_network(key.clone())
+            .await;
+        self.access_mode_handler.set_network(key);
+        self.network_identity = network;
+        self.apply_network_rules().await;
+    }
+
+    /// Applies the network rule that matches the current network, if any. The action of the rule
+    /// overrides the target state until the network changes or a target state is set, without
+    /// changing the target state itself.
+    async fn apply_network_rules(&mut self) {
+        let network_rules = &self.settings.network_rules;
+        let action = network_rules.action_for(&self.network_identity);
+        if action.is_none()
+            && network_rules.matching_action(&self.network_identity)
+                == Some(NetworkAction::Disconnect)
+        {
+            log::warn!("Ignoring network rule to disconnect, since disconnecting is not allowed");
+        }
+        if action == self.network_rule_action {
+            return;
+        }
+
+        let previous_target_state = self.effective_target_state();
+        self.network_rule_action = action;
+        let target_state = self.effective_target_state();
+        let blocked_by_rule = match &self.tunnel_state {
+            TunnelState::Error(state) => {
+                matches!(state.cause(), ErrorStateCause::BlockedByNetworkRule)
+            }
+            _ => false,
+        };
+
+        match action {
+            Some(NetworkAction::Block) => {
+                log::debug!("Applying network rule: {}", NetworkAction::Block);
+                self.send_tunnel_command(TunnelCommand::Block(
+                    ErrorStateCause::BlockedByNetworkRule,
+                ));
+                return;
+            }
+            Some(action) => log::debug!("Applying network rule: {action}"),
+            None => log::debug!(
+                "No network rule applies, returning to target state {:?}",
+                *self.target_state
+            ),
+        }
+        if target_state != previous_target_state || blocked_by_rule {
+            match target_state {
+                TargetState::Secured => self.connect_tunnel(),
+                TargetState::Unsecured => self.disconnect_tunnel(),
+            }
+        }
+    }
+
+    /// The target state that the tunnel is driven towards. This is the target state set by the
+    /// user, unless it is overridden by a network rule.
+    fn effective_target_state(&self) -> TargetState {
+        match self.network_rule_action {
+            Some(NetworkAction::Connect | NetworkAction::Block) => TargetState::Secured,
+            Some(NetworkAction::Disconnect) => TargetState::Unsecured,
+            None => *self.target_state,
+        }
+    }
+
     async fn handle_command(&mut self, command: DaemonCommand) {
         use self::DaemonCommand::*;
         if self.tunnel_state.is_disconnected() {
@@ -1230,6 +1528,7 @@ impl Daemon {
             SubmitVoucher(tx, voucher) => self.on_submit_voucher(tx, voucher),
             GetRelayLocations(tx) => self.on_get_relay_locations(tx),
             UpdateRelayLocations => self.on_update_relay_locations().await,
+            ImportRelayList(tx, blob) => self.on_import_relay_list(tx, blob),
             LoginAccount(tx, account_number) => self.on_login_account(tx, account_number),
             LogoutAccount(tx) => self.on_logout_account(tx),
             GetDevice(tx) => self.on_get_device(tx),
@@ -1238,8 +1537,11 @@ impl Daemon {
             RemoveDevice(tx, account_number, device_id) => {
                 self.on_remove_device(tx, account_number, device_id)
             }
+            SetDeviceLabel(tx, label) => self.on_set_device_label(tx, label),
+            GetDeviceLabels(tx) => self.on_get_device_labels(tx),
             GetAccountHistory(tx) => self.on_get_account_history(tx),
             ClearAccountHistory(tx) => self.on_clear_account_history(tx).await,
+            GetVoucherHistory(tx) => self.on_get_voucher_history(tx),
             SetRelaySettings(tx, update) => self.on_set_relay_settings(tx, update).await,
             SetAllowLan(tx, allow_lan) => self.on_set_allow_lan(tx, allow_lan).await,
             SetShowBetaReleases(tx, enabled) => self.on_set_show_beta_releases(tx, enabled).await,
@@ -1273,6 +1575,18 @@ impl Daemon {
                 self.on_set_relay_override(tx, relay_override).await
             }
             ClearAllRelayOverrides(tx) => self.on_clear_all_relay_overrides(tx).await,
+            SetRelayRotationSettings(tx, relay_rotation) => {
+                self.on_set_relay_rotation_settings(tx, relay_rotation)
+                    .await
+            }
+            SetNetworkRules(tx, network_rules) => {
+                self.on_set_network_rules(tx, network_rules).await
+            }
+            SetExpiryWarningSettings(tx, expiry_warnings) => {
+                self.on_set_expiry_warning_settings(tx, expiry_warnings)
+                    .await
+            }
+            GetCurrentNetwork(tx) => self.on_get_current_network(tx),
             SetWireguardMtu(tx, mtu) => self.on_set_wireguard_mtu(tx, mtu).await,
             SetWireguardRotationInterval(tx, interval) => {
                 self.on_set_wireguard_rotation_interval(tx, interval).await
@@ -1294,9 +1608,20 @@ impl Daemon {
             UpdateApiAccessMethod(tx, method) => self.on_update_api_access_method(tx, method).await,
             ClearCustomApiAccessMethods(tx) => self.on_clear_custom_api_access_methods(tx).await,
             GetCurrentAccessMethod(tx) => self.on_get_current_api_access_method(tx),
+            GetApiAccessMethodStatistics(tx) => self.on_get_api_access_method_statistics(tx),
             SetApiAccessMethod(tx, method) => self.on_set_api_access_method(tx, method).await,
             TestApiAccessMethodById(tx, method) => self.on_test_api_access_method(tx, method).await,
             TestCustomApiAccessMethod(tx, proxy) => self.on_test_proxy_as_access_method(tx, proxy),
+            SetSystemProxyPacUrl(tx, pac_url) => {
+                self.on_set_system_proxy_pac_url(tx, pac_url).await
+            }
+            SetEncryptedDnsProxyResolvers(tx, resolvers) => {
+                self.on_set_encrypted_dns_proxy_resolvers(tx, resolvers)
+                    .await
+            }
+            GetEncryptedDnsProxyConfigs(tx) => self.on_get_encrypted_dns_proxy_configs(tx),
+            RefreshEncryptedDnsProxyConfigs(tx) => self.on_refresh_encrypted_dns_proxy_configs(tx),
+            SetTorSettings(tx, tor_settings) => self.on_set_tor_settings(tx, tor_settings).await,
             IsPerformingPostUpgrade(tx) => self.on_is_performing_post_upgrade(tx),
             GetCurrentVersion(tx) => self.on_get_current_version(tx),
             #[cfg(not(target_os = "android"))]
@@ -1334,8 +1659,22 @@ impl Daemon {
                 self.on_verify_play_purchase(tx, play_purchase)
             }
             ApplyJsonSettings(tx, blob) => self.on_apply_json_settings(tx, blob).await,
+            DiffJsonSettings(tx, blob) => self.on_diff_json_settings(tx, blob).await,
             ExportJsonSettings(tx) => self.on_export_json_settings(tx),
+            ExportSettingsBackup(tx) => self.on_export_settings_backup(tx),
+            ImportSettingsBackup(tx, blob) => self.on_import_settings_backup(tx, blob).await,
+            CreateSettingsProfile(tx, profile) => {
+                self.on_create_settings_profile(tx, profile).await
+            }
+            GetSettingsProfiles(tx) => self.on_get_settings_profiles(tx),
+            ApplySettingsProfile(tx, name) => self.on_apply_settings_profile(tx, name).await,
+            DeleteSettingsProfile(tx, name) => self.on_delete_settings_profile(tx, name).await,
+            GetSettingsPolicy(tx) => self.on_get_settings_policy(tx),
+            GetEventHooks(tx) => self.on_get_event_hooks(tx),
             GetFeatureIndicators(tx) => self.on_get_feature_indicators(tx),
+            RunSpeedTest(tx, endpoint, duration) => {
+                self.on_run_speed_test(tx, endpoint, duration).await
+            }
         }
     }
 
@@ -1358,7 +1697,7 @@ impl Daemon {
                         error.display_chain_with_msg("Failed to update account history")
                     );
                 }
-                if *self.target_state == TargetState::Secured {
+                if self.effective_target_state() == TargetState::Secured {
                     log::debug!("Initiating tunnel restart because the account number changed");
                     self.reconnect_tunnel();
                 }
@@ -1370,7 +1709,7 @@ impl Daemon {
             AccountEvent::Device(PrivateDeviceEvent::Revoked) => {
                 // If we're currently in a secured state, reconnect to make sure we immediately
                 // enter the error state.
-                if *self.target_state == TargetState::Secured {
+                if self.effective_target_state() == TargetState::Secured {
                     self.connect_tunnel();
                 }
             }
@@ -1379,7 +1718,9 @@ impl Daemon {
                     self.schedule_reconnect(WG_RECONNECT_DELAY);
                 }
             }
-            AccountEvent::Expiry(expiry) if *self.target_state == TargetState::Secured => {
+            AccountEvent::Expiry(expiry)
+                if self.effective_target_state() == TargetState::Secured =>
+            {
                 if expiry >= &chrono::Utc::now() {
                     if let TunnelState::Error(ref state) = self.tunnel_state {
                         if matches!(state.cause(), ErrorStateCause::AuthFailed(_)) {
@@ -1394,12 +1735,21 @@ impl Daemon {
                     )))
                 }
             }
+            AccountEvent::ExpiryWarning(warning) => {
+                self.event_hooks
+                    .notify(HookEvent::AccountExpiryWarning, warning);
+                self.management_interface
+                    .notifier()
+                    .notify_account_expiry_warning(warning.clone());
+            }
             _ => (),
         }
         if let AccountEvent::Device(event) = event {
+            let event = DeviceEvent::from(event);
+            self.event_hooks.notify(HookEvent::Device, &event);
             self.management_interface
                 .notifier()
-                .notify_device_event(DeviceEvent::from(event));
+                .notify_device_event(event);
         }
     }
 
@@ -1446,6 +1796,7 @@ impl Daemon {
     ) {
         let account_manager = self.account_manager.clone();
         let notifier = self.management_interface.notifier().clone();
+        let event_hooks = self.event_hooks.clone();
         tokio::spawn(async move {
             if let Ok(Some(_)) = account_manager
                 .data_after_login()
@@ -1474,6 +1825,7 @@ impl Daemon {
                         new_state: DeviceState::LoggedOut,
                     },
                 };
+                event_hooks.notify(HookEvent::Device, &event);
                 notifier.notify_device_event(event);
             }
         });
@@ -1510,7 +1862,9 @@ impl Daemon {
     }
 
     fn on_reconnect(&mut self, tx: oneshot::Sender<bool>) {
-        if *self.target_state == TargetState::Secured || self.tunnel_state.is_in_error_state() {
+        if self.effective_target_state() == TargetState::Secured
+            || self.tunnel_state.is_in_error_state()
+        {
             self.connect_tunnel();
             Self::oneshot_send(tx, true, "reconnect issued");
         } else {
@@ -1564,8 +1918,24 @@ impl Daemon {
         account_number: AccountNumber,
     ) {
         let account = self.account_manager.account_service.clone();
+        let cache = self.account_data_cache.clone();
         tokio::spawn(async move {
-            let result = account.get_data(account_number).await;
+            let result = match account.get_data(account_number.clone()).await {
+                Ok(data) => {
+                    cache.store_account_data(&account_number, &data).await;
+                    Ok(data)
+                }
+                Err(error) if error.is_network_error() => {
+                    match cache.account_data(&account_number).await {
+                        Some(data) => {
+                            log::warn!("Failed to reach the API. Returning cached account data");
+                            Ok(data)
+                        }
+                        None => Err(error),
+                    }
+                }
+                Err(error) => Err(error),
+            };
             Self::oneshot_send(tx, result, "account data");
         });
     }
@@ -1594,13 +1964,19 @@ impl Daemon {
 
     fn on_submit_voucher(&mut self, tx: ResponseTx<VoucherSubmission, Error>, voucher: String) {
         let manager = self.account_manager.clone();
+        let cache = self.account_data_cache.clone();
         tokio::spawn(async move {
+            let result = manager.submit_voucher(voucher).await;
+            if let Ok(submission) = &result {
+                if let Ok(Some(device)) = manager.data().await.map(|s| s.into_device()) {
+                    cache
+                        .record_voucher(&device.account_number, submission)
+                        .await;
+                }
+            }
             Self::oneshot_send(
                 tx,
-                manager
-                    .submit_voucher(voucher)
-                    .await
-                    .map_err(Error::VoucherSubmission),
+                result.map_err(Error::VoucherSubmission),
                 "submit_voucher response",
             );
         });
@@ -1614,6 +1990,14 @@ impl Daemon {
         self.relay_list_updater.update().await;
     }
 
+    fn on_import_relay_list(&mut self, tx: ResponseTx<(), relay_list::Error>, blob: String) {
+        let mut relay_list_updater = self.relay_list_updater.clone();
+        tokio::spawn(async move {
+            let result = relay_list_updater.import(blob).await;
+            Self::oneshot_send(tx, result, "import_relay_list response");
+        });
+    }
+
     fn on_login_account(&mut self, tx: ResponseTx<(), Error>, account_number: String) {
         let account_manager = self.account_manager.clone();
         let availability = self.api_runtime.availability_handle();
@@ -1680,13 +2064,27 @@ impl Daemon {
 
     fn on_list_devices(&self, tx: ResponseTx<Vec<Device>, Error>, token: AccountNumber) {
         let service = self.account_manager.device_service.clone();
+        let cache = self.account_data_cache.clone();
         tokio::spawn(async move {
+            let result = match service.list_devices(token.clone()).await {
+                Ok(devices) => {
+                    cache.store_devices(&token, &devices).await;
+                    Ok(devices)
+                }
+                Err(error) if error.is_network_error() => match cache.devices(&token).await {
+                    Some((devices, fetched)) => {
+                        log::warn!(
+                            "Failed to reach the API. Returning device list fetched at {fetched}"
+                        );
+                        Ok(devices)
+                    }
+                    None => Err(error),
+                },
+                Err(error) => Err(error),
+            };
             Self::oneshot_send(
                 tx,
-                service
-                    .list_devices(token)
-                    .await
-                    .map_err(Error::ListDevicesError),
+                result.map_err(Error::ListDevicesError),
                 "list_devices response",
             );
         });
@@ -1700,19 +2098,25 @@ impl Daemon {
     ) {
         let device_service = self.account_manager.device_service.clone();
         let notifier = self.management_interface.notifier().clone();
+        let cache = self.account_data_cache.clone();
+        let labels = self.device_labels.clone();
 
         tokio::spawn(async move {
             let result = device_service
-                .remove_device(account_number.clone(), device_id)
-                .await
-                .map(move |new_devices| {
-                    // FIXME: We should be able to get away with only returning the removed ID,
-                    //        and not have to request the list from the API.
-                    notifier.notify_remove_device_event(RemoveDeviceEvent {
-                        account_number,
-                        new_devices,
-                    });
+                .remove_device(account_number.clone(), device_id.clone())
+                .await;
+            if let Ok(new_devices) = &result {
+                cache.store_devices(&account_number, new_devices).await;
+                labels.remove(&device_id).await;
+            }
+            let result = result.map(move |new_devices| {
+                // FIXME: We should be able to get away with only returning the removed ID,
+                //        and not have to request the list from the API.
+                notifier.notify_remove_device_event(RemoveDeviceEvent {
+                    account_number,
+                    new_devices,
                 });
+            });
             Self::oneshot_send(
                 tx,
                 result.map_err(Error::RemoveDeviceError),
@@ -1721,6 +2125,21 @@ impl Daemon {
         });
     }
 
+    fn on_set_device_label(&self, tx: ResponseTx<(), Error>, label: DeviceLabel) {
+        let labels = self.device_labels.clone();
+        tokio::spawn(async move {
+            let result = labels.set(label).await.map_err(Error::DeviceLabels);
+            Self::oneshot_send(tx, result, "set_device_label response");
+        });
+    }
+
+    fn on_get_device_labels(&self, tx: oneshot::Sender<Vec<DeviceLabel>>) {
+        let labels = self.device_labels.clone();
+        tokio::spawn(async move {
+            Self::oneshot_send(tx, labels.get_all().await, "get_device_labels response");
+        });
+    }
+
     fn on_get_account_history(&mut self, tx: oneshot::Sender<Option<AccountNumber>>) {
         Self::oneshot_send(
             tx,
@@ -1729,7 +2148,20 @@ impl Daemon {
         );
     }
 
+    fn on_get_voucher_history(&mut self, tx: oneshot::Sender<Vec<RedeemedVoucher>>) {
+        let cache = self.account_data_cache.clone();
+        let account_number = self.account_history.get();
+        tokio::spawn(async move {
+            let vouchers = match account_number {
+                Some(account_number) => cache.vouchers(&account_number).await,
+                None => vec![],
+            };
+            Self::oneshot_send(tx, vouchers, "get_voucher_history response");
+        });
+    }
+
     async fn on_clear_account_history(&mut self, tx: ResponseTx<(), Error>) {
+        self.account_data_cache.clear().await;
         let result = self
             .account_history
             .clear()
@@ -1784,6 +2216,8 @@ impl Daemon {
             );
             last_error = Some("Failed to clear account history");
         }
+        self.account_data_cache.clear().await;
+        self.device_labels.clear().await;
 
         if let Err(e) = self.settings.reset().await {
             log::error!("Failed to reset settings: {}", e);
@@ -2497,6 +2931,94 @@ impl Daemon {
         }
     }
 
+    async fn on_set_relay_rotation_settings(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        relay_rotation: RelayRotationSettings,
+    ) {
+        match self
+            .settings
+            .update(move |settings| settings.relay_rotation = relay_rotation)
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_relay_rotation_settings response");
+                if settings_changed && self.tunnel_state.is_connected() {
+                    // Limits are counted from when the new settings were applied
+                    self.start_relay_rotation();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_relay_rotation_settings response");
+            }
+        }
+    }
+
+    async fn on_set_network_rules(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        network_rules: NetworkRulesSettings,
+    ) {
+        match self
+            .settings
+            .update(move |settings| settings.network_rules = network_rules)
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_network_rules response");
+                if settings_changed {
+                    self.apply_network_rules().await;
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_network_rules response");
+            }
+        }
+    }
+
+    async fn on_set_expiry_warning_settings(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        expiry_warnings: ExpiryWarningSettings,
+    ) {
+        let thresholds = expiry_warnings.thresholds.clone();
+        match self
+            .settings
+            .update(move |settings| settings.expiry_warnings = expiry_warnings)
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_expiry_warning_settings response");
+                if settings_changed {
+                    if let Err(error) = self
+                        .account_manager
+                        .set_expiry_warning_thresholds(thresholds)
+                        .await
+                    {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to update expiry warnings")
+                        );
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_expiry_warning_settings response");
+            }
+        }
+    }
+
+    fn on_get_current_network(&self, tx: oneshot::Sender<NetworkIdentity>) {
+        Self::oneshot_send(
+            tx,
+            self.network_identity.clone(),
+            "get_current_network response",
+        );
+    }
+
     async fn on_clear_all_relay_overrides(&mut self, tx: ResponseTx<(), settings::Error>) {
         match self
             .settings
@@ -2684,6 +3206,66 @@ impl Daemon {
         Self::oneshot_send(tx, result, "clear_custom_api_access_methods response");
     }
 
+    async fn on_set_system_proxy_pac_url(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        pac_url: Option<String>,
+    ) {
+        let result = self
+            .set_system_proxy_pac_url(pac_url)
+            .await
+            .map_err(Error::AccessMethodError);
+        Self::oneshot_send(tx, result, "set_system_proxy_pac_url response");
+    }
+
+    async fn on_set_encrypted_dns_proxy_resolvers(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        resolvers: Vec<DohResolver>,
+    ) {
+        let result = self
+            .set_encrypted_dns_proxy_resolvers(resolvers)
+            .await
+            .map_err(Error::AccessMethodError);
+        Self::oneshot_send(tx, result, "set_encrypted_dns_proxy_resolvers response");
+    }
+
+    async fn on_set_tor_settings(&mut self, tx: ResponseTx<(), Error>, tor_settings: TorSettings) {
+        let result = self
+            .set_tor_settings(tor_settings)
+            .await
+            .map_err(Error::AccessMethodError);
+        Self::oneshot_send(tx, result, "set_tor_settings response");
+    }
+
+    fn on_get_encrypted_dns_proxy_configs(
+        &mut self,
+        tx: ResponseTx<Vec<EncryptedDnsProxyConfigInfo>, Error>,
+    ) {
+        let handle = self.access_mode_handler.clone();
+        tokio::spawn(async move {
+            let result = handle
+                .encrypted_dns_proxy_configs()
+                .await
+                .map_err(Error::ApiConnectionModeError);
+            Self::oneshot_send(tx, result, "get_encrypted_dns_proxy_configs response");
+        });
+    }
+
+    fn on_refresh_encrypted_dns_proxy_configs(
+        &mut self,
+        tx: ResponseTx<Vec<EncryptedDnsProxyConfigInfo>, Error>,
+    ) {
+        let handle = self.access_mode_handler.clone();
+        tokio::spawn(async move {
+            let result = handle
+                .refresh_encrypted_dns_proxy_configs()
+                .await
+                .map_err(Error::ApiConnectionModeError);
+            Self::oneshot_send(tx, result, "refresh_encrypted_dns_proxy_configs response");
+        });
+    }
+
     fn on_get_current_api_access_method(&mut self, tx: ResponseTx<AccessMethodSetting, Error>) {
         let handle = self.access_mode_handler.clone();
         tokio::spawn(async move {
@@ -2696,6 +3278,20 @@ impl Daemon {
         });
     }
 
+    fn on_get_api_access_method_statistics(
+        &mut self,
+        tx: ResponseTx<Vec<AccessMethodStatus>, Error>,
+    ) {
+        let handle = self.access_mode_handler.clone();
+        tokio::spawn(async move {
+            let result = handle
+                .statistics()
+                .await
+                .map_err(Error::ApiConnectionModeError);
+            Self::oneshot_send(tx, result, "get_api_access_method_statistics response");
+        });
+    }
+
     fn on_test_proxy_as_access_method(
         &mut self,
         tx: ResponseTx<bool, Error>,
@@ -2785,15 +3381,31 @@ impl Daemon {
     async fn on_reset_settings(&mut self, tx: ResponseTx<(), settings::Error>) {
         let result = self.settings.reset().await;
         Self::oneshot_send(tx, result, "reset_settings response");
+        self.apply_all_settings().await;
+    }
 
+    /// Push all settings to the tunnel state machine and other components, after the settings
+    /// have been replaced wholesale.
+    async fn apply_all_settings(&mut self) {
         // TODO: All of the functions below should probably be handled by settings observers
         //       whenever settings are updated. For instance, changing "allow_lan" should probably
         //       cause a tunnel command to be sent.
 
         #[cfg(any(target_os = "windows", target_os = "macos", target_os = "android"))]
         {
+            let exclude_paths = if self.settings.split_tunnel.enable_exclusions {
+                self.settings
+                    .split_tunnel
+                    .apps
+                    .iter()
+                    .cloned()
+                    .map(SplitApp::to_tunnel_command_repr)
+                    .collect()
+            } else {
+                vec![]
+            };
             let (tx, _rx) = oneshot::channel();
-            self.send_tunnel_command(TunnelCommand::SetExcludedApps(tx, vec![]));
+            self.send_tunnel_command(TunnelCommand::SetExcludedApps(tx, exclude_paths));
         }
 
         let (tx, _rx) = oneshot::channel();
@@ -2820,6 +3432,7 @@ impl Daemon {
         });
 
         let interval = self.settings.tunnel_options.wireguard.rotation_interval;
+        let thresholds = self.settings.expiry_warnings.thresholds.clone();
         let account_manager = self.account_manager.clone();
         tokio::spawn(async move {
             if let Err(error) = account_manager
@@ -2831,6 +3444,15 @@ impl Daemon {
                     error.display_chain_with_msg("Failed to update rotation interval")
                 );
             }
+            if let Err(error) = account_manager
+                .set_expiry_warning_thresholds(thresholds)
+                .await
+            {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to update expiry warnings")
+                );
+            }
         });
 
         self.reconnect_tunnel();
@@ -2846,7 +3468,9 @@ impl Daemon {
         // Block all traffic before shutting down to ensure that no traffic can leak on boot or
         // shutdown.
         if !user_init_shutdown
-            && (*self.target_state == TargetState::Secured || self.settings.auto_connect)
+            && (*self.target_state == TargetState::Secured
+                || self.effective_target_state() == TargetState::Secured
+                || self.settings.auto_connect)
         {
             log::debug!("Blocking firewall during shutdown");
             let (tx, _rx) = oneshot::channel();
@@ -2864,7 +3488,9 @@ impl Daemon {
         // TODO: See if this can be made to also shut down the daemon
         //       without causing the service to be restarted.
 
-        if *self.target_state == TargetState::Secured {
+        if *self.target_state == TargetState::Secured
+            || self.effective_target_state() == TargetState::Secured
+        {
             let (tx, _rx) = oneshot::channel();
             self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(true, tx));
         }
@@ -2924,18 +3550,157 @@ impl Daemon {
         tx: ResponseTx<(), settings::patch::Error>,
         blob: String,
     ) {
-        let result = settings::patch::merge_validate_patch(&mut self.settings, &blob).await;
+        let result = match settings::signed_patch::open_patch(&self.settings_dir, &blob).await {
+            Ok(opened) => {
+                let result = settings::patch::merge_validate_patch(
+                    &mut self.settings,
+                    &opened.patch,
+                    opened.origin,
+                )
+                .await;
+                if let (Ok(()), Some(version)) = (&result, opened.version) {
+                    // Prevent older patches from undoing this one
+                    if let Err(error) = settings::signed_patch::PatchVersions::record(
+                        &self.settings_dir,
+                        settings::signed_patch::PatchSource::Imported,
+                        version,
+                    )
+                    .await
+                    {
+                        log::error!("{}", error.display_chain());
+                    }
+                }
+                result
+            }
+            Err(error) => Err(settings::patch::Error::Signature(error)),
+        };
         if result.is_ok() {
             self.reconnect_tunnel();
         }
         Self::oneshot_send(tx, result, "apply_json_settings response");
     }
 
+    async fn on_diff_json_settings(
+        &mut self,
+        tx: ResponseTx<Vec<SettingsPatchChange>, settings::patch::Error>,
+        blob: String,
+    ) {
+        let result = match settings::signed_patch::open_patch(&self.settings_dir, &blob).await {
+            Ok(opened) => settings::patch::diff_patch(
+                &self.settings,
+                self.settings.policy(),
+                &opened.patch,
+                opened.origin,
+            ),
+            Err(error) => Err(settings::patch::Error::Signature(error)),
+        };
+        Self::oneshot_send(tx, result, "diff_json_settings response");
+    }
+
+    async fn handle_managed_profile_update(&mut self, patch: String) {
+        match settings::patch::apply_managed_patch(&mut self.settings, &patch).await {
+            Ok(true) => {
+                log::info!("Applied updated managed profile");
+                self.reconnect_tunnel();
+            }
+            Ok(false) => (),
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to apply managed profile")
+                );
+            }
+        }
+    }
+
     fn on_export_json_settings(&mut self, tx: ResponseTx<String, settings::patch::Error>) {
         let result = settings::patch::export_settings(&self.settings);
         Self::oneshot_send(tx, result, "export_json_settings response");
     }
 
+    fn on_export_settings_backup(&mut self, tx: ResponseTx<String, settings::backup::Error>) {
+        let result = settings::backup::export(&self.settings);
+        Self::oneshot_send(tx, result, "export_settings_backup response");
+    }
+
+    async fn on_import_settings_backup(
+        &mut self,
+        tx: ResponseTx<(), settings::backup::Error>,
+        blob: String,
+    ) {
+        let new_settings = match settings::backup::import(&blob).await {
+            Ok(new_settings) => new_settings,
+            Err(error) => {
+                Self::oneshot_send(tx, Err(error), "import_settings_backup response");
+                return;
+            }
+        };
+        let result = self
+            .settings
+            .update(move |settings| *settings = new_settings)
+            .await
+            .map_err(settings::backup::Error::Settings);
+        let settings_changed = matches!(result, Ok(true));
+        Self::oneshot_send(tx, result.map(|_| ()), "import_settings_backup response");
+        if settings_changed {
+            self.apply_all_settings().await;
+        }
+    }
+
+    async fn on_create_settings_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::profiles::Error>,
+        profile: SettingsProfile,
+    ) {
+        let result = self.profiles.create(profile).await;
+        Self::oneshot_send(tx, result, "create_settings_profile response");
+    }
+
+    fn on_get_settings_profiles(&self, tx: oneshot::Sender<Vec<SettingsProfile>>) {
+        let profiles = self.profiles.profiles().to_vec();
+        Self::oneshot_send(tx, profiles, "get_settings_profiles response");
+    }
+
+    async fn on_apply_settings_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::profiles::Error>,
+        name: String,
+    ) {
+        let profile = match self.profiles.get(&name) {
+            Ok(profile) => profile.clone(),
+            Err(error) => {
+                Self::oneshot_send(tx, Err(error), "apply_settings_profile response");
+                return;
+            }
+        };
+        let result = settings::profiles::apply(&mut self.settings, &profile).await;
+        let settings_changed = matches!(result, Ok(true));
+        Self::oneshot_send(tx, result.map(|_| ()), "apply_settings_profile response");
+        if settings_changed {
+            log::info!("Applied settings profile \"{name}\"");
+            self.apply_all_settings().await;
+        }
+    }
+
+    async fn on_delete_settings_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::profiles::Error>,
+        name: String,
+    ) {
+        let result = self.profiles.delete(&name).await;
+        Self::oneshot_send(tx, result, "delete_settings_profile response");
+    }
+
+    fn on_get_settings_policy(&self, tx: oneshot::Sender<SettingsPolicy>) {
+        let policy = self.settings.policy().clone();
+        Self::oneshot_send(tx, policy, "get_settings_policy response");
+    }
+
+    fn on_get_event_hooks(&self, tx: oneshot::Sender<Vec<EventHook>>) {
+        let hooks = self.event_hooks.hooks().to_vec();
+        Self::oneshot_send(tx, hooks, "get_event_hooks response");
+    }
+
     fn on_get_feature_indicators(&self, tx: oneshot::Sender<FeatureIndicators>) {
         let feature_indicators = match &self.tunnel_state {
             TunnelState::Connecting {
@@ -2949,16 +3714,63 @@ impl Daemon {
         Self::oneshot_send(tx, feature_indicators, "get_feature_indicators response");
     }
 
+    async fn on_run_speed_test(
+        &mut self,
+        tx: ResponseTx<SpeedTestResult, speed_test::Error>,
+        endpoint: String,
+        duration: Duration,
+    ) {
+        if !self.tunnel_state.is_connected() {
+            Self::oneshot_send(
+                tx,
+                Err(speed_test::Error::NotConnected),
+                "run_speed_test response",
+            );
+            return;
+        }
+
+        let relay = self
+            .parameters_generator
+            .get_last_location()
+            .await
+            .and_then(|location| location.hostname);
+        let relay_selector = self.relay_selector.clone();
+        let cache_dir = self.cache_dir.clone();
+
+        tokio::spawn(async move {
+            let result = speed_test::run(&endpoint, duration, relay).await;
+
+            if let Ok(result) = &result {
+                let latency_cache = relay_selector.record_speed_test(result.clone());
+                if let Err(error) =
+                    speed_test::store_latency_cache(&cache_dir, &latency_cache).await
+                {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to update relay latency cache")
+                    );
+                }
+            }
+
+            Self::oneshot_send(tx, result, "run_speed_test response");
+        });
+    }
+
     /// Set the target state of the client. If it changed trigger the operations needed to
-    /// progress towards that state.
+    /// progress towards that state. This replaces any network rule that overrides the target
+    /// state, until the network changes.
     /// Returns a bool representing whether a state change was initiated.
     async fn set_target_state(&mut self, new_state: TargetState) -> bool {
-        if new_state != *self.target_state || self.tunnel_state.is_in_error_state() {
-            log::debug!("Target state {:?} => {:?}", *self.target_state, new_state);
+        let current_state = self.effective_target_state();
+        if let Some(action) = self.network_rule_action.take() {
+            log::debug!("Target state {new_state:?} replaces network rule: {action}");
+        }
+        self.target_state.set(new_state).await;
 
-            self.target_state.set(new_state).await;
+        if new_state != current_state || self.tunnel_state.is_in_error_state() {
+            log::debug!("Target state {current_state:?} => {new_state:?}");
 
-            match *self.target_state {
+            match new_state {
                 TargetState::Secured => self.connect_tunnel(),
                 TargetState::Unsecured => self.disconnect_tunnel(),
             }
@@ -2977,7 +3789,7 @@ impl Daemon {
     }
 
     fn reconnect_tunnel(&mut self) {
-        if *self.target_state == TargetState::Secured {
+        if self.effective_target_state() == TargetState::Secured {
             self.connect_tunnel();
         }
     }
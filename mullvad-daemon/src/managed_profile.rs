@@ -0,0 +1,325 @@
+//! Managed profiles let an operator distribute settings to a fleet of devices. When a profile is
+//! configured, the daemon periodically fetches a signed settings patch from a URL, applies it,
+//! and prevents the settings that the patch changes from being edited locally.
+//!
+//! The profile is configured in [CONFIG_FILE] in the settings directory, which must only be
+//! writable by root:
+//!
+//! ```json
+//! { "url": "https://example.com/mullvad-profile.json", "refresh_interval_secs": 3600 }
+//! ```
+//!
+//! The patch must be signed by a trusted key, and must not be older than the most recently
+//! fetched patch. See [crate::settings::signed_patch].
+
+use crate::{
+    settings::signed_patch::{
+        self, PatchSource, PatchVersions, SignedPatch, TrustedKeys, VerifiedPatch,
+    },
+    DaemonEventSender, InternalDaemonEvent,
+};
+use mullvad_api::rest::{self, RequestServiceHandle};
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use talpid_core::mpsc::Sender;
+use talpid_types::ErrorExt;
+use tokio::{fs, io};
+
+/// Root-only file in the settings directory that configures the managed profile.
+pub const CONFIG_FILE: &str = "managed-profile.json";
+/// The most recently fetched signed patch, so that it can be applied before the profile can be
+/// fetched again.
+const CACHE_FILE: &str = "managed-profile-patch.json";
+
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to read managed profile configuration")]
+    ReadConfig(#[source] signed_patch::Error),
+
+    #[error("Invalid managed profile configuration")]
+    ParseConfig(#[source] serde_json::Error),
+
+    #[error("Invalid managed profile URL: {0}")]
+    InvalidUrl(String, #[source] url::ParseError),
+
+    #[error("Managed profile URL must use HTTPS: {0}")]
+    InsecureUrl(String),
+
+    #[error("Failed to fetch managed profile")]
+    Fetch(#[source] rest::Error),
+
+    #[error("Failed to verify managed profile")]
+    Verify(#[source] signed_patch::Error),
+
+    #[error("Failed to read cached managed profile")]
+    ReadCache(#[source] io::Error),
+
+    #[error("Failed to parse cached managed profile")]
+    ParseCache(#[source] serde_json::Error),
+
+    #[error("Failed to serialize managed profile")]
+    SerializeCache(#[source] serde_json::Error),
+
+    #[error("Failed to write cached managed profile")]
+    WriteCache(#[source] io::Error),
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// HTTPS URL to fetch the signed patch from. Other schemes are rejected.
+    url: String,
+    /// How often to fetch the patch, in seconds.
+    #[serde(default)]
+    refresh_interval_secs: Option<u64>,
+}
+
+impl Config {
+    /// Read the configuration from the settings directory. Returns `None` if no managed profile
+    /// is configured.
+    pub async fn load(settings_dir: &Path) -> Result<Option<Self>, Error> {
+        let Some(contents) = signed_patch::read_root_only(&settings_dir.join(CONFIG_FILE))
+            .await
+            .map_err(Error::ReadConfig)?
+        else {
+            return Ok(None);
+        };
+        Self::parse(&contents).map(Some)
+    }
+
+    fn parse(contents: &str) -> Result<Self, Error> {
+        let mut config: Self = serde_json::from_str(contents).map_err(Error::ParseConfig)?;
+        let url = url::Url::parse(&config.url)
+            .map_err(|error| Error::InvalidUrl(config.url.clone(), error))?;
+        if url.scheme() != "https" || url.host().is_none() {
+            return Err(Error::InsecureUrl(config.url));
+        }
+        config.url = url.into();
+        Ok(config)
+    }
+
+    fn refresh_interval(&self) -> Duration {
+        self.refresh_interval_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL)
+            .max(MIN_REFRESH_INTERVAL)
+    }
+}
+
+/// Returns the most recently fetched patch, if there is one, it is still signed by a trusted key
+/// and no newer patch has been fetched.
+pub async fn load_cached_patch(
+    settings_dir: &Path,
+    cache_dir: &Path,
+) -> Result<Option<String>, Error> {
+    let contents = match fs::read_to_string(cache_dir.join(CACHE_FILE)).await {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(Error::ReadCache(error)),
+    };
+    let signed: SignedPatch = serde_json::from_str(&contents).map_err(Error::ParseCache)?;
+    let trusted_keys = TrustedKeys::load(settings_dir)
+        .await
+        .map_err(Error::Verify)?;
+    let versions = PatchVersions::load(settings_dir)
+        .await
+        .map_err(Error::Verify)?;
+    let verified = verify(&signed, &trusted_keys, &versions)?;
+    Ok(Some(verified.patch))
+}
+
+/// Periodically fetch the profile, and send the patch to the daemon whenever it has been
+/// verified.
+pub(crate) fn spawn(
+    config: Config,
+    settings_dir: PathBuf,
+    cache_dir: PathBuf,
+    api_service: RequestServiceHandle,
+    event_tx: DaemonEventSender,
+) {
+    let refresh_interval = config.refresh_interval();
+    let url = config.url;
+    let fetch = move || {
+        let api_service = api_service.clone();
+        let url = url.clone();
+        async move {
+            let request = rest::get(&url)?;
+            let response = api_service.request(request).await?;
+            response.deserialize::<SignedPatch>().await
+        }
+    };
+
+    tokio::spawn(async move {
+        loop {
+            let result = match load_trust(&settings_dir).await {
+                Ok((trusted_keys, versions)) => {
+                    fetch_verified(fetch(), &trusted_keys, &versions).await
+                }
+                Err(error) => Err(Error::Verify(error)),
+            };
+            match result {
+                Ok((signed, verified)) => {
+                    if let Err(error) = write_cache(&cache_dir, &signed).await {
+                        log::error!("{}", error.display_chain());
+                    }
+                    if let Err(error) =
+                        PatchVersions::record(&settings_dir, PatchSource::Managed, verified.version)
+                            .await
+                    {
+                        log::error!("{}", error.display_chain());
+                    }
+                    if event_tx
+                        .send(InternalDaemonEvent::ManagedProfileUpdate(verified.patch))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Err(error) => log::error!("{}", error.display_chain()),
+            }
+            tokio::time::sleep(refresh_interval).await;
+        }
+    });
+}
+
+/// Load the trusted keys and the versions of the most recently applied patches.
+async fn load_trust(
+    settings_dir: &Path,
+) -> Result<(TrustedKeys, PatchVersions), signed_patch::Error> {
+    Ok((
+        TrustedKeys::load(settings_dir).await?,
+        PatchVersions::load(settings_dir).await?,
+    ))
+}
+
+/// Fetch the signed patch and return it along with the verified patch, if it is signed by a
+/// trusted key and is not older than the most recently fetched patch.
+async fn fetch_verified(
+    fetch: impl Future<Output = Result<SignedPatch, rest::Error>>,
+    trusted_keys: &TrustedKeys,
+    versions: &PatchVersions,
+) -> Result<(SignedPatch, VerifiedPatch), Error> {
+    let signed = fetch.await.map_err(Error::Fetch)?;
+    let verified = verify(&signed, trusted_keys, versions)?;
+    Ok((signed, verified))
+}
+
+fn verify(
+    signed: &SignedPatch,
+    trusted_keys: &TrustedKeys,
+    versions: &PatchVersions,
+) -> Result<VerifiedPatch, Error> {
+    let verified = signed.verify(trusted_keys).map_err(Error::Verify)?;
+    versions
+        .check(PatchSource::Managed, verified.version)
+        .map_err(Error::Verify)?;
+    Ok(verified)
+}
+
+async fn write_cache(cache_dir: &Path, signed: &SignedPatch) -> Result<(), Error> {
+    let contents = serde_json::to_vec(signed).map_err(Error::SerializeCache)?;
+    fs::write(cache_dir.join(CACHE_FILE), contents)
+        .await
+        .map_err(Error::WriteCache)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    /// Stand-in for the profile server
+    async fn serve(signed: SignedPatch) -> Result<SignedPatch, rest::Error> {
+        Ok(signed)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_profile() {
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+        let trusted_keys: TrustedKeys = BASE64_STANDARD
+            .encode(key_pair.public_key())
+            .parse()
+            .unwrap();
+
+        let sign = |signed_patch: &str| SignedPatch {
+            signed_patch: signed_patch.to_owned(),
+            signature: BASE64_STANDARD.encode(key_pair.sign(signed_patch.as_bytes())),
+        };
+        let versions = PatchVersions::default();
+
+        let signed = sign(r#"{ "version": 2, "patch": { "bridge_state": "on" } }"#);
+        let (_, fetched) = fetch_verified(serve(signed.clone()), &trusted_keys, &versions)
+            .await
+            .unwrap();
+        assert_eq!(fetched.version, 2);
+        assert_eq!(fetched.patch, r#"{"bridge_state":"on"}"#);
+
+        let tampered = SignedPatch {
+            signed_patch: r#"{ "version": 2, "patch": { "bridge_state": "off" } }"#.to_owned(),
+            ..signed.clone()
+        };
+        assert!(matches!(
+            fetch_verified(serve(tampered), &trusted_keys, &versions).await,
+            Err(Error::Verify(signed_patch::Error::Untrusted))
+        ));
+
+        // Older patches are rejected once a newer one has been fetched
+        let dir = tempfile::tempdir().unwrap();
+        PatchVersions::record(dir.path(), PatchSource::Managed, 2)
+            .await
+            .unwrap();
+        let versions = PatchVersions::load(dir.path()).await.unwrap();
+        fetch_verified(serve(signed), &trusted_keys, &versions)
+            .await
+            .unwrap();
+        let old = sign(r#"{ "version": 1, "patch": { "bridge_state": "off" } }"#);
+        assert!(matches!(
+            fetch_verified(serve(old), &trusted_keys, &versions).await,
+            Err(Error::Verify(signed_patch::Error::Outdated {
+                version: 1,
+                latest: 2
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_refresh_interval() {
+        let config: Config = serde_json::from_str(r#"{ "url": "https://example.com" }"#).unwrap();
+        assert_eq!(config.refresh_interval(), DEFAULT_REFRESH_INTERVAL);
+
+        let config: Config =
+            serde_json::from_str(r#"{ "url": "https://example.com", "refresh_interval_secs": 1 }"#)
+                .unwrap();
+        assert_eq!(config.refresh_interval(), MIN_REFRESH_INTERVAL);
+
+        serde_json::from_str::<Config>(r#"{ "url": "https://example.com", "unknown": 1 }"#)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_https_only() {
+        Config::parse(r#"{ "url": "https://example.com/profile.json" }"#).unwrap();
+        assert!(matches!(
+            Config::parse(r#"{ "url": "http://example.com/profile.json" }"#),
+            Err(Error::InsecureUrl(_))
+        ));
+        assert!(matches!(
+            Config::parse(r#"{ "url": "example.com/profile.json" }"#),
+            Err(Error::InvalidUrl(..))
+        ));
+        // Only the scheme decides whether a URL uses HTTPS
+        assert!(matches!(
+            Config::parse(r#"{ "url": "http://https://example.com/profile.json" }"#),
+            Err(Error::InsecureUrl(_))
+        ));
+        let config = Config::parse(r#"{ "url": "HTTPS://Example.com/profile.json" }"#).unwrap();
+        assert_eq!(config.url, "https://example.com/profile.json");
+    }
+}
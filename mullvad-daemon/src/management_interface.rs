@@ -1,4 +1,7 @@
-use crate::{account_history, device, version_check, DaemonCommand, DaemonCommandSender};
+use crate::{
+    account_history, device, relay_list, speed_test, version_check, DaemonCommand,
+    DaemonCommandSender,
+};
 use futures::{
     channel::{mpsc, oneshot},
     StreamExt,
@@ -10,11 +13,14 @@ use mullvad_management_interface::{
 };
 use mullvad_types::{
     account::AccountNumber,
+    device::DeviceLabel,
+    network_rules::NetworkRulesSettings,
+    profile::SettingsProfile,
     relay_constraints::{
         BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
     },
     relay_list::RelayList,
-    settings::{DnsOptions, Settings},
+    settings::{DnsOptions, ExpiryWarningSettings, RelayRotationSettings, Settings},
     states::{TargetState, TunnelState},
     version,
     wireguard::{RotationInterval, RotationIntervalError},
@@ -171,6 +177,16 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn import_relay_list(&self, blob: Request<String>) -> ServiceResult<()> {
+        log::debug!("import_relay_list");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ImportRelayList(tx, blob.into_inner()))?;
+        self.wait_for_result(rx)
+            .await?
+            .map_err(map_relay_list_error)?;
+        Ok(Response::new(()))
+    }
+
     async fn set_relay_settings(
         &self,
         request: Request<types::RelaySettings>,
@@ -416,6 +432,54 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_relay_rotation_settings(
+        &self,
+        request: Request<types::RelayRotationSettings>,
+    ) -> ServiceResult<()> {
+        let relay_rotation =
+            RelayRotationSettings::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_relay_rotation_settings({relay_rotation:?})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRelayRotationSettings(tx, relay_rotation))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn set_network_rules(
+        &self,
+        request: Request<types::NetworkRulesSettings>,
+    ) -> ServiceResult<()> {
+        let network_rules =
+            NetworkRulesSettings::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_network_rules");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetNetworkRules(tx, network_rules))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn set_expiry_warning_settings(
+        &self,
+        request: Request<types::ExpiryWarningSettings>,
+    ) -> ServiceResult<()> {
+        let expiry_warnings =
+            ExpiryWarningSettings::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_expiry_warning_settings({expiry_warnings:?})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetExpiryWarningSettings(tx, expiry_warnings))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn get_current_network(&self, _: Request<()>) -> ServiceResult<types::NetworkIdentity> {
+        log::debug!("get_current_network");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetCurrentNetwork(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|network| Response::new(types::NetworkIdentity::from(network)))
+    }
+
     async fn clear_all_relay_overrides(&self, _: Request<()>) -> ServiceResult<()> {
         log::debug!("clear_all_relay_overrides");
         let (tx, rx) = oneshot::channel();
@@ -497,6 +561,20 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    async fn get_voucher_history(&self, _: Request<()>) -> ServiceResult<types::VoucherHistory> {
+        log::debug!("get_voucher_history");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetVoucherHistory(tx))?;
+        self.wait_for_result(rx).await.map(|vouchers| {
+            Response::new(types::VoucherHistory {
+                vouchers: vouchers
+                    .into_iter()
+                    .map(types::RedeemedVoucher::from)
+                    .collect(),
+            })
+        })
+    }
+
     async fn get_www_auth_token(&self, _: Request<()>) -> ServiceResult<String> {
         log::debug!("get_www_auth_token");
         let (tx, rx) = oneshot::channel();
@@ -569,6 +647,25 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_device_label(&self, request: Request<types::DeviceLabel>) -> ServiceResult<()> {
+        log::debug!("set_device_label");
+        let (tx, rx) = oneshot::channel();
+        let label = DeviceLabel::from(request.into_inner());
+        self.send_command_to_daemon(DaemonCommand::SetDeviceLabel(tx, label))?;
+        self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_device_labels(&self, _: Request<()>) -> ServiceResult<types::DeviceLabels> {
+        log::debug!("get_device_labels");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetDeviceLabels(tx))?;
+        let labels = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::DeviceLabels {
+            labels: labels.into_iter().map(types::DeviceLabel::from).collect(),
+        }))
+    }
+
     // WireGuard key management
     //
 
@@ -761,6 +858,100 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    async fn get_api_access_method_statistics(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::AccessMethodStatusList> {
+        log::debug!("get_api_access_method_statistics");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetApiAccessMethodStatistics(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(|statuses| types::AccessMethodStatusList {
+                statuses: statuses
+                    .into_iter()
+                    .map(types::AccessMethodStatus::from)
+                    .collect(),
+            })
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn set_system_proxy_pac_url(&self, request: Request<String>) -> ServiceResult<()> {
+        let pac_url = request.into_inner();
+        let pac_url = if !pac_url.is_empty() {
+            Some(pac_url)
+        } else {
+            None
+        };
+        log::debug!("set_system_proxy_pac_url({:?})", pac_url);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetSystemProxyPacUrl(tx, pac_url))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn set_encrypted_dns_proxy_resolvers(
+        &self,
+        request: Request<types::DohResolverList>,
+    ) -> ServiceResult<()> {
+        let resolvers = request
+            .into_inner()
+            .resolvers
+            .into_iter()
+            .map(mullvad_types::access_method::DohResolver::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(map_protobuf_type_err)?;
+        log::debug!("set_encrypted_dns_proxy_resolvers({:?})", resolvers);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetEncryptedDnsProxyResolvers(tx, resolvers))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn get_encrypted_dns_proxy_configs(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::EncryptedDnsProxyConfigList> {
+        log::debug!("get_encrypted_dns_proxy_configs");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetEncryptedDnsProxyConfigs(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(encrypted_dns_proxy_config_list)
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn refresh_encrypted_dns_proxy_configs(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::EncryptedDnsProxyConfigList> {
+        log::debug!("refresh_encrypted_dns_proxy_configs");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RefreshEncryptedDnsProxyConfigs(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(encrypted_dns_proxy_config_list)
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn set_tor_settings(&self, request: Request<types::TorSettings>) -> ServiceResult<()> {
+        let tor_settings = mullvad_types::access_method::TorSettings::from(request.into_inner());
+        log::debug!("set_tor_settings({:?})", tor_settings);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetTorSettings(tx, tor_settings))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
     async fn test_custom_api_access_method(
         &self,
         config: Request<types::CustomProxy>,
@@ -995,6 +1186,22 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn diff_json_settings(
+        &self,
+        blob: Request<String>,
+    ) -> ServiceResult<types::SettingsPatchDiff> {
+        log::debug!("diff_json_settings");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::DiffJsonSettings(tx, blob.into_inner()))?;
+        let changes = self.wait_for_result(rx).await??;
+        Ok(Response::new(types::SettingsPatchDiff {
+            changes: changes
+                .into_iter()
+                .map(types::SettingsPatchChange::from)
+                .collect(),
+        }))
+    }
+
     async fn export_json_settings(&self, _: Request<()>) -> ServiceResult<String> {
         log::debug!("export_json_settings");
         let (tx, rx) = oneshot::channel();
@@ -1003,6 +1210,82 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(blob))
     }
 
+    async fn export_settings_backup(&self, _: Request<()>) -> ServiceResult<String> {
+        log::debug!("export_settings_backup");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ExportSettingsBackup(tx))?;
+        let blob = self.wait_for_result(rx).await??;
+        Ok(Response::new(blob))
+    }
+
+    async fn import_settings_backup(&self, blob: Request<String>) -> ServiceResult<()> {
+        log::debug!("import_settings_backup");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ImportSettingsBackup(tx, blob.into_inner()))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn create_settings_profile(
+        &self,
+        request: Request<types::SettingsProfile>,
+    ) -> ServiceResult<()> {
+        log::debug!("create_settings_profile");
+        let profile =
+            SettingsProfile::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::CreateSettingsProfile(tx, profile))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn get_settings_profiles(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::SettingsProfiles> {
+        log::debug!("get_settings_profiles");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetSettingsProfiles(tx))?;
+        let profiles = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::SettingsProfiles {
+            profiles: profiles.iter().map(types::SettingsProfile::from).collect(),
+        }))
+    }
+
+    async fn apply_settings_profile(&self, name: Request<String>) -> ServiceResult<()> {
+        log::debug!("apply_settings_profile");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ApplySettingsProfile(tx, name.into_inner()))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn delete_settings_profile(&self, name: Request<String>) -> ServiceResult<()> {
+        log::debug!("delete_settings_profile");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::DeleteSettingsProfile(tx, name.into_inner()))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn get_settings_policy(&self, _: Request<()>) -> ServiceResult<types::SettingsPolicy> {
+        log::debug!("get_settings_policy");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetSettingsPolicy(tx))?;
+        let policy = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::SettingsPolicy::from(&policy)))
+    }
+
+    async fn get_event_hooks(&self, _: Request<()>) -> ServiceResult<types::EventHooks> {
+        log::debug!("get_event_hooks");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetEventHooks(tx))?;
+        let hooks = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::EventHooks {
+            hooks: hooks.iter().map(types::EventHook::from).collect(),
+        }))
+    }
+
     #[cfg(target_os = "android")]
     async fn init_play_purchase(
         &self,
@@ -1073,6 +1356,31 @@ impl ManagementService for ManagementServiceImpl {
 
         Ok(Response::new(feature_indicators))
     }
+
+    async fn run_speed_test(
+        &self,
+        request: Request<types::SpeedTestRequest>,
+    ) -> ServiceResult<types::SpeedTestResult> {
+        let request = request.into_inner();
+        let duration = request
+            .duration
+            .map(Duration::try_from)
+            .transpose()
+            .map_err(|_| Status::invalid_argument("unexpected negative duration"))?
+            .unwrap_or(speed_test::DEFAULT_DURATION);
+        log::debug!("run_speed_test({:?}, {:?})", request.endpoint, duration);
+        if request.endpoint.is_empty() {
+            return Err(Status::invalid_argument("no speed test server was given"));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RunSpeedTest(tx, request.endpoint, duration))?;
+        let result = self
+            .wait_for_result(rx)
+            .await?
+            .map_err(map_speed_test_error)?;
+        Ok(Response::new(types::SpeedTestResult::from(result)))
+    }
 }
 
 impl ManagementServiceImpl {
@@ -1233,6 +1541,19 @@ impl ManagementInterfaceEventBroadcaster {
         })
     }
 
+    /// Notify that the account is about to run out of time.
+    pub(crate) fn notify_account_expiry_warning(
+        &self,
+        warning: mullvad_types::account::AccountExpiryWarning,
+    ) {
+        log::debug!("Broadcasting account expiry warning");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::AccountExpiryWarning(
+                types::AccountExpiryWarning::from(warning),
+            )),
+        })
+    }
+
     /// Notify that a device was revoked using `RemoveDevice`.
     pub(crate) fn notify_remove_device_event(
         &self,
@@ -1260,6 +1581,17 @@ impl ManagementInterfaceEventBroadcaster {
     }
 }
 
+fn encrypted_dns_proxy_config_list(
+    configs: Vec<mullvad_types::access_method::EncryptedDnsProxyConfigInfo>,
+) -> types::EncryptedDnsProxyConfigList {
+    types::EncryptedDnsProxyConfigList {
+        configs: configs
+            .into_iter()
+            .map(types::EncryptedDnsProxyConfig::from)
+            .collect(),
+    }
+}
+
 /// Converts [`crate::Error`] into a tonic status.
 fn map_daemon_error(error: crate::Error) -> Status {
     use crate::Error as DaemonError;
@@ -1282,6 +1614,14 @@ fn map_daemon_error(error: crate::Error) -> Status {
             Status::unauthenticated(error.to_string())
         }
         DaemonError::VersionCheckError(error) => map_version_check_error(error),
+        DaemonError::AccessMethodError(
+            crate::access_method::Error::InvalidPacUrl(_)
+            | crate::access_method::Error::InvalidDohResolver(_)
+            | crate::access_method::Error::InvalidTorBridge(_),
+        ) => Status::invalid_argument(error.to_string()),
+        DaemonError::ApiConnectionModeError(crate::api::Error::FetchEncryptedDnsProxyConfigs) => {
+            Status::unavailable(error.to_string())
+        }
         error => Status::unknown(error.to_string()),
     }
 }
@@ -1362,6 +1702,32 @@ fn map_version_check_error(error: version_check::Error) -> Status {
     }
 }
 
+/// Converts an instance of [`crate::speed_test::Error`] into a tonic status.
+fn map_speed_test_error(error: speed_test::Error) -> Status {
+    match error {
+        speed_test::Error::NotConnected => Status::failed_precondition(error.to_string()),
+        speed_test::Error::InvalidDuration => Status::invalid_argument(error.to_string()),
+        speed_test::Error::Connect(..) | speed_test::Error::Timeout => {
+            Status::unavailable(error.display_chain())
+        }
+        speed_test::Error::Io(..) => Status::unknown(error.display_chain()),
+    }
+}
+
+/// Converts an instance of [`crate::relay_list::Error`] into a tonic status.
+fn map_relay_list_error(error: relay_list::Error) -> Status {
+    match error {
+        relay_list::Error::ParseSignedRelayList(..)
+        | relay_list::Error::Signature(..)
+        | relay_list::Error::MissingCreationTime
+        | relay_list::Error::OutdatedRelayList { .. } => {
+            Status::invalid_argument(error.display_chain())
+        }
+        relay_list::Error::NoSigningKeys => Status::failed_precondition(error.to_string()),
+        _ => Status::unknown(error.display_chain()),
+    }
+}
+
 fn map_protobuf_type_err(err: types::FromProtobufTypeError) -> Status {
     match err {
         types::FromProtobufTypeError::InvalidArgument(err) => Status::invalid_argument(err),
@@ -172,6 +172,16 @@ pub async fn migrate_all(cache_dir: &Path, settings_dir: &Path) -> Result<Option
     Ok(migration_data)
 }
 
+/// Migrate settings that were not read from the settings directory, such as an imported backup,
+/// to the current format. Data that is no longer stored in the settings, such as the account
+/// number in very old formats, is discarded.
+pub async fn migrate_settings_value(settings: &mut serde_json::Value) -> Result<()> {
+    if migrate_settings(None, settings).await?.is_some() {
+        log::warn!("Discarding account data found in migrated settings");
+    }
+    Ok(())
+}
+
 async fn migrate_settings(
     directories: Option<Directories<'_>>,
     settings: &mut serde_json::Value,
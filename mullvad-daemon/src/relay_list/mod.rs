@@ -1,7 +1,11 @@
 //! Relay list updater
+//!
+//! Relay lists are only accepted if they are signed by one of the keys compiled into the app, see
+//! [RelayListSigningKeys]. This applies both to lists downloaded from the API and to lists imported
+//! by the user. Imported lists must also be at least as new as the current list.
 
 use futures::{
-    channel::mpsc,
+    channel::{mpsc, oneshot},
     future::{Fuse, FusedFuture},
     Future, FutureExt, SinkExt, StreamExt,
 };
@@ -11,7 +15,11 @@ use std::{
 };
 use tokio::fs::File;
 
-use mullvad_api::{availability::ApiAvailability, rest::MullvadRestHandle, RelayListProxy};
+use chrono::{DateTime, Utc};
+use mullvad_api::{
+    availability::ApiAvailability, rest::MullvadRestHandle, RelayListProxy,
+    RelayListSignatureError, RelayListSigningKeys, SignedRelayList,
+};
 use mullvad_relay_selector::RelaySelector;
 use mullvad_types::relay_list::RelayList;
 use talpid_future::retry::{retry_future, ExponentialBackoff, Jittered};
@@ -39,18 +47,43 @@ pub enum Error {
 
     #[error("Mullvad relay selector error")]
     RelaySelector(#[from] mullvad_relay_selector::Error),
+
+    #[error("Failed to parse signed relay list")]
+    ParseSignedRelayList(#[source] serde_json::Error),
+
+    #[error("Relay list signature is not valid")]
+    Signature(#[from] RelayListSignatureError),
+
+    #[error("No relay list signing keys are pinned, so relay lists cannot be imported")]
+    NoSigningKeys,
+
+    #[error("The imported relay list does not say when it was created")]
+    MissingCreationTime,
+
+    #[error(
+        "The imported relay list was created at {imported}, before the current one at {current}"
+    )]
+    OutdatedRelayList {
+        imported: DateTime<Utc>,
+        current: DateTime<Utc>,
+    },
+}
+
+enum UpdaterCommand {
+    Update,
+    Import(String, oneshot::Sender<Result<(), Error>>),
 }
 
 #[derive(Clone)]
 pub struct RelayListUpdaterHandle {
-    tx: mpsc::Sender<()>,
+    tx: mpsc::Sender<UpdaterCommand>,
 }
 
 impl RelayListUpdaterHandle {
     pub async fn update(&mut self) {
         if let Err(error) = self
             .tx
-            .send(())
+            .send(UpdaterCommand::Update)
             .await
             .map_err(|_| Error::DownloaderShutdown)
         {
@@ -60,10 +93,22 @@ impl RelayListUpdaterHandle {
             );
         }
     }
+
+    /// Replace the relay list with a [SignedRelayList] obtained out-of-band, e.g. when the API is
+    /// unreachable. The list must be signed by a pinned key.
+    pub async fn import(&mut self, signed_relay_list: String) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(UpdaterCommand::Import(signed_relay_list, tx))
+            .await
+            .map_err(|_| Error::DownloaderShutdown)?;
+        rx.await.map_err(|_| Error::DownloaderShutdown)?
+    }
 }
 
 pub struct RelayListUpdater {
     api_client: RelayListProxy,
+    signing_keys: RelayListSigningKeys,
     cache_path: PathBuf,
     relay_selector: RelaySelector,
     on_update: Box<dyn Fn(&RelayList) + Send + 'static>,
@@ -75,14 +120,25 @@ impl RelayListUpdater {
     pub fn spawn(
         selector: RelaySelector,
         api_handle: MullvadRestHandle,
+        signing_keys: RelayListSigningKeys,
         cache_dir: &Path,
         on_update: impl Fn(&RelayList) + Send + 'static,
     ) -> RelayListUpdaterHandle {
         let (tx, cmd_rx) = mpsc::channel(1);
         let api_availability = api_handle.availability.clone();
         let api_client = RelayListProxy::new(api_handle);
+        if signing_keys.is_empty() {
+            if cfg!(feature = "api-override") {
+                log::warn!(
+                    "No relay list signing keys are pinned. Relay list signatures are not checked"
+                );
+            } else {
+                log::error!("No relay list signing keys are pinned. All relay lists are rejected");
+            }
+        }
         let updater = RelayListUpdater {
             api_client,
+            signing_keys,
             cache_path: cache_dir.join(RELAYS_FILENAME),
             relay_selector: selector,
             on_update: Box::new(on_update),
@@ -95,7 +151,7 @@ impl RelayListUpdater {
         RelayListUpdaterHandle { tx }
     }
 
-    async fn run(mut self, mut cmd_rx: mpsc::Receiver<()>) {
+    async fn run(mut self, mut cmd_rx: mpsc::Receiver<UpdaterCommand>) {
         let mut download_future = Box::pin(Fuse::terminated());
         loop {
             let next_check = tokio::time::sleep(UPDATE_CHECK_INTERVAL).fuse();
@@ -116,11 +172,14 @@ impl RelayListUpdater {
 
                 cmd = cmd_rx.next() => {
                     match cmd {
-                        Some(()) => {
+                        Some(UpdaterCommand::Update) => {
                             let tag = self.relay_selector.etag();
                             download_future = Box::pin(Self::download_relay_list(self.api_availability.clone(), self.api_client.clone(), tag).fuse());
                             self.last_check = SystemTime::now();
                         },
+                        Some(UpdaterCommand::Import(signed_relay_list, result_tx)) => {
+                            let _ = result_tx.send(self.import_relay_list(&signed_relay_list).await);
+                        },
                         None => {
                             log::trace!("Relay list updater shutting down");
                             return;
@@ -134,14 +193,20 @@ impl RelayListUpdater {
 
     async fn consume_new_relay_list(
         &mut self,
-        result: Result<Option<RelayList>, mullvad_api::Error>,
+        result: Result<Option<SignedRelayList>, mullvad_api::Error>,
     ) {
         match result {
-            Ok(Some(relay_list)) => {
-                if let Err(err) = self.update_cache(relay_list).await {
-                    log::error!("Failed to update relay list cache: {}", err);
+            Ok(Some(signed_relay_list)) => match signed_relay_list.verify(&self.signing_keys) {
+                Ok(relay_list) => {
+                    if let Err(err) = self.update_cache(relay_list).await {
+                        log::error!("Failed to update relay list cache: {}", err);
+                    }
                 }
-            }
+                Err(error) => log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Rejecting downloaded relay list")
+                ),
+            },
             Ok(None) => log::debug!("Relay list is up-to-date"),
             Err(error) => log::error!(
                 "{}",
@@ -150,6 +215,18 @@ impl RelayListUpdater {
         }
     }
 
+    async fn import_relay_list(&mut self, signed_relay_list: &str) -> Result<(), Error> {
+        if self.signing_keys.is_empty() {
+            return Err(Error::NoSigningKeys);
+        }
+        let signed_relay_list: SignedRelayList =
+            serde_json::from_str(signed_relay_list).map_err(Error::ParseSignedRelayList)?;
+        let relay_list = signed_relay_list.verify(&self.signing_keys)?;
+        check_freshness(&relay_list, &self.relay_selector.get_relays())?;
+        log::info!("Importing relay list");
+        self.update_cache(relay_list).await
+    }
+
     /// Returns true if the current parsed_relays is older than UPDATE_INTERVAL
     fn should_update(&mut self) -> bool {
         let last_check = std::cmp::max(self.relay_selector.last_updated(), self.last_check);
@@ -166,10 +243,10 @@ impl RelayListUpdater {
         api_handle: ApiAvailability,
         proxy: RelayListProxy,
         tag: Option<String>,
-    ) -> impl Future<Output = Result<Option<RelayList>, mullvad_api::Error>> + 'static {
+    ) -> impl Future<Output = Result<Option<SignedRelayList>, mullvad_api::Error>> + 'static {
         let download_futures = move || {
             let available = api_handle.wait_background();
-            let req = proxy.relay_list(tag.clone());
+            let req = proxy.signed_relay_list(tag.clone());
             async move {
                 available.await?;
                 req.await.map_err(mullvad_api::Error::from)
@@ -211,3 +288,43 @@ impl RelayListUpdater {
         Ok(())
     }
 }
+
+/// Checks that an imported relay list is not older than the current one. An old list is still
+/// validly signed, so this prevents rolling back to relays or keys that have since been removed.
+fn check_freshness(imported: &RelayList, current: &RelayList) -> Result<(), Error> {
+    let imported = imported.created.ok_or(Error::MissingCreationTime)?;
+    match current.created {
+        Some(current) if imported < current => Err(Error::OutdatedRelayList { imported, current }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn relay_list(created: Option<&str>) -> RelayList {
+        RelayList {
+            created: created.map(|created| created.parse().unwrap()),
+            ..RelayList::empty()
+        }
+    }
+
+    #[test]
+    fn test_check_freshness() {
+        let older = relay_list(Some("2026-10-01T12:00:00Z"));
+        let newer = relay_list(Some("2026-10-02T12:00:00Z"));
+
+        check_freshness(&newer, &older).unwrap();
+        check_freshness(&newer, &newer).unwrap();
+        check_freshness(&older, &relay_list(None)).unwrap();
+        assert!(matches!(
+            check_freshness(&older, &newer),
+            Err(Error::OutdatedRelayList { .. })
+        ));
+        assert!(matches!(
+            check_freshness(&relay_list(None), &older),
+            Err(Error::MissingCreationTime)
+        ));
+    }
+}
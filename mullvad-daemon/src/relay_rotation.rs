@@ -0,0 +1,158 @@
+//! Reconnects to a different exit relay once the limits in [`RelayRotationSettings`] are
+//! reached, so that the exit IP changes without any user interaction.
+//!
+//! This is unrelated to WireGuard key rotation, which replaces the key of the device but keeps
+//! the tunnel connected to the same relay.
+
+use crate::{tunnel::ParametersGenerator, DaemonCommand, DaemonEventSender};
+use futures::{
+    channel::{mpsc, oneshot},
+    future::{abortable, AbortHandle},
+};
+use mullvad_types::settings::RelayRotationSettings;
+use std::{
+    fmt,
+    sync::Weak,
+    time::{Duration, Instant},
+};
+use talpid_core::{mpsc::Sender, tunnel_state_machine::TunnelCommand};
+
+/// How often to check whether the tunnel should be rotated.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Why the exit relay is being rotated.
+#[derive(Debug, PartialEq, Eq)]
+enum Reason {
+    Interval(Duration),
+    Traffic(u64),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Interval(interval) => {
+                write!(f, "connected for more than {} seconds", interval.as_secs())
+            }
+            Reason::Traffic(limit) => write!(f, "more than {limit} bytes transferred"),
+        }
+    }
+}
+
+/// Returns why the tunnel should be rotated, given how long it has been connected and how many
+/// bytes have been transferred through it, or `None` if no limit has been reached yet.
+fn rotation_due(
+    settings: &RelayRotationSettings,
+    connected_for: Duration,
+    transferred: Option<u64>,
+) -> Option<Reason> {
+    if let Some(interval) = settings.interval {
+        if connected_for >= interval {
+            return Some(Reason::Interval(interval));
+        }
+    }
+    if let (Some(limit), Some(transferred)) = (settings.traffic_limit, transferred) {
+        if transferred >= limit {
+            return Some(Reason::Traffic(limit));
+        }
+    }
+    None
+}
+
+/// Watches a connected tunnel and reconnects to a different exit relay once a limit in
+/// `settings` is reached. The returned handle must be used to stop watching once the tunnel is
+/// no longer connected.
+pub(crate) fn spawn(
+    settings: RelayRotationSettings,
+    tunnel_command_tx: Weak<mpsc::UnboundedSender<TunnelCommand>>,
+    parameters_generator: ParametersGenerator,
+    daemon_command_tx: DaemonEventSender<DaemonCommand>,
+) -> AbortHandle {
+    let (future, abort_handle) = abortable(async move {
+        let connected_at = Instant::now();
+        let baseline = transferred_bytes(&tunnel_command_tx).await;
+        if settings.interval.is_none() && baseline.is_none() {
+            log::warn!("The tunnel does not report traffic statistics, relay rotation is disabled");
+            return;
+        }
+
+        let reason = loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let transferred = match baseline {
+                Some(baseline) => transferred_bytes(&tunnel_command_tx)
+                    .await
+                    .map(|total| total.saturating_sub(baseline)),
+                None => None,
+            };
+            if let Some(reason) = rotation_due(&settings, connected_at.elapsed(), transferred) {
+                break reason;
+            }
+        };
+
+        if !parameters_generator
+            .exclude_last_exit(settings.change_city)
+            .await
+        {
+            log::debug!("Not connected to a Mullvad relay, not rotating");
+            return;
+        }
+        log::info!("Rotating exit relay: {reason}");
+        let (tx, rx) = oneshot::channel();
+        let _ = daemon_command_tx.send(DaemonCommand::Reconnect(tx));
+        // suppress "unable to send" warning:
+        let _ = rx.await;
+    });
+    tokio::spawn(future);
+    abort_handle
+}
+
+/// Returns the total number of bytes sent and received through the current tunnel, or `None` if
+/// the tunnel does not report that.
+async fn transferred_bytes(
+    tunnel_command_tx: &Weak<mpsc::UnboundedSender<TunnelCommand>>,
+) -> Option<u64> {
+    let tunnel_command_tx = tunnel_command_tx.upgrade()?;
+    let (tx, rx) = oneshot::channel();
+    tunnel_command_tx
+        .unbounded_send(TunnelCommand::GetTunnelStats(tx))
+        .ok()?;
+    let stats = rx.await.ok()??;
+    Some(stats.tx_bytes.saturating_add(stats.rx_bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+    const GIGABYTE: u64 = 1_000_000_000;
+
+    #[test]
+    fn test_rotation_due() {
+        let settings = RelayRotationSettings {
+            interval: Some(HOUR),
+            traffic_limit: Some(GIGABYTE),
+            change_city: false,
+        };
+
+        assert_eq!(rotation_due(&settings, HOUR / 2, Some(GIGABYTE / 2)), None);
+        assert_eq!(
+            rotation_due(&settings, HOUR, Some(0)),
+            Some(Reason::Interval(HOUR))
+        );
+        assert_eq!(
+            rotation_due(&settings, Duration::ZERO, Some(GIGABYTE)),
+            Some(Reason::Traffic(GIGABYTE))
+        );
+        // Tunnels without traffic statistics can only be rotated by time.
+        assert_eq!(rotation_due(&settings, HOUR / 2, None), None);
+    }
+
+    #[test]
+    fn test_rotation_disabled() {
+        let settings = RelayRotationSettings::default();
+        assert_eq!(
+            rotation_due(&settings, 100 * HOUR, Some(100 * GIGABYTE)),
+            None
+        );
+    }
+}
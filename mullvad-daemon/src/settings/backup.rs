@@ -0,0 +1,155 @@
+//! Complete backups of the settings, for moving them to a different device or restoring them
+//! later. Unlike [patches](super::patch), a backup contains every setting and replaces the
+//! existing settings entirely.
+//!
+//! A backup is a JSON object containing the settings along with the version of the settings
+//! format:
+//!
+//! ```json
+//! { "settings_version": 10, "settings": { ... } }
+//! ```
+//!
+//! Backups created by older versions of the app are migrated to the current format when they
+//! are imported.
+
+use crate::migrations;
+use mullvad_types::settings::{Settings, CURRENT_SETTINGS_VERSION};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to serialize settings")]
+    Serialize(#[source] serde_json::Error),
+
+    #[error("Failed to parse settings backup")]
+    Parse(#[source] serde_json::Error),
+
+    #[error("The backup was created by a newer version of the app (settings version {0})")]
+    UnsupportedVersion(u32),
+
+    #[error("The settings version of the backup does not match its contents")]
+    VersionMismatch,
+
+    #[error("Failed to migrate settings backup")]
+    Migrate(#[source] migrations::Error),
+
+    #[error("The backup contains invalid settings")]
+    InvalidSettings(#[source] serde_json::Error),
+
+    #[error("Failed to apply settings backup")]
+    Settings(#[source] super::Error),
+}
+
+/// Converts an [Error] to a management interface status
+impl From<Error> for mullvad_management_interface::Status {
+    fn from(error: Error) -> mullvad_management_interface::Status {
+        use mullvad_management_interface::Status;
+
+        match error {
+            Error::Parse(_)
+            | Error::UnsupportedVersion(_)
+            | Error::VersionMismatch
+            | Error::Migrate(_)
+            | Error::InvalidSettings(_) => Status::invalid_argument(error.to_string()),
+            Error::Settings(error) => Status::from(error),
+            Error::Serialize(_) => Status::internal(error.to_string()),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SettingsBackup {
+    /// Version of the settings format. This is read before the settings themselves, which may
+    /// be in an older format.
+    settings_version: u32,
+    settings: serde_json::Value,
+}
+
+/// Returns a backup of all settings.
+pub fn export(settings: &Settings) -> Result<String, Error> {
+    let backup = SettingsBackup {
+        settings_version: settings.settings_version as u32,
+        settings: serde_json::to_value(settings).map_err(Error::Serialize)?,
+    };
+    serde_json::to_string_pretty(&backup).map_err(Error::Serialize)
+}
+
+/// Parses a backup created by [export], migrating it to the current settings format if needed.
+pub async fn import(blob: &str) -> Result<Settings, Error> {
+    let SettingsBackup {
+        settings_version,
+        mut settings,
+    } = serde_json::from_str(blob).map_err(Error::Parse)?;
+
+    if settings_version > CURRENT_SETTINGS_VERSION as u32 {
+        return Err(Error::UnsupportedVersion(settings_version));
+    }
+    // The first settings format did not include a version
+    let contents_version = settings
+        .get("settings_version")
+        .map(|version| version.as_u64() == Some(u64::from(settings_version)));
+    if contents_version == Some(false) {
+        return Err(Error::VersionMismatch);
+    }
+
+    migrations::migrate_settings_value(&mut settings)
+        .await
+        .map_err(Error::Migrate)?;
+
+    serde_json::from_value(settings).map_err(Error::InvalidSettings)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::{relay_constraints::SelectedObfuscation, settings::SettingsVersion};
+
+    #[tokio::test]
+    async fn test_export_import() {
+        let mut settings = Settings {
+            allow_lan: true,
+            ..Default::default()
+        };
+        settings.tunnel_options.wireguard.mtu = Some(1380);
+        settings.tunnel_options.dns_options.state = mullvad_types::settings::DnsState::Custom;
+
+        let backup = export(&settings).unwrap();
+        assert_eq!(import(&backup).await.unwrap(), settings);
+    }
+
+    #[tokio::test]
+    async fn test_import_older_version() {
+        let mut settings = serde_json::to_value(Settings::default()).unwrap();
+        settings["settings_version"] = serde_json::json!(SettingsVersion::V8);
+        settings["obfuscation_settings"]["selected_obfuscation"] = serde_json::json!("off");
+        let backup = serde_json::json!({
+            "settings_version": SettingsVersion::V8,
+            "settings": settings,
+        });
+
+        // The V8 migration replaces "off" with "auto"
+        let imported = import(&backup.to_string()).await.unwrap();
+        assert!(imported.settings_version > SettingsVersion::V8);
+        assert_eq!(
+            imported.obfuscation_settings.selected_obfuscation,
+            SelectedObfuscation::Auto
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_invalid_version() {
+        let settings = serde_json::to_value(Settings::default()).unwrap();
+
+        let backup = serde_json::json!({ "settings_version": 1000, "settings": settings });
+        assert!(matches!(
+            import(&backup.to_string()).await,
+            Err(Error::UnsupportedVersion(1000))
+        ));
+
+        let backup = serde_json::json!({ "settings_version": 9, "settings": settings });
+        assert!(matches!(
+            import(&backup.to_string()).await,
+            Err(Error::VersionMismatch)
+        ));
+    }
+}
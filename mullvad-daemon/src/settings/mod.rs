@@ -1,6 +1,7 @@
 use futures::TryFutureExt;
 use mullvad_types::{
     custom_list::Error as CustomListError,
+    policy::{LockedSetting, SettingsPolicy},
     relay_constraints::{RelayConstraints, RelaySettings, WireguardConstraints},
     settings::{DnsState, Settings},
 };
@@ -16,7 +17,11 @@ use tokio::{
     io::{self, AsyncWriteExt},
 };
 
+pub mod backup;
 pub mod patch;
+pub mod policy;
+pub mod profiles;
+pub mod signed_patch;
 
 const SETTINGS_FILE: &str = "settings.json";
 
@@ -39,6 +44,12 @@ pub enum Error {
 
     #[error("Failed to apply settings update")]
     UpdateFailed(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("The setting is managed by a configuration profile")]
+    Locked,
+
+    #[error("{0} is locked by the administrator policy")]
+    LockedByPolicy(LockedSetting),
 }
 
 /// Converts an [Error] to a management interface status
@@ -60,6 +71,9 @@ impl From<Error> for mullvad_management_interface::Status {
             Error::SerializeError(..) | Error::ParseError(..) | Error::UpdateFailed(..) => {
                 Status::new(Code::Internal, error.to_string())
             }
+            Error::Locked | Error::LockedByPolicy(_) => {
+                Status::new(Code::PermissionDenied, error.to_string())
+            }
         }
     }
 }
@@ -92,6 +106,10 @@ fn handle_custom_list_error(
 pub struct SettingsPersister {
     settings: Settings,
     path: PathBuf,
+    /// Patch from a managed profile that later updates may not undo.
+    locked_patch: Option<serde_json::Value>,
+    /// Settings pinned by the administrator.
+    policy: SettingsPolicy,
     #[allow(clippy::type_complexity)]
     on_change_listeners: Vec<Box<dyn Fn(&Settings) + Send + Sync>>,
 }
@@ -123,9 +141,18 @@ impl SettingsPersister {
             settings.show_beta_releases = true;
         }
 
+        let policy = policy::load_or_fail_closed(settings_dir, &settings).await;
+        if let Some(setting) = policy.violation(&settings) {
+            log::info!("Enforcing settings policy. {setting} was changed");
+            policy.enforce(&mut settings);
+            should_save = true;
+        }
+
         let mut persister = SettingsPersister {
             settings,
             path,
+            locked_patch: None,
+            policy,
             on_change_listeners: vec![],
         };
 
@@ -231,6 +258,16 @@ impl SettingsPersister {
     /// Resets default settings
     pub async fn reset(&mut self) -> Result<(), Error> {
         self.settings = Self::default_settings();
+        if let Some(locked_patch) = &self.locked_patch {
+            match patch::merge_patch_to_settings(&self.settings, locked_patch) {
+                Ok(settings) => self.settings = settings,
+                Err(error) => log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to reapply managed profile")
+                ),
+            }
+        }
+        self.policy.enforce(&mut self.settings);
         let path = self.path.clone();
         self.save()
             .or_else(|e| async move {
@@ -346,6 +383,13 @@ impl SettingsPersister {
             return Ok(false);
         }
 
+        self.check_policy(&new_settings)?;
+        if let Some(locked_patch) = &self.locked_patch {
+            if patch::violates_locked_patch(&new_settings, locked_patch) {
+                return Err(Error::Locked);
+            }
+        }
+
         Self::save_inner(&self.path, &new_settings).await?;
         self.settings = new_settings;
 
@@ -354,6 +398,33 @@ impl SettingsPersister {
         Ok(true)
     }
 
+    /// Replace the settings, and reject any later update that would undo the changes made by
+    /// `locked_patch`.
+    async fn replace_locked(
+        &mut self,
+        new_settings: Settings,
+        locked_patch: serde_json::Value,
+    ) -> Result<MadeChanges, Error> {
+        let previous_patch = self.locked_patch.replace(locked_patch);
+        let result = self.update(move |settings| *settings = new_settings).await;
+        if result.is_err() {
+            self.locked_patch = previous_patch;
+        }
+        result
+    }
+
+    pub fn policy(&self) -> &SettingsPolicy {
+        &self.policy
+    }
+
+    /// Fail if `settings` change any setting that is locked by the administrator policy.
+    pub fn check_policy(&self, settings: &Settings) -> Result<(), Error> {
+        match self.policy.violation(settings) {
+            Some(setting) => Err(Error::LockedByPolicy(setting)),
+            None => Ok(()),
+        }
+    }
+
     /// Return a compact summary of important settings
     pub fn summary(&self) -> SettingsSummary<'_> {
         SettingsSummary {
@@ -513,7 +584,7 @@ impl SettingsSummary<'_> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use mullvad_types::settings::SettingsVersion;
+    use mullvad_types::settings::{ExpiryWarningSettings, SettingsVersion};
 
     #[test]
     #[should_panic]
@@ -606,6 +677,25 @@ mod test {
         let _ = SettingsPersister::load_from_bytes(settings).unwrap();
     }
 
+    #[test]
+    fn test_invalid_expiry_warning_thresholds_are_dropped() {
+        let settings: ExpiryWarningSettings = serde_json::from_str(
+            r#"{
+                "thresholds": [
+                    { "secs": 3600, "nanos": 0 },
+                    { "secs": 0, "nanos": 0 },
+                    { "secs": 31536001, "nanos": 0 },
+                    { "secs": 18446744073709551615, "nanos": 0 }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            settings.thresholds,
+            vec![std::time::Duration::from_secs(3600)]
+        );
+    }
+
     /// The [`SettingsPersister`] should always succeed when deserializing a
     /// [`Settings`] object from disk. However, there is a distinction between
     /// different error cases.
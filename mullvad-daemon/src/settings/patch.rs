@@ -11,12 +11,21 @@
 //!    existing settings.
 //!
 //! Permitted settings and merge strategies are defined in the [PERMITTED_SUBKEYS] constant.
+//! Settings that control how the daemon reaches the API, or that send traffic through a proxy, can
+//! only be edited by signed patches. These are defined in [SIGNED_PERMITTED_SUBKEYS].
 //!
 //! This implementation must be kept in sync with the
 //! [spec](../../../docs/settings-patch-format.md).
 
-use super::SettingsPersister;
-use mullvad_types::settings::Settings;
+use super::{MadeChanges, SettingsPersister};
+use mullvad_types::{
+    access_method,
+    custom_list::{self, CustomList},
+    policy::SettingsPolicy,
+    relay_constraints::{RelayConstraints, RelaySettings},
+    settings::{Settings, SettingsPatchChange},
+};
+use std::collections::BTreeSet;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -41,6 +50,15 @@ pub enum Error {
     /// Recursion limit reached
     #[error("Maximum JSON object depth reached")]
     RecursionLimit,
+    /// Invalid custom list
+    #[error("Invalid custom list")]
+    CustomList(#[source] custom_list::Error),
+    /// A location refers to a custom list that does not exist
+    #[error("Unknown custom list: {0}")]
+    UnknownCustomList(String),
+    /// Signature verification failed
+    #[error("Failed to verify signed settings patch")]
+    Signature(#[source] super::signed_patch::Error),
     /// Settings error
     #[error("Settings error")]
     Settings(#[source] super::Error),
@@ -56,7 +74,15 @@ impl From<Error> for mullvad_management_interface::Status {
             | Error::UnknownOrProhibitedKey(_)
             | Error::ParsePatch(_)
             | Error::DeserializePatched(_)
-            | Error::RecursionLimit => Status::invalid_argument(error.to_string()),
+            | Error::RecursionLimit
+            | Error::CustomList(_)
+            | Error::UnknownCustomList(_) => Status::invalid_argument(error.to_string()),
+            Error::Signature(
+                super::signed_patch::Error::Untrusted
+                | super::signed_patch::Error::Unsigned
+                | super::signed_patch::Error::Outdated { .. },
+            ) => Status::permission_denied(error.to_string()),
+            Error::Signature(_) => Status::invalid_argument(error.to_string()),
             Error::Settings(error) => Status::from(error),
             Error::SerializeSettings(error) | Error::SerializeValue(error) => {
                 Status::internal(error.to_string())
@@ -118,15 +144,129 @@ enum PermittedKeyValue {
     Any,
 }
 
-const PERMITTED_SUBKEYS: &PermittedKey = &PermittedKey::object(&[(
-    "relay_overrides",
+/// Whether a patch is signed by a trusted key, which decides the settings that it may edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchOrigin {
+    Unsigned,
+    Signed,
+}
+
+impl PatchOrigin {
+    fn permitted_subkeys(self) -> &'static PermittedKey {
+        match self {
+            PatchOrigin::Unsigned => PERMITTED_SUBKEYS,
+            PatchOrigin::Signed => SIGNED_PERMITTED_SUBKEYS,
+        }
+    }
+}
+
+/// Settings that may be edited by any patch.
+const PERMITTED_SUBKEYS: &PermittedKey = &PermittedKey::object(&[
+    ("relay_overrides", RELAY_OVERRIDES),
+    (
+        "relay_settings",
+        RELAY_SETTINGS.merge_strategy(MergeStrategy::Custom(merge_relay_settings)),
+    ),
+    ("obfuscation_settings", OBFUSCATION_SETTINGS),
+    ("bridge_state", PermittedKey::any()),
+    (
+        "bridge_settings",
+        PermittedKey::object(&[
+            ("bridge_type", PermittedKey::any()),
+            ("normal", PermittedKey::any()),
+        ]),
+    ),
+    ("custom_lists", CUSTOM_LISTS),
+]);
+
+/// Settings that may be edited by patches signed by a trusted key. These are the settings in
+/// [PERMITTED_SUBKEYS], along with custom bridges, API access methods, domain fronts and DoH
+/// resolvers.
+const SIGNED_PERMITTED_SUBKEYS: &PermittedKey = &PermittedKey::object(&[
+    ("relay_overrides", RELAY_OVERRIDES),
+    (
+        "relay_settings",
+        RELAY_SETTINGS.merge_strategy(MergeStrategy::Custom(merge_relay_settings)),
+    ),
+    ("obfuscation_settings", OBFUSCATION_SETTINGS),
+    ("bridge_state", PermittedKey::any()),
+    (
+        "bridge_settings",
+        PermittedKey::object(&[
+            ("bridge_type", PermittedKey::any()),
+            ("normal", PermittedKey::any()),
+            ("custom", PermittedKey::any()),
+        ]),
+    ),
+    (
+        "api_access_methods",
+        PermittedKey::object(&[
+            (
+                "custom",
+                PermittedKey::array(&PermittedKey::object(&[
+                    ("name", PermittedKey::any()),
+                    ("enabled", PermittedKey::any()),
+                    // Built-in access methods cannot be added
+                    (
+                        "access_method",
+                        PermittedKey::object(&[("custom", PermittedKey::any())]),
+                    ),
+                ]))
+                .merge_strategy(MergeStrategy::Custom(merge_access_methods)),
+            ),
+            ("domain_fronts", PermittedKey::any()),
+            ("doh_resolvers", PermittedKey::any()),
+        ]),
+    ),
+    ("custom_lists", CUSTOM_LISTS),
+]);
+
+const RELAY_OVERRIDES: PermittedKey = PermittedKey::array(&PermittedKey::object(&[
+    ("hostname", PermittedKey::any()),
+    ("ipv4_addr_in", PermittedKey::any()),
+    ("ipv6_addr_in", PermittedKey::any()),
+]))
+.merge_strategy(MergeStrategy::Custom(merge_relay_overrides));
+
+const OBFUSCATION_SETTINGS: PermittedKey = PermittedKey::object(&[
+    ("selected_obfuscation", PermittedKey::any()),
+    ("udp2tcp", PermittedKey::any()),
+    ("shadowsocks", PermittedKey::any()),
+]);
+
+const CUSTOM_LISTS: PermittedKey = PermittedKey::object(&[(
+    "custom_lists",
     PermittedKey::array(&PermittedKey::object(&[
-        ("hostname", PermittedKey::any()),
-        ("ipv4_addr_in", PermittedKey::any()),
-        ("ipv6_addr_in", PermittedKey::any()),
+        ("name", PermittedKey::any()),
+        ("locations", PermittedKey::any()),
     ]))
-    .merge_strategy(MergeStrategy::Custom(merge_relay_overrides)),
+    .merge_strategy(MergeStrategy::Custom(merge_custom_lists)),
+)]);
+
+/// Relay constraints. Custom tunnel endpoints are not permitted.
+const RELAY_SETTINGS: PermittedKey = PermittedKey::object(&[(
+    "normal",
+    PermittedKey::object(&[
+        ("location", PermittedKey::any()),
+        ("providers", PermittedKey::any()),
+        ("ownership", PermittedKey::any()),
+        ("tunnel_protocol", PermittedKey::any()),
+        (
+            "wireguard_constraints",
+            PermittedKey::object(&[
+                ("port", PermittedKey::any()),
+                ("ip_version", PermittedKey::any()),
+                ("use_multihop", PermittedKey::any()),
+                ("entry_location", PermittedKey::any()),
+            ]),
+        ),
+        (
+            "openvpn_constraints",
+            PermittedKey::object(&[("port", PermittedKey::any())]),
+        ),
+    ]),
 )]);
+
 /// Prohibit stack overflow via excessive recursion. It might be possible to forgo this when
 /// tail-call optimization can be enforced?
 const RECURSE_LIMIT: usize = 15;
@@ -162,16 +302,169 @@ fn export_settings_inner(settings: &Settings) -> Result<serde_json::Value, Error
         );
     }
 
+    // Only export settings that differ from the defaults
+    let default_settings = Settings::default();
+
+    let custom_lists =
+        serde_json::to_value(&settings.custom_lists).map_err(Error::SerializeValue)?;
+    let custom_lists = custom_lists["custom_lists"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+
+    if matches!(settings.relay_settings, RelaySettings::Normal(_))
+        && settings.relay_settings != default_settings.relay_settings
+    {
+        let mut relay_settings =
+            serde_json::to_value(&settings.relay_settings).map_err(Error::SerializeValue)?;
+        custom_list_ids_to_names(&mut relay_settings, &custom_lists)?;
+        out.insert("relay_settings".to_owned(), relay_settings);
+    }
+    if settings.obfuscation_settings != default_settings.obfuscation_settings {
+        out.insert(
+            "obfuscation_settings".to_owned(),
+            serde_json::to_value(&settings.obfuscation_settings).map_err(Error::SerializeValue)?,
+        );
+    }
+    if settings.bridge_state != default_settings.bridge_state {
+        out.insert(
+            "bridge_state".to_owned(),
+            serde_json::to_value(settings.bridge_state).map_err(Error::SerializeValue)?,
+        );
+    }
+    if settings.bridge_settings != default_settings.bridge_settings {
+        let mut bridge_settings =
+            serde_json::to_value(&settings.bridge_settings).map_err(Error::SerializeValue)?;
+        custom_list_ids_to_names(&mut bridge_settings, &custom_lists)?;
+        out.insert("bridge_settings".to_owned(), bridge_settings);
+    }
+
+    // IDs are local to this device and are assigned when the patch is applied
+    let access_methods = settings
+        .api_access_methods
+        .iter_custom()
+        .map(without_id)
+        .collect::<Result<Vec<_>, _>>()?;
+    let domain_fronts = settings.api_access_methods.domain_fronts();
+    let doh_resolvers = settings.api_access_methods.doh_resolvers();
+    if !access_methods.is_empty() || !domain_fronts.is_empty() || !doh_resolvers.is_empty() {
+        let mut api_access_methods = serde_json::Map::new();
+        if !access_methods.is_empty() {
+            api_access_methods.insert("custom".to_owned(), serde_json::json!(access_methods));
+        }
+        if !domain_fronts.is_empty() {
+            api_access_methods.insert("domain_fronts".to_owned(), serde_json::json!(domain_fronts));
+        }
+        if !doh_resolvers.is_empty() {
+            api_access_methods.insert("doh_resolvers".to_owned(), serde_json::json!(doh_resolvers));
+        }
+        out.insert(
+            "api_access_methods".to_owned(),
+            serde_json::Value::Object(api_access_methods),
+        );
+    }
+    let custom_lists = custom_lists
+        .iter()
+        .map(without_id)
+        .collect::<Result<Vec<_>, _>>()?;
+    if !custom_lists.is_empty() {
+        out.insert(
+            "custom_lists".to_owned(),
+            serde_json::json!({ "custom_lists": custom_lists }),
+        );
+    }
+
     Ok(serde_json::Value::Object(out))
 }
 
-/// Update the settings with the supplied patch. Only settings specified in `PERMITTED_SUBKEYS` can
-/// be updated. All other changes are rejected
+fn without_id(value: impl serde::Serialize) -> Result<serde_json::Value, Error> {
+    let mut value = serde_json::to_value(value).map_err(Error::SerializeValue)?;
+    if let Some(obj) = value.as_object_mut() {
+        obj.remove("id");
+    }
+    Ok(value)
+}
+
+/// Settings that may contain locations referring to custom lists.
+const CUSTOM_LIST_REFERENCES: &[&str] = &["relay_settings", "bridge_settings"];
+
+/// Locations refer to custom lists by ID, but IDs are local to a device. Replace each `list_id`
+/// in `value` with the `list_name` of the list in `custom_lists`.
+fn custom_list_ids_to_names(
+    value: &mut serde_json::Value,
+    custom_lists: &[serde_json::Value],
+) -> Result<(), Error> {
+    for_each_custom_list_reference(value, &mut |reference| {
+        let Some(list) = reference
+            .get("list_id")
+            .and_then(|id| custom_lists.iter().find(|list| list.get("id") == Some(id)))
+        else {
+            // Dangling references are exported as is
+            return Ok(());
+        };
+        reference.remove("list_id");
+        reference.insert("list_name".to_owned(), list["name"].clone());
+        Ok(())
+    })
+}
+
+/// Replace each `list_name` in `value` with the `list_id` of the list with that name in
+/// `custom_lists`. This is the inverse of [custom_list_ids_to_names].
+fn custom_list_names_to_ids(
+    value: &mut serde_json::Value,
+    custom_lists: &[serde_json::Value],
+) -> Result<(), Error> {
+    for_each_custom_list_reference(value, &mut |reference| {
+        let Some(name) = reference.remove("list_name") else {
+            return Ok(());
+        };
+        let list = custom_lists
+            .iter()
+            .find(|list| list.get("name") == Some(&name))
+            .ok_or_else(|| {
+                Error::UnknownCustomList(name.as_str().unwrap_or_default().to_owned())
+            })?;
+        reference.insert("list_id".to_owned(), list["id"].clone());
+        Ok(())
+    })
+}
+
+/// Call `f` with every custom list location in `value`, i.e. every object under a `custom_list`
+/// key.
+fn for_each_custom_list_reference(
+    value: &mut serde_json::Value,
+    f: &mut impl FnMut(&mut serde_json::Map<String, serde_json::Value>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    match value {
+        serde_json::Value::Object(obj) => {
+            for (key, value) in obj.iter_mut() {
+                match value {
+                    serde_json::Value::Object(reference) if key == "custom_list" => f(reference)?,
+                    value => for_each_custom_list_reference(value, f)?,
+                }
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for value in values {
+                for_each_custom_list_reference(value, f)?;
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Update the settings with the supplied patch. Only settings specified in `PERMITTED_SUBKEYS`, or
+/// `SIGNED_PERMITTED_SUBKEYS` for signed patches, can be updated. All other changes are rejected
 pub async fn merge_validate_patch(
     settings: &mut SettingsPersister,
     json_patch: &str,
+    origin: PatchOrigin,
 ) -> Result<(), Error> {
-    let new_settings = merge_validate_patch_inner(settings, json_patch)?;
+    let new_settings = merge_validate_patch_inner(settings, json_patch, origin)?;
+    settings
+        .check_policy(&new_settings)
+        .map_err(Error::Settings)?;
 
     settings
         .update(move |settings| *settings = new_settings)
@@ -181,14 +474,142 @@ pub async fn merge_validate_patch(
     Ok(())
 }
 
-fn merge_validate_patch_inner(settings: &Settings, json_patch: &str) -> Result<Settings, Error> {
-    let mut settings_value: serde_json::Value =
-        serde_json::to_value(settings).map_err(Error::SerializeSettings)?;
+/// Return the changes that applying the supplied patch would make to the settings, without
+/// applying them. The patch is validated the same way as by [merge_validate_patch].
+pub fn diff_patch(
+    settings: &Settings,
+    policy: &SettingsPolicy,
+    json_patch: &str,
+    origin: PatchOrigin,
+) -> Result<Vec<SettingsPatchChange>, Error> {
+    let new_settings = merge_validate_patch_inner(settings, json_patch, origin)?;
+    if let Some(setting) = policy.violation(&new_settings) {
+        return Err(Error::Settings(super::Error::LockedByPolicy(setting)));
+    }
+
+    let old_value = serde_json::to_value(settings).map_err(Error::SerializeSettings)?;
+    let new_value = serde_json::to_value(&new_settings).map_err(Error::SerializeSettings)?;
+
+    let mut changes = vec![];
+    diff_values(String::new(), &old_value, &new_value, &mut changes);
+    Ok(changes)
+}
+
+/// Append a change to `changes` for every value that differs between `old` and `new`. Objects and
+/// arrays are compared element by element.
+fn diff_values(
+    path: String,
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+    changes: &mut Vec<SettingsPatchChange>,
+) {
+    use serde_json::Value;
+
+    match (old, new) {
+        (Value::Object(old_obj), Value::Object(new_obj)) => {
+            let keys: BTreeSet<_> = old_obj.keys().chain(new_obj.keys()).collect();
+            for key in keys {
+                let subpath = if path.is_empty() {
+                    key.to_owned()
+                } else {
+                    format!("{path}.{key}")
+                };
+                diff_values(
+                    subpath,
+                    old_obj.get(key).unwrap_or(&Value::Null),
+                    new_obj.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        (Value::Array(old_array), Value::Array(new_array)) => {
+            for index in 0..old_array.len().max(new_array.len()) {
+                diff_values(
+                    format!("{path}[{index}]"),
+                    old_array.get(index).unwrap_or(&Value::Null),
+                    new_array.get(index).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        (old, new) if old != new => changes.push(SettingsPatchChange {
+            path,
+            old_value: Some(old)
+                .filter(|value| !value.is_null())
+                .map(Value::to_string),
+            new_value: Some(new)
+                .filter(|value| !value.is_null())
+                .map(Value::to_string),
+        }),
+        _ => (),
+    }
+}
+
+/// Apply a patch from a managed profile, and prevent the settings that it changes from being
+/// edited until another managed patch replaces it.
+pub async fn apply_managed_patch(
+    settings: &mut SettingsPersister,
+    json_patch: &str,
+) -> Result<MadeChanges, Error> {
+    let patch_value = parse_validate_patch(json_patch, PatchOrigin::Signed)?;
+    let new_settings = merge_patch_to_settings(settings, &patch_value)?;
+
+    settings
+        .replace_locked(new_settings, patch_value)
+        .await
+        .map_err(Error::Settings)
+}
+
+/// Returns whether `settings` undo any of the changes made by the validated `locked_patch`.
+pub(super) fn violates_locked_patch(settings: &Settings, locked_patch: &serde_json::Value) -> bool {
+    match merge_patch_to_settings(settings, locked_patch) {
+        Ok(patched_settings) => patched_settings != *settings,
+        Err(_) => true,
+    }
+}
+
+fn merge_validate_patch_inner(
+    settings: &Settings,
+    json_patch: &str,
+    origin: PatchOrigin,
+) -> Result<Settings, Error> {
+    let patch_value = parse_validate_patch(json_patch, origin)?;
+    merge_patch_to_settings(settings, &patch_value)
+}
+
+fn parse_validate_patch(json_patch: &str, origin: PatchOrigin) -> Result<serde_json::Value, Error> {
     let patch_value: serde_json::Value =
         serde_json::from_str(json_patch).map_err(Error::ParsePatch)?;
+    validate_patch_value(origin.permitted_subkeys(), &patch_value, 0)?;
+    Ok(patch_value)
+}
+
+/// Merge a validated patch into the settings.
+pub(super) fn merge_patch_to_settings(
+    settings: &Settings,
+    patch_value: &serde_json::Value,
+) -> Result<Settings, Error> {
+    let mut settings_value: serde_json::Value =
+        serde_json::to_value(settings).map_err(Error::SerializeSettings)?;
+
+    // Patches have been validated, so every permitted key may be merged
+    merge_patch_to_value(
+        SIGNED_PERMITTED_SUBKEYS,
+        &mut settings_value,
+        patch_value,
+        0,
+    )?;
 
-    validate_patch_value(PERMITTED_SUBKEYS, &patch_value, 0)?;
-    merge_patch_to_value(PERMITTED_SUBKEYS, &mut settings_value, &patch_value, 0)?;
+    // Resolve custom lists referenced by name now that the lists in the patch have IDs
+    let custom_lists = settings_value["custom_lists"]["custom_lists"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    for key in CUSTOM_LIST_REFERENCES {
+        if let Some(value) = settings_value.get_mut(*key) {
+            custom_list_names_to_ids(value, &custom_lists)?;
+        }
+    }
 
     let new_settings: Settings =
         serde_json::from_value(settings_value).map_err(Error::DeserializePatched)?;
@@ -259,6 +680,98 @@ fn merge_relay_overrides(
     Ok(serde_json::Value::Array(new_array))
 }
 
+/// Merge relay constraints into the existing ones. If a custom tunnel endpoint is currently used,
+/// the patch is applied to the default relay constraints instead.
+fn merge_relay_settings(
+    current_settings: &serde_json::Value,
+    patch: &serde_json::Value,
+) -> Result<serde_json::Value, Error> {
+    let mut new_settings = if current_settings.get("normal").is_some() {
+        current_settings.to_owned()
+    } else {
+        serde_json::to_value(RelaySettings::Normal(RelayConstraints::default()))
+            .map_err(Error::SerializeValue)?
+    };
+    merge_patch_to_value(&RELAY_SETTINGS, &mut new_settings, patch, 0)?;
+    Ok(new_settings)
+}
+
+/// Replace custom access methods that have the same name as an access method in the patch, and
+/// append the rest.
+fn merge_access_methods(
+    current_settings: &serde_json::Value,
+    patch: &serde_json::Value,
+) -> Result<serde_json::Value, Error> {
+    merge_named_entries(current_settings, patch, |_name| {
+        serde_json::to_value(access_method::Id::new()).map_err(Error::SerializeValue)
+    })
+}
+
+/// Replace the locations of custom lists that have the same name as a list in the patch, and
+/// append the rest.
+fn merge_custom_lists(
+    current_settings: &serde_json::Value,
+    patch: &serde_json::Value,
+) -> Result<serde_json::Value, Error> {
+    merge_named_entries(current_settings, patch, |name| {
+        let list = CustomList::new(name.to_owned()).map_err(Error::CustomList)?;
+        serde_json::to_value(list.id).map_err(Error::SerializeValue)
+    })
+}
+
+/// Replace values in existing entries in the array if there's an entry with a matching name,
+/// keeping the ID of the existing entry. Entries with new names are appended and assigned an ID
+/// using `new_id`.
+fn merge_named_entries(
+    current_settings: &serde_json::Value,
+    patch: &serde_json::Value,
+    new_id: impl Fn(&str) -> Result<serde_json::Value, Error>,
+) -> Result<serde_json::Value, Error> {
+    let patch_array = patch
+        .as_array()
+        .ok_or(Error::InvalidOrMissingValue("expected JSON array in patch"))?;
+    let mut new_array = match current_settings {
+        serde_json::Value::Null => vec![],
+        current => current
+            .as_array()
+            .ok_or(Error::InvalidOrMissingValue(
+                "existing entries should be an array",
+            ))?
+            .clone(),
+    };
+
+    for patch_entry in patch_array {
+        let patch_obj = patch_entry
+            .as_object()
+            .ok_or(Error::InvalidOrMissingValue("all entries must be objects"))?;
+        let patch_name = patch_obj
+            .get("name")
+            .and_then(|name| name.as_str())
+            .ok_or(Error::InvalidOrMissingValue("name"))?;
+
+        let existing_obj = new_array.iter_mut().find_map(|value| {
+            value
+                .as_object_mut()
+                .filter(|obj| obj.get("name").and_then(|name| name.as_str()) == Some(patch_name))
+        });
+
+        match existing_obj {
+            Some(existing_obj) => {
+                for (k, v) in patch_obj {
+                    existing_obj.insert(k.to_owned(), v.to_owned());
+                }
+            }
+            None => {
+                let mut new_obj = patch_obj.clone();
+                new_obj.insert("id".to_owned(), new_id(patch_name)?);
+                new_array.push(serde_json::Value::Object(new_obj));
+            }
+        }
+    }
+
+    Ok(serde_json::Value::Array(new_array))
+}
+
 fn merge_patch_to_value(
     permitted_key: &'static PermittedKey,
     current_value: &mut serde_json::Value,
@@ -452,9 +965,14 @@ fn test_valid_patch_files() {
     const OVERRIDE_PATCH: &str =
         include_str!("../../../docs/patch-examples/override-relay-ips.json");
 
+    const CENSORSHIP_PATCH: &str =
+        include_str!("../../../docs/patch-examples/censorship-circumvention.json");
+
     let prev_settings = Settings::default();
-    let _ = merge_validate_patch_inner(&prev_settings, OVERRIDE_PATCH)
+    let _ = merge_validate_patch_inner(&prev_settings, OVERRIDE_PATCH, PatchOrigin::Unsigned)
         .expect("failed to apply relay overrides");
+    let _ = merge_validate_patch_inner(&prev_settings, CENSORSHIP_PATCH, PatchOrigin::Unsigned)
+        .expect("failed to apply censorship circumvention patch");
 }
 
 #[test]
@@ -555,3 +1073,271 @@ fn test_patch_relay_override() {
 
     assert_eq!(current, expected);
 }
+
+#[test]
+fn test_prohibited_settings() {
+    let settings = Settings::default();
+
+    // Custom tunnel endpoints may not be set
+    let patch = r#"{ "relay_settings": { "custom_tunnel_endpoint": { "host": "1.2.3.4" } } }"#;
+    assert!(matches!(
+        merge_validate_patch_inner(&settings, patch, PatchOrigin::Unsigned),
+        Err(Error::UnknownOrProhibitedKey(_))
+    ));
+
+    // Built-in access methods may not be added
+    let patch = r#"{ "api_access_methods": { "custom": [ { "name": "test", "enabled": true, "access_method": { "built_in": "direct" } } ] } }"#;
+    assert!(matches!(
+        merge_validate_patch_inner(&settings, patch, PatchOrigin::Signed),
+        Err(Error::UnknownOrProhibitedKey(_))
+    ));
+
+    // Nothing is applied if any part of the patch is invalid
+    let patch = r#"{ "bridge_state": "on", "obfuscation_settings": { "selected_obfuscation": "invalid" } }"#;
+    assert!(matches!(
+        merge_validate_patch_inner(&settings, patch, PatchOrigin::Unsigned),
+        Err(Error::DeserializePatched(_))
+    ));
+}
+
+/// Access methods, proxies and resolvers can only be set by signed patches.
+#[test]
+fn test_unsigned_prohibited_settings() {
+    let settings = Settings::default();
+    let patches = [
+        r#"{ "api_access_methods": { "custom": [ { "name": "test", "enabled": true, "access_method": { "custom": { "socks5_remote": { "endpoint": "192.0.2.1:1080", "auth": null } } } } ] } }"#,
+        r#"{ "api_access_methods": { "doh_resolvers": [ { "name": "test", "addrs": [ "192.0.2.1" ] } ] } }"#,
+        r#"{ "bridge_settings": { "custom": { "shadowsocks": { "endpoint": "192.0.2.1:443", "password": "test", "cipher": "aes-256-gcm" } } } }"#,
+    ];
+    for patch in patches {
+        assert!(matches!(
+            merge_validate_patch_inner(&settings, patch, PatchOrigin::Unsigned),
+            Err(Error::UnknownOrProhibitedKey(_))
+        ));
+        merge_validate_patch_inner(&settings, patch, PatchOrigin::Signed).unwrap();
+    }
+}
+
+/// Domain fronts decide where API traffic is sent, so only signed patches may set them.
+#[test]
+fn test_patch_domain_fronts() {
+    let settings = Settings::default();
+    let patch = r#"{ "api_access_methods": { "domain_fronts": [ "cdn.example.com" ] } }"#;
+    assert!(matches!(
+        merge_validate_patch_inner(&settings, patch, PatchOrigin::Unsigned),
+        Err(Error::UnknownOrProhibitedKey(_))
+    ));
+    let new_settings = merge_validate_patch_inner(&settings, patch, PatchOrigin::Signed).unwrap();
+    assert_eq!(
+        new_settings.api_access_methods.domain_fronts(),
+        ["cdn.example.com".to_owned()]
+    );
+}
+
+/// The Tor settings decide which bridge the daemon connects to, and pluggable transports are run
+/// by the daemon, so no patch may set them.
+#[test]
+fn test_patch_tor_settings() {
+    let settings = Settings::default();
+    let patch = r#"{ "api_access_methods": { "tor_settings": {
+        "bridge": "192.0.2.1:443 0123456789ABCDEF0123456789ABCDEF01234567"
+    } } }"#;
+    for origin in [PatchOrigin::Unsigned, PatchOrigin::Signed] {
+        assert!(matches!(
+            merge_validate_patch_inner(&settings, patch, origin),
+            Err(Error::UnknownOrProhibitedKey(_))
+        ));
+    }
+}
+
+#[test]
+fn test_patch_custom_lists() {
+    let mut settings = Settings::default();
+    let existing = CustomList::new("existing".to_owned()).unwrap();
+    let existing_id = existing.id;
+    settings.custom_lists.add(existing).unwrap();
+
+    let patch = r#"{ "custom_lists": { "custom_lists": [
+        { "name": "existing", "locations": [ { "country": "se" } ] },
+        { "name": "new", "locations": [ { "country": "no" } ] }
+    ] } }"#;
+    let new_settings = merge_validate_patch_inner(&settings, patch, PatchOrigin::Unsigned).unwrap();
+
+    let lists: Vec<_> = new_settings.custom_lists.iter().collect();
+    assert_eq!(lists.len(), 2);
+
+    // Existing lists keep their ID and have their locations replaced
+    assert_eq!(lists[0].id, existing_id);
+    assert_eq!(lists[0].name, "existing");
+    assert_eq!(lists[0].locations.len(), 1);
+
+    // New lists are assigned a new ID
+    assert_ne!(lists[1].id, existing_id);
+    assert_eq!(lists[1].name, "new");
+
+    // Lists without a name are rejected
+    let patch = r#"{ "custom_lists": { "custom_lists": [ { "locations": [] } ] } }"#;
+    merge_validate_patch_inner(&settings, patch, PatchOrigin::Unsigned).unwrap_err();
+}
+
+#[test]
+fn test_patch_export_roundtrip() {
+    let mut settings = Settings {
+        bridge_state: mullvad_types::relay_constraints::BridgeState::On,
+        ..Default::default()
+    };
+    settings
+        .custom_lists
+        .add(CustomList::new("test".to_owned()).unwrap())
+        .unwrap();
+
+    let exported = export_settings_inner(&settings).expect("patch export failed");
+
+    // IDs are not exported
+    let expected = r#"{ "bridge_state": "on", "custom_lists": { "custom_lists": [ { "name": "test", "locations": [] } ] } }"#;
+    let expected: serde_json::Value = serde_json::from_str(expected).unwrap();
+    assert_eq!(exported, expected);
+
+    let imported = merge_validate_patch_inner(
+        &Settings::default(),
+        &exported.to_string(),
+        PatchOrigin::Unsigned,
+    )
+    .expect("failed to apply exported patch");
+    assert_eq!(imported.bridge_state, settings.bridge_state);
+    assert_eq!(imported.custom_lists.iter().count(), 1);
+}
+
+/// Locations that refer to a custom list must refer to the imported list, which is assigned a new
+/// ID.
+#[test]
+fn test_patch_export_roundtrip_custom_list_location() {
+    use mullvad_types::{
+        constraints::Constraint,
+        relay_constraints::{GeographicLocationConstraint, LocationConstraint},
+    };
+
+    let mut list = CustomList::new("favorites".to_owned()).unwrap();
+    list.locations
+        .insert(GeographicLocationConstraint::country("se"));
+    let mut settings = Settings::default();
+    settings.custom_lists.add(list.clone()).unwrap();
+    settings.relay_settings = RelaySettings::Normal(RelayConstraints {
+        location: Constraint::Only(LocationConstraint::CustomList { list_id: list.id }),
+        ..Default::default()
+    });
+
+    let exported = export_settings_inner(&settings).expect("patch export failed");
+    assert_eq!(
+        exported["relay_settings"]["normal"]["location"]["only"]["custom_list"],
+        serde_json::json!({ "list_name": "favorites" })
+    );
+
+    let imported = merge_validate_patch_inner(
+        &Settings::default(),
+        &exported.to_string(),
+        PatchOrigin::Unsigned,
+    )
+    .expect("failed to apply exported patch");
+    let imported_list = imported.custom_lists.iter().next().unwrap();
+    assert_ne!(imported_list.id, list.id);
+    assert_eq!(imported_list.locations, list.locations);
+    let RelaySettings::Normal(constraints) = &imported.relay_settings else {
+        panic!("expected normal relay settings");
+    };
+    assert_eq!(
+        constraints.location,
+        Constraint::Only(LocationConstraint::CustomList {
+            list_id: imported_list.id
+        })
+    );
+
+    // References to lists that do not exist are rejected
+    let patch = r#"{ "relay_settings": { "normal": { "location": { "only": { "custom_list": { "list_name": "missing" } } } } } }"#;
+    assert!(matches!(
+        merge_validate_patch_inner(&Settings::default(), patch, PatchOrigin::Unsigned),
+        Err(Error::UnknownCustomList(name)) if name == "missing"
+    ));
+}
+
+#[test]
+fn test_patch_diff() {
+    let settings = Settings::default();
+
+    let patch = r#"{ "obfuscation_settings": { "selected_obfuscation": "udp2_tcp" } }"#;
+    let changes = diff_patch(
+        &settings,
+        &SettingsPolicy::default(),
+        patch,
+        PatchOrigin::Unsigned,
+    )
+    .unwrap();
+    assert_eq!(
+        changes,
+        vec![SettingsPatchChange {
+            path: "obfuscation_settings.selected_obfuscation".to_owned(),
+            old_value: Some(r#""auto""#.to_owned()),
+            new_value: Some(r#""udp2_tcp""#.to_owned()),
+        }]
+    );
+
+    // Patches that do not change anything result in an empty diff
+    let patch = r#"{ "bridge_state": "auto" }"#;
+    assert!(diff_patch(
+        &settings,
+        &SettingsPolicy::default(),
+        patch,
+        PatchOrigin::Unsigned
+    )
+    .unwrap()
+    .is_empty());
+}
+
+#[test]
+fn test_patch_policy() {
+    use mullvad_types::{policy::LockedSetting, relay_constraints::GeographicLocationConstraint};
+
+    let policy = SettingsPolicy {
+        allowed_locations: vec![GeographicLocationConstraint::country("se")],
+        ..Default::default()
+    };
+    let settings = Settings::default();
+
+    let patch = r#"{ "relay_settings": { "normal": { "location": { "only": { "location": { "country": "se" } } } } } }"#;
+    diff_patch(&settings, &policy, patch, PatchOrigin::Unsigned).unwrap();
+
+    let patch = r#"{ "relay_settings": { "normal": { "location": { "only": { "location": { "country": "de" } } } } } }"#;
+    assert!(matches!(
+        diff_patch(&settings, &policy, patch, PatchOrigin::Unsigned),
+        Err(Error::Settings(super::Error::LockedByPolicy(
+            LockedSetting::RelayLocation
+        )))
+    ));
+}
+
+#[test]
+fn test_locked_patch() {
+    let patch = r#"{ "bridge_state": "on", "custom_lists": { "custom_lists": [ { "name": "managed", "locations": [] } ] } }"#;
+    let patch_value = parse_validate_patch(patch, PatchOrigin::Signed).unwrap();
+    let settings = merge_patch_to_settings(&Settings::default(), &patch_value).unwrap();
+    assert!(!violates_locked_patch(&settings, &patch_value));
+
+    // Settings that are not part of the patch may be edited
+    let mut edited = settings.clone();
+    edited.allow_lan = true;
+    edited
+        .custom_lists
+        .add(CustomList::new("local".to_owned()).unwrap())
+        .unwrap();
+    assert!(!violates_locked_patch(&edited, &patch_value));
+
+    // Settings that are part of the patch may not
+    let mut edited = settings.clone();
+    edited.bridge_state = mullvad_types::relay_constraints::BridgeState::Off;
+    assert!(violates_locked_patch(&edited, &patch_value));
+
+    let mut edited = settings.clone();
+    let managed_list = edited.custom_lists.iter().next().unwrap().id;
+    edited.custom_lists.remove(&managed_list).unwrap();
+    assert!(violates_locked_patch(&edited, &patch_value));
+}
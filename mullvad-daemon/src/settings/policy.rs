@@ -0,0 +1,130 @@
+//! Administrator policy that pins some settings, so that they cannot be changed by users who can
+//! talk to the daemon. The policy is read from [POLICY_FILE] in the settings directory, which must
+//! only be writable by root, when the daemon starts. See [SettingsPolicy] for the format.
+
+use super::signed_patch;
+use mullvad_types::{policy::SettingsPolicy, settings::Settings};
+use std::path::Path;
+use talpid_types::ErrorExt;
+
+pub const POLICY_FILE: &str = "policy.json";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to read settings policy")]
+    Read(#[source] signed_patch::Error),
+
+    #[error("Invalid settings policy")]
+    Parse(#[source] serde_json::Error),
+}
+
+/// Read the policy from the settings directory. If the policy file cannot be used, the error is
+/// logged and [SettingsPolicy::fail_closed] is returned.
+pub async fn load_or_fail_closed(settings_dir: &Path, settings: &Settings) -> SettingsPolicy {
+    load(settings_dir).await.unwrap_or_else(|error| {
+        log::error!(
+            "{}",
+            error.display_chain_with_msg("Locking down settings since the policy cannot be used")
+        );
+        SettingsPolicy::fail_closed(settings)
+    })
+}
+
+/// Read the policy from the settings directory. If there is no policy file, no settings are
+/// locked.
+pub async fn load(settings_dir: &Path) -> Result<SettingsPolicy, Error> {
+    let Some(contents) = signed_patch::read_root_only(&settings_dir.join(POLICY_FILE))
+        .await
+        .map_err(Error::Read)?
+    else {
+        return Ok(SettingsPolicy::default());
+    };
+    serde_json::from_str(&contents).map_err(Error::Parse)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::{policy::LockedSetting, settings::DnsState};
+
+    #[tokio::test]
+    async fn test_missing_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = load_or_fail_closed(dir.path(), &Settings::default()).await;
+        assert_eq!(policy, SettingsPolicy::default());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_policy_fails_closed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(POLICY_FILE);
+        tokio::fs::write(&path, r#"{ "allow_lan": "#).await.unwrap();
+
+        let settings = Settings {
+            allow_lan: true,
+            ..Default::default()
+        };
+        let policy = load_or_fail_closed(dir.path(), &settings).await;
+        assert_eq!(policy, SettingsPolicy::fail_closed(&settings));
+        assert_eq!(
+            policy.locked_settings(),
+            vec![
+                LockedSetting::LockdownMode,
+                LockedSetting::AllowLan,
+                LockedSetting::Dns
+            ]
+        );
+        assert_eq!(
+            policy.violation(&settings),
+            Some(LockedSetting::LockdownMode)
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_insecure_policy_fails_closed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(POLICY_FILE);
+        tokio::fs::write(&path, "{}").await.unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+        assert!(matches!(
+            load(dir.path()).await,
+            Err(Error::Read(signed_patch::Error::InsecurePermissions(_)))
+        ));
+        let settings = Settings::default();
+        assert_eq!(
+            load_or_fail_closed(dir.path(), &settings).await,
+            SettingsPolicy::fail_closed(&settings)
+        );
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        let policy: SettingsPolicy = serde_json::from_str(
+            r#"{
+                "block_when_disconnected": true,
+                "dns_options": {
+                    "state": "custom",
+                    "default_options": {},
+                    "custom_options": { "addresses": ["10.0.0.1"] }
+                },
+                "allowed_locations": [{ "country": "se" }, { "city": ["de", "ber"] }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            policy.locked_settings(),
+            vec![
+                LockedSetting::LockdownMode,
+                LockedSetting::Dns,
+                LockedSetting::RelayLocation
+            ]
+        );
+        assert_eq!(policy.dns_options.unwrap().state, DnsState::Custom);
+
+        serde_json::from_str::<SettingsPolicy>(r#"{ "auto_connect": true }"#).unwrap_err();
+    }
+}
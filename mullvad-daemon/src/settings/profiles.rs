@@ -0,0 +1,236 @@
+//! Named settings profiles, which let the user switch between sets of settings with a single
+//! command. Profiles are stored in [PROFILES_FILE], next to the settings file.
+//!
+//! A profile only contains some settings, see [SettingsProfile]. Applying it overwrites those
+//! settings in a single update, so that the rest of the daemon sees one settings change.
+
+use super::{MadeChanges, SettingsPersister};
+use mullvad_types::profile::SettingsProfile;
+use std::path::{Path, PathBuf};
+use talpid_types::ErrorExt;
+use tokio::{
+    fs,
+    io::{self, AsyncWriteExt},
+};
+
+const PROFILES_FILE: &str = "settings-profiles.json";
+const PROFILE_NAME_MAX_SIZE: usize = 30;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Unable to read settings profiles from {0}")]
+    Read(String, #[source] io::Error),
+
+    #[error("Unable to parse settings profiles")]
+    Parse(#[source] serde_json::Error),
+
+    #[error("Unable to serialize settings profiles")]
+    Serialize(#[source] serde_json::Error),
+
+    #[error("Unable to write settings profiles to {0}")]
+    Write(String, #[source] io::Error),
+
+    #[error("Profile names must be between 1 and {PROFILE_NAME_MAX_SIZE} characters long")]
+    InvalidName,
+
+    #[error("The profile does not contain any settings")]
+    EmptyProfile,
+
+    #[error("A profile named \"{0}\" already exists")]
+    ProfileExists(String),
+
+    #[error("There is no profile named \"{0}\"")]
+    ProfileNotFound(String),
+
+    #[error("Failed to apply settings profile")]
+    Settings(#[source] super::Error),
+}
+
+/// Converts an [Error] to a management interface status
+impl From<Error> for mullvad_management_interface::Status {
+    fn from(error: Error) -> mullvad_management_interface::Status {
+        use mullvad_management_interface::Status;
+
+        match error {
+            Error::InvalidName | Error::EmptyProfile => Status::invalid_argument(error.to_string()),
+            Error::ProfileExists(_) => Status::already_exists(error.to_string()),
+            Error::ProfileNotFound(_) => Status::not_found(error.to_string()),
+            Error::Settings(error) => Status::from(error),
+            Error::Read(..) | Error::Parse(_) | Error::Serialize(_) | Error::Write(..) => {
+                Status::internal(error.to_string())
+            }
+        }
+    }
+}
+
+pub struct ProfileStore {
+    path: PathBuf,
+    profiles: Vec<SettingsProfile>,
+}
+
+impl ProfileStore {
+    /// Loads the profiles from the settings directory. If they cannot be read, there are no
+    /// profiles.
+    pub async fn load(settings_dir: &Path) -> Self {
+        let path = settings_dir.join(PROFILES_FILE);
+        let profiles = match Self::load_from_file(&path).await {
+            Ok(profiles) => profiles,
+            Err(Error::Read(_, error)) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to load settings profiles")
+                );
+                vec![]
+            }
+        };
+        ProfileStore { path, profiles }
+    }
+
+    async fn load_from_file(path: &Path) -> Result<Vec<SettingsProfile>, Error> {
+        let bytes = fs::read(path)
+            .await
+            .map_err(|error| Error::Read(path.display().to_string(), error))?;
+        serde_json::from_slice(&bytes).map_err(Error::Parse)
+    }
+
+    pub fn profiles(&self) -> &[SettingsProfile] {
+        &self.profiles
+    }
+
+    pub fn get(&self, name: &str) -> Result<&SettingsProfile, Error> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| Error::ProfileNotFound(name.to_owned()))
+    }
+
+    /// Add a new profile and write the profiles to disk.
+    pub async fn create(&mut self, profile: SettingsProfile) -> Result<(), Error> {
+        let mut profiles = self.profiles.clone();
+        Self::insert(&mut profiles, profile)?;
+        self.save(profiles).await
+    }
+
+    /// Remove a profile and write the profiles to disk.
+    pub async fn delete(&mut self, name: &str) -> Result<(), Error> {
+        let mut profiles = self.profiles.clone();
+        Self::remove(&mut profiles, name)?;
+        self.save(profiles).await
+    }
+
+    fn insert(profiles: &mut Vec<SettingsProfile>, profile: SettingsProfile) -> Result<(), Error> {
+        let name_len = profile.name.chars().count();
+        if name_len == 0 || name_len > PROFILE_NAME_MAX_SIZE {
+            return Err(Error::InvalidName);
+        }
+        if profile.fields().is_empty() {
+            return Err(Error::EmptyProfile);
+        }
+        if profiles
+            .iter()
+            .any(|existing| existing.name == profile.name)
+        {
+            return Err(Error::ProfileExists(profile.name));
+        }
+        profiles.push(profile);
+        Ok(())
+    }
+
+    fn remove(profiles: &mut Vec<SettingsProfile>, name: &str) -> Result<(), Error> {
+        let len = profiles.len();
+        profiles.retain(|profile| profile.name != name);
+        if profiles.len() == len {
+            return Err(Error::ProfileNotFound(name.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// Write `profiles` to disk, and keep them if that succeeds.
+    async fn save(&mut self, profiles: Vec<SettingsProfile>) -> Result<(), Error> {
+        let display = || self.path.display().to_string();
+
+        let buffer = serde_json::to_string_pretty(&profiles).map_err(Error::Serialize)?;
+        let mut file = mullvad_fs::AtomicFile::new(&self.path)
+            .await
+            .map_err(|error| Error::Write(display(), error))?;
+        file.write_all(buffer.as_bytes())
+            .await
+            .map_err(|error| Error::Write(display(), error))?;
+        file.finalize()
+            .await
+            .map_err(|error| Error::Write(display(), error))?;
+
+        self.profiles = profiles;
+        Ok(())
+    }
+}
+
+/// Apply a profile to the settings. Nothing is changed if the profile is invalid or if any of its
+/// settings are locked.
+pub async fn apply(
+    settings: &mut SettingsPersister,
+    profile: &SettingsProfile,
+) -> Result<MadeChanges, Error> {
+    settings
+        .try_update(|settings| profile.apply(settings))
+        .await
+        .map_err(Error::Settings)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::{
+        profile::ProfileField,
+        settings::{DnsState, Settings},
+    };
+
+    fn profile(name: &str) -> SettingsProfile {
+        let mut settings = Settings::default();
+        settings.tunnel_options.dns_options.state = DnsState::Custom;
+        SettingsProfile::capture(
+            name.to_owned(),
+            &settings,
+            &[ProfileField::Dns, ProfileField::Lan],
+        )
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut profiles = vec![];
+        ProfileStore::insert(&mut profiles, profile("office")).unwrap();
+        ProfileStore::insert(&mut profiles, profile("travel")).unwrap();
+        assert!(matches!(
+            ProfileStore::insert(&mut profiles, profile("office")),
+            Err(Error::ProfileExists(_))
+        ));
+
+        ProfileStore::remove(&mut profiles, "office").unwrap();
+        assert_eq!(profiles, vec![profile("travel")]);
+        assert!(matches!(
+            ProfileStore::remove(&mut profiles, "office"),
+            Err(Error::ProfileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_profile() {
+        let mut profiles = vec![];
+        assert!(matches!(
+            ProfileStore::insert(&mut profiles, profile("")),
+            Err(Error::InvalidName)
+        ));
+        assert!(matches!(
+            ProfileStore::insert(&mut profiles, profile(&"a".repeat(31))),
+            Err(Error::InvalidName)
+        ));
+
+        let empty = SettingsProfile::capture("empty".to_owned(), &Settings::default(), &[]);
+        assert!(matches!(
+            ProfileStore::insert(&mut profiles, empty),
+            Err(Error::EmptyProfile)
+        ));
+        assert!(profiles.is_empty());
+    }
+}
@@ -0,0 +1,494 @@
+//! Settings patches signed with Ed25519.
+//!
+//! A signed patch is a JSON object containing a versioned settings patch, as a string, along with
+//! a base64-encoded signature of that string:
+//!
+//! ```json
+//! { "signed_patch": "{\"version\": 1, \"patch\": {\"relay_overrides\": []}}", "signature": "..." }
+//! ```
+//!
+//! Signatures are checked against the public keys listed in [TRUSTED_KEYS_FILE], in the settings
+//! directory. Once signing is enabled, unsigned patches are rejected. See [signing_enabled].
+//!
+//! The version of the most recent signed patch is stored in [VERSIONS_FILE], and patches with a
+//! lower version are rejected, so that an old patch cannot be applied again to undo a newer one.
+
+use super::patch::PatchOrigin;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use std::{path::Path, str::FromStr};
+use tokio::{fs, io, io::AsyncWriteExt};
+
+/// File containing the public keys that settings patches may be signed with. Each line holds one
+/// base64-encoded Ed25519 public key. Empty lines and lines starting with `#` are ignored.
+pub const TRUSTED_KEYS_FILE: &str = "trusted-patch-keys";
+
+/// File containing the version of the most recently applied signed patch from each
+/// [PatchSource].
+pub const VERSIONS_FILE: &str = "signed-patch-versions.json";
+
+const PUBLIC_KEY_LEN: usize = 32;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to read file {0}")]
+    Read(String, #[source] io::Error),
+
+    #[error("{0} must be owned by root and must not be writable by other users")]
+    InsecurePermissions(String),
+
+    #[error("Invalid trusted key on line {0}")]
+    InvalidKey(usize),
+
+    #[error("Failed to parse signed settings patch")]
+    Parse(#[source] serde_json::Error),
+
+    #[error("Invalid signature encoding")]
+    InvalidSignature,
+
+    #[error("The settings patch is not signed by a trusted key")]
+    Untrusted,
+
+    #[error("Settings patches must be signed by a trusted key")]
+    Unsigned,
+
+    #[error("Signed settings patch version {version} is older than version {latest}")]
+    Outdated { version: u64, latest: u64 },
+
+    #[error("Failed to parse signed settings patch versions")]
+    ParseVersions(#[source] serde_json::Error),
+
+    #[error("Failed to serialize signed settings patch versions")]
+    SerializeVersions(#[source] serde_json::Error),
+
+    #[error("Failed to save signed settings patch versions")]
+    WriteVersions(#[source] io::Error),
+}
+
+/// A settings patch along with its signature.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignedPatch {
+    /// The patch, exactly as it was signed.
+    pub signed_patch: String,
+    /// Base64-encoded Ed25519 signature of `signed_patch`.
+    pub signature: String,
+}
+
+/// The signed contents of a [SignedPatch].
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VersionedPatch {
+    version: u64,
+    patch: serde_json::Value,
+}
+
+/// A settings patch whose signature has been verified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedPatch {
+    /// Version of the patch. Newer patches have higher versions.
+    pub version: u64,
+    pub patch: String,
+}
+
+impl SignedPatch {
+    /// Returns the patch if it is signed by any of `trusted_keys`.
+    pub fn verify(&self, trusted_keys: &TrustedKeys) -> Result<VerifiedPatch, Error> {
+        let signature = BASE64_STANDARD
+            .decode(&self.signature)
+            .map_err(|_| Error::InvalidSignature)?;
+        let trusted = trusted_keys.0.iter().any(|key| {
+            UnparsedPublicKey::new(&ED25519, key)
+                .verify(self.signed_patch.as_bytes(), &signature)
+                .is_ok()
+        });
+        if !trusted {
+            return Err(Error::Untrusted);
+        }
+        let versioned: VersionedPatch =
+            serde_json::from_str(&self.signed_patch).map_err(Error::Parse)?;
+        Ok(VerifiedPatch {
+            version: versioned.version,
+            patch: versioned.patch.to_string(),
+        })
+    }
+}
+
+/// Public keys that settings patches may be signed with.
+#[derive(Debug, Default, Clone)]
+pub struct TrustedKeys(Vec<[u8; PUBLIC_KEY_LEN]>);
+
+impl TrustedKeys {
+    /// Read the trusted keys from the settings directory. If there is no key file, no keys are
+    /// trusted.
+    pub async fn load(settings_dir: &Path) -> Result<Self, Error> {
+        match read_root_only(&settings_dir.join(TRUSTED_KEYS_FILE)).await? {
+            Some(contents) => contents.parse(),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromStr for TrustedKeys {
+    type Err = Error;
+
+    fn from_str(contents: &str) -> Result<Self, Error> {
+        let mut keys = vec![];
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let key = BASE64_STANDARD
+                .decode(line)
+                .ok()
+                .and_then(|key| <[u8; PUBLIC_KEY_LEN]>::try_from(key).ok())
+                .ok_or(Error::InvalidKey(index + 1))?;
+            keys.push(key);
+        }
+        Ok(Self(keys))
+    }
+}
+
+/// Where a signed patch comes from. Versions are tracked separately for each source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchSource {
+    /// Imported by a user, e.g. using `mullvad import-settings`.
+    Imported,
+    /// Fetched for a managed profile. See [crate::managed_profile].
+    Managed,
+}
+
+/// Version of the most recently applied signed patch from each [PatchSource].
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PatchVersions {
+    imported: u64,
+    managed: u64,
+}
+
+impl PatchVersions {
+    /// Read the versions from the settings directory. If the file exists but cannot be read, an
+    /// error is returned, so that no signed patch is accepted.
+    pub async fn load(settings_dir: &Path) -> Result<Self, Error> {
+        let path = settings_dir.join(VERSIONS_FILE);
+        match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents).map_err(Error::ParseVersions),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(Error::Read(path.display().to_string(), error)),
+        }
+    }
+
+    /// Returns an error if `version` is older than the latest version from `source`.
+    pub fn check(&self, source: PatchSource, version: u64) -> Result<(), Error> {
+        let latest = self.latest(source);
+        if version < latest {
+            return Err(Error::Outdated { version, latest });
+        }
+        Ok(())
+    }
+
+    /// Store `version` as the latest version from `source`, unless a newer version is stored.
+    pub async fn record(
+        settings_dir: &Path,
+        source: PatchSource,
+        version: u64,
+    ) -> Result<(), Error> {
+        let mut versions = Self::load(settings_dir).await?;
+        let latest = match source {
+            PatchSource::Imported => &mut versions.imported,
+            PatchSource::Managed => &mut versions.managed,
+        };
+        if version <= *latest {
+            return Ok(());
+        }
+        *latest = version;
+
+        let json = serde_json::to_string_pretty(&versions).map_err(Error::SerializeVersions)?;
+        async {
+            let mut file = mullvad_fs::AtomicFile::new(settings_dir.join(VERSIONS_FILE)).await?;
+            file.write_all(json.as_bytes()).await?;
+            file.write_all(b"\n").await?;
+            file.finalize().await
+        }
+        .await
+        .map_err(Error::WriteVersions)
+    }
+
+    fn latest(&self, source: PatchSource) -> u64 {
+        match source {
+            PatchSource::Imported => self.imported,
+            PatchSource::Managed => self.managed,
+        }
+    }
+}
+
+/// A patch that has been accepted by [open_patch].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenedPatch {
+    pub patch: String,
+    pub origin: PatchOrigin,
+    /// Version of a signed patch. It should be recorded using [PatchVersions::record] once the
+    /// patch has been applied.
+    pub version: Option<u64>,
+}
+
+/// Returns whether patches must be signed. This is the case if there is a trusted key file, even
+/// an empty one, or if a managed profile is configured.
+pub async fn signing_enabled(settings_dir: &Path) -> bool {
+    exists(&settings_dir.join(TRUSTED_KEYS_FILE)).await
+        || exists(&settings_dir.join(crate::managed_profile::CONFIG_FILE)).await
+}
+
+/// Returns whether `path` exists. Errors other than the file not existing count as existing, so
+/// that they do not turn signing off.
+async fn exists(path: &Path) -> bool {
+    match fs::metadata(path).await {
+        Ok(_) => true,
+        Err(error) => error.kind() != io::ErrorKind::NotFound,
+    }
+}
+
+/// Returns the patch contained in `blob`, which may either be a signed or a plain patch. Signed
+/// patches are verified against the keys in `settings_dir` and must not be older than the latest
+/// imported patch. Plain patches are only accepted if signing is not enabled.
+pub async fn open_patch(settings_dir: &Path, blob: &str) -> Result<OpenedPatch, Error> {
+    let trusted_keys = TrustedKeys::load(settings_dir).await?;
+    let signing_enabled = signing_enabled(settings_dir).await;
+    let versions = PatchVersions::load(settings_dir).await?;
+    open_patch_inner(&trusted_keys, signing_enabled, &versions, blob)
+}
+
+fn open_patch_inner(
+    trusted_keys: &TrustedKeys,
+    signing_enabled: bool,
+    versions: &PatchVersions,
+    blob: &str,
+) -> Result<OpenedPatch, Error> {
+    let value: serde_json::Value = serde_json::from_str(blob).map_err(Error::Parse)?;
+    if value.get("signed_patch").is_none() {
+        if signing_enabled || !trusted_keys.is_empty() {
+            return Err(Error::Unsigned);
+        }
+        return Ok(OpenedPatch {
+            patch: blob.to_owned(),
+            origin: PatchOrigin::Unsigned,
+            version: None,
+        });
+    }
+    let signed: SignedPatch = serde_json::from_value(value).map_err(Error::Parse)?;
+    let verified = signed.verify(trusted_keys)?;
+    versions.check(PatchSource::Imported, verified.version)?;
+    Ok(OpenedPatch {
+        patch: verified.patch,
+        origin: PatchOrigin::Signed,
+        version: Some(verified.version),
+    })
+}
+
+/// Read a file that may only be edited by root, or return `None` if it does not exist.
+pub(crate) async fn read_root_only(path: &Path) -> Result<Option<String>, Error> {
+    let display = || path.display().to_string();
+
+    let metadata = match fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(Error::Read(display(), error)),
+    };
+
+    // On Windows, the settings directory is only writable by administrators
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if metadata.uid() != 0 || metadata.mode() & 0o022 != 0 {
+            return Err(Error::InsecurePermissions(display()));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+
+    fs::read_to_string(path)
+        .await
+        .map(Some)
+        .map_err(|error| Error::Read(display(), error))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    const PATCH: &str = r#"{ "bridge_state": "on" }"#;
+
+    fn sign(key_pair: &Ed25519KeyPair, version: u64, patch: &str) -> String {
+        let signed_patch = format!(r#"{{ "version": {version}, "patch": {patch} }}"#);
+        let signed = SignedPatch {
+            signature: BASE64_STANDARD.encode(key_pair.sign(signed_patch.as_bytes())),
+            signed_patch,
+        };
+        serde_json::to_string(&signed).unwrap()
+    }
+
+    fn trusted_keys(key_pair: &Ed25519KeyPair) -> TrustedKeys {
+        let contents = format!(
+            "# Fleet signing key\n{}\n",
+            BASE64_STANDARD.encode(key_pair.public_key())
+        );
+        contents.parse().unwrap()
+    }
+
+    fn opened(patch: &str) -> String {
+        serde_json::from_str::<serde_json::Value>(patch)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_signed_patch() {
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+        let other_key_pair = Ed25519KeyPair::from_seed_unchecked(&[2; 32]).unwrap();
+        let trusted_keys = trusted_keys(&key_pair);
+        let versions = PatchVersions::default();
+
+        let blob = sign(&key_pair, 1, PATCH);
+        assert_eq!(
+            open_patch_inner(&trusted_keys, true, &versions, &blob).unwrap(),
+            OpenedPatch {
+                patch: opened(PATCH),
+                origin: PatchOrigin::Signed,
+                version: Some(1),
+            }
+        );
+
+        let blob = sign(&other_key_pair, 1, PATCH);
+        assert!(matches!(
+            open_patch_inner(&trusted_keys, true, &versions, &blob),
+            Err(Error::Untrusted)
+        ));
+
+        let mut tampered: SignedPatch = serde_json::from_str(&sign(&key_pair, 1, PATCH)).unwrap();
+        tampered.signed_patch = r#"{ "version": 2, "patch": { "bridge_state": "on" } }"#.to_owned();
+        let tampered = serde_json::to_string(&tampered).unwrap();
+        assert!(matches!(
+            open_patch_inner(&trusted_keys, true, &versions, &tampered),
+            Err(Error::Untrusted)
+        ));
+
+        // Signed patches must be versioned
+        let unversioned = SignedPatch {
+            signed_patch: PATCH.to_owned(),
+            signature: BASE64_STANDARD.encode(key_pair.sign(PATCH.as_bytes())),
+        };
+        let unversioned = serde_json::to_string(&unversioned).unwrap();
+        assert!(matches!(
+            open_patch_inner(&trusted_keys, true, &versions, &unversioned),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_outdated_patch() {
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+        let trusted_keys = trusted_keys(&key_pair);
+        let versions = PatchVersions {
+            imported: 2,
+            managed: 5,
+        };
+
+        assert!(matches!(
+            open_patch_inner(&trusted_keys, true, &versions, &sign(&key_pair, 1, PATCH)),
+            Err(Error::Outdated {
+                version: 1,
+                latest: 2
+            })
+        ));
+        // The latest version may be applied again
+        open_patch_inner(&trusted_keys, true, &versions, &sign(&key_pair, 2, PATCH)).unwrap();
+        open_patch_inner(&trusted_keys, true, &versions, &sign(&key_pair, 3, PATCH)).unwrap();
+
+        // Versions are tracked for each source
+        versions.check(PatchSource::Managed, 5).unwrap();
+        assert!(versions.check(PatchSource::Managed, 4).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_record_version() {
+        let settings_dir = tempfile::tempdir().unwrap();
+        let dir = settings_dir.path();
+
+        PatchVersions::record(dir, PatchSource::Imported, 3)
+            .await
+            .unwrap();
+        // Older versions do not replace newer ones
+        PatchVersions::record(dir, PatchSource::Imported, 2)
+            .await
+            .unwrap();
+        PatchVersions::record(dir, PatchSource::Managed, 1)
+            .await
+            .unwrap();
+
+        let versions = PatchVersions::load(dir).await.unwrap();
+        assert_eq!(versions.latest(PatchSource::Imported), 3);
+        assert_eq!(versions.latest(PatchSource::Managed), 1);
+
+        // A corrupt file rejects every signed patch rather than none
+        fs::write(dir.join(VERSIONS_FILE), "corrupt").await.unwrap();
+        assert!(matches!(
+            PatchVersions::load(dir).await,
+            Err(Error::ParseVersions(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsigned_patch() {
+        let versions = PatchVersions::default();
+        assert_eq!(
+            open_patch_inner(&TrustedKeys::default(), false, &versions, PATCH).unwrap(),
+            OpenedPatch {
+                patch: PATCH.to_owned(),
+                origin: PatchOrigin::Unsigned,
+                version: None,
+            }
+        );
+
+        // Unsigned patches are rejected when signing is enabled, even if no key is trusted
+        assert!(matches!(
+            open_patch_inner(&TrustedKeys::default(), true, &versions, PATCH),
+            Err(Error::Unsigned)
+        ));
+
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+        assert!(matches!(
+            open_patch_inner(&trusted_keys(&key_pair), false, &versions, PATCH),
+            Err(Error::Unsigned)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_signing_enabled() {
+        let settings_dir = tempfile::tempdir().unwrap();
+        let dir = settings_dir.path();
+        assert!(!signing_enabled(dir).await);
+
+        // An empty key file enables signing
+        fs::write(dir.join(TRUSTED_KEYS_FILE), "").await.unwrap();
+        assert!(signing_enabled(dir).await);
+        fs::remove_file(dir.join(TRUSTED_KEYS_FILE)).await.unwrap();
+
+        fs::write(dir.join(crate::managed_profile::CONFIG_FILE), "{}")
+            .await
+            .unwrap();
+        assert!(signing_enabled(dir).await);
+    }
+
+    #[test]
+    fn test_invalid_trusted_key() {
+        assert!(matches!(
+            "\n# comment\nnot a key\n".parse::<TrustedKeys>(),
+            Err(Error::InvalidKey(3))
+        ));
+    }
+}
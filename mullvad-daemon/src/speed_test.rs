@@ -0,0 +1,333 @@
+//! Measures latency, jitter and throughput through the tunnel against a speed test server.
+//!
+//! There is no public speed test server, so the server has to be given by the user. The protocol is
+//! deliberately simple, so that a server can be run with [`serve`]. Every phase of the test uses its own TCP connection, which is opened by sending a
+//! single command byte:
+//!
+//! * [`PING`]: The server echoes everything it receives.
+//! * [`DOWNLOAD`]: The server sends data until the client closes the connection.
+//! * [`UPLOAD`]: The server discards everything it receives.
+
+use chrono::Utc;
+use mullvad_relay_selector::RelayLatencyCache;
+use mullvad_types::speed_test::SpeedTestResult;
+use std::{
+    io,
+    path::Path,
+    time::{Duration, Instant},
+};
+use talpid_types::ErrorExt;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// How long to measure throughput in each direction, unless otherwise specified.
+pub const DEFAULT_DURATION: Duration = Duration::from_secs(5);
+
+/// Upper bound of the duration of each throughput measurement.
+const MAX_DURATION: Duration = Duration::from_secs(60);
+
+/// Where the results of previous speed tests are cached on disk.
+const LATENCY_CACHE_FILENAME: &str = "relay-latency.json";
+
+/// Echo everything that is received.
+pub const PING: u8 = b'P';
+/// Send data until the connection is closed.
+pub const DOWNLOAD: u8 = b'D';
+/// Discard everything that is received.
+pub const UPLOAD: u8 = b'U';
+
+const PING_COUNT: u64 = 10;
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("The tunnel is not connected")]
+    NotConnected,
+
+    #[error("The test duration must be between 1 and {} seconds", MAX_DURATION.as_secs())]
+    InvalidDuration,
+
+    #[error("Failed to connect to speed test server {0}")]
+    Connect(String, #[source] io::Error),
+
+    #[error("Timed out while communicating with the speed test server")]
+    Timeout,
+
+    #[error("Connection to the speed test server failed")]
+    Io(#[source] io::Error),
+}
+
+/// Run a full speed test against `endpoint`. `relay` is the hostname of the relay that the
+/// tunnel is connected to, and is only used to label the result.
+pub async fn run(
+    endpoint: &str,
+    duration: Duration,
+    relay: Option<String>,
+) -> Result<SpeedTestResult, Error> {
+    if duration < Duration::from_secs(1) || duration > MAX_DURATION {
+        return Err(Error::InvalidDuration);
+    }
+
+    log::info!("Running speed test against {endpoint}");
+
+    let round_trip_times = measure_round_trip_times(endpoint).await?;
+    let download_bps = measure_download(endpoint, duration).await?;
+    let upload_bps = measure_upload(endpoint, duration).await?;
+
+    let result = SpeedTestResult {
+        relay,
+        endpoint: endpoint.to_owned(),
+        latency: median(&round_trip_times),
+        jitter: jitter(&round_trip_times),
+        download_bps,
+        upload_bps,
+        timestamp: Utc::now(),
+    };
+
+    log::info!(
+        "Speed test finished: latency: {} ms, jitter: {} ms, download: {} Mbit/s, upload: {} Mbit/s",
+        result.latency.as_millis(),
+        result.jitter.as_millis(),
+        result.download_bps / 1_000_000,
+        result.upload_bps / 1_000_000,
+    );
+
+    Ok(result)
+}
+
+async fn connect(endpoint: &str, command: u8) -> Result<TcpStream, Error> {
+    let mut stream = with_timeout(TcpStream::connect(endpoint))
+        .await?
+        .map_err(|error| Error::Connect(endpoint.to_owned(), error))?;
+    stream.set_nodelay(true).map_err(Error::Io)?;
+    with_timeout(stream.write_all(&[command]))
+        .await?
+        .map_err(Error::Io)?;
+    Ok(stream)
+}
+
+async fn measure_round_trip_times(endpoint: &str) -> Result<Vec<Duration>, Error> {
+    let mut stream = connect(endpoint, PING).await?;
+    let mut round_trip_times = Vec::with_capacity(PING_COUNT as usize);
+
+    for sequence_number in 0..PING_COUNT {
+        let request = sequence_number.to_be_bytes();
+        let mut response = [0u8; 8];
+
+        let start = Instant::now();
+        with_timeout(async {
+            stream.write_all(&request).await?;
+            stream.read_exact(&mut response).await
+        })
+        .await?
+        .map_err(Error::Io)?;
+        round_trip_times.push(start.elapsed());
+
+        if response != request {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Unexpected ping response",
+            )));
+        }
+    }
+
+    Ok(round_trip_times)
+}
+
+async fn measure_download(endpoint: &str, duration: Duration) -> Result<u64, Error> {
+    let mut stream = connect(endpoint, DOWNLOAD).await?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut received = 0u64;
+
+    let start = Instant::now();
+    let deadline = tokio::time::Instant::now() + duration;
+    loop {
+        match tokio::time::timeout_at(deadline, stream.read(&mut buffer)).await {
+            Ok(Ok(0)) | Err(_) => break,
+            Ok(Ok(read)) => received += read as u64,
+            Ok(Err(error)) => return Err(Error::Io(error)),
+        }
+    }
+
+    Ok(bits_per_second(received, start.elapsed()))
+}
+
+async fn measure_upload(endpoint: &str, duration: Duration) -> Result<u64, Error> {
+    let mut stream = connect(endpoint, UPLOAD).await?;
+    let buffer = vec![0u8; CHUNK_SIZE];
+    let mut sent = 0u64;
+
+    let start = Instant::now();
+    let deadline = tokio::time::Instant::now() + duration;
+    loop {
+        match tokio::time::timeout_at(deadline, stream.write(&buffer)).await {
+            Ok(Ok(written)) => sent += written as u64,
+            Err(_) => break,
+            Ok(Err(error)) => return Err(Error::Io(error)),
+        }
+    }
+    let elapsed = start.elapsed();
+    let _ = with_timeout(stream.shutdown()).await;
+
+    Ok(bits_per_second(sent, elapsed))
+}
+
+async fn with_timeout<T>(future: impl std::future::Future<Output = T>) -> Result<T, Error> {
+    tokio::time::timeout(IO_TIMEOUT, future)
+        .await
+        .map_err(|_| Error::Timeout)
+}
+
+fn bits_per_second(bytes: u64, elapsed: Duration) -> u64 {
+    let micros = elapsed.as_micros().max(1);
+    u64::try_from(u128::from(bytes) * 8 * 1_000_000 / micros).unwrap_or(u64::MAX)
+}
+
+fn median(samples: &[Duration]) -> Duration {
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+    sorted.get(sorted.len() / 2).copied().unwrap_or_default()
+}
+
+/// Mean absolute difference between consecutive samples.
+fn jitter(samples: &[Duration]) -> Duration {
+    let differences: Vec<_> = samples
+        .windows(2)
+        .map(|pair| pair[0].max(pair[1]) - pair[0].min(pair[1]))
+        .collect();
+    let count = u32::try_from(differences.len()).unwrap_or(u32::MAX);
+    if count == 0 {
+        return Duration::ZERO;
+    }
+    differences.into_iter().sum::<Duration>() / count
+}
+
+/// Serve speed tests to clients connecting to `listener`.
+pub async fn serve(listener: TcpListener) -> io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(error) = handle_client(stream).await {
+                log::debug!(
+                    "{}",
+                    error.display_chain_with_msg("Speed test client disconnected")
+                );
+            }
+        });
+    }
+}
+
+async fn handle_client(mut stream: TcpStream) -> io::Result<()> {
+    match stream.read_u8().await? {
+        PING => {
+            let (mut reader, mut writer) = stream.split();
+            tokio::io::copy(&mut reader, &mut writer).await?;
+        }
+        DOWNLOAD => {
+            let buffer = vec![0u8; CHUNK_SIZE];
+            loop {
+                stream.write_all(&buffer).await?;
+            }
+        }
+        UPLOAD => {
+            tokio::io::copy(&mut stream, &mut tokio::io::sink()).await?;
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Unknown speed test command",
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Read the results of previous speed tests from `cache_dir`.
+pub(crate) fn load_latency_cache(cache_dir: &Path) -> RelayLatencyCache {
+    let path = cache_dir.join(LATENCY_CACHE_FILENAME);
+    if !path.exists() {
+        return RelayLatencyCache::default();
+    }
+    RelayLatencyCache::from_file(&path).unwrap_or_else(|error| {
+        log::error!(
+            "{}",
+            error.display_chain_with_msg("Failed to load relay latency cache")
+        );
+        RelayLatencyCache::default()
+    })
+}
+
+/// Write the results of previous speed tests to `cache_dir`.
+pub(crate) async fn store_latency_cache(
+    cache_dir: &Path,
+    cache: &RelayLatencyCache,
+) -> Result<(), mullvad_relay_selector::Error> {
+    let path = cache_dir.join(LATENCY_CACHE_FILENAME);
+    log::debug!("Writing relay latency cache to {}", path.display());
+    let mut bytes = vec![];
+    cache.write(&mut bytes)?;
+    tokio::fs::write(&path, bytes)
+        .await
+        .map_err(mullvad_relay_selector::Error::WriteRelayCache)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn spawn_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        tokio::spawn(serve(listener));
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn test_speed_test_against_local_server() {
+        let endpoint = spawn_server().await;
+
+        let result = run(
+            &endpoint,
+            Duration::from_secs(1),
+            Some("se-got-wg-001".to_owned()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.endpoint, endpoint);
+        assert_eq!(result.relay.as_deref(), Some("se-got-wg-001"));
+        assert!(result.latency < IO_TIMEOUT);
+        assert!(result.download_bps > 0);
+        assert!(result.upload_bps > 0);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let result = run(&endpoint, Duration::from_secs(1), None).await;
+        assert!(matches!(result, Err(Error::Connect(..))));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_duration() {
+        let result = run("127.0.0.1:1", Duration::ZERO, None).await;
+        assert!(matches!(result, Err(Error::InvalidDuration)));
+    }
+
+    #[test]
+    fn test_statistics() {
+        let samples = [10, 30, 20].map(Duration::from_millis);
+        assert_eq!(median(&samples), Duration::from_millis(20));
+        assert_eq!(jitter(&samples), Duration::from_millis(15));
+        assert_eq!(
+            bits_per_second(1_000_000, Duration::from_secs(2)),
+            4_000_000
+        );
+    }
+}
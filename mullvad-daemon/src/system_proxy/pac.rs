@@ -0,0 +1,120 @@
+//! A minimal PAC file reader. PAC files are JavaScript programs, and running them would require
+//! a JavaScript engine in the daemon. Instead, every string literal in the file is read as a
+//! possible result of `FindProxyForURL`, such as `"PROXY proxy.example.com:3128; DIRECT"`, and
+//! all proxies in them are returned in the order they appear. The conditions in the script are
+//! ignored, so some of the proxies may not be meant for the API. Proxies that do not work are
+//! skipped when they are tried.
+use super::{ProxyKind, ProxySpec, Url};
+
+/// Return the proxies that the script in `pac` may return.
+pub fn parse(pac: &str) -> Vec<ProxySpec> {
+    let mut proxies = vec![];
+    for literal in string_literals(pac) {
+        for proxy in literal.split(';').filter_map(parse_entry) {
+            if !proxies.contains(&proxy) {
+                proxies.push(proxy);
+            }
+        }
+    }
+    proxies
+}
+
+/// Parse one entry of a `FindProxyForURL` result, e.g. `PROXY proxy.example.com:3128`. Returns
+/// `None` for `DIRECT`, for unsupported proxy types and for anything that is not an entry.
+fn parse_entry(entry: &str) -> Option<ProxySpec> {
+    let mut parts = entry.split_whitespace();
+    let kind = match parts.next()?.to_ascii_uppercase().as_str() {
+        "PROXY" | "HTTP" => ProxyKind::Http,
+        "HTTPS" => ProxyKind::Https,
+        // A bare `SOCKS` means SOCKS4, which is not supported
+        "SOCKS5" => ProxyKind::Socks5,
+        _ => return None,
+    };
+    let url = Url::parse(parts.next()?).ok()?;
+    if parts.next().is_some() || url.scheme.is_some() || url.userinfo.is_some() {
+        return None;
+    }
+    Some(ProxySpec {
+        kind,
+        host: url.host.to_owned(),
+        port: url.port?,
+        credentials: None,
+    })
+}
+
+/// Return the contents of all string literals in a JavaScript source, skipping comments.
+fn string_literals(source: &str) -> Vec<String> {
+    let mut literals = vec![];
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                let mut literal = String::new();
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => literal.extend(chars.next()),
+                        next if next == c => break,
+                        next => literal.push(next),
+                    }
+                }
+                literals.push(literal);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = None;
+                for next in chars.by_ref() {
+                    if previous == Some('*') && next == '/' {
+                        break;
+                    }
+                    previous = Some(next);
+                }
+            }
+            _ => (),
+        }
+    }
+    literals
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PAC: &str = r#"
+        // Internal hosts are reached directly. "PROXY commented.example.com:1"
+        function FindProxyForURL(url, host) {
+            if (shExpMatch(host, "*.corp.example.com")) {
+                return "DIRECT";
+            }
+            /* 'PROXY also-commented.example.com:1' */
+            if (isInNet(dnsResolve(host), "10.0.0.0", "255.0.0.0")) {
+                return 'SOCKS5 10.0.0.1:1080; SOCKS 10.0.0.2:1080';
+            }
+            return "PROXY proxy.corp.example.com:3128; HTTPS [fd00::1]:443; DIRECT";
+        }
+    "#;
+
+    #[test]
+    fn test_parse_pac() {
+        let proxy = |kind, host: &str, port| ProxySpec {
+            kind,
+            host: host.to_owned(),
+            port,
+            credentials: None,
+        };
+        assert_eq!(
+            parse(PAC),
+            vec![
+                proxy(ProxyKind::Socks5, "10.0.0.1", 1080),
+                proxy(ProxyKind::Http, "proxy.corp.example.com", 3128),
+                proxy(ProxyKind::Https, "fd00::1", 443),
+            ]
+        );
+    }
+}
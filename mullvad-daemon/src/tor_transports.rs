@@ -0,0 +1,126 @@
+//! Pluggable transports used by the Tor access method to reach its bridge. The daemon runs them
+//! with its own privileges, so they can only be configured in [CONFIG_FILE] in the settings
+//! directory, which must only be writable by root:
+//!
+//! ```json
+//! {
+//!     "transports": [
+//!         { "protocols": ["obfs4"], "path": "/usr/bin/lyrebird", "arguments": [] }
+//!     ]
+//! }
+//! ```
+//!
+//! The file is read when the daemon starts. Transports cannot be set over the management
+//! interface or by a settings patch.
+
+use crate::{hooks, settings::signed_patch};
+use mullvad_types::access_method::TorTransport;
+use std::path::Path;
+
+/// Root-only file in the settings directory that configures the pluggable transports.
+pub const CONFIG_FILE: &str = "tor-transports.json";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to read pluggable transport configuration")]
+    ReadConfig(#[source] signed_patch::Error),
+
+    #[error("Invalid pluggable transport configuration")]
+    ParseConfig(#[source] serde_json::Error),
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    transports: Vec<TorTransport>,
+}
+
+/// Read the pluggable transports from the settings directory. Transports whose executables may be
+/// modified by users other than root are skipped.
+pub async fn load(settings_dir: &Path) -> Result<Vec<TorTransport>, Error> {
+    let Some(contents) = signed_patch::read_root_only(&settings_dir.join(CONFIG_FILE))
+        .await
+        .map_err(Error::ReadConfig)?
+    else {
+        return Ok(vec![]);
+    };
+    let config: Config = serde_json::from_str(&contents).map_err(Error::ParseConfig)?;
+
+    let mut transports = Vec::with_capacity(config.transports.len());
+    for transport in config.transports {
+        if transport.protocols.is_empty() {
+            log::error!(
+                "Ignoring pluggable transport {}: no protocols",
+                transport.path.display()
+            );
+            continue;
+        }
+        match hooks::check_executable(&transport.path).await {
+            Ok(()) => transports.push(transport),
+            Err(reason) => log::error!(
+                "Ignoring pluggable transport {}: {reason}",
+                transport.path.display()
+            ),
+        }
+    }
+    Ok(transports)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = serde_json::from_str(
+            r#"{ "transports": [ { "protocols": ["obfs4"], "path": "/usr/bin/lyrebird" } ] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.transports,
+            [TorTransport {
+                protocols: vec!["obfs4".to_owned()],
+                path: "/usr/bin/lyrebird".into(),
+                arguments: vec![],
+            }]
+        );
+
+        serde_json::from_str::<Config>(
+            r#"{ "transports": [ { "protocols": [], "path": "/bin/true", "env": {} } ] }"#,
+        )
+        .unwrap_err();
+    }
+
+    /// Transports that unprivileged users could replace are skipped.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_load_skips_unsafe_transports() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let writable = dir.path().join("lyrebird");
+        std::fs::write(&writable, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&writable, std::fs::Permissions::from_mode(0o777)).unwrap();
+
+        let config = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &config,
+            format!(
+                r#"{{ "transports": [
+                    {{ "protocols": ["obfs4"], "path": "{}" }},
+                    {{ "protocols": ["obfs4"], "path": "lyrebird" }}
+                ] }}"#,
+                writable.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&config, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        match load(dir.path()).await {
+            Ok(transports) => assert_eq!(transports, []),
+            // The configuration itself is rejected unless the tests run as root
+            Err(Error::ReadConfig(_)) => assert!(!nix::unistd::Uid::effective().is_root()),
+            Err(error) => panic!("unexpected error: {error}"),
+        }
+    }
+}
@@ -8,7 +8,9 @@ use std::{
 
 use tokio::sync::Mutex;
 
-use mullvad_relay_selector::{GetRelay, RelaySelector, RuntimeParameters, WireguardConfig};
+use mullvad_relay_selector::{
+    ExitExclusion, GetRelay, RelaySelector, RuntimeParameters, WireguardConfig,
+};
 use mullvad_types::{
     endpoint::MullvadWireguardEndpoint, location::GeoIpLocation, relay_list::Relay,
     settings::TunnelOptions,
@@ -23,9 +25,12 @@ use talpid_types::net::{
 #[cfg(target_os = "android")]
 use talpid_types::net::{obfuscation::ObfuscatorConfig, wireguard, TunnelParameters};
 
-use talpid_types::{tunnel::ParameterGenerationError, ErrorExt};
+use talpid_types::{net::IpVersion, tunnel::ParameterGenerationError, ErrorExt};
 
-use crate::device::{AccountManagerHandle, Error as DeviceError, PrivateAccountAndDevice};
+use crate::{
+    device::{AccountManagerHandle, Error as DeviceError, PrivateAccountAndDevice},
+    wireguard_ip_versions::WorkingIpVersions,
+};
 
 /// The IP-addresses that the client uses when it connects to a server that supports the
 /// "Same IP" functionality. This means all clients have the same in-tunnel IP on these
@@ -63,6 +68,12 @@ struct InnerParametersGenerator {
     account_manager: AccountManagerHandle,
 
     last_generated_relays: Option<LastSelectedRelays>,
+    /// Exit relay to avoid the next time tunnel parameters are generated.
+    exit_exclusion: Option<ExitExclusion>,
+    /// Key of the network that the host is connected to, if it can be identified.
+    current_network: Option<String>,
+    /// IP version that WireGuard last managed to connect over, for each network.
+    working_ip_versions: WorkingIpVersions,
 }
 
 impl ParametersGenerator {
@@ -71,6 +82,7 @@ impl ParametersGenerator {
         account_manager: AccountManagerHandle,
         relay_selector: RelaySelector,
         tunnel_options: TunnelOptions,
+        working_ip_versions: WorkingIpVersions,
     ) -> Self {
         Self(Arc::new(Mutex::new(InnerParametersGenerator {
             tunnel_options,
@@ -79,6 +91,9 @@ impl ParametersGenerator {
             account_manager,
 
             last_generated_relays: None,
+            exit_exclusion: None,
+            current_network: None,
+            working_ip_versions,
         })))
     }
 
@@ -103,6 +118,40 @@ impl ParametersGenerator {
         }
     }
 
+    /// Avoid the exit relay of the last generated tunnel parameters the next time parameters are
+    /// generated, and optionally every other relay in the same city. Returns `false` if the last
+    /// parameters did not use a Mullvad relay.
+    pub async fn exclude_last_exit(&self, different_city: bool) -> bool {
+        let mut inner = self.0.lock().await;
+        let relay = match inner.last_generated_relays.as_ref() {
+            Some(LastSelectedRelays::WireGuard { wg_exit, .. }) => wg_exit.clone(),
+            #[cfg(not(target_os = "android"))]
+            Some(LastSelectedRelays::OpenVpn { relay, .. }) => relay.clone(),
+            None => return false,
+        };
+        inner.exit_exclusion = Some(ExitExclusion {
+            relay,
+            different_city,
+        });
+        true
+    }
+
+    /// Sets the network that the host is connected to. `None` means that the network cannot be
+    /// identified.
+    pub async fn set_current_network(&self, network: Option<String>) {
+        self.0.lock().await.current_network = network;
+    }
+
+    /// Remembers that a WireGuard tunnel could be established over `ip_version` on the current
+    /// network. This IP version is tried first the next time the network is joined.
+    pub async fn record_working_ip_version(&self, ip_version: IpVersion) {
+        let inner = &mut *self.0.lock().await;
+        inner
+            .working_ip_versions
+            .set(inner.current_network.as_deref(), ip_version)
+            .await;
+    }
+
     /// Gets the location associated with the last generated tunnel parameters.
     pub async fn get_last_location(&self) -> Option<GeoIpLocation> {
         let inner = self.0.lock().await;
@@ -163,9 +212,17 @@ impl InnerParametersGenerator {
         ipv6: bool,
     ) -> Result<TunnelParameters, Error> {
         let data = self.device().await?;
+        let runtime_params = RuntimeParameters {
+            ipv6,
+            exclude_exit: self.exit_exclusion.take(),
+            preferred_ip_version: self
+                .working_ip_versions
+                .get(self.current_network.as_deref())
+                .unwrap_or_default(),
+        };
         let selected_relay = self
             .relay_selector
-            .get_relay(retry_attempt as usize, RuntimeParameters { ipv6 })?;
+            .get_relay(retry_attempt as usize, runtime_params)?;
 
         match selected_relay {
             #[cfg(not(target_os = "android"))]
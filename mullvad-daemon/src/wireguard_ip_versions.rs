@@ -0,0 +1,150 @@
+//! Remembers the IP version that WireGuard last managed to connect over on each network, so that
+//! it can be tried first when the network is joined again. Entries for identified networks are
+//! kept in the cache directory. The entry for networks that cannot be identified is only kept in
+//! memory.
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use talpid_types::{net::IpVersion, ErrorExt};
+use tokio::io::AsyncWriteExt;
+
+const IP_VERSIONS_FILENAME: &str = "wireguard-ip-versions.json";
+
+/// Maximum number of identified networks to remember.
+const MAX_NETWORKS: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    /// Key of the network, as returned by `NetworkIdentity::key`.
+    network: String,
+    ip_version: IpVersion,
+}
+
+/// Working IP version for each network.
+pub struct WorkingIpVersions {
+    path: PathBuf,
+    /// Identified networks, most recently used first.
+    networks: Vec<Entry>,
+    unidentified: Option<IpVersion>,
+}
+
+impl WorkingIpVersions {
+    /// Load the working IP versions from `cache_dir`.
+    pub async fn load(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(IP_VERSIONS_FILENAME);
+        let networks = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+                log::warn!("Failed to parse working IP versions: {error}");
+                vec![]
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(error) => {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read working IP versions")
+                );
+                vec![]
+            }
+        };
+        Self {
+            path,
+            networks,
+            unidentified: None,
+        }
+    }
+
+    /// Return the IP version that last worked on `network`, if any.
+    pub fn get(&self, network: Option<&str>) -> Option<IpVersion> {
+        match network {
+            Some(network) => self
+                .networks
+                .iter()
+                .find(|entry| entry.network == network)
+                .map(|entry| entry.ip_version),
+            None => self.unidentified,
+        }
+    }
+
+    /// Record that `ip_version` worked on `network`. The entries are saved if they changed.
+    pub async fn set(&mut self, network: Option<&str>, ip_version: IpVersion) {
+        let Some(network) = network else {
+            self.unidentified = Some(ip_version);
+            return;
+        };
+        let entry = Entry {
+            network: network.to_owned(),
+            ip_version,
+        };
+        if self.networks.first() == Some(&entry) {
+            return;
+        }
+        self.networks.retain(|existing| existing.network != network);
+        self.networks.insert(0, entry);
+        self.networks.truncate(MAX_NETWORKS);
+        self.save().await;
+    }
+
+    async fn save(&self) {
+        let json = match serde_json::to_string_pretty(&self.networks) {
+            Ok(json) => json,
+            Err(error) => {
+                log::error!("Failed to serialize working IP versions: {error}");
+                return;
+            }
+        };
+        let result = async {
+            let mut file = mullvad_fs::AtomicFile::new(&self.path).await?;
+            file.write_all(json.as_bytes()).await?;
+            file.write_all(b"\n").await?;
+            file.finalize().await
+        }
+        .await;
+        if let Err(error) = result {
+            log::warn!(
+                "{}",
+                error.display_chain_with_msg("Failed to save working IP versions")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Saving to this directory fails, which is only logged.
+    const DUMMY_CACHE_DIR: &str = "/nonexistent/wireguard-ip-versions-test";
+
+    #[tokio::test]
+    async fn test_ip_version_per_network() {
+        let mut versions = WorkingIpVersions::load(Path::new(DUMMY_CACHE_DIR)).await;
+        assert_eq!(versions.get(Some("home")), None);
+        assert_eq!(versions.get(None), None);
+
+        versions.set(Some("home"), IpVersion::V6).await;
+        versions.set(None, IpVersion::V4).await;
+        assert_eq!(versions.get(Some("home")), Some(IpVersion::V6));
+        assert_eq!(versions.get(Some("work")), None);
+        assert_eq!(versions.get(None), Some(IpVersion::V4));
+
+        versions.set(Some("home"), IpVersion::V4).await;
+        assert_eq!(versions.get(Some("home")), Some(IpVersion::V4));
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_network_is_forgotten() {
+        let mut versions = WorkingIpVersions::load(Path::new(DUMMY_CACHE_DIR)).await;
+        for i in 0..MAX_NETWORKS {
+            versions.set(Some(&i.to_string()), IpVersion::V6).await;
+        }
+        // Use the first network again so that the second one is the least recently used.
+        versions.set(Some("0"), IpVersion::V6).await;
+        versions.set(Some("new"), IpVersion::V6).await;
+
+        assert_eq!(versions.get(Some("0")), Some(IpVersion::V6));
+        assert_eq!(versions.get(Some("1")), None);
+        assert_eq!(versions.get(Some("new")), Some(IpVersion::V6));
+    }
+}
@@ -0,0 +1,214 @@
+use core::fmt;
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use chacha20::{
+    cipher::{KeyIvInit, StreamCipher},
+    ChaCha20,
+};
+use serde::{Deserialize, Serialize};
+
+/// Length of the random nonce that the client sends before any obfuscated data.
+pub const NONCE_LEN: usize = 12;
+
+/// Parse a proxy config that encrypts all traffic with a ChaCha20 keystream.
+///
+/// A ChaCha20 configuration is represented by the proxy type `ProxyType::ChaCha20`. The following
+/// bytes of an IPv6 address are interpreted to derive a ChaCha20 configuration:
+/// bytes 2-4 - u16le - proxy type - must be 0x04
+/// bytes 4-8 - [u8; 4] - 4 bytes representing the proxy IPv4 address
+/// bytes 8-10 - u16le - port on which the proxy is listening
+/// bytes 10-16 - [u8; 6] - key bytes. Must not be all zeros
+/// Given the above, `2001:400:b9d5:9a75:3a04:eafd:1100:ad9e` will have the second hexlet (0x0400)
+/// represent the proxy type, the next 2 hexlets (0xb9d5,0x9a75) represent the IPv4 address for the
+/// proxy endpoint, the next hexlet (`3a04`) represents the port for the proxy endpoint, and
+/// the final 3 hexlets `eafd:1100:ad9e` represent the key (0xEA, 0xFD, 0x11, 0x00, 0xAD, 0x9E).
+///
+/// The key is public to anyone who can resolve the record, so the obfuscation does not provide
+/// confidentiality. Its purpose is to make the traffic look random to passive observers, which
+/// plain XOR with a short key does not.
+pub fn parse_chacha20(data: [u8; 12]) -> Result<super::ProxyConfig, super::Error> {
+    let (ip_bytes, tail) = data.split_first_chunk::<4>().unwrap();
+    let (port_bytes, key_bytes) = tail.split_first_chunk::<2>().unwrap();
+    let key_bytes = <[u8; 6]>::try_from(key_bytes).unwrap();
+
+    let ip = Ipv4Addr::from(*ip_bytes);
+    let port = u16::from_le_bytes(*port_bytes);
+    if port == 0 {
+        return Err(super::Error::InvalidPort(port));
+    }
+    let addr = SocketAddrV4::new(ip, port);
+
+    let key = ChaCha20Key::try_from(key_bytes)?;
+
+    Ok(super::ProxyConfig {
+        addr,
+        obfuscation: Some(super::ObfuscationConfig::ChaCha20(key)),
+    })
+}
+
+/// Key material for the ChaCha20 obfuscation, as published in the AAAA record.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ChaCha20Key([u8; 6]);
+
+impl fmt::Debug for ChaCha20Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x")?;
+        for byte in self.0 {
+            write!(f, "{byte:0>2x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<[u8; 6]> for ChaCha20Key {
+    type Error = super::Error;
+
+    fn try_from(key_bytes: [u8; 6]) -> Result<Self, Self::Error> {
+        if key_bytes == [0; 6] {
+            return Err(super::Error::EmptyChaCha20Key);
+        }
+        Ok(Self(key_bytes))
+    }
+}
+
+/// Direction of the traffic that an obfuscator is used for. Each direction uses its own key, so
+/// that the keystream is never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+/// Applies a ChaCha20 keystream to the traffic in one direction of a connection.
+///
+/// The 256-bit cipher key consists of the 6 key bytes from the config, followed by zeros, with
+/// the last byte set to 0x01 for client to server traffic and 0x02 for server to client traffic.
+/// The client picks a random nonce for each connection and sends it unobfuscated before any other
+/// data. Both directions use that nonce.
+pub struct ChaCha20Obfuscator {
+    cipher: ChaCha20,
+}
+
+impl ChaCha20Obfuscator {
+    pub fn new(key: &ChaCha20Key, direction: Direction, nonce: &[u8; NONCE_LEN]) -> Self {
+        let mut cipher_key = [0u8; 32];
+        cipher_key[..key.0.len()].copy_from_slice(&key.0);
+        cipher_key[31] = match direction {
+            Direction::ClientToServer => 0x01,
+            Direction::ServerToClient => 0x02,
+        };
+        Self {
+            cipher: ChaCha20::new(&cipher_key.into(), nonce.into()),
+        }
+    }
+
+    /// Create the client side obfuscation for a new connection, using a random nonce.
+    pub fn client(key: &ChaCha20Key) -> super::Obfuscation {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        super::Obfuscation {
+            header: nonce.to_vec(),
+            read: Box::new(Self::new(key, Direction::ServerToClient, &nonce)),
+            write: Box::new(Self::new(key, Direction::ClientToServer, &nonce)),
+        }
+    }
+}
+
+impl super::Obfuscator for ChaCha20Obfuscator {
+    fn obfuscate(&mut self, buffer: &mut [u8]) {
+        self.cipher.apply_keystream(buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv6Addr, SocketAddrV4};
+
+    use crate::config::chacha::{ChaCha20Key, ChaCha20Obfuscator, Direction};
+    use crate::config::{Error, ObfuscationConfig, Obfuscator, ProxyConfig};
+
+    #[test]
+    fn chacha20_parsing() {
+        struct Test {
+            input: Ipv6Addr,
+            expected: Result<ProxyConfig, Error>,
+        }
+        let tests = vec![
+            Test {
+                input: "2001:400:7f00:1:3905:0102:304:506"
+                    .parse::<Ipv6Addr>()
+                    .unwrap(),
+                expected: Ok(ProxyConfig {
+                    addr: "127.0.0.1:1337".parse::<SocketAddrV4>().unwrap(),
+                    obfuscation: Some(ObfuscationConfig::ChaCha20(
+                        ChaCha20Key::try_from([0x01, 0x02, 0x03, 0x04, 0x05, 0x06]).unwrap(),
+                    )),
+                }),
+            },
+            Test {
+                input: "2001:400:c0a8:101:bb01:0:0:100"
+                    .parse::<Ipv6Addr>()
+                    .unwrap(),
+                expected: Ok(ProxyConfig {
+                    addr: "192.168.1.1:443".parse::<SocketAddrV4>().unwrap(),
+                    obfuscation: Some(ObfuscationConfig::ChaCha20(
+                        ChaCha20Key::try_from([0, 0, 0, 0, 0x01, 0]).unwrap(),
+                    )),
+                }),
+            },
+            Test {
+                input: "2001:400:c0a8:101:bb01::".parse::<Ipv6Addr>().unwrap(),
+                expected: Err(Error::EmptyChaCha20Key),
+            },
+            Test {
+                input: "2001:400:c0a8:101:0:0102:304:506"
+                    .parse::<Ipv6Addr>()
+                    .unwrap(),
+                expected: Err(Error::InvalidPort(0)),
+            },
+        ];
+
+        for t in tests {
+            let parsed = ProxyConfig::try_from(t.input);
+            assert_eq!(parsed, t.expected);
+        }
+    }
+
+    #[test]
+    fn obfuscation() {
+        const INPUT: &[u8] = &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        const NONCE: [u8; super::NONCE_LEN] = [7; super::NONCE_LEN];
+        let key = ChaCha20Key::try_from([0xff, 0x04, 0x02, 0x04, 0x00, 0x00]).unwrap();
+
+        let mut client_write = ChaCha20Obfuscator::new(&key, Direction::ClientToServer, &NONCE);
+        let mut server_read = ChaCha20Obfuscator::new(&key, Direction::ClientToServer, &NONCE);
+        let mut server_write = ChaCha20Obfuscator::new(&key, Direction::ServerToClient, &NONCE);
+
+        let mut request = INPUT.to_vec();
+        client_write.obfuscate(&mut request);
+        let mut response = INPUT.to_vec();
+        server_write.obfuscate(&mut response);
+
+        assert_ne!(request, INPUT);
+        // The two directions must not share a keystream
+        assert_ne!(request, response);
+
+        server_read.obfuscate(&mut request);
+        assert_eq!(request, INPUT);
+    }
+
+    #[test]
+    fn client_nonce_is_random() {
+        let key = ChaCha20Key::try_from([0x01, 0, 0, 0, 0, 0]).unwrap();
+        let first = ChaCha20Obfuscator::client(&key);
+        let second = ChaCha20Obfuscator::client(&key);
+        assert_eq!(first.header.len(), super::NONCE_LEN);
+        assert_ne!(first.header, second.header);
+    }
+
+    #[test]
+    fn chacha20_key_debug_fmt() {
+        let key = ChaCha20Key::try_from([0x01, 0xff, 0x31, 0x00, 0x00, 0x0a]).unwrap();
+        let key_str = format!("{key:?}");
+        assert_eq!(key_str, "0x01ff3100000a");
+    }
+}
@@ -4,9 +4,11 @@
 use core::fmt;
 use std::net::{Ipv6Addr, SocketAddrV4};
 
+mod chacha;
 mod plain;
 mod xor;
 
+pub use chacha::ChaCha20Key;
 use serde::{Deserialize, Serialize};
 pub use xor::XorKey;
 
@@ -21,6 +23,8 @@ pub enum Error {
     InvalidPort(u16),
     /// The key to use for XOR obfuscation was empty (all zeros)
     EmptyXorKey,
+    /// The key to use for ChaCha20 obfuscation was empty (all zeros)
+    EmptyChaCha20Key,
 }
 
 impl fmt::Display for Error {
@@ -30,6 +34,9 @@ impl fmt::Display for Error {
             Self::XorV1Unsupported => write!(f, "XorV1 proxy types are not supported"),
             Self::InvalidPort(port) => write!(f, "Port {port} is not valid for remote endpoint"),
             Self::EmptyXorKey => write!(f, "The key material for XOR obfuscation is empty"),
+            Self::EmptyChaCha20Key => {
+                write!(f, "The key material for ChaCha20 obfuscation is empty")
+            }
         }
     }
 }
@@ -46,6 +53,8 @@ enum ProxyType {
     XorV1,
     /// XorV2
     XorV2,
+    /// ChaCha20 keystream with a random nonce per connection
+    ChaCha20,
 }
 
 impl TryFrom<[u8; 2]> for ProxyType {
@@ -56,6 +65,7 @@ impl TryFrom<[u8; 2]> for ProxyType {
             0x01 => Ok(Self::Plain),
             0x02 => Ok(Self::XorV1),
             0x03 => Ok(Self::XorV2),
+            0x04 => Ok(Self::ChaCha20),
             unknown => Err(Error::UnknownProxyType(unknown)),
         }
     }
@@ -66,6 +76,16 @@ pub trait Obfuscator: Send {
     fn obfuscate(&mut self, buffer: &mut [u8]);
 }
 
+/// The obfuscation of a single connection to a proxy.
+pub struct Obfuscation {
+    /// Sent to the proxy as is, before any obfuscated data.
+    pub header: Vec<u8>,
+    /// Deobfuscates data received from the proxy.
+    pub read: Box<dyn Obfuscator>,
+    /// Obfuscates data sent to the proxy.
+    pub write: Box<dyn Obfuscator>,
+}
+
 /// Represents a Mullvad Encrypted DNS proxy configuration. Created by parsing
 /// the config out of an IPv6 address resolved over DoH.
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
@@ -81,13 +101,60 @@ pub struct ProxyConfig {
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum ObfuscationConfig {
     XorV2(xor::XorKey),
+    ChaCha20(chacha::ChaCha20Key),
 }
 
 impl ObfuscationConfig {
-    /// Instantiate an obfuscator from the given obfuscation config.
-    pub fn create_obfuscator(&self) -> Box<dyn Obfuscator> {
+    /// Instantiate the obfuscation for a new connection from the given obfuscation config.
+    pub fn create_obfuscation(&self) -> Obfuscation {
+        match self {
+            Self::XorV2(key) => Obfuscation {
+                header: vec![],
+                read: Box::new(xor::XorObfuscator::new(*key)),
+                write: Box::new(xor::XorObfuscator::new(*key)),
+            },
+            Self::ChaCha20(key) => chacha::ChaCha20Obfuscator::client(key),
+        }
+    }
+
+    /// Length of the header that the client sends before any obfuscated data.
+    #[cfg(test)]
+    pub(crate) fn header_len(&self) -> usize {
+        match self {
+            Self::XorV2(_) => 0,
+            Self::ChaCha20(_) => chacha::NONCE_LEN,
+        }
+    }
+
+    /// Instantiate the proxy side of the obfuscation, given the header received from the client.
+    /// Returns the obfuscators for reading from and writing to the client. Used by stand-in proxy
+    /// servers in tests.
+    #[cfg(test)]
+    pub(crate) fn create_server_obfuscation(
+        &self,
+        header: &[u8],
+    ) -> (Box<dyn Obfuscator>, Box<dyn Obfuscator>) {
+        use chacha::{ChaCha20Obfuscator, Direction};
         match self {
-            Self::XorV2(key) => Box::new(xor::XorObfuscator::new(*key)),
+            Self::XorV2(key) => (
+                Box::new(xor::XorObfuscator::new(*key)),
+                Box::new(xor::XorObfuscator::new(*key)),
+            ),
+            Self::ChaCha20(key) => {
+                let nonce = <&[u8; chacha::NONCE_LEN]>::try_from(header).unwrap();
+                (
+                    Box::new(ChaCha20Obfuscator::new(
+                        key,
+                        Direction::ClientToServer,
+                        nonce,
+                    )),
+                    Box::new(ChaCha20Obfuscator::new(
+                        key,
+                        Direction::ServerToClient,
+                        nonce,
+                    )),
+                )
+            }
         }
     }
 }
@@ -107,6 +174,7 @@ impl TryFrom<Ipv6Addr> for ProxyConfig {
             ProxyType::Plain => plain::parse_plain(proxy_config_payload),
             ProxyType::XorV1 => Err(Error::XorV1Unsupported),
             ProxyType::XorV2 => xor::parse_xor(proxy_config_payload),
+            ProxyType::ChaCha20 => chacha::parse_chacha20(proxy_config_payload),
         }
     }
 }
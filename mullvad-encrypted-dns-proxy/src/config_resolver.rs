@@ -8,9 +8,13 @@ use std::{net::IpAddr, sync::Arc, time::Duration};
 use tokio::time::error::Elapsed;
 
 /// The port to connect to the DoH resolvers over.
-const RESOLVER_PORT: u16 = 443;
+pub const RESOLVER_PORT: u16 = 443;
 const DEFAULT_TIMEOUT: Duration = std::time::Duration::from_secs(10);
+/// The domain whose AAAA records contain the proxy configurations.
+pub const DEFAULT_DOMAIN: &str = "frakta.eu";
 
+/// A DoH resolver. `name` is the hostname that the resolver's certificate is valid for.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Nameserver {
     pub name: String,
     pub addr: Vec<IpAddr>,
@@ -62,7 +66,7 @@ pub fn default_resolvers() -> Vec<Nameserver> {
 }
 
 pub async fn resolve_default_config() -> Result<Vec<config::ProxyConfig>, Error> {
-    resolve_configs(&default_resolvers(), "frakta.eu").await
+    resolve_configs(&default_resolvers(), DEFAULT_DOMAIN).await
 }
 
 /// Look up the `domain` towards the given `resolvers`, and try to deserialize all the returned
@@ -71,6 +75,26 @@ pub async fn resolve_configs(
     resolvers: &[Nameserver],
     domain: &str,
 ) -> Result<Vec<config::ProxyConfig>, Error> {
+    let (nameservers, resolver_config) = doh_config(resolvers);
+    resolve_config_with_resolverconfig(nameservers, resolver_config, domain, DEFAULT_TIMEOUT).await
+}
+
+/// Look up the IP addresses of `hostname` towards the given `resolvers`. Unlike a lookup using the
+/// system resolver, this only requires the resolvers to be reachable on [`RESOLVER_PORT`].
+pub async fn resolve_hostname(
+    resolvers: &[Nameserver],
+    hostname: &str,
+) -> Result<Vec<IpAddr>, Error> {
+    let (nameservers, resolver_config) = doh_config(resolvers);
+    let resolver = TokioAsyncResolver::tokio(nameservers, resolver_config);
+    let lookup = tokio::time::timeout(DEFAULT_TIMEOUT, resolver.lookup_ip(hostname))
+        .await
+        .map_err(Error::Timeout)?
+        .map_err(Error::ResolutionError)?;
+    Ok(lookup.into_iter().collect())
+}
+
+fn doh_config(resolvers: &[Nameserver]) -> (ResolverConfig, ResolverOpts) {
     let mut nameservers = ResolverConfig::new();
     for resolver in resolvers.iter() {
         let ns_config_group = NameServerConfigGroup::from_ips_https(
@@ -89,7 +113,7 @@ pub async fn resolve_configs(
     let mut resolver_config: ResolverOpts = Default::default();
 
     resolver_config.timeout = Duration::from_secs(5);
-    resolve_config_with_resolverconfig(nameservers, resolver_config, domain, DEFAULT_TIMEOUT).await
+    (nameservers, resolver_config)
 }
 
 pub async fn resolve_config_with_resolverconfig(
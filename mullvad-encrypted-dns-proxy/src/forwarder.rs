@@ -1,6 +1,10 @@
 //! Forward TCP traffic over various proxy configurations.
 
-use std::io;
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
 
 use tokio::{
     io::{AsyncRead, AsyncWrite},
@@ -16,6 +20,9 @@ use crate::config::Obfuscator;
 pub struct Forwarder<S> {
     read_obfuscator: Option<Box<dyn Obfuscator>>,
     write_obfuscator: Option<Box<dyn Obfuscator>>,
+    /// Obfuscated data that has not been written to `stream` yet. Initially contains the header
+    /// of the obfuscation, if any.
+    write_buffer: Vec<u8>,
     stream: S,
 }
 
@@ -25,22 +32,38 @@ where
 {
     /// Create a [`Forwarder`] with a connected `stream` to an encrypted DNS proxy server
     pub fn from_stream(proxy_config: &crate::config::ProxyConfig, stream: S) -> io::Result<Self> {
-        let (read_obfuscator, write_obfuscator) =
+        let (read_obfuscator, write_obfuscator, write_buffer) =
             if let Some(obfuscation_config) = &proxy_config.obfuscation {
+                let obfuscation = obfuscation_config.create_obfuscation();
                 (
-                    Some(obfuscation_config.create_obfuscator()),
-                    Some(obfuscation_config.create_obfuscator()),
+                    Some(obfuscation.read),
+                    Some(obfuscation.write),
+                    obfuscation.header,
                 )
             } else {
-                (None, None)
+                (None, None, vec![])
             };
 
         Ok(Self {
             read_obfuscator,
             write_obfuscator,
+            write_buffer,
             stream,
         })
     }
+
+    /// Write all of `write_buffer` to the stream.
+    fn poll_write_buffer(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.write_buffer.is_empty() {
+            let n_bytes_written =
+                ready!(Pin::new(&mut self.stream).poll_write(cx, &self.write_buffer))?;
+            if n_bytes_written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.write_buffer.drain(..n_bytes_written);
+        }
+        Poll::Ready(Ok(()))
+    }
 }
 
 /// Forward TCP traffic over various proxy configurations.
@@ -57,18 +80,25 @@ impl Forwarder<TcpStream> {
         let (server_read, server_write) = self.stream.into_split();
         let (client_read, client_write) = client_stream.into_split();
         let _ = tokio::join!(
-            forward(self.read_obfuscator, client_read, server_write),
-            forward(self.write_obfuscator, server_read, client_write)
+            forward(
+                self.write_obfuscator,
+                self.write_buffer,
+                client_read,
+                server_write
+            ),
+            forward(self.read_obfuscator, vec![], server_read, client_write)
         );
     }
 }
 
 async fn forward(
     mut obfuscator: Option<Box<dyn Obfuscator>>,
+    header: Vec<u8>,
     mut source: impl AsyncRead + Unpin,
     mut sink: impl AsyncWrite + Unpin,
 ) -> io::Result<()> {
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    sink.write_all(&header).await?;
     let mut buf = vec![0u8; 1024 * 64];
     while let Ok(n_bytes_read) = AsyncReadExt::read(&mut source, &mut buf).await {
         if n_bytes_read == 0 {
@@ -93,13 +123,14 @@ where
         cx: &mut std::task::Context<'_>,
         buf: &mut tokio::io::ReadBuf<'_>,
     ) -> std::task::Poll<std::io::Result<()>> {
-        use std::task::{ready, Poll};
         let socket = std::pin::pin!(&mut self.stream);
+        // Only the newly read bytes must be deobfuscated, `buf` may already contain data.
+        let already_filled = buf.filled().len();
         match ready!(socket.poll_read(cx, buf)) {
             // in this case, we can read and deobfuscate.
             Ok(()) => {
                 if let Some(read_obfuscator) = &mut self.read_obfuscator {
-                    read_obfuscator.obfuscate(buf.filled_mut());
+                    read_obfuscator.obfuscate(&mut buf.filled_mut()[already_filled..]);
                 }
                 Poll::Ready(Ok(()))
             }
@@ -117,21 +148,30 @@ where
         cx: &mut std::task::Context<'_>,
         buf: &[u8],
     ) -> std::task::Poll<Result<usize, io::Error>> {
+        // The obfuscators are stateful, so data must only be obfuscated once it has been accepted.
+        // Previously accepted data is written first, so that at most one buffer is kept around.
+        ready!(self.poll_write_buffer(cx))?;
+
         let mut owned_buf = buf.to_vec();
         if let Some(write_obfuscator) = &mut self.write_obfuscator {
             write_obfuscator.obfuscate(&mut owned_buf);
         }
-        let stream = std::pin::pin!(&mut self.stream);
-        // If the object is not ready for writing, the method returns Poll::Pending
-        // and arranges for the current task (via cx.waker()) to receive a notification
-        // when the object becomes writable or is closed.
-        stream.poll_write(cx, &owned_buf)
+        self.write_buffer = owned_buf;
+
+        // `buf` has been accepted, so it is reported as written even if the stream is not ready
+        // yet. The rest is written by the next call to `poll_write`, `poll_flush` or
+        // `poll_shutdown`.
+        if let Poll::Ready(Err(err)) = self.poll_write_buffer(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(buf.len()))
     }
 
     fn poll_flush(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Result<(), io::Error>> {
+        ready!(self.poll_write_buffer(cx))?;
         std::pin::pin!(&mut self.stream).poll_flush(cx)
     }
 
@@ -139,19 +179,20 @@ where
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Result<(), io::Error>> {
+        ready!(self.poll_write_buffer(cx))?;
         std::pin::pin!(&mut self.stream).poll_shutdown(cx)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::net::{Ipv4Addr, SocketAddrV4};
+    use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
     use tokio::{
         io::{AsyncReadExt, AsyncWriteExt},
-        net::TcpStream,
+        net::{TcpListener, TcpStream},
     };
 
-    use crate::config::{ObfuscationConfig, XorKey};
+    use crate::config::{ChaCha20Key, ObfuscationConfig, ProxyConfig, XorKey};
 
     use super::Forwarder;
 
@@ -171,16 +212,19 @@ mod tests {
         let xor_key = XorKey::try_from(XOR_KEY).unwrap();
         let obfuscation_config = ObfuscationConfig::XorV2(xor_key);
 
-        let mut client_read_xor = obfuscation_config.create_obfuscator();
-        let mut client_write_xor = obfuscation_config.create_obfuscator();
+        let obfuscation = obfuscation_config.create_obfuscation();
+        let mut client_read_xor = obfuscation.read;
+        let mut client_write_xor = obfuscation.write;
 
         // Server future - receives one TCP connection, then echos everything it reads from it back to
         // the client, using obfuscation via the forwarder in both cases.
         tokio::spawn(async move {
             let (client_conn, _) = server_listener.accept().await.unwrap();
+            let obfuscation = obfuscation_config.create_obfuscation();
             let mut forwarder = Forwarder {
-                read_obfuscator: Some(obfuscation_config.create_obfuscator()),
-                write_obfuscator: Some(obfuscation_config.create_obfuscator()),
+                read_obfuscator: Some(obfuscation.read),
+                write_obfuscator: Some(obfuscation.write),
+                write_buffer: obfuscation.header,
                 stream: client_conn,
             };
             let mut buf = vec![0u8; 1024];
@@ -203,4 +247,127 @@ mod tests {
             assert_eq!(original_payload, read_buf);
         }
     }
+
+    /// Stand-in for an encrypted DNS proxy server. Accepts one connection, deobfuscates what the
+    /// client sends and echoes it back obfuscated. Returns the raw bytes received from the client.
+    async fn stand_in_proxy(
+        listener: TcpListener,
+        obfuscation_config: ObfuscationConfig,
+    ) -> Vec<u8> {
+        let (mut client_conn, _) = listener.accept().await.unwrap();
+        let mut header = vec![0u8; obfuscation_config.header_len()];
+        client_conn.read_exact(&mut header).await.unwrap();
+        let (mut read_obfuscator, mut write_obfuscator) =
+            obfuscation_config.create_server_obfuscation(&header);
+
+        let mut received = header;
+        let mut buf = vec![0u8; 1024];
+        loop {
+            let bytes_read = client_conn.read(&mut buf).await.unwrap();
+            if bytes_read == 0 {
+                break;
+            }
+            let data = &mut buf[..bytes_read];
+            received.extend_from_slice(data);
+            read_obfuscator.obfuscate(data);
+            write_obfuscator.obfuscate(data);
+            client_conn.write_all(data).await.unwrap();
+        }
+        received
+    }
+
+    async fn bind_stand_in_proxy(
+        obfuscation_config: ObfuscationConfig,
+    ) -> (ProxyConfig, tokio::task::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        let SocketAddr::V4(addr) = listener.local_addr().unwrap() else {
+            unreachable!("listener is bound to an IPv4 address");
+        };
+        let proxy_config = ProxyConfig {
+            addr,
+            obfuscation: Some(obfuscation_config.clone()),
+        };
+        let server = tokio::spawn(stand_in_proxy(listener, obfuscation_config));
+        (proxy_config, server)
+    }
+
+    /// Send some data through a [`Forwarder`] to a stand-in proxy and check that it is echoed back
+    /// unchanged. Returns the raw bytes received by the proxy.
+    async fn round_trip(obfuscation_config: ObfuscationConfig, payload: &[u8]) -> Vec<u8> {
+        let (proxy_config, server) = bind_stand_in_proxy(obfuscation_config).await;
+
+        let mut forwarder = Forwarder::connect(&proxy_config).await.unwrap();
+        for _ in 0..5 {
+            forwarder.write_all(payload).await.unwrap();
+            let mut read_buf = vec![0u8; payload.len()];
+            forwarder.read_exact(&mut read_buf).await.unwrap();
+            assert_eq!(payload, read_buf);
+        }
+        forwarder.shutdown().await.unwrap();
+
+        server.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn xor_round_trip() {
+        let xor_key = XorKey::try_from([0x01, 0x02, 0x03, 0x04, 0x00, 0x00]).unwrap();
+        let payload = (1..127).collect::<Vec<u8>>();
+
+        let received = round_trip(ObfuscationConfig::XorV2(xor_key), &payload).await;
+        assert_eq!(received.len(), 5 * payload.len());
+    }
+
+    #[tokio::test]
+    async fn chacha20_round_trip() {
+        let key = ChaCha20Key::try_from([0x01, 0x02, 0x03, 0x04, 0x05, 0x06]).unwrap();
+        let payload = [
+            0x16, 0x03, 0x01, 0x00, 0x80, 0x01, 0x00, 0x00, 0x7c, 0x03, 0x03,
+        ];
+
+        let first = round_trip(ObfuscationConfig::ChaCha20(key), &payload).await;
+        let second = round_trip(ObfuscationConfig::ChaCha20(key), &payload).await;
+
+        let header_len = ObfuscationConfig::ChaCha20(key).header_len();
+        assert_eq!(first.len(), header_len + 5 * payload.len());
+        // Repeated data must not repeat on the wire, neither within nor across connections
+        let first_data = &first[header_len..];
+        assert_ne!(&first_data[..payload.len()], payload);
+        assert_ne!(
+            &first_data[..payload.len()],
+            &first_data[payload.len()..2 * payload.len()]
+        );
+        assert_ne!(first_data, &second[header_len..]);
+    }
+
+    // Forwards a local client connection to a stand-in proxy with [`Forwarder::forward`].
+    #[tokio::test]
+    async fn forward_chacha20() {
+        let key = ChaCha20Key::try_from([0xff, 0x00, 0x00, 0x00, 0x00, 0x01]).unwrap();
+        let obfuscation_config = ObfuscationConfig::ChaCha20(key);
+        let header_len = obfuscation_config.header_len();
+        let (proxy_config, server) = bind_stand_in_proxy(obfuscation_config).await;
+
+        let local_listener = TcpListener::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        let local_addr = local_listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (client_conn, _) = local_listener.accept().await.unwrap();
+            let forwarder = Forwarder::connect(&proxy_config).await.unwrap();
+            forwarder.forward(client_conn).await;
+        });
+
+        let mut client_connection = TcpStream::connect(local_addr).await.unwrap();
+        let payload = (0..=255).collect::<Vec<u8>>();
+        client_connection.write_all(&payload).await.unwrap();
+        let mut read_buf = vec![0u8; payload.len()];
+        client_connection.read_exact(&mut read_buf).await.unwrap();
+        assert_eq!(payload, read_buf);
+        client_connection.shutdown().await.unwrap();
+
+        let received = server.await.unwrap();
+        assert_eq!(received.len(), header_len + payload.len());
+    }
 }
@@ -3,25 +3,28 @@
 
 use std::collections::HashSet;
 
-use crate::config::ProxyConfig;
-use crate::config_resolver::{self, resolve_default_config};
+use crate::config::{ObfuscationConfig, ProxyConfig};
+use crate::config_resolver::{self, default_resolvers, resolve_configs, Nameserver};
 
 /// Keep track of fetched proxy configurations.
 ///
 /// To avoid censorship and getting stuck, the proxy must have a way to efficiently try all
 /// available proxies, and not get stuck on trying only a subset. [`EncryptedDnsProxyState`]
 /// implements a config selection algorithm that exhaustively iterates over all available
-/// proxies in an order that favours configs that are more likely to not be censored, i.e. XorV2
-/// proxies, in [`Self::next_configuration`].
+/// proxies in an order that favours configs that are more likely to not be censored, i.e. ChaCha20
+/// and then XorV2 proxies, in [`Self::next_configuration`].
 ///
 /// It is up to the consumer of [`EncryptedDnsProxyState`] to call [`Self::fetch_configs`] to fetch
-/// new configs as needed, e.g. after creating the initial state.
+/// new configs as needed, e.g. after creating the initial state. Configs fetched earlier, e.g.
+/// by a previous run, can be added with [`Self::add_configs`].
 #[derive(Debug, Default)]
 pub struct EncryptedDnsProxyState {
     /// Note that we rely on the randomness of the ordering of the items in the hashset to pick a
     /// random configurations every time.
     configurations: HashSet<ProxyConfig>,
     tried_configurations: HashSet<ProxyConfig>,
+    /// DoH resolvers to use in addition to [`default_resolvers`].
+    resolvers: Vec<Nameserver>,
 }
 
 /// Failed to fetch a proxy configuration over DNS.
@@ -30,9 +33,9 @@ pub struct FetchConfigError(pub config_resolver::Error);
 
 impl EncryptedDnsProxyState {
     /// Select a config.
-    /// Always select an obfuscated configuration, if there are any left untried. If no obfuscated
-    /// configurations exist, try plain configurations. The order is randomized due to the hash set
-    /// storing the configurations in a random order.
+    /// Always select the most obfuscated configuration that is left untried, see
+    /// `obfuscation_rank`. If no obfuscated configurations exist, try plain configurations. The
+    /// order is randomized due to the hash set storing the configurations in a random order.
     pub fn next_configuration(&mut self) -> Option<ProxyConfig> {
         if self.should_reset() {
             self.reset();
@@ -42,17 +45,14 @@ impl EncryptedDnsProxyState {
         // iteration order of a given HashSet instance. Since for now, there will be only 2
         // different configurations, it barely matters. In the future, we should use `rand`
         // instead, so that the behavior is explicit and clear.
-        let selected_config = {
-            // First, create an iterator for the difference between all configs and tried configs.
-            let mut difference = self.configurations.difference(&self.tried_configurations);
-            // Pick the first configuration if there are any. If there are none, one can only assume
-            // that the configuration set is empty, so an early return is fine.
-            let first_config = difference.next()?;
-            // See if there are any unused obfuscated configurations in the rest of the set.
-            let obfuscated_config = difference.find(|config| config.obfuscation.is_some());
-            // If there is an obfuscated configuration, use that. Otherwise, use the first one.
-            obfuscated_config.unwrap_or(first_config).clone()
-        };
+        //
+        // If there are no untried configurations, one can only assume that the configuration set
+        // is empty, so an early return is fine.
+        let selected_config = self
+            .configurations
+            .difference(&self.tried_configurations)
+            .max_by_key(|config| obfuscation_rank(config))?
+            .clone();
 
         self.tried_configurations.insert(selected_config.clone());
         Some(selected_config)
@@ -60,18 +60,48 @@ impl EncryptedDnsProxyState {
 
     /// Fetch a config, but error out only when no existing configuration was there.
     pub async fn fetch_configs(&mut self) -> Result<(), FetchConfigError> {
-        match resolve_default_config().await {
-            Ok(new_configs) => {
-                self.configurations = HashSet::from_iter(new_configs.into_iter());
-            }
+        match self.refresh_configs().await {
+            Ok(_) => Ok(()),
             Err(err) => {
-                log::error!("Failed to fetch a new proxy configuration: {err:?}");
+                log::error!("Failed to fetch a new proxy configuration: {:?}", err.0);
                 if self.is_empty() {
-                    return Err(FetchConfigError(err));
+                    return Err(err);
                 }
+                Ok(())
             }
         }
-        Ok(())
+    }
+
+    /// Fetch configs and replace the current ones with them. Unlike [`Self::fetch_configs`], this
+    /// fails even if there are existing configurations. Returns the fetched configurations.
+    pub async fn refresh_configs(&mut self) -> Result<Vec<ProxyConfig>, FetchConfigError> {
+        let resolvers: Vec<_> = self
+            .resolvers
+            .iter()
+            .cloned()
+            .chain(default_resolvers())
+            .collect();
+        let new_configs = resolve_configs(&resolvers, config_resolver::DEFAULT_DOMAIN)
+            .await
+            .map_err(FetchConfigError)?;
+        self.configurations = HashSet::from_iter(new_configs.iter().cloned());
+        Ok(new_configs)
+    }
+
+    /// Add configurations that were fetched earlier. They are replaced by the next successful
+    /// fetch.
+    pub fn add_configs(&mut self, configs: impl IntoIterator<Item = ProxyConfig>) {
+        self.configurations.extend(configs);
+    }
+
+    /// Return all known configurations.
+    pub fn configurations(&self) -> impl Iterator<Item = &ProxyConfig> {
+        self.configurations.iter()
+    }
+
+    /// Set the DoH resolvers to use in addition to the default ones when fetching configs.
+    pub fn set_resolvers(&mut self, resolvers: Vec<Nameserver>) {
+        self.resolvers = resolvers;
     }
 
     fn is_empty(&self) -> bool {
@@ -94,3 +124,59 @@ impl EncryptedDnsProxyState {
         self.tried_configurations.clear();
     }
 }
+
+/// How likely a configuration is to not be censored. Higher is better.
+fn obfuscation_rank(config: &ProxyConfig) -> u8 {
+    match config.obfuscation {
+        None => 0,
+        Some(ObfuscationConfig::XorV2(_)) => 1,
+        Some(ObfuscationConfig::ChaCha20(_)) => 2,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{ChaCha20Key, XorKey};
+
+    #[test]
+    fn test_added_configs_are_selected() {
+        let plain = ProxyConfig {
+            addr: "192.0.2.1:443".parse().unwrap(),
+            obfuscation: None,
+        };
+        let xor = ProxyConfig {
+            addr: "192.0.2.2:443".parse().unwrap(),
+            obfuscation: Some(ObfuscationConfig::XorV2(XorKey::try_from([1; 6]).unwrap())),
+        };
+
+        let mut state = EncryptedDnsProxyState::default();
+        assert_eq!(state.next_configuration(), None);
+
+        state.add_configs([plain.clone(), xor.clone()]);
+        assert_eq!(state.configurations().count(), 2);
+        // Obfuscated configurations are preferred
+        assert_eq!(state.next_configuration(), Some(xor.clone()));
+        assert_eq!(state.next_configuration(), Some(plain));
+        assert_eq!(state.next_configuration(), Some(xor));
+    }
+
+    #[test]
+    fn test_chacha20_is_preferred() {
+        let xor = ProxyConfig {
+            addr: "192.0.2.1:443".parse().unwrap(),
+            obfuscation: Some(ObfuscationConfig::XorV2(XorKey::try_from([1; 6]).unwrap())),
+        };
+        let chacha20 = ProxyConfig {
+            addr: "192.0.2.2:443".parse().unwrap(),
+            obfuscation: Some(ObfuscationConfig::ChaCha20(
+                ChaCha20Key::try_from([1; 6]).unwrap(),
+            )),
+        };
+
+        let mut state = EncryptedDnsProxyState::default();
+        state.add_configs([xor.clone(), chacha20.clone()]);
+        assert_eq!(state.next_configuration(), Some(chacha20));
+        assert_eq!(state.next_configuration(), Some(xor));
+    }
+}
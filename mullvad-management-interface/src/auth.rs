@@ -0,0 +1,358 @@
+//! Authorization of management interface clients.
+//!
+//! Every connection to the management interface is assigned a [Role] when it is accepted, and each
+//! RPC requires a minimum role, see [Role::required_for]. On Linux and macOS, the role is derived
+//! from the credentials of the peer process and the groups named by [ADMIN_GROUP_VAR] and
+//! [OPERATOR_GROUP_VAR]. If neither is set, every client is an admin.
+
+use futures::future::{self, Either, Ready};
+use std::{
+    fmt,
+    task::{Context, Poll},
+};
+use tonic::{body::BoxBody, codegen::http, Status};
+use tower::{Layer, Service};
+
+/// Environment variable naming the group whose members are admins.
+pub const ADMIN_GROUP_VAR: &str = "MULLVAD_MANAGEMENT_ADMIN_GROUP";
+/// Environment variable naming the group whose members are operators.
+pub const OPERATOR_GROUP_VAR: &str = "MULLVAD_MANAGEMENT_OPERATOR_GROUP";
+
+const SERVICE_PATH: &str = "/mullvad_daemon.management_interface.ManagementService/";
+
+/// RPCs that only read the state of the daemon.
+const READ_ONLY_METHODS: &[&str] = &[
+    "GetTunnelState",
+    "EventsListen",
+    "GetCurrentVersion",
+    "GetVersionInfo",
+    "IsPerformingPostUpgrade",
+    "GetRelayLocations",
+    "GetSettings",
+    "GetCurrentNetwork",
+    "GetAccountData",
+    "GetVoucherHistory",
+    "GetDevice",
+    "ListDevices",
+    "GetDeviceLabels",
+    "GetWireguardKey",
+    "GetCurrentApiAccessMethod",
+    "GetApiAccessMethodStatistics",
+    "GetEncryptedDnsProxyConfigs",
+    "GetSplitTunnelProcesses",
+    "GetExcludedProcesses",
+    "DiffJsonSettings",
+    "ExportJsonSettings",
+    "ExportSettingsBackup",
+    "GetSettingsProfiles",
+    "GetSettingsPolicy",
+    "GetEventHooks",
+    "GetFeatureIndicators",
+];
+
+/// RPCs that control the tunnel without changing any settings.
+const OPERATOR_METHODS: &[&str] = &[
+    "ConnectTunnel",
+    "DisconnectTunnel",
+    "ReconnectTunnel",
+    "UpdateRelayLocations",
+    "RefreshEncryptedDnsProxyConfigs",
+    "UpdateDevice",
+    "TestCustomApiAccessMethod",
+    "TestApiAccessMethodById",
+    "CheckVolumes",
+    "RunSpeedTest",
+];
+
+/// What a management interface client is allowed to do. Each role may do everything that the
+/// roles before it may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// May read the state and settings of the daemon and listen to events.
+    ReadOnly,
+    /// May also connect and disconnect the tunnel.
+    Operator,
+    /// May call every RPC, including those that change settings, log in or out or reset the
+    /// daemon.
+    Admin,
+}
+
+impl Role {
+    /// Returns the role required to call the RPC at `path`, for example
+    /// `/mullvad_daemon.management_interface.ManagementService/ConnectTunnel`. Unknown RPCs
+    /// require [Role::Admin].
+    pub fn required_for(path: &str) -> Role {
+        match path.strip_prefix(SERVICE_PATH) {
+            Some(method) => Self::required_for_method(method),
+            None => Role::Admin,
+        }
+    }
+
+    /// Returns the role required to call the RPC named `method`, for example `ConnectTunnel`.
+    pub fn required_for_method(method: &str) -> Role {
+        if READ_ONLY_METHODS.contains(&method) {
+            Role::ReadOnly
+        } else if OPERATOR_METHODS.contains(&method) {
+            Role::Operator
+        } else {
+            Role::Admin
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::ReadOnly => f.write_str("read-only"),
+            Role::Operator => f.write_str("operator"),
+            Role::Admin => f.write_str("admin"),
+        }
+    }
+}
+
+/// Maps Unix users to roles, based on the groups that they are members of.
+#[cfg(unix)]
+#[derive(Debug, Default, Clone)]
+pub struct RoleMapping {
+    admin_group: Option<nix::unistd::Gid>,
+    operator_group: Option<nix::unistd::Gid>,
+}
+
+#[cfg(unix)]
+impl RoleMapping {
+    pub fn new(
+        admin_group: Option<nix::unistd::Gid>,
+        operator_group: Option<nix::unistd::Gid>,
+    ) -> Self {
+        RoleMapping {
+            admin_group,
+            operator_group,
+        }
+    }
+
+    /// Read the groups from [ADMIN_GROUP_VAR] and [OPERATOR_GROUP_VAR].
+    pub fn from_env() -> Result<Self, crate::Error> {
+        let group = |var| -> Result<_, crate::Error> {
+            let Ok(name) = std::env::var(var) else {
+                return Ok(None);
+            };
+            let group = nix::unistd::Group::from_name(&name)
+                .map_err(crate::Error::ObtainGidError)?
+                .ok_or(crate::Error::NoGidError)?;
+            Ok(Some(group.gid))
+        };
+        Ok(Self::new(
+            group(ADMIN_GROUP_VAR)?,
+            group(OPERATOR_GROUP_VAR)?,
+        ))
+    }
+
+    /// Returns whether any role group is configured. If not, every client is an admin.
+    pub fn is_enabled(&self) -> bool {
+        self.admin_group.is_some() || self.operator_group.is_some()
+    }
+
+    /// Returns the role of the process at the other end of `stream`.
+    pub fn role(&self, stream: &tokio::net::UnixStream) -> Role {
+        if !self.is_enabled() {
+            return Role::Admin;
+        }
+        match stream.peer_cred() {
+            Ok(cred) => self.role_of(
+                nix::unistd::Uid::from_raw(cred.uid()),
+                nix::unistd::Gid::from_raw(cred.gid()),
+            ),
+            Err(error) => {
+                log::error!("Failed to obtain peer credentials: {error}");
+                Role::ReadOnly
+            }
+        }
+    }
+
+    /// Returns the role of the user `uid`, whose process runs with the primary group `gid`.
+    fn role_of(&self, uid: nix::unistd::Uid, gid: nix::unistd::Gid) -> Role {
+        if !self.is_enabled() || uid.is_root() || uid == nix::unistd::Uid::effective() {
+            return Role::Admin;
+        }
+        let is_member = |group: Option<nix::unistd::Gid>| {
+            group.is_some_and(|group| is_group_member(uid, gid, group))
+        };
+        if is_member(self.admin_group) {
+            Role::Admin
+        } else if is_member(self.operator_group) {
+            Role::Operator
+        } else {
+            Role::ReadOnly
+        }
+    }
+}
+
+/// Returns whether the user `uid` is a member of `group`. Membership is looked up for every
+/// connection, so that changes take effect without restarting the daemon.
+#[cfg(unix)]
+fn is_group_member(uid: nix::unistd::Uid, gid: nix::unistd::Gid, group: nix::unistd::Gid) -> bool {
+    use nix::unistd::{Group, User};
+
+    if gid == group {
+        return true;
+    }
+    let Ok(Some(user)) = User::from_uid(uid) else {
+        return false;
+    };
+    user.gid == group
+        || Group::from_gid(group)
+            .ok()
+            .flatten()
+            .is_some_and(|group| group.mem.contains(&user.name))
+}
+
+/// Layer that rejects RPCs which the [Role] of the connection does not permit.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AuthorizationLayer;
+
+impl<S> Layer<S> for AuthorizationLayer {
+    type Service = Authorization<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Authorization { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Authorization<S> {
+    inner: S,
+}
+
+impl<S, B> Service<http::Request<B>> for Authorization<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Either<S::Future, Ready<Result<S::Response, S::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        // The role is inserted by the server as the connect info of the connection
+        let role = request
+            .extensions()
+            .get::<Role>()
+            .copied()
+            .unwrap_or(Role::ReadOnly);
+        let required = Role::required_for(request.uri().path());
+        if role < required {
+            log::debug!(
+                "Denied {} to a client with the {role} role",
+                request.uri().path()
+            );
+            let status = Status::permission_denied(format!(
+                "This requires the {required} role, but the client has the {role} role"
+            ));
+            return Either::Right(future::ready(Ok(status.into_http())));
+        }
+        Either::Left(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tonic::Code;
+
+    fn path(method: &str) -> String {
+        format!("{SERVICE_PATH}{method}")
+    }
+
+    #[test]
+    fn test_required_role() {
+        assert_eq!(Role::required_for(&path("GetSettings")), Role::ReadOnly);
+        assert_eq!(Role::required_for(&path("EventsListen")), Role::ReadOnly);
+        assert_eq!(Role::required_for(&path("ConnectTunnel")), Role::Operator);
+        assert_eq!(Role::required_for(&path("SetAllowLan")), Role::Admin);
+        assert_eq!(Role::required_for(&path("LoginAccount")), Role::Admin);
+        assert_eq!(Role::required_for(&path("FactoryReset")), Role::Admin);
+        assert_eq!(Role::required_for(&path("NotAnRpc")), Role::Admin);
+        assert_eq!(Role::required_for_method("GetSettings"), Role::ReadOnly);
+        assert_eq!(
+            Role::required_for("/other.Service/GetSettings"),
+            Role::Admin
+        );
+    }
+
+    /// Every RPC in the role tables must exist in the protocol, so that tables cannot refer to
+    /// RPCs that are added later or have been renamed.
+    #[test]
+    fn test_role_tables_match_proto() {
+        const PROTO: &str = include_str!("../proto/management_interface.proto");
+        for method in READ_ONLY_METHODS.iter().chain(OPERATOR_METHODS) {
+            assert!(
+                PROTO.contains(&format!("rpc {method}(")),
+                "{method} is not an RPC"
+            );
+        }
+    }
+
+    #[test]
+    fn test_authorization() {
+        let inner = tower::service_fn(|_: http::Request<()>| async {
+            Ok::<_, std::convert::Infallible>(http::Response::new(BoxBody::default()))
+        });
+        let mut service = AuthorizationLayer.layer(inner);
+
+        let mut call = |method: &str, role: Option<Role>| {
+            let mut request = http::Request::builder().uri(path(method)).body(()).unwrap();
+            if let Some(role) = role {
+                request.extensions_mut().insert(role);
+            }
+            let response = futures::executor::block_on(service.call(request)).unwrap();
+            Status::from_header_map(response.headers()).map(|status| status.code())
+        };
+
+        assert_eq!(call("GetSettings", Some(Role::ReadOnly)), None);
+        assert_eq!(
+            call("ConnectTunnel", Some(Role::ReadOnly)),
+            Some(Code::PermissionDenied)
+        );
+        assert_eq!(call("ConnectTunnel", Some(Role::Operator)), None);
+        assert_eq!(
+            call("SetAllowLan", Some(Role::Operator)),
+            Some(Code::PermissionDenied)
+        );
+        assert_eq!(call("SetAllowLan", Some(Role::Admin)), None);
+        assert_eq!(call("ConnectTunnel", None), Some(Code::PermissionDenied));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_role_mapping() {
+        use nix::unistd::{Gid, Uid};
+
+        // Connections from the user running the daemon are always admins
+        let (_client, server) = tokio::net::UnixStream::pair().unwrap();
+        let mapping = RoleMapping::new(Some(Gid::from_raw(4242)), None);
+        assert_eq!(mapping.role(&server), Role::Admin);
+
+        let user = Uid::from_raw(4242);
+        let group = Gid::from_raw(4242);
+        let other_group = Gid::from_raw(4343);
+        assert_eq!(RoleMapping::default().role_of(user, group), Role::Admin);
+        assert_eq!(
+            RoleMapping::new(Some(group), None).role_of(user, group),
+            Role::Admin
+        );
+        assert_eq!(
+            RoleMapping::new(Some(other_group), Some(group)).role_of(user, group),
+            Role::Operator
+        );
+        assert_eq!(
+            RoleMapping::new(Some(other_group), None).role_of(user, group),
+            Role::ReadOnly
+        );
+
+        assert!(!RoleMapping::default().is_enabled());
+        assert!(RoleMapping::new(None, Some(group)).is_enabled());
+    }
+}
@@ -7,6 +7,7 @@ use futures::{Stream, StreamExt};
 use mullvad_types::wireguard::DaitaSettings;
 use mullvad_types::{
     access_method::AccessMethodSetting,
+    account::AccountExpiryWarning,
     device::{DeviceEvent, RemoveDeviceEvent},
     relay_list::RelayList,
     settings::Settings,
@@ -17,18 +18,25 @@ use mullvad_types::{
 #[cfg(not(target_os = "android"))]
 use mullvad_types::{
     access_method::{self, AccessMethod},
-    account::{AccountData, AccountNumber, VoucherSubmission},
+    account::{AccountData, AccountNumber, RedeemedVoucher, VoucherSubmission},
     custom_list::{CustomList, Id},
-    device::{Device, DeviceId, DeviceState},
+    device::{Device, DeviceId, DeviceLabel, DeviceState},
     features::FeatureIndicators,
+    hooks::EventHook,
+    network_rules::NetworkRulesSettings,
+    policy::SettingsPolicy,
+    profile::SettingsProfile,
     relay_constraints::{
         BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
     },
-    settings::DnsOptions,
+    settings::{DnsOptions, ExpiryWarningSettings, RelayRotationSettings, SettingsPatchChange},
+    speed_test::SpeedTestResult,
     wireguard::{PublicKey, QuantumResistantState, RotationInterval},
 };
 #[cfg(not(target_os = "android"))]
 use std::{path::Path, str::FromStr};
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::NetworkIdentity;
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 #[cfg(not(target_os = "android"))]
@@ -51,6 +59,7 @@ pub enum DaemonEvent {
     Device(DeviceEvent),
     RemoveDevice(RemoveDeviceEvent),
     NewAccessMethod(AccessMethodSetting),
+    AccountExpiryWarning(AccountExpiryWarning),
 }
 
 impl TryFrom<types::daemon_event::Event> for DaemonEvent {
@@ -81,6 +90,11 @@ impl TryFrom<types::daemon_event::Event> for DaemonEvent {
                     .map(DaemonEvent::NewAccessMethod)
                     .map_err(Error::InvalidResponse)
             }
+            types::daemon_event::Event::AccountExpiryWarning(warning) => {
+                AccountExpiryWarning::try_from(warning)
+                    .map(DaemonEvent::AccountExpiryWarning)
+                    .map_err(Error::InvalidResponse)
+            }
         }
     }
 }
@@ -242,6 +256,22 @@ impl MullvadProxyClient {
             })
     }
 
+    pub async fn get_api_access_method_statistics(
+        &mut self,
+    ) -> Result<Vec<access_method::AccessMethodStatus>> {
+        self.0
+            .get_api_access_method_statistics(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner()
+            .statuses
+            .into_iter()
+            .map(|status| {
+                access_method::AccessMethodStatus::try_from(status).map_err(Error::InvalidResponse)
+            })
+            .collect()
+    }
+
     pub async fn test_api_access_method(&mut self, id: access_method::Id) -> Result<bool> {
         let result = self
             .0
@@ -263,6 +293,72 @@ impl MullvadProxyClient {
         Ok(result.into_inner())
     }
 
+    /// Set the URL of the PAC file used by the system proxy access method. `None` clears it, so
+    /// that no PAC file is used.
+    pub async fn set_system_proxy_pac_url(&mut self, pac_url: Option<String>) -> Result<()> {
+        self.0
+            .set_system_proxy_pac_url(pac_url.unwrap_or_default())
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    /// Set the DoH resolvers used to fetch Encrypted DNS proxy configs, in addition to the
+    /// default ones.
+    pub async fn set_encrypted_dns_proxy_resolvers(
+        &mut self,
+        resolvers: Vec<access_method::DohResolver>,
+    ) -> Result<()> {
+        let resolvers = types::DohResolverList {
+            resolvers: resolvers
+                .into_iter()
+                .map(types::DohResolver::from)
+                .collect(),
+        };
+        self.0
+            .set_encrypted_dns_proxy_resolvers(resolvers)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    /// Set the bridge used by the Tor access method.
+    pub async fn set_tor_settings(
+        &mut self,
+        tor_settings: access_method::TorSettings,
+    ) -> Result<()> {
+        self.0
+            .set_tor_settings(types::TorSettings::from(tor_settings))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn get_encrypted_dns_proxy_configs(
+        &mut self,
+    ) -> Result<Vec<access_method::EncryptedDnsProxyConfigInfo>> {
+        let configs = self
+            .0
+            .get_encrypted_dns_proxy_configs(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        encrypted_dns_proxy_configs(configs)
+    }
+
+    /// Fetch new Encrypted DNS proxy configs and return them.
+    pub async fn refresh_encrypted_dns_proxy_configs(
+        &mut self,
+    ) -> Result<Vec<access_method::EncryptedDnsProxyConfigInfo>> {
+        let configs = self
+            .0
+            .refresh_encrypted_dns_proxy_configs(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        encrypted_dns_proxy_configs(configs)
+    }
+
     pub async fn update_relay_locations(&mut self) -> Result<()> {
         self.0
             .update_relay_locations(())
@@ -271,6 +367,14 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn import_relay_list(&mut self, signed_relay_list: String) -> Result<()> {
+        self.0
+            .import_relay_list(signed_relay_list)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn set_relay_settings(&mut self, update: RelaySettings) -> Result<()> {
         let update = types::RelaySettings::from(update);
         self.0
@@ -418,6 +522,49 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_relay_rotation_settings(
+        &mut self,
+        settings: RelayRotationSettings,
+    ) -> Result<()> {
+        if let Some(interval) = settings.interval {
+            types::Duration::try_from(interval).map_err(|_| Error::DurationTooLarge)?;
+        }
+        self.0
+            .set_relay_rotation_settings(types::RelayRotationSettings::from(&settings))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn set_network_rules(&mut self, settings: NetworkRulesSettings) -> Result<()> {
+        self.0
+            .set_network_rules(types::NetworkRulesSettings::from(&settings))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn set_expiry_warning_settings(
+        &mut self,
+        settings: ExpiryWarningSettings,
+    ) -> Result<()> {
+        self.0
+            .set_expiry_warning_settings(types::ExpiryWarningSettings::from(&settings))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn get_current_network(&mut self) -> Result<NetworkIdentity> {
+        let network = self
+            .0
+            .get_current_network(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        NetworkIdentity::try_from(network).map_err(Error::InvalidResponse)
+    }
+
     pub async fn clear_all_relay_overrides(&mut self) -> Result<()> {
         self.0
             .clear_all_relay_overrides(())
@@ -489,6 +636,18 @@ impl MullvadProxyClient {
         VoucherSubmission::try_from(result).map_err(Error::InvalidResponse)
     }
 
+    pub async fn get_voucher_history(&mut self) -> Result<Vec<RedeemedVoucher>> {
+        self.0
+            .get_voucher_history(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner()
+            .vouchers
+            .into_iter()
+            .map(|voucher| RedeemedVoucher::try_from(voucher).map_err(Error::InvalidResponse))
+            .collect()
+    }
+
     pub async fn get_device(&mut self) -> Result<DeviceState> {
         let state = self
             .0
@@ -532,6 +691,24 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_device_label(&mut self, label: DeviceLabel) -> Result<()> {
+        self.0
+            .set_device_label(types::DeviceLabel::from(label))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn get_device_labels(&mut self) -> Result<Vec<DeviceLabel>> {
+        let labels = self
+            .0
+            .get_device_labels(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        Ok(labels.labels.into_iter().map(DeviceLabel::from).collect())
+    }
+
     pub async fn set_wireguard_rotation_interval(
         &mut self,
         interval: RotationInterval,
@@ -753,11 +930,100 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn diff_json_settings(&mut self, blob: String) -> Result<Vec<SettingsPatchChange>> {
+        let diff = self
+            .0
+            .diff_json_settings(blob)
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        Ok(diff
+            .changes
+            .into_iter()
+            .map(SettingsPatchChange::from)
+            .collect())
+    }
+
     pub async fn export_json_settings(&mut self) -> Result<String> {
         let blob = self.0.export_json_settings(()).await.map_err(Error::Rpc)?;
         Ok(blob.into_inner())
     }
 
+    pub async fn export_settings_backup(&mut self) -> Result<String> {
+        let blob = self
+            .0
+            .export_settings_backup(())
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(blob.into_inner())
+    }
+
+    pub async fn import_settings_backup(&mut self, blob: String) -> Result<()> {
+        self.0
+            .import_settings_backup(blob)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn create_settings_profile(&mut self, profile: SettingsProfile) -> Result<()> {
+        self.0
+            .create_settings_profile(types::SettingsProfile::from(&profile))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn get_settings_profiles(&mut self) -> Result<Vec<SettingsProfile>> {
+        self.0
+            .get_settings_profiles(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner()
+            .profiles
+            .into_iter()
+            .map(|profile| SettingsProfile::try_from(profile).map_err(Error::InvalidResponse))
+            .collect()
+    }
+
+    pub async fn apply_settings_profile(&mut self, name: String) -> Result<()> {
+        self.0
+            .apply_settings_profile(name)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn delete_settings_profile(&mut self, name: String) -> Result<()> {
+        self.0
+            .delete_settings_profile(name)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn get_settings_policy(&mut self) -> Result<SettingsPolicy> {
+        let policy = self
+            .0
+            .get_settings_policy(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        SettingsPolicy::try_from(policy).map_err(Error::InvalidResponse)
+    }
+
+    pub async fn get_event_hooks(&mut self) -> Result<Vec<EventHook>> {
+        self.0
+            .get_event_hooks(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner()
+            .hooks
+            .into_iter()
+            .map(|hook| EventHook::try_from(hook).map_err(Error::InvalidResponse))
+            .collect()
+    }
+
     pub async fn get_feature_indicators(&mut self) -> Result<FeatureIndicators> {
         self.0
             .get_feature_indicators(())
@@ -766,6 +1032,26 @@ impl MullvadProxyClient {
             .map(|response| response.into_inner())
             .map(FeatureIndicators::from)
     }
+
+    /// Measure latency and throughput through the tunnel, against the speed test server at
+    /// `endpoint`.
+    pub async fn run_speed_test(
+        &mut self,
+        endpoint: String,
+        duration: std::time::Duration,
+    ) -> Result<SpeedTestResult> {
+        let duration = types::Duration::try_from(duration).map_err(|_| Error::DurationTooLarge)?;
+        let result = self
+            .0
+            .run_speed_test(types::SpeedTestRequest {
+                endpoint,
+                duration: Some(duration),
+            })
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        SpeedTestResult::try_from(result).map_err(Error::InvalidResponse)
+    }
 }
 
 #[cfg(not(target_os = "android"))]
@@ -799,3 +1085,17 @@ fn map_custom_list_error(status: Status) -> Error {
         _other => Error::Rpc(status),
     }
 }
+
+#[cfg(not(target_os = "android"))]
+fn encrypted_dns_proxy_configs(
+    configs: types::EncryptedDnsProxyConfigList,
+) -> Result<Vec<access_method::EncryptedDnsProxyConfigInfo>> {
+    configs
+        .configs
+        .into_iter()
+        .map(|config| {
+            access_method::EncryptedDnsProxyConfigInfo::try_from(config)
+                .map_err(Error::InvalidResponse)
+        })
+        .collect()
+}
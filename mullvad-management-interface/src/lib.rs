@@ -1,9 +1,12 @@
+pub mod auth;
 pub mod client;
 pub mod types;
 
+use auth::{AuthorizationLayer, Role};
+#[cfg(not(target_os = "android"))]
 use parity_tokio_ipc::Endpoint as IpcEndpoint;
 #[cfg(unix)]
-use std::{env, fs, os::unix::fs::PermissionsExt};
+use std::{env, fs, os::unix::fs::PermissionsExt, path::PathBuf};
 use std::{
     future::Future,
     io,
@@ -142,33 +145,34 @@ pub fn spawn_rpc_server<T: ManagementService, F: Future<Output = ()> + Send + 's
     abort_rx: F,
     rpc_socket_path: impl AsRef<std::path::Path>,
 ) -> std::result::Result<ServerJoinHandle, Error> {
-    use futures::stream::TryStreamExt;
-    use parity_tokio_ipc::SecurityAttributes;
-
-    let mut endpoint = IpcEndpoint::new(rpc_socket_path.as_ref().to_string_lossy().to_string());
-    endpoint.set_security_attributes(
-        SecurityAttributes::allow_everyone_create()
-            .map_err(Error::SecurityAttributes)?
-            .set_mode(0o766)
-            .map_err(Error::SecurityAttributes)?,
-    );
-    let incoming = endpoint.incoming().map_err(Error::StartServerError)?;
-
     #[cfg(unix)]
-    if let Some(group_name) = &*MULLVAD_MANAGEMENT_SOCKET_GROUP {
-        let group = nix::unistd::Group::from_name(group_name)
-            .map_err(Error::ObtainGidError)?
-            .ok_or(Error::NoGidError)?;
-        nix::unistd::chown(rpc_socket_path.as_ref(), None, Some(group.gid))
-            .map_err(Error::SetGidError)?;
-        fs::set_permissions(rpc_socket_path, PermissionsExt::from_mode(0o760))
-            .map_err(Error::PermissionsError)?;
-    }
+    let incoming = UnixIncoming::bind(rpc_socket_path.as_ref(), auth::RoleMapping::from_env()?)?;
+
+    #[cfg(windows)]
+    let incoming = {
+        use futures::stream::TryStreamExt;
+        use parity_tokio_ipc::SecurityAttributes;
+
+        let mut endpoint = IpcEndpoint::new(rpc_socket_path.as_ref().to_string_lossy().to_string());
+        endpoint.set_security_attributes(
+            SecurityAttributes::allow_everyone_create()
+                .map_err(Error::SecurityAttributes)?
+                .set_mode(0o766)
+                .map_err(Error::SecurityAttributes)?,
+        );
+        // Access to the named pipe is restricted by its security attributes, so every client is
+        // an admin
+        endpoint
+            .incoming()
+            .map_err(Error::StartServerError)?
+            .map_ok(|stream| StreamBox(stream, Role::Admin))
+    };
 
     Ok(tokio::spawn(async move {
         if let Err(execution_error) = Server::builder()
+            .layer(AuthorizationLayer)
             .add_service(ManagementServiceServer::new(service))
-            .serve_with_incoming_shutdown(incoming.map_ok(StreamBox), abort_rx)
+            .serve_with_incoming_shutdown(incoming, abort_rx)
             .await
             .map_err(Error::GrpcTransportError)
         {
@@ -178,13 +182,82 @@ pub fn spawn_rpc_server<T: ManagementService, F: Future<Output = ()> + Send + 's
     }))
 }
 
+/// Allow everyone to connect to the socket at `path`, or only members of the group named by
+/// `MULLVAD_MANAGEMENT_SOCKET_GROUP` if it is set. What clients may do once connected is decided
+/// by [auth::RoleMapping].
+#[cfg(unix)]
+pub fn set_socket_permissions(path: &std::path::Path) -> Result<(), Error> {
+    match &*MULLVAD_MANAGEMENT_SOCKET_GROUP {
+        Some(group_name) => {
+            let group = nix::unistd::Group::from_name(group_name)
+                .map_err(Error::ObtainGidError)?
+                .ok_or(Error::NoGidError)?;
+            nix::unistd::chown(path, None, Some(group.gid)).map_err(Error::SetGidError)?;
+            fs::set_permissions(path, PermissionsExt::from_mode(0o760))
+                .map_err(Error::PermissionsError)
+        }
+        None => fs::set_permissions(path, PermissionsExt::from_mode(0o766))
+            .map_err(Error::PermissionsError),
+    }
+}
+
+/// Stream of connections to the management interface socket. Each connection is assigned a
+/// [Role] based on the credentials of the peer.
+///
+/// Removes the socket file when dropped.
+#[cfg(unix)]
+struct UnixIncoming {
+    path: PathBuf,
+    listener: tokio::net::UnixListener,
+    roles: auth::RoleMapping,
+}
+
+#[cfg(unix)]
+impl UnixIncoming {
+    fn bind(path: &std::path::Path, roles: auth::RoleMapping) -> Result<Self, Error> {
+        let listener = tokio::net::UnixListener::bind(path).map_err(Error::StartServerError)?;
+        let incoming = UnixIncoming {
+            path: path.to_owned(),
+            listener,
+            roles,
+        };
+        set_socket_permissions(path)?;
+        Ok(incoming)
+    }
+}
+
+#[cfg(unix)]
+impl futures::Stream for UnixIncoming {
+    type Item = io::Result<StreamBox<tokio::net::UnixStream>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = Pin::into_inner(self);
+        this.listener.poll_accept(cx).map(|result| {
+            Some(result.map(|(stream, _addr)| {
+                let role = this.roles.role(&stream);
+                StreamBox(stream, role)
+            }))
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixIncoming {
+    fn drop(&mut self) {
+        if let Ok(()) = fs::remove_file(&self.path) {
+            log::trace!("Removed socket file at: {}", self.path.display())
+        }
+    }
+}
+
+/// Connection to the management interface, and the [Role] of the client.
 #[derive(Debug)]
-struct StreamBox<T: AsyncRead + AsyncWrite>(pub T);
+struct StreamBox<T: AsyncRead + AsyncWrite>(pub T, Role);
 impl<T: AsyncRead + AsyncWrite> Connected for StreamBox<T> {
-    type ConnectInfo = Option<()>;
+    type ConnectInfo = Role;
 
     fn connect_info(&self) -> Self::ConnectInfo {
-        None
+        self.1
     }
 }
 impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for StreamBox<T> {
@@ -11,6 +11,18 @@ mod settings {
                 direct: Some(settings.direct().clone().into()),
                 mullvad_bridges: Some(settings.mullvad_bridges().clone().into()),
                 encrypted_dns_proxy: Some(settings.encrypted_dns_proxy().clone().into()),
+                system_proxy: Some(settings.system_proxy().clone().into()),
+                pac_url: settings.pac_url().map(str::to_owned),
+                domain_fronting: Some(settings.domain_fronting().clone().into()),
+                domain_fronts: settings.domain_fronts().to_vec(),
+                tor: Some(settings.tor().clone().into()),
+                tor_settings: Some(settings.tor_settings().clone().into()),
+                doh_resolvers: settings
+                    .doh_resolvers()
+                    .iter()
+                    .cloned()
+                    .map(proto::DohResolver::from)
+                    .collect(),
                 custom: settings
                     .iter_custom()
                     .cloned()
@@ -45,22 +57,156 @@ mod settings {
                 ))
                 .and_then(access_method::AccessMethodSetting::try_from)?;
 
+            let system_proxy = settings
+                .system_proxy
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "Could not deserialize System proxy Access Method from protobuf",
+                ))
+                .and_then(access_method::AccessMethodSetting::try_from)?;
+
+            let domain_fronting = settings
+                .domain_fronting
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "Could not deserialize Domain fronting Access Method from protobuf",
+                ))
+                .and_then(access_method::AccessMethodSetting::try_from)?;
+
+            let tor = settings
+                .tor
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "Could not deserialize Tor Access Method from protobuf",
+                ))
+                .and_then(access_method::AccessMethodSetting::try_from)?;
+
             let custom = settings
                 .custom
                 .iter()
                 .map(access_method::AccessMethodSetting::try_from)
                 .collect::<Result<Vec<_>, _>>()?;
 
+            let doh_resolvers = settings
+                .doh_resolvers
+                .into_iter()
+                .map(access_method::DohResolver::try_from)
+                .collect::<Result<Vec<_>, _>>()?;
+
             Ok(access_method::Settings::new(
                 direct,
                 mullvad_bridges,
                 encrypted_dns_proxy,
+                system_proxy,
+                domain_fronting,
+                tor,
                 custom,
+                settings.pac_url,
+                settings.domain_fronts,
+                doh_resolvers,
+                settings
+                    .tor_settings
+                    .map(access_method::TorSettings::from)
+                    .unwrap_or_default(),
             ))
         }
     }
 }
 
+/// Implements conversions for the Encrypted DNS proxy resolvers and cached configs.
+mod encrypted_dns_proxy {
+    use crate::types::{proto, FromProtobufTypeError, Timestamp};
+    use chrono::{DateTime, Utc};
+    use mullvad_types::access_method::{DohResolver, EncryptedDnsProxyConfigInfo};
+
+    impl From<DohResolver> for proto::DohResolver {
+        fn from(resolver: DohResolver) -> Self {
+            proto::DohResolver {
+                name: resolver.name,
+                addrs: resolver.addrs.iter().map(|addr| addr.to_string()).collect(),
+            }
+        }
+    }
+
+    impl TryFrom<proto::DohResolver> for DohResolver {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(resolver: proto::DohResolver) -> Result<Self, Self::Error> {
+            let addrs = resolver
+                .addrs
+                .iter()
+                .map(|addr| addr.parse())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid resolver address"))?;
+            Ok(DohResolver {
+                name: resolver.name,
+                addrs,
+            })
+        }
+    }
+
+    fn to_timestamp(time: DateTime<Utc>) -> Timestamp {
+        Timestamp {
+            seconds: time.timestamp(),
+            nanos: time.timestamp_subsec_nanos() as i32,
+        }
+    }
+
+    fn from_timestamp(timestamp: Timestamp) -> Result<DateTime<Utc>, FromProtobufTypeError> {
+        DateTime::from_timestamp(timestamp.seconds, timestamp.nanos as u32)
+            .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))
+    }
+
+    impl From<EncryptedDnsProxyConfigInfo> for proto::EncryptedDnsProxyConfig {
+        fn from(config: EncryptedDnsProxyConfigInfo) -> Self {
+            proto::EncryptedDnsProxyConfig {
+                endpoint: config.endpoint.to_string(),
+                obfuscation: config.obfuscation,
+                fetched: Some(to_timestamp(config.fetched)),
+                last_success: config.last_success.map(to_timestamp),
+                expires: Some(to_timestamp(config.expires)),
+            }
+        }
+    }
+
+    impl TryFrom<proto::EncryptedDnsProxyConfig> for EncryptedDnsProxyConfigInfo {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(config: proto::EncryptedDnsProxyConfig) -> Result<Self, Self::Error> {
+            let missing = || FromProtobufTypeError::InvalidArgument("missing timestamp");
+            Ok(EncryptedDnsProxyConfigInfo {
+                endpoint: config
+                    .endpoint
+                    .parse()
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid endpoint"))?,
+                obfuscation: config.obfuscation,
+                fetched: from_timestamp(config.fetched.ok_or_else(missing)?)?,
+                last_success: config.last_success.map(from_timestamp).transpose()?,
+                expires: from_timestamp(config.expires.ok_or_else(missing)?)?,
+            })
+        }
+    }
+}
+
+/// Implements conversions for the bridge of the Tor access method.
+mod tor {
+    use crate::types::proto;
+    use mullvad_types::access_method::TorSettings;
+
+    impl From<TorSettings> for proto::TorSettings {
+        fn from(settings: TorSettings) -> Self {
+            proto::TorSettings {
+                bridge: settings.bridge,
+            }
+        }
+    }
+
+    impl From<proto::TorSettings> for TorSettings {
+        fn from(settings: proto::TorSettings) -> Self {
+            TorSettings {
+                bridge: settings.bridge,
+            }
+        }
+    }
+}
+
 /// Implements conversions for the auxiliary
 /// [`crate::types::proto::AccessMethodSetting`] type to the internal
 /// [`mullvad_types::access_method::AccessMethodSetting`] data type.
@@ -130,6 +276,11 @@ mod data {
                 proto::access_method::AccessMethod::EncryptedDnsProxy(proxy) => {
                     AccessMethod::from(proxy)
                 }
+                proto::access_method::AccessMethod::SystemProxy(proxy) => AccessMethod::from(proxy),
+                proto::access_method::AccessMethod::DomainFronting(fronting) => {
+                    AccessMethod::from(fronting)
+                }
+                proto::access_method::AccessMethod::Tor(tor) => AccessMethod::from(tor),
                 proto::access_method::AccessMethod::Custom(custom) => {
                     CustomProxy::try_from(custom).map(AccessMethod::from)?
                 }
@@ -164,6 +315,24 @@ mod data {
         }
     }
 
+    impl From<proto::access_method::SystemProxy> for AccessMethod {
+        fn from(_value: proto::access_method::SystemProxy) -> Self {
+            AccessMethod::from(BuiltInAccessMethod::SystemProxy)
+        }
+    }
+
+    impl From<proto::access_method::DomainFronting> for AccessMethod {
+        fn from(_value: proto::access_method::DomainFronting) -> Self {
+            AccessMethod::from(BuiltInAccessMethod::DomainFronting)
+        }
+    }
+
+    impl From<proto::access_method::Tor> for AccessMethod {
+        fn from(_value: proto::access_method::Tor) -> Self {
+            AccessMethod::from(BuiltInAccessMethod::Tor)
+        }
+    }
+
     impl TryFrom<proto::Socks5Local> for AccessMethod {
         type Error = FromProtobufTypeError;
 
@@ -210,6 +379,19 @@ mod data {
                         proto::access_method::EncryptedDnsProxy {},
                     )
                 }
+                mullvad_types::access_method::BuiltInAccessMethod::SystemProxy => {
+                    proto::access_method::AccessMethod::SystemProxy(
+                        proto::access_method::SystemProxy {},
+                    )
+                }
+                mullvad_types::access_method::BuiltInAccessMethod::DomainFronting => {
+                    proto::access_method::AccessMethod::DomainFronting(
+                        proto::access_method::DomainFronting {},
+                    )
+                }
+                mullvad_types::access_method::BuiltInAccessMethod::Tor => {
+                    proto::access_method::AccessMethod::Tor(proto::access_method::Tor {})
+                }
             }
         }
     }
@@ -260,3 +442,91 @@ mod data {
         }
     }
 }
+
+/// Convert the statistics of access methods to and from protobuf.
+mod statistics {
+    use crate::types::{proto, FromProtobufTypeError, Timestamp};
+    use chrono::{DateTime, Utc};
+    use mullvad_types::access_method::{AccessMethodStatistics, AccessMethodStatus, Id};
+
+    impl From<AccessMethodStatus> for proto::AccessMethodStatus {
+        fn from(status: AccessMethodStatus) -> Self {
+            proto::AccessMethodStatus {
+                id: Some(proto::Uuid::from(status.id)),
+                name: status.name,
+                enabled: status.enabled,
+                active: status.active,
+                statistics: Some(proto::AccessMethodStatistics::from(status.statistics)),
+            }
+        }
+    }
+
+    impl From<AccessMethodStatistics> for proto::AccessMethodStatistics {
+        fn from(statistics: AccessMethodStatistics) -> Self {
+            let to_timestamp = |time: DateTime<Utc>| Timestamp {
+                seconds: time.timestamp(),
+                nanos: time.timestamp_subsec_nanos() as i32,
+            };
+            proto::AccessMethodStatistics {
+                successes: statistics.successes,
+                failures: statistics.failures,
+                consecutive_failures: statistics.consecutive_failures,
+                last_success: statistics.last_success.map(to_timestamp),
+                last_failure: statistics.last_failure.map(to_timestamp),
+                latency: statistics.latency.map(|latency| {
+                    prost_types::Duration::try_from(latency)
+                        .expect("Failed to convert std::time::Duration to prost_types::Duration")
+                }),
+            }
+        }
+    }
+
+    impl TryFrom<proto::AccessMethodStatus> for AccessMethodStatus {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(status: proto::AccessMethodStatus) -> Result<Self, Self::Error> {
+            let id = status
+                .id
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "missing access method id",
+                ))
+                .and_then(Id::try_from)?;
+            let statistics = status
+                .statistics
+                .map(AccessMethodStatistics::try_from)
+                .transpose()?
+                .unwrap_or_default();
+            Ok(AccessMethodStatus {
+                id,
+                name: status.name,
+                enabled: status.enabled,
+                active: status.active,
+                statistics,
+            })
+        }
+    }
+
+    impl TryFrom<proto::AccessMethodStatistics> for AccessMethodStatistics {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(statistics: proto::AccessMethodStatistics) -> Result<Self, Self::Error> {
+            let from_timestamp = |timestamp: Timestamp| {
+                DateTime::from_timestamp(timestamp.seconds, timestamp.nanos as u32)
+                    .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))
+            };
+            let latency = statistics
+                .latency
+                .map(std::time::Duration::try_from)
+                .transpose()
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid duration"))?;
+            Ok(AccessMethodStatistics {
+                successes: statistics.successes,
+                failures: statistics.failures,
+                consecutive_failures: statistics.consecutive_failures,
+                last_success: statistics.last_success.map(from_timestamp).transpose()?,
+                last_failure: statistics.last_failure.map(from_timestamp).transpose()?,
+                latency,
+            })
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use crate::types;
 use chrono::DateTime;
-use mullvad_types::account::{AccountData, VoucherSubmission};
+use mullvad_types::account::{
+    AccountData, AccountExpiryWarning, RedeemedVoucher, VoucherSubmission,
+};
 #[cfg(target_os = "android")]
 use mullvad_types::account::{PlayPurchase, PlayPurchasePaymentToken};
 
@@ -44,6 +46,10 @@ impl From<AccountData> for types::AccountData {
                 seconds: data.expiry.timestamp(),
                 nanos: 0,
             }),
+            cached_at: data.cached_at.map(|cached_at| types::Timestamp {
+                seconds: cached_at.timestamp(),
+                nanos: 0,
+            }),
         }
     }
 }
@@ -59,13 +65,91 @@ impl TryFrom<types::AccountData> for AccountData {
         let expiry = DateTime::from_timestamp(expiry.seconds, expiry.nanos as u32)
             .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))?;
 
+        let cached_at = data
+            .cached_at
+            .map(|cached_at| {
+                DateTime::from_timestamp(cached_at.seconds, cached_at.nanos as u32)
+                    .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))
+            })
+            .transpose()?;
+
         Ok(AccountData {
             id: data.id,
             expiry,
+            cached_at,
+        })
+    }
+}
+
+impl From<RedeemedVoucher> for types::RedeemedVoucher {
+    fn from(voucher: RedeemedVoucher) -> Self {
+        types::RedeemedVoucher {
+            redeemed: Some(types::Timestamp {
+                seconds: voucher.redeemed.timestamp(),
+                nanos: 0,
+            }),
+            seconds_added: voucher.time_added,
+            new_expiry: Some(types::Timestamp {
+                seconds: voucher.new_expiry.timestamp(),
+                nanos: 0,
+            }),
+        }
+    }
+}
+
+impl TryFrom<types::RedeemedVoucher> for RedeemedVoucher {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(voucher: types::RedeemedVoucher) -> Result<Self, FromProtobufTypeError> {
+        let timestamp = |timestamp: Option<types::Timestamp>| {
+            let timestamp =
+                timestamp.ok_or(FromProtobufTypeError::InvalidArgument("missing timestamp"))?;
+            DateTime::from_timestamp(timestamp.seconds, timestamp.nanos as u32)
+                .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))
+        };
+
+        Ok(RedeemedVoucher {
+            redeemed: timestamp(voucher.redeemed)?,
+            time_added: voucher.seconds_added,
+            new_expiry: timestamp(voucher.new_expiry)?,
         })
     }
 }
 
+impl From<AccountExpiryWarning> for types::AccountExpiryWarning {
+    fn from(warning: AccountExpiryWarning) -> Self {
+        types::AccountExpiryWarning {
+            expiry: Some(types::Timestamp {
+                seconds: warning.expiry.timestamp(),
+                nanos: 0,
+            }),
+            threshold: Some(
+                prost_types::Duration::try_from(warning.threshold)
+                    .expect("Failed to convert std::time::Duration to prost_types::Duration"),
+            ),
+        }
+    }
+}
+
+impl TryFrom<types::AccountExpiryWarning> for AccountExpiryWarning {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(warning: types::AccountExpiryWarning) -> Result<Self, FromProtobufTypeError> {
+        let expiry = warning
+            .expiry
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing expiry"))?;
+        let expiry = DateTime::from_timestamp(expiry.seconds, expiry.nanos as u32)
+            .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))?;
+        let threshold = warning
+            .threshold
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing threshold"))?;
+        let threshold = std::time::Duration::try_from(threshold)
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid duration"))?;
+
+        Ok(AccountExpiryWarning { expiry, threshold })
+    }
+}
+
 #[cfg(target_os = "android")]
 impl TryFrom<types::PlayPurchase> for PlayPurchase {
     type Error = FromProtobufTypeError;
@@ -193,3 +193,23 @@ impl From<Vec<mullvad_types::device::Device>> for proto::DeviceList {
         }
     }
 }
+
+impl From<mullvad_types::device::DeviceLabel> for proto::DeviceLabel {
+    fn from(label: mullvad_types::device::DeviceLabel) -> Self {
+        proto::DeviceLabel {
+            device_id: label.device_id,
+            label: label.label,
+            note: label.note,
+        }
+    }
+}
+
+impl From<proto::DeviceLabel> for mullvad_types::device::DeviceLabel {
+    fn from(label: proto::DeviceLabel) -> Self {
+        mullvad_types::device::DeviceLabel {
+            device_id: label.device_id,
+            label: label.label,
+            note: label.note,
+        }
+    }
+}
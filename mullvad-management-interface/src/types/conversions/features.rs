@@ -18,6 +18,7 @@ impl From<mullvad_types::features::FeatureIndicator> for proto::FeatureIndicator
             mullvad_types::features::FeatureIndicator::CustomMtu => CustomMtu,
             mullvad_types::features::FeatureIndicator::CustomMssFix => CustomMssFix,
             mullvad_types::features::FeatureIndicator::Daita => Daita,
+            mullvad_types::features::FeatureIndicator::RelayRotation => RelayRotation,
         }
     }
 }
@@ -39,6 +40,7 @@ impl From<proto::FeatureIndicator> for mullvad_types::features::FeatureIndicator
             proto::FeatureIndicator::CustomMtu => Self::CustomMtu,
             proto::FeatureIndicator::CustomMssFix => Self::CustomMssFix,
             proto::FeatureIndicator::Daita => Self::Daita,
+            proto::FeatureIndicator::RelayRotation => Self::RelayRotation,
         }
     }
 }
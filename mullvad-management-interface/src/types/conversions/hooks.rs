@@ -0,0 +1,53 @@
+use crate::types::{proto, FromProtobufTypeError};
+use mullvad_types::hooks::{EventHook, HookEvent};
+
+impl From<&EventHook> for proto::EventHook {
+    fn from(hook: &EventHook) -> Self {
+        Self {
+            path: hook.path.to_string_lossy().into_owned(),
+            events: hook
+                .events
+                .iter()
+                .map(|event| proto::event_hook::Event::from(*event) as i32)
+                .collect(),
+            timeout_secs: hook.timeout_secs,
+        }
+    }
+}
+
+impl From<HookEvent> for proto::event_hook::Event {
+    fn from(event: HookEvent) -> Self {
+        match event {
+            HookEvent::TunnelState => Self::TunnelState,
+            HookEvent::Device => Self::Device,
+            HookEvent::Settings => Self::Settings,
+            HookEvent::AccountExpiryWarning => Self::AccountExpiryWarning,
+        }
+    }
+}
+
+impl TryFrom<proto::EventHook> for EventHook {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(hook: proto::EventHook) -> Result<Self, Self::Error> {
+        Ok(Self {
+            path: hook.path.into(),
+            events: hook
+                .events
+                .into_iter()
+                .map(try_hook_event_from_i32)
+                .collect::<Result<_, _>>()?,
+            timeout_secs: hook.timeout_secs,
+        })
+    }
+}
+
+fn try_hook_event_from_i32(event: i32) -> Result<HookEvent, FromProtobufTypeError> {
+    match proto::event_hook::Event::try_from(event) {
+        Ok(proto::event_hook::Event::TunnelState) => Ok(HookEvent::TunnelState),
+        Ok(proto::event_hook::Event::Device) => Ok(HookEvent::Device),
+        Ok(proto::event_hook::Event::Settings) => Ok(HookEvent::Settings),
+        Ok(proto::event_hook::Event::AccountExpiryWarning) => Ok(HookEvent::AccountExpiryWarning),
+        Err(_) => Err(FromProtobufTypeError::InvalidArgument("invalid hook event")),
+    }
+}
@@ -6,11 +6,16 @@ mod custom_list;
 mod custom_tunnel;
 mod device;
 mod features;
+mod hooks;
 mod location;
 mod net;
+mod network_rules;
+mod policy;
+mod profile;
 pub mod relay_constraints;
 mod relay_list;
 mod settings;
+mod speed_test;
 #[cfg(target_os = "windows")]
 mod split_tunnel;
 mod states;
@@ -46,6 +46,7 @@ impl From<talpid_types::net::TunnelEndpoint> for proto::TunnelEndpoint {
                 address: entry.address.to_string(),
                 protocol: i32::from(proto::TransportProtocol::from(entry.protocol)),
             }),
+            ip_version: i32::from(proto::IpVersion::from(endpoint.ip_version)),
             tunnel_metadata: endpoint
                 .tunnel_interface
                 .map(|tunnel_interface| proto::TunnelMetadata { tunnel_interface }),
@@ -141,6 +142,9 @@ impl TryFrom<proto::TunnelEndpoint> for talpid_types::net::TunnelEndpoint {
                     })
                 })
                 .transpose()?,
+            ip_version: proto::IpVersion::try_from(endpoint.ip_version)
+                .map(talpid_net::IpVersion::from)
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid IP version"))?,
             tunnel_interface: endpoint
                 .tunnel_metadata
                 .map(|tunnel_metadata| tunnel_metadata.tunnel_interface),
@@ -207,11 +211,11 @@ pub fn try_transport_protocol_from_i32(
 }
 
 mod proxy {
-    use std::net::Ipv4Addr;
+    use std::net::{IpAddr, Ipv4Addr};
 
     use crate::types::{proto, FromProtobufTypeError};
     use talpid_types::net::proxy::{
-        CustomProxy, Shadowsocks, Socks5Local, Socks5Remote, SocksAuth,
+        CustomProxy, HttpAuth, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote, SocksAuth,
     };
 
     impl TryFrom<proto::CustomProxy> for CustomProxy {
@@ -228,6 +232,9 @@ mod proxy {
                 Some(proto::custom_proxy::ProxyMethod::Shadowsocks(shadowsocks)) => {
                     CustomProxy::Shadowsocks(Shadowsocks::try_from(shadowsocks)?)
                 }
+                Some(proto::custom_proxy::ProxyMethod::HttpConnect(http)) => {
+                    CustomProxy::HttpConnect(HttpConnect::try_from(http)?)
+                }
                 None => {
                     return Err(FromProtobufTypeError::InvalidArgument(
                         "CustomProxy missing proxy_method field",
@@ -295,6 +302,24 @@ mod proxy {
         }
     }
 
+    impl TryFrom<proto::HttpConnect> for HttpConnect {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(value: proto::HttpConnect) -> Result<Self, Self::Error> {
+            let ip = value.ip.parse::<IpAddr>().map_err(|_| {
+                FromProtobufTypeError::InvalidArgument(
+                    "Could not parse HTTP CONNECT message from protobuf",
+                )
+            })?;
+            let auth = value.auth.map(HttpAuth::try_from).transpose()?;
+            Ok(HttpConnect::new(
+                (ip, value.port as u16),
+                value.tls_server_name,
+                auth,
+            ))
+        }
+    }
+
     impl From<CustomProxy> for proto::CustomProxy {
         fn from(value: CustomProxy) -> Self {
             proto::CustomProxy {
@@ -314,6 +339,11 @@ mod proxy {
                             config,
                         ))
                     }
+                    CustomProxy::HttpConnect(config) => {
+                        proto::custom_proxy::ProxyMethod::HttpConnect(proto::HttpConnect::from(
+                            config,
+                        ))
+                    }
                 }),
             }
         }
@@ -353,6 +383,39 @@ mod proxy {
         }
     }
 
+    impl From<HttpConnect> for proto::HttpConnect {
+        fn from(value: HttpConnect) -> Self {
+            proto::HttpConnect {
+                ip: value.endpoint.ip().to_string(),
+                port: value.endpoint.port() as u32,
+                tls_server_name: value.tls_server_name,
+                auth: value.auth.map(proto::HttpAuth::from),
+            }
+        }
+    }
+
+    impl From<HttpAuth> for proto::HttpAuth {
+        fn from(value: HttpAuth) -> Self {
+            proto::HttpAuth {
+                username: value.username().to_string(),
+                password: value.password().to_string(),
+            }
+        }
+    }
+
+    impl TryFrom<proto::HttpAuth> for HttpAuth {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(value: proto::HttpAuth) -> Result<Self, Self::Error> {
+            HttpAuth::new(value.username, value.password).map_err(|_| {
+                FromProtobufTypeError::InvalidArgument(
+                    "Failed to parse HTTP proxy with authentication. \
+                     Make sure the credentials are valid.",
+                )
+            })
+        }
+    }
+
     impl From<SocksAuth> for proto::SocksAuth {
         fn from(value: SocksAuth) -> Self {
             proto::SocksAuth {
@@ -0,0 +1,190 @@
+use crate::types::{proto, FromProtobufTypeError};
+use mullvad_types::network_rules::{
+    NetworkAction, NetworkMatcher, NetworkRule, NetworkRulesSettings,
+};
+use talpid_types::net::NetworkIdentity;
+
+impl From<&NetworkRulesSettings> for proto::NetworkRulesSettings {
+    fn from(settings: &NetworkRulesSettings) -> Self {
+        Self {
+            rules: settings
+                .rules
+                .iter()
+                .map(proto::NetworkRule::from)
+                .collect(),
+            known_networks: settings
+                .known_networks
+                .iter()
+                .map(proto::NetworkMatcher::from)
+                .collect(),
+            other_networks: settings
+                .other_networks
+                .map(|action| i32::from(proto::network_rule::Action::from(action))),
+            unknown_networks: settings
+                .unknown_networks
+                .map(|action| i32::from(proto::network_rule::Action::from(action))),
+            allow_disconnect: settings.allow_disconnect,
+        }
+    }
+}
+
+impl TryFrom<proto::NetworkRulesSettings> for NetworkRulesSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: proto::NetworkRulesSettings) -> Result<Self, Self::Error> {
+        Ok(Self {
+            rules: settings
+                .rules
+                .into_iter()
+                .map(NetworkRule::try_from)
+                .collect::<Result<_, _>>()?,
+            known_networks: settings
+                .known_networks
+                .into_iter()
+                .map(NetworkMatcher::try_from)
+                .collect::<Result<_, _>>()?,
+            other_networks: settings
+                .other_networks
+                .map(try_network_action_from_i32)
+                .transpose()?,
+            unknown_networks: settings
+                .unknown_networks
+                .map(try_network_action_from_i32)
+                .transpose()?,
+            allow_disconnect: settings.allow_disconnect,
+        })
+    }
+}
+
+impl From<&NetworkRule> for proto::NetworkRule {
+    fn from(rule: &NetworkRule) -> Self {
+        use proto::network_rule::Matcher;
+
+        let matcher = match &rule.matcher {
+            NetworkMatcher::ConnectionId(id) => Matcher::ConnectionId(id.clone()),
+            NetworkMatcher::Ssid(ssid) => Matcher::Ssid(ssid.clone()),
+            NetworkMatcher::GatewayMac(mac) => Matcher::GatewayMac(mac.clone()),
+            NetworkMatcher::Subnet(subnet) => Matcher::Subnet(subnet.to_string()),
+        };
+        Self {
+            matcher: Some(matcher),
+            action: i32::from(proto::network_rule::Action::from(rule.action)),
+        }
+    }
+}
+
+impl TryFrom<proto::NetworkRule> for NetworkRule {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(rule: proto::NetworkRule) -> Result<Self, Self::Error> {
+        use proto::network_rule::Matcher;
+
+        let matcher = match rule.matcher {
+            Some(Matcher::ConnectionId(id)) => NetworkMatcher::ConnectionId(id),
+            Some(Matcher::Ssid(ssid)) => NetworkMatcher::Ssid(ssid),
+            Some(Matcher::GatewayMac(mac)) => NetworkMatcher::GatewayMac(mac),
+            Some(Matcher::Subnet(subnet)) => NetworkMatcher::Subnet(
+                subnet
+                    .parse()
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid subnet"))?,
+            ),
+            None => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "missing network rule matcher",
+                ))
+            }
+        };
+        Ok(Self {
+            matcher,
+            action: try_network_action_from_i32(rule.action)?,
+        })
+    }
+}
+
+impl From<&NetworkMatcher> for proto::NetworkMatcher {
+    fn from(matcher: &NetworkMatcher) -> Self {
+        use proto::network_matcher::Matcher;
+
+        let matcher = match matcher {
+            NetworkMatcher::ConnectionId(id) => Matcher::ConnectionId(id.clone()),
+            NetworkMatcher::Ssid(ssid) => Matcher::Ssid(ssid.clone()),
+            NetworkMatcher::GatewayMac(mac) => Matcher::GatewayMac(mac.clone()),
+            NetworkMatcher::Subnet(subnet) => Matcher::Subnet(subnet.to_string()),
+        };
+        Self {
+            matcher: Some(matcher),
+        }
+    }
+}
+
+impl TryFrom<proto::NetworkMatcher> for NetworkMatcher {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(matcher: proto::NetworkMatcher) -> Result<Self, Self::Error> {
+        use proto::network_matcher::Matcher;
+
+        match matcher.matcher {
+            Some(Matcher::ConnectionId(id)) => Ok(NetworkMatcher::ConnectionId(id)),
+            Some(Matcher::Ssid(ssid)) => Ok(NetworkMatcher::Ssid(ssid)),
+            Some(Matcher::GatewayMac(mac)) => Ok(NetworkMatcher::GatewayMac(mac)),
+            Some(Matcher::Subnet(subnet)) => subnet
+                .parse()
+                .map(NetworkMatcher::Subnet)
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid subnet")),
+            None => Err(FromProtobufTypeError::InvalidArgument(
+                "missing network matcher",
+            )),
+        }
+    }
+}
+
+impl From<NetworkAction> for proto::network_rule::Action {
+    fn from(action: NetworkAction) -> Self {
+        match action {
+            NetworkAction::Connect => proto::network_rule::Action::Connect,
+            NetworkAction::Disconnect => proto::network_rule::Action::Disconnect,
+            NetworkAction::Block => proto::network_rule::Action::Block,
+        }
+    }
+}
+
+fn try_network_action_from_i32(action: i32) -> Result<NetworkAction, FromProtobufTypeError> {
+    match proto::network_rule::Action::try_from(action) {
+        Ok(proto::network_rule::Action::Connect) => Ok(NetworkAction::Connect),
+        Ok(proto::network_rule::Action::Disconnect) => Ok(NetworkAction::Disconnect),
+        Ok(proto::network_rule::Action::Block) => Ok(NetworkAction::Block),
+        Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+            "invalid network action",
+        )),
+    }
+}
+
+impl From<NetworkIdentity> for proto::NetworkIdentity {
+    fn from(network: NetworkIdentity) -> Self {
+        Self {
+            interface: network.interface,
+            gateway: network.gateway.map(|gateway| gateway.to_string()),
+            gateway_mac: network.gateway_mac,
+            connection_id: network.connection_id,
+            ssid: network.ssid,
+        }
+    }
+}
+
+impl TryFrom<proto::NetworkIdentity> for NetworkIdentity {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(network: proto::NetworkIdentity) -> Result<Self, Self::Error> {
+        Ok(Self {
+            interface: network.interface,
+            gateway: network
+                .gateway
+                .map(|gateway| gateway.parse())
+                .transpose()
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid gateway address"))?,
+            gateway_mac: network.gateway_mac,
+            connection_id: network.connection_id,
+            ssid: network.ssid,
+        })
+    }
+}
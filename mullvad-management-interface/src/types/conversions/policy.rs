@@ -0,0 +1,37 @@
+use crate::types::{proto, FromProtobufTypeError};
+use mullvad_types::{
+    policy::SettingsPolicy, relay_constraints::GeographicLocationConstraint, settings::DnsOptions,
+};
+
+impl From<&SettingsPolicy> for proto::SettingsPolicy {
+    fn from(policy: &SettingsPolicy) -> Self {
+        Self {
+            block_when_disconnected: policy.block_when_disconnected,
+            allow_lan: policy.allow_lan,
+            dns_options: policy.dns_options.as_ref().map(proto::DnsOptions::from),
+            allowed_locations: policy
+                .allowed_locations
+                .iter()
+                .cloned()
+                .map(proto::GeographicLocationConstraint::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::SettingsPolicy> for SettingsPolicy {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(policy: proto::SettingsPolicy) -> Result<Self, Self::Error> {
+        Ok(Self {
+            block_when_disconnected: policy.block_when_disconnected,
+            allow_lan: policy.allow_lan,
+            dns_options: policy.dns_options.map(DnsOptions::try_from).transpose()?,
+            allowed_locations: policy
+                .allowed_locations
+                .into_iter()
+                .map(GeographicLocationConstraint::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
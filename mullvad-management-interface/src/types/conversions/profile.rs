@@ -0,0 +1,51 @@
+use crate::types::{proto, FromProtobufTypeError};
+use mullvad_types::{
+    profile::SettingsProfile,
+    relay_constraints::{BridgeState, ObfuscationSettings, RelaySettings},
+    settings::DnsOptions,
+};
+
+impl From<&SettingsProfile> for proto::SettingsProfile {
+    fn from(profile: &SettingsProfile) -> Self {
+        Self {
+            name: profile.name.clone(),
+            relay_settings: profile
+                .relay_settings
+                .clone()
+                .map(proto::RelaySettings::from),
+            obfuscation_settings: profile
+                .obfuscation_settings
+                .as_ref()
+                .map(proto::ObfuscationSettings::from),
+            bridge_state: profile.bridge_state.map(proto::BridgeState::from),
+            allow_lan: profile.allow_lan,
+            block_when_disconnected: profile.block_when_disconnected,
+            dns_options: profile.dns_options.as_ref().map(proto::DnsOptions::from),
+        }
+    }
+}
+
+impl TryFrom<proto::SettingsProfile> for SettingsProfile {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(profile: proto::SettingsProfile) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: profile.name,
+            relay_settings: profile
+                .relay_settings
+                .map(RelaySettings::try_from)
+                .transpose()?,
+            obfuscation_settings: profile
+                .obfuscation_settings
+                .map(ObfuscationSettings::try_from)
+                .transpose()?,
+            bridge_state: profile
+                .bridge_state
+                .map(BridgeState::try_from)
+                .transpose()?,
+            allow_lan: profile.allow_lan,
+            block_when_disconnected: profile.block_when_disconnected,
+            dns_options: profile.dns_options.map(DnsOptions::try_from).transpose()?,
+        })
+    }
+}
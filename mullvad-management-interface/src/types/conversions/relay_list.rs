@@ -56,6 +56,7 @@ impl From<mullvad_types::relay_list::BridgeEndpointData> for proto::BridgeEndpoi
                     protocol: proto::TransportProtocol::from(endpoint.protocol) as i32,
                 })
                 .collect(),
+            domain_fronts: bridge.domain_fronts,
         }
     }
 }
@@ -180,6 +181,7 @@ impl TryFrom<proto::RelayList> for mullvad_types::relay_list::RelayList {
 
         Ok(mullvad_types::relay_list::RelayList {
             etag: None,
+            created: None,
             countries,
             openvpn: mullvad_types::relay_list::OpenVpnEndpointData::try_from(openvpn)?,
             bridge: mullvad_types::relay_list::BridgeEndpointData::try_from(bridge)?,
@@ -351,7 +353,10 @@ impl TryFrom<proto::BridgeEndpointData> for mullvad_types::relay_list::BridgeEnd
             .map(mullvad_types::relay_list::ShadowsocksEndpointData::try_from)
             .collect::<Result<Vec<_>, FromProtobufTypeError>>()?;
 
-        Ok(mullvad_types::relay_list::BridgeEndpointData { shadowsocks })
+        Ok(mullvad_types::relay_list::BridgeEndpointData {
+            shadowsocks,
+            domain_fronts: bridge.domain_fronts,
+        })
     }
 }
 
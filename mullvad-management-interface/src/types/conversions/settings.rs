@@ -54,10 +54,91 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
                 .cloned()
                 .map(proto::RelayOverride::from)
                 .collect(),
+            relay_rotation: Some(proto::RelayRotationSettings::from(&settings.relay_rotation)),
+            network_rules: Some(proto::NetworkRulesSettings::from(&settings.network_rules)),
+            expiry_warnings: Some(proto::ExpiryWarningSettings::from(
+                &settings.expiry_warnings,
+            )),
+        }
+    }
+}
+
+impl From<&mullvad_types::settings::RelayRotationSettings> for proto::RelayRotationSettings {
+    fn from(settings: &mullvad_types::settings::RelayRotationSettings) -> Self {
+        Self {
+            interval: settings.interval.map(|interval| {
+                prost_types::Duration::try_from(interval)
+                    .expect("Failed to convert std::time::Duration to prost_types::Duration for relay_rotation.interval")
+            }),
+            traffic_limit: settings.traffic_limit,
+            change_city: settings.change_city,
         }
     }
 }
 
+impl From<&mullvad_types::settings::ExpiryWarningSettings> for proto::ExpiryWarningSettings {
+    fn from(settings: &mullvad_types::settings::ExpiryWarningSettings) -> Self {
+        Self {
+            thresholds: settings
+                .thresholds
+                .iter()
+                .filter_map(|threshold| prost_types::Duration::try_from(*threshold).ok())
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::ExpiryWarningSettings> for mullvad_types::settings::ExpiryWarningSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: proto::ExpiryWarningSettings) -> Result<Self, Self::Error> {
+        let thresholds = settings
+            .thresholds
+            .into_iter()
+            .map(|threshold| {
+                std::time::Duration::try_from(threshold)
+                    .ok()
+                    .filter(|threshold| {
+                        mullvad_types::settings::ExpiryWarningSettings::is_valid_threshold(
+                            *threshold,
+                        )
+                    })
+                    .ok_or(FromProtobufTypeError::InvalidArgument(
+                        "expiry warning thresholds must be positive and at most 365 days",
+                    ))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { thresholds })
+    }
+}
+
+impl TryFrom<proto::RelayRotationSettings> for mullvad_types::settings::RelayRotationSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: proto::RelayRotationSettings) -> Result<Self, Self::Error> {
+        let interval = settings
+            .interval
+            .map(std::time::Duration::try_from)
+            .transpose()
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid duration"))?;
+        if interval.is_some_and(|interval| interval.is_zero()) {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "relay rotation interval must be positive",
+            ));
+        }
+        if settings.traffic_limit == Some(0) {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "relay rotation traffic limit must be positive",
+            ));
+        }
+        Ok(Self {
+            interval,
+            traffic_limit: settings.traffic_limit,
+            change_city: settings.change_city,
+        })
+    }
+}
+
 impl From<&mullvad_types::settings::DnsOptions> for proto::DnsOptions {
     fn from(options: &mullvad_types::settings::DnsOptions) -> Self {
         use proto::dns_options;
@@ -183,6 +264,21 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 .into_iter()
                 .map(mullvad_types::relay_constraints::RelayOverride::try_from)
                 .collect::<Result<Vec<_>, _>>()?,
+            relay_rotation: settings
+                .relay_rotation
+                .map(mullvad_types::settings::RelayRotationSettings::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            network_rules: settings
+                .network_rules
+                .map(mullvad_types::network_rules::NetworkRulesSettings::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            expiry_warnings: settings
+                .expiry_warnings
+                .map(mullvad_types::settings::ExpiryWarningSettings::try_from)
+                .transpose()?
+                .unwrap_or_default(),
             show_beta_releases: settings.show_beta_releases,
             #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             split_tunnel: mullvad_types::settings::SplitTunnelSettings::from(split_tunnel),
@@ -356,3 +452,52 @@ impl TryFrom<proto::DnsOptions> for mullvad_types::settings::DnsOptions {
         })
     }
 }
+
+impl From<mullvad_types::settings::SettingsPatchChange> for proto::SettingsPatchChange {
+    fn from(change: mullvad_types::settings::SettingsPatchChange) -> Self {
+        Self {
+            path: change.path,
+            old_value: change.old_value,
+            new_value: change.new_value,
+        }
+    }
+}
+
+impl From<proto::SettingsPatchChange> for mullvad_types::settings::SettingsPatchChange {
+    fn from(change: proto::SettingsPatchChange) -> Self {
+        Self {
+            path: change.path,
+            old_value: change.old_value,
+            new_value: change.new_value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::settings::ExpiryWarningSettings;
+    use std::time::Duration;
+
+    #[test]
+    fn test_expiry_warning_settings() {
+        let settings = ExpiryWarningSettings {
+            thresholds: vec![Duration::from_secs(60 * 60), Duration::MAX],
+        };
+        let proto_settings = proto::ExpiryWarningSettings::from(&settings);
+        assert_eq!(proto_settings.thresholds.len(), 1);
+        assert_eq!(
+            ExpiryWarningSettings::try_from(proto_settings).unwrap(),
+            ExpiryWarningSettings {
+                thresholds: vec![Duration::from_secs(60 * 60)],
+            }
+        );
+
+        let too_long = ExpiryWarningSettings {
+            thresholds: vec![ExpiryWarningSettings::MAX_THRESHOLD + Duration::from_secs(1)],
+        };
+        assert!(
+            ExpiryWarningSettings::try_from(proto::ExpiryWarningSettings::from(&too_long)).is_err()
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use crate::types;
+use chrono::DateTime;
+use mullvad_types::speed_test::SpeedTestResult;
+
+use super::FromProtobufTypeError;
+
+impl From<SpeedTestResult> for types::SpeedTestResult {
+    fn from(result: SpeedTestResult) -> Self {
+        types::SpeedTestResult {
+            relay: result.relay,
+            endpoint: result.endpoint,
+            latency: Some(
+                prost_types::Duration::try_from(result.latency)
+                    .expect("Failed to convert std::time::Duration to prost_types::Duration"),
+            ),
+            jitter: Some(
+                prost_types::Duration::try_from(result.jitter)
+                    .expect("Failed to convert std::time::Duration to prost_types::Duration"),
+            ),
+            download_bps: result.download_bps,
+            upload_bps: result.upload_bps,
+            timestamp: Some(types::Timestamp {
+                seconds: result.timestamp.timestamp(),
+                nanos: result.timestamp.timestamp_subsec_nanos() as i32,
+            }),
+        }
+    }
+}
+
+impl TryFrom<types::SpeedTestResult> for SpeedTestResult {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(result: types::SpeedTestResult) -> Result<Self, FromProtobufTypeError> {
+        let to_duration = |duration: Option<prost_types::Duration>| {
+            duration
+                .map(std::time::Duration::try_from)
+                .ok_or(FromProtobufTypeError::InvalidArgument("missing duration"))?
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid duration"))
+        };
+
+        let timestamp = result
+            .timestamp
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing timestamp"))?;
+        let timestamp = DateTime::from_timestamp(timestamp.seconds, timestamp.nanos as u32)
+            .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))?;
+
+        Ok(SpeedTestResult {
+            relay: result.relay,
+            endpoint: result.endpoint,
+            latency: to_duration(result.latency)?,
+            jitter: to_duration(result.jitter)?,
+            download_bps: result.download_bps,
+            upload_bps: result.upload_bps,
+            timestamp,
+        })
+    }
+}
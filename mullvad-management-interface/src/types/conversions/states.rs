@@ -105,6 +105,9 @@ impl From<mullvad_types::states::TunnelState> for proto::TunnelState {
                             talpid_tunnel::ErrorStateCause::IsOffline => {
                                 i32::from(Cause::IsOffline)
                             }
+                            talpid_tunnel::ErrorStateCause::BlockedByNetworkRule => {
+                                i32::from(Cause::BlockedByNetworkRule)
+                            }
                             #[cfg(target_os = "android")]
                             talpid_tunnel::ErrorStateCause::VpnPermissionDenied => {
                                 i32::from(Cause::VpnPermissionDenied)
@@ -316,6 +319,9 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                     Ok(proto::error_state::Cause::IsOffline) => {
                         talpid_tunnel::ErrorStateCause::IsOffline
                     }
+                    Ok(proto::error_state::Cause::BlockedByNetworkRule) => {
+                        talpid_tunnel::ErrorStateCause::BlockedByNetworkRule
+                    }
                     Ok(proto::error_state::Cause::SetDnsError) => {
                         talpid_tunnel::ErrorStateCause::SetDnsError
                     }
@@ -9,7 +9,8 @@ mod relay_selector;
 // Re-exports
 pub use error::Error;
 pub use relay_selector::{
-    detailer, query, relays::WireguardConfig, AdditionalRelayConstraints,
-    AdditionalWireguardConstraints, GetRelay, RelaySelector, RuntimeParameters, SelectedBridge,
-    SelectedObfuscator, SelectorConfig, RETRY_ORDER,
+    detailer, latency::RelayLatencyCache, query, relays::WireguardConfig,
+    AdditionalRelayConstraints, AdditionalWireguardConstraints, ExitExclusion, GetRelay,
+    RelaySelector, RuntimeParameters, SelectedBridge, SelectedObfuscator, SelectorConfig,
+    RETRY_ORDER,
 };
@@ -19,6 +19,8 @@ use talpid_types::net::obfuscation::ObfuscatorConfig;
 
 use crate::SelectedObfuscator;
 
+use super::{latency::RelayLatencyCache, ExitExclusion};
+
 /// Port ranges available for WireGuard relays that have extra IPs for Shadowsocks.
 /// For relays that have no additional IPs, only ports provided by the relay list are available.
 const SHADOWSOCKS_EXTRA_PORT_RANGES: &[RangeInclusive<u16>] = &[1..=u16::MAX];
@@ -46,6 +48,59 @@ pub fn pick_random_relay(relays: &[Relay]) -> Option<&Relay> {
     pick_random_relay_weighted(relays, |relay| relay.weight)
 }
 
+/// Picks a relay using [pick_random_relay_weighted], scaling the `weight` member of each relay
+/// by how well the relay performed in previous speed tests.
+pub fn pick_random_relay_by_latency<'a>(
+    relays: &'a [Relay],
+    latency_cache: &RelayLatencyCache,
+) -> Option<&'a Relay> {
+    pick_random_relay_weighted(relays, |relay| latency_cache.weight(relay))
+}
+
+/// Picks an exit relay using [pick_random_relay_by_latency]. If `exclusion` is set, the excluded
+/// relay is not picked, and relays in a different city are preferred if requested. If every relay
+/// is excluded, the exclusion is ignored.
+pub fn pick_random_exit_relay<'a>(
+    relays: &'a [Relay],
+    latency_cache: &RelayLatencyCache,
+    exclusion: Option<&ExitExclusion>,
+) -> Option<&'a Relay> {
+    let Some(exclusion) = exclusion else {
+        return pick_random_relay_by_latency(relays, latency_cache);
+    };
+    let excluded = &exclusion.relay;
+    let pick = |candidates: Vec<&'a Relay>| {
+        pick_random_relay_weighted(&candidates, |relay| latency_cache.weight(relay)).copied()
+    };
+
+    if exclusion.different_city {
+        let other_city = pick(
+            relays
+                .iter()
+                .filter(|relay| {
+                    relay.location.country_code != excluded.location.country_code
+                        || relay.location.city_code != excluded.location.city_code
+                })
+                .collect(),
+        );
+        if other_city.is_some() {
+            return other_city;
+        }
+        log::warn!(
+            "No relay outside of {} matches the constraints, picking a relay in the same city",
+            excluded.location.city
+        );
+    }
+
+    pick(relays.iter().filter(|relay| *relay != excluded).collect()).or_else(|| {
+        log::warn!(
+            "{} is the only relay matching the constraints, it cannot be rotated away from",
+            excluded.hostname
+        );
+        pick_random_relay_by_latency(relays, latency_cache)
+    })
+}
+
 /// Pick a random relay from the given slice. Will return `None` if the given slice is empty.
 /// If all of the relays have a weight of 0, one will be picked at random without bias,
 /// otherwise roulette wheel selection will be used to pick only relays with non-zero
@@ -223,10 +278,108 @@ pub fn select_random_port<R: RangeBounds<u16> + Iterator<Item = u16> + Clone>(
 #[cfg(test)]
 mod tests {
     use super::{
-        get_shadowsocks_obfuscator_inner, port_if_in_range, SHADOWSOCKS_EXTRA_PORT_RANGES,
+        get_shadowsocks_obfuscator_inner, pick_random_exit_relay, port_if_in_range,
+        SHADOWSOCKS_EXTRA_PORT_RANGES,
+    };
+    use crate::relay_selector::{latency::RelayLatencyCache, ExitExclusion};
+    use mullvad_types::{
+        constraints::Constraint,
+        location::Location,
+        relay_list::{Relay, RelayEndpointData, WireguardRelayEndpointData},
+        speed_test::SpeedTestResult,
     };
-    use mullvad_types::constraints::Constraint;
-    use std::{net::IpAddr, ops::RangeInclusive};
+    use std::{net::IpAddr, ops::RangeInclusive, time::Duration};
+    use talpid_types::net::wireguard::PublicKey;
+
+    fn relay(hostname: &str, city_code: &str) -> Relay {
+        Relay {
+            hostname: hostname.to_string(),
+            ipv4_addr_in: "127.0.0.1".parse().unwrap(),
+            ipv6_addr_in: None,
+            overridden_ipv4: false,
+            overridden_ipv6: false,
+            include_in_country: true,
+            active: true,
+            owned: true,
+            provider: "provider".to_string(),
+            weight: 1,
+            endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
+                public_key: PublicKey::from_base64("BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=")
+                    .unwrap(),
+                daita: false,
+                shadowsocks_extra_addr_in: vec![],
+            }),
+            location: Location {
+                country: "Sweden".to_string(),
+                country_code: "se".to_string(),
+                city: city_code.to_string(),
+                city_code: city_code.to_string(),
+                latitude: 57.70887,
+                longitude: 11.97456,
+            },
+        }
+    }
+
+    /// A latency cache with a recent speed test against the same server through each relay.
+    fn latency_cache(measurements: &[(&str, Duration)]) -> RelayLatencyCache {
+        let mut cache = RelayLatencyCache::default();
+        for (hostname, latency) in measurements {
+            cache.insert(SpeedTestResult {
+                relay: Some(hostname.to_string()),
+                endpoint: "192.0.2.1:5201".to_string(),
+                latency: *latency,
+                jitter: Duration::ZERO,
+                download_bps: 0,
+                upload_bps: 0,
+                timestamp: chrono::Utc::now(),
+            });
+        }
+        cache
+    }
+
+    /// Relays are weighed by latency also when a relay is excluded, e.g. when rotating.
+    #[test]
+    fn test_exit_exclusion_is_weighed_by_latency() {
+        const PICKS: usize = 10_000;
+
+        let relays = [
+            relay("current", "got"),
+            relay("slow", "sto"),
+            relay("fast", "sto"),
+        ]
+        .map(|relay| Relay {
+            weight: 1000,
+            ..relay
+        });
+        // The median latency is 50 ms, so `fast` gets four times the weight of `slow`
+        let cache = latency_cache(&[
+            ("slow", Duration::from_millis(80)),
+            ("fast", Duration::from_millis(20)),
+        ]);
+        assert_eq!(cache.weight(&relays[1]), 625);
+        assert_eq!(cache.weight(&relays[2]), 2500);
+
+        for different_city in [false, true] {
+            let exclusion = ExitExclusion {
+                relay: relays[0].clone(),
+                different_city,
+            };
+            let mut fast_picks = 0;
+            for _ in 0..PICKS {
+                let exit = pick_random_exit_relay(&relays, &cache, Some(&exclusion)).unwrap();
+                assert_ne!(exit.hostname, "current");
+                if exit.hostname == "fast" {
+                    fast_picks += 1;
+                }
+            }
+            // `fast` should be picked 80 % of the time. The bounds are more than ten standard
+            // deviations away.
+            assert!(
+                (PICKS * 75 / 100..PICKS * 85 / 100).contains(&fast_picks),
+                "fast relay picked {fast_picks} out of {PICKS} times"
+            );
+        }
+    }
 
     /// Test whether select ports are available when relay has no extra IPs
     #[test]
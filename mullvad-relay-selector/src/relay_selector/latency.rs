@@ -0,0 +1,288 @@
+//! This module keeps track of how well relays have performed in previous speed tests, which is
+//! used to bias relay selection towards relays that have been fast in the past.
+//!
+//! ## Overview
+//!
+//! A speed test measures the latency to a speed test server through a relay, so the result depends
+//! on both the relay and the server. [`RelayLatencyCache`] keeps the most recent [`SpeedTestResult`]
+//! for each pair of relay hostname and server. When picking a relay at random, the weight of a relay
+//! is scaled by [`RelayLatencyCache::weight`], which compares its latency to that of other relays
+//! measured against the same server. Relays without such a comparison keep their weight from the
+//! relay list.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufReader},
+    path::Path,
+    time::Duration,
+};
+
+use chrono::Utc;
+use mullvad_types::{relay_list::Relay, speed_test::SpeedTestResult};
+
+use crate::error::Error;
+
+/// Measurements older than this are not considered when weighing relays.
+const MAX_MEASUREMENT_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The weight of a relay is scaled by at most this factor in either direction.
+const MAX_WEIGHT_FACTOR: u32 = 4;
+
+#[derive(Debug, Default, Clone)]
+pub struct RelayLatencyCache {
+    /// Measurements by relay hostname and speed test server.
+    measurements: HashMap<(String, String), SpeedTestResult>,
+}
+
+impl RelayLatencyCache {
+    /// Read a previously stored cache from disk.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        log::debug!(
+            "Reading relay latency cache from {}",
+            path.as_ref().display()
+        );
+        let file = std::fs::File::open(path).map_err(Error::OpenRelayCache)?;
+        let measurements: Vec<SpeedTestResult> =
+            serde_json::from_reader(BufReader::new(file)).map_err(Error::Serialize)?;
+        let mut cache = Self::default();
+        for measurement in measurements {
+            cache.insert(measurement);
+        }
+        Ok(cache)
+    }
+
+    /// Write the cache to `writer` in the format expected by [`RelayLatencyCache::from_file`].
+    pub fn write(&self, writer: impl io::Write) -> Result<(), Error> {
+        let measurements: Vec<_> = self.measurements.values().collect();
+        serde_json::to_writer_pretty(writer, &measurements).map_err(Error::Serialize)
+    }
+
+    /// Record a new measurement, replacing any previous one for the same relay and server.
+    /// Results that were not measured through a Mullvad relay are ignored.
+    pub fn insert(&mut self, result: SpeedTestResult) {
+        if let Some(hostname) = result.relay.clone() {
+            self.measurements
+                .insert((hostname, result.endpoint.clone()), result);
+        }
+    }
+
+    /// Returns the most recent measurement through the relay with the given hostname against the
+    /// speed test server `endpoint`.
+    pub fn get(&self, hostname: &str, endpoint: &str) -> Option<&SpeedTestResult> {
+        self.measurements
+            .get(&(hostname.to_owned(), endpoint.to_owned()))
+    }
+
+    /// Returns the weight of `relay`, adjusted by how its latency compares to the median latency
+    /// of all relays measured against the same speed test server. Only the most recent such
+    /// measurement of `relay` is used. Relays that have not been measured recently, or that no
+    /// other relay can be compared with, keep their original weight.
+    pub fn weight(&self, relay: &Relay) -> u64 {
+        let Some((latency, reference)) = self
+            .recent()
+            .filter(|measurement| measurement.relay.as_ref() == Some(&relay.hostname))
+            .filter_map(|measurement| {
+                let reference = self.reference_latency(&measurement.endpoint)?;
+                Some((measurement, reference))
+            })
+            .max_by_key(|(measurement, _)| measurement.timestamp)
+            .map(|(measurement, reference)| (Self::latency(measurement), reference))
+        else {
+            return relay.weight;
+        };
+
+        let min_latency = reference / MAX_WEIGHT_FACTOR;
+        let max_latency = reference * MAX_WEIGHT_FACTOR;
+        let latency = latency
+            .clamp(min_latency, max_latency)
+            .max(Duration::from_micros(1));
+
+        let weight = u128::from(relay.weight) * reference.as_micros() / latency.as_micros();
+        u64::try_from(weight).unwrap_or(u64::MAX)
+    }
+
+    /// Median latency of the relays recently measured against `endpoint`, or `None` if fewer than
+    /// two relays have been.
+    fn reference_latency(&self, endpoint: &str) -> Option<Duration> {
+        let mut latencies: Vec<_> = self
+            .recent()
+            .filter(|measurement| measurement.endpoint == endpoint)
+            .map(Self::latency)
+            .collect();
+        if latencies.len() < 2 {
+            return None;
+        }
+        latencies.sort();
+        let middle = latencies.len() / 2;
+        if latencies.len() % 2 == 0 {
+            Some((latencies[middle - 1] + latencies[middle]) / 2)
+        } else {
+            Some(latencies[middle])
+        }
+    }
+
+    fn recent(&self) -> impl Iterator<Item = &SpeedTestResult> {
+        self.measurements
+            .values()
+            .filter(|measurement| Self::is_recent(measurement))
+    }
+
+    fn latency(measurement: &SpeedTestResult) -> Duration {
+        measurement.latency + measurement.jitter
+    }
+
+    fn is_recent(measurement: &SpeedTestResult) -> bool {
+        Utc::now()
+            .signed_duration_since(measurement.timestamp)
+            .to_std()
+            // The measurement is from the future. Trust it rather than guessing how skewed the
+            // clock is.
+            .map(|age| age <= MAX_MEASUREMENT_AGE)
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mullvad_types::{
+        location::Location,
+        relay_list::{RelayEndpointData, WireguardRelayEndpointData},
+    };
+    use talpid_types::net::wireguard::PublicKey;
+
+    fn relay(hostname: &str, weight: u64) -> Relay {
+        Relay {
+            hostname: hostname.to_string(),
+            ipv4_addr_in: "127.0.0.1".parse().unwrap(),
+            ipv6_addr_in: None,
+            overridden_ipv4: false,
+            overridden_ipv6: false,
+            include_in_country: true,
+            active: true,
+            owned: true,
+            provider: "provider".to_string(),
+            weight,
+            endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
+                public_key: PublicKey::from_base64("BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=")
+                    .unwrap(),
+                daita: false,
+                shadowsocks_extra_addr_in: vec![],
+            }),
+            location: Location {
+                country: "Sweden".to_string(),
+                country_code: "se".to_string(),
+                city: "Gothenburg".to_string(),
+                city_code: "got".to_string(),
+                latitude: 57.70887,
+                longitude: 11.97456,
+            },
+        }
+    }
+
+    fn measurement(hostname: &str, latency: Duration, age: chrono::TimeDelta) -> SpeedTestResult {
+        measurement_against(hostname, "192.0.2.1:5201", latency, age)
+    }
+
+    fn measurement_against(
+        hostname: &str,
+        endpoint: &str,
+        latency: Duration,
+        age: chrono::TimeDelta,
+    ) -> SpeedTestResult {
+        SpeedTestResult {
+            relay: Some(hostname.to_string()),
+            endpoint: endpoint.to_string(),
+            latency,
+            jitter: Duration::ZERO,
+            download_bps: 0,
+            upload_bps: 0,
+            timestamp: Utc::now() - age,
+        }
+    }
+
+    #[test]
+    fn test_unmeasured_relay_keeps_weight() {
+        let cache = RelayLatencyCache::default();
+        assert_eq!(cache.weight(&relay("se-got-wg-001", 100)), 100);
+    }
+
+    /// Relays are compared to the median latency of the relays measured against the same server.
+    #[test]
+    fn test_latency_scales_weight() {
+        let mut cache = RelayLatencyCache::default();
+        let age = chrono::TimeDelta::zero();
+        cache.insert(measurement("fast", Duration::from_millis(25), age));
+        cache.insert(measurement("median", Duration::from_millis(50), age));
+        cache.insert(measurement("slow", Duration::from_millis(100), age));
+        cache.insert(measurement("unusable", Duration::from_secs(10), age));
+
+        // The median of four latencies is the mean of the middle two, i.e. 75 ms
+        assert_eq!(cache.weight(&relay("fast", 100)), 300);
+        assert_eq!(cache.weight(&relay("median", 100)), 150);
+        assert_eq!(cache.weight(&relay("slow", 100)), 75);
+        assert_eq!(
+            cache.weight(&relay("unusable", 100)),
+            100 / u64::from(MAX_WEIGHT_FACTOR)
+        );
+    }
+
+    /// A single measurement against a server says nothing about how fast the relay is.
+    #[test]
+    fn test_single_relay_per_server_keeps_weight() {
+        let mut cache = RelayLatencyCache::default();
+        let age = chrono::TimeDelta::zero();
+        cache.insert(measurement_against(
+            "fast",
+            "192.0.2.1:5201",
+            Duration::from_millis(1),
+            age,
+        ));
+        cache.insert(measurement_against(
+            "slow",
+            "192.0.2.2:5201",
+            Duration::from_secs(1),
+            age,
+        ));
+
+        assert_eq!(cache.weight(&relay("fast", 100)), 100);
+        assert_eq!(cache.weight(&relay("slow", 100)), 100);
+    }
+
+    #[test]
+    fn test_old_measurement_is_ignored() {
+        let mut cache = RelayLatencyCache::default();
+        cache.insert(measurement(
+            "se-got-wg-001",
+            Duration::from_millis(1),
+            chrono::TimeDelta::days(30),
+        ));
+        cache.insert(measurement(
+            "se-got-wg-002",
+            Duration::from_millis(100),
+            chrono::TimeDelta::zero(),
+        ));
+        assert_eq!(cache.weight(&relay("se-got-wg-001", 100)), 100);
+        assert_eq!(cache.weight(&relay("se-got-wg-002", 100)), 100);
+    }
+
+    #[test]
+    fn test_serialization_roundtrip() {
+        let mut cache = RelayLatencyCache::default();
+        let result = measurement(
+            "se-got-wg-001",
+            Duration::from_millis(30),
+            chrono::TimeDelta::zero(),
+        );
+        cache.insert(result.clone());
+
+        let mut bytes = vec![];
+        cache.write(&mut bytes).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("relay-latency.json");
+        std::fs::write(&path, bytes).unwrap();
+        let loaded = RelayLatencyCache::from_file(&path).unwrap();
+
+        assert_eq!(loaded.get("se-got-wg-001", &result.endpoint), Some(&result));
+    }
+}
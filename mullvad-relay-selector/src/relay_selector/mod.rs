@@ -2,11 +2,13 @@
 
 pub mod detailer;
 mod helpers;
+pub mod latency;
 mod matcher;
 mod parsed_relays;
 pub mod query;
 pub mod relays;
 
+use latency::RelayLatencyCache;
 use matcher::{filter_matching_bridges, filter_matching_relay_list};
 use parsed_relays::ParsedRelays;
 use relays::{Multihop, Singlehop, WireguardConfig};
@@ -41,12 +43,14 @@ use mullvad_types::{
     },
     relay_list::{Relay, RelayEndpointData, RelayList},
     settings::Settings,
+    speed_test::SpeedTestResult,
     wireguard::QuantumResistantState,
     CustomTunnelEndpoint, Intersection,
 };
 use talpid_types::{
     net::{
-        obfuscation::ObfuscatorConfig, proxy::CustomProxy, Endpoint, TransportProtocol, TunnelType,
+        obfuscation::ObfuscatorConfig, proxy::CustomProxy, Endpoint, IpVersion, TransportProtocol,
+        TunnelType,
     },
     ErrorExt,
 };
@@ -142,6 +146,23 @@ pub struct AdditionalWireguardConstraints {
 pub struct RuntimeParameters {
     /// Whether IPv6 is available
     pub ipv6: bool,
+    /// Exit relay to avoid, e.g. because the daemon is rotating away from it.
+    pub exclude_exit: Option<ExitExclusion>,
+    /// IP version to try first when WireGuard may connect over either IP version and IPv6 is
+    /// available, e.g. the one that last worked on the current network.
+    pub preferred_ip_version: IpVersion,
+}
+
+/// An exit relay that should not be selected again.
+///
+/// If every matching relay is excluded, the exclusion is ignored rather than failing to select a
+/// relay at all.
+#[derive(Clone, Debug)]
+pub struct ExitExclusion {
+    /// The relay to avoid.
+    pub relay: Relay,
+    /// Whether to also avoid other relays in the same city as `relay`.
+    pub different_city: bool,
 }
 
 impl RuntimeParameters {
@@ -168,7 +189,11 @@ impl RuntimeParameters {
 #[allow(clippy::derivable_impls)]
 impl Default for RuntimeParameters {
     fn default() -> Self {
-        RuntimeParameters { ipv6: false }
+        RuntimeParameters {
+            ipv6: false,
+            exclude_exit: None,
+            preferred_ip_version: IpVersion::V4,
+        }
     }
 }
 
@@ -422,6 +447,20 @@ impl RelaySelector {
         parsed_relays.update(relays);
     }
 
+    /// Replace the cache of relay speed test results that is used to bias relay selection.
+    pub fn set_latency_cache(&self, latency_cache: RelayLatencyCache) {
+        let mut parsed_relays = self.parsed_relays.lock().unwrap();
+        parsed_relays.set_latency_cache(latency_cache);
+    }
+
+    /// Record the result of a speed test, making relays that perform well more likely to be
+    /// selected in the future. Returns the updated cache.
+    pub fn record_speed_test(&self, result: SpeedTestResult) -> RelayLatencyCache {
+        let mut parsed_relays = self.parsed_relays.lock().unwrap();
+        parsed_relays.record_speed_test(result);
+        parsed_relays.latency_cache().clone()
+    }
+
     fn set_overrides(&mut self, relay_overrides: &[RelayOverride]) {
         let mut parsed_relays = self.parsed_relays.lock().unwrap();
         parsed_relays.set_overrides(relay_overrides);
@@ -434,6 +473,12 @@ impl RelaySelector {
         parsed_relays.original_list().clone()
     }
 
+    /// Returns the CDN hostnames that the relay list advertises for domain fronting.
+    pub fn get_domain_fronts(&self) -> Vec<String> {
+        let parsed_relays = self.parsed_relays.lock().unwrap();
+        parsed_relays.original_list().bridge.domain_fronts.clone()
+    }
+
     pub fn etag(&self) -> Option<String> {
         self.parsed_relays.lock().unwrap().etag()
     }
@@ -491,7 +536,7 @@ impl RelaySelector {
             }
             SpecializedSelectorConfig::Normal(normal_config) => {
                 let parsed_relays = &self.parsed_relays.lock().unwrap();
-                Self::get_relay_inner(&query, parsed_relays, normal_config.custom_lists)
+                Self::get_relay_inner(&query, parsed_relays, normal_config.custom_lists, None)
             }
         }
     }
@@ -531,11 +576,28 @@ impl RelaySelector {
                 let query = Self::pick_and_merge_query(
                     retry_attempt,
                     retry_order,
-                    runtime_params,
+                    &runtime_params,
                     &normal_config,
                     parsed_relays,
                 )?;
-                Self::get_relay_inner(&query, parsed_relays, normal_config.custom_lists)
+                if let Some(query) =
+                    Self::alternate_ip_version(&query, retry_attempt, &runtime_params)
+                {
+                    if let Ok(relay) = Self::get_relay_inner(
+                        &query,
+                        parsed_relays,
+                        normal_config.custom_lists,
+                        runtime_params.exclude_exit.as_ref(),
+                    ) {
+                        return Ok(relay);
+                    }
+                }
+                Self::get_relay_inner(
+                    &query,
+                    parsed_relays,
+                    normal_config.custom_lists,
+                    runtime_params.exclude_exit.as_ref(),
+                )
             }
         }
     }
@@ -557,7 +619,7 @@ impl RelaySelector {
     fn pick_and_merge_query(
         retry_attempt: usize,
         retry_order: &[RelayQuery],
-        runtime_params: RuntimeParameters,
+        runtime_params: &RuntimeParameters,
         user_config: &NormalSelectorConfig<'_>,
         parsed_relays: &ParsedRelays,
     ) -> Result<RelayQuery, Error> {
@@ -569,12 +631,42 @@ impl RelaySelector {
             // settings
             .filter(|query| runtime_params.compatible(query))
             .filter_map(|query| query.clone().intersection(user_query.clone()))
-            .filter(|query| Self::get_relay_inner(query, parsed_relays, user_config.custom_lists).is_ok())
+            .filter(|query| Self::get_relay_inner(query, parsed_relays, user_config.custom_lists, None).is_ok())
             .cycle() // If the above filters remove all relays, cycle will also return an empty iterator
             .nth(retry_attempt)
             .ok_or(Error::NoRelay)
     }
 
+    /// Connect to WireGuard relays over both IPv4 and IPv6 in quick succession when `query` does
+    /// not require a specific IP version, instead of always using IPv4. Even retry attempts use
+    /// the preferred IP version and odd attempts use the other one.
+    ///
+    /// Returns `None` if `query` should be used as is.
+    fn alternate_ip_version(
+        query: &RelayQuery,
+        retry_attempt: usize,
+        runtime_params: &RuntimeParameters,
+    ) -> Option<RelayQuery> {
+        if !runtime_params.ipv6
+            || query.tunnel_protocol() == Constraint::Only(TunnelType::OpenVpn)
+            || query.wireguard_constraints().ip_version.is_only()
+        {
+            return None;
+        }
+        let ip_version = match (runtime_params.preferred_ip_version, retry_attempt % 2) {
+            (preferred, 0) => preferred,
+            (IpVersion::V4, _) => IpVersion::V6,
+            (IpVersion::V6, _) => IpVersion::V4,
+        };
+        let mut wireguard_constraints = query.wireguard_constraints().clone();
+        wireguard_constraints.ip_version = Constraint::Only(ip_version);
+        let mut query = query.clone();
+        query
+            .set_wireguard_constraints(wireguard_constraints)
+            .ok()?;
+        Some(query)
+    }
+
     /// "Execute" the given query, yielding a final set of relays and/or bridges which the VPN
     /// traffic shall be routed through.
     ///
@@ -584,6 +676,7 @@ impl RelaySelector {
     /// - `config`: Configuration settings that influence relay selection, including bridge state
     ///   and custom lists.
     /// - `parsed_relays`: The complete set of parsed relays available for selection.
+    /// - `exclude_exit`: An exit relay that should be avoided if possible.
     ///
     /// # Returns
     /// * A randomly selected relay that meets the specified constraints (and a random bridge/entry
@@ -595,13 +688,14 @@ impl RelaySelector {
         query: &RelayQuery,
         parsed_relays: &ParsedRelays,
         custom_lists: &CustomListsSettings,
+        exclude_exit: Option<&ExitExclusion>,
     ) -> Result<GetRelay, Error> {
         match query.tunnel_protocol() {
             Constraint::Only(TunnelType::Wireguard) => {
-                Self::get_wireguard_relay(query, custom_lists, parsed_relays)
+                Self::get_wireguard_relay(query, custom_lists, parsed_relays, exclude_exit)
             }
             Constraint::Only(TunnelType::OpenVpn) => {
-                Self::get_openvpn_relay(query, custom_lists, parsed_relays)
+                Self::get_openvpn_relay(query, custom_lists, parsed_relays, exclude_exit)
             }
             Constraint::Any => {
                 // Try Wireguard, then OpenVPN, then fail
@@ -612,7 +706,7 @@ impl RelaySelector {
                         .expect("unreachable since tunnel constraint is 'any', not wg");
                     // If a suitable relay is found, short-circuit and return it
                     if let Ok(relay) =
-                        Self::get_relay_inner(&new_query, parsed_relays, custom_lists)
+                        Self::get_relay_inner(&new_query, parsed_relays, custom_lists, exclude_exit)
                     {
                         return Ok(relay);
                     }
@@ -627,6 +721,7 @@ impl RelaySelector {
         query: &RelayQuery,
         parsed_relays: &ParsedRelays,
         custom_lists: &CustomListsSettings,
+        exclude_exit: Option<&ExitExclusion>,
     ) -> Result<GetRelay, Error> {
         // FIXME: A bit of defensive programming - calling `get_wireguard_relay` with a query that
         // doesn't specify Wireguard as the desired tunnel type is not valid and will lead
@@ -634,7 +729,7 @@ impl RelaySelector {
         // to lift this invariant to be checked by the type system instead.
         let mut query = query.clone();
         query.set_tunnel_protocol(Constraint::Only(TunnelType::Wireguard))?;
-        Self::get_wireguard_relay(&query, custom_lists, parsed_relays)
+        Self::get_wireguard_relay(&query, custom_lists, parsed_relays, exclude_exit)
     }
 
     /// Derive a valid relay configuration from `query`.
@@ -654,12 +749,14 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &ParsedRelays,
+        exclude_exit: Option<&ExitExclusion>,
     ) -> Result<GetRelay, Error> {
         assert_eq!(
             query.tunnel_protocol(),
             Constraint::Only(TunnelType::Wireguard)
         );
-        let inner = Self::get_wireguard_relay_config(query, custom_lists, parsed_relays)?;
+        let inner =
+            Self::get_wireguard_relay_config(query, custom_lists, parsed_relays, exclude_exit)?;
         let endpoint = Self::get_wireguard_endpoint(query, parsed_relays, &inner)?;
         let obfuscator =
             Self::get_wireguard_obfuscator(query, inner.clone(), &endpoint, parsed_relays)?;
@@ -681,15 +778,26 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &ParsedRelays,
+        exclude_exit: Option<&ExitExclusion>,
     ) -> Result<WireguardConfig, Error> {
         // TODO: Remove when Android gets support for multihop.
         if cfg!(target_os = "android") {
-            let relay = Self::get_wireguard_singlehop_config(query, custom_lists, parsed_relays)
-                .ok_or(Error::NoRelay)?;
+            let relay = Self::get_wireguard_singlehop_config(
+                query,
+                custom_lists,
+                parsed_relays,
+                exclude_exit,
+            )
+            .ok_or(Error::NoRelay)?;
             return Ok(WireguardConfig::from(relay));
         }
         let inner = if query.singlehop() {
-            match Self::get_wireguard_singlehop_config(query, custom_lists, parsed_relays) {
+            match Self::get_wireguard_singlehop_config(
+                query,
+                custom_lists,
+                parsed_relays,
+                exclude_exit,
+            ) {
                 Some(exit) => WireguardConfig::from(exit),
                 None => {
                     // If we found no matching relays because DAITA was enabled, and
@@ -701,6 +809,7 @@ impl RelaySelector {
                             query,
                             custom_lists,
                             parsed_relays,
+                            exclude_exit,
                         )?;
                         WireguardConfig::from(multihop)
                     } else {
@@ -714,9 +823,19 @@ impl RelaySelector {
             // entry relay with smarting routing enabled, even if multihop is turned on
             // Also implied: Multihop is enabled.
             let multihop = if query.using_daita() && query.use_multihop_if_necessary() {
-                Self::get_wireguard_auto_multihop_config(query, custom_lists, parsed_relays)?
+                Self::get_wireguard_auto_multihop_config(
+                    query,
+                    custom_lists,
+                    parsed_relays,
+                    exclude_exit,
+                )?
             } else {
-                Self::get_wireguard_multihop_config(query, custom_lists, parsed_relays)?
+                Self::get_wireguard_multihop_config(
+                    query,
+                    custom_lists,
+                    parsed_relays,
+                    exclude_exit,
+                )?
             };
             WireguardConfig::from(multihop)
         };
@@ -733,9 +852,10 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &ParsedRelays,
+        exclude_exit: Option<&ExitExclusion>,
     ) -> Option<Singlehop> {
         let candidates = filter_matching_relay_list(query, parsed_relays, custom_lists);
-        helpers::pick_random_relay(&candidates)
+        helpers::pick_random_exit_relay(&candidates, parsed_relays.latency_cache(), exclude_exit)
             .cloned()
             .map(Singlehop::new)
     }
@@ -749,6 +869,7 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &ParsedRelays,
+        exclude_exit: Option<&ExitExclusion>,
     ) -> Result<Multihop, Error> {
         let mut exit_relay_query = query.clone();
 
@@ -759,7 +880,12 @@ impl RelaySelector {
 
         let exit_candidates =
             filter_matching_relay_list(&exit_relay_query, parsed_relays, custom_lists);
-        let exit = helpers::pick_random_relay(&exit_candidates).ok_or(Error::NoRelay)?;
+        let exit = helpers::pick_random_exit_relay(
+            &exit_candidates,
+            parsed_relays.latency_cache(),
+            exclude_exit,
+        )
+        .ok_or(Error::NoRelay)?;
 
         // generate a list of potential entry relays, disregarding any location constraint
         let mut entry_query = query.clone();
@@ -800,6 +926,7 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &ParsedRelays,
+        exclude_exit: Option<&ExitExclusion>,
     ) -> Result<Multihop, Error> {
         // Here, we modify the original query just a bit.
         // The actual query for an entry relay is identical as for an exit relay, with the
@@ -834,9 +961,13 @@ impl RelaySelector {
             ([exit], entries) if entries.contains(exit) => {
                 helpers::pick_random_relay_excluding(entries, exit).map(|entry| (exit, entry))
             }
-            (exits, entries) => helpers::pick_random_relay(exits).and_then(|exit| {
-                helpers::pick_random_relay_excluding(entries, exit).map(|entry| (exit, entry))
-            }),
+            (exits, entries) => {
+                helpers::pick_random_exit_relay(exits, parsed_relays.latency_cache(), exclude_exit)
+                    .and_then(|exit| {
+                        helpers::pick_random_relay_excluding(entries, exit)
+                            .map(|entry| (exit, entry))
+                    })
+            }
         }
         .ok_or(Error::NoRelay)?;
 
@@ -919,13 +1050,14 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &ParsedRelays,
+        exclude_exit: Option<&ExitExclusion>,
     ) -> Result<GetRelay, Error> {
         assert_eq!(
             query.tunnel_protocol(),
             Constraint::Only(TunnelType::OpenVpn)
         );
-        let exit =
-            Self::choose_openvpn_relay(query, custom_lists, parsed_relays).ok_or(Error::NoRelay)?;
+        let exit = Self::choose_openvpn_relay(query, custom_lists, parsed_relays, exclude_exit)
+            .ok_or(Error::NoRelay)?;
         let endpoint = Self::get_openvpn_endpoint(query, &exit, parsed_relays)?;
         let bridge = Self::get_openvpn_bridge(
             query,
@@ -1128,11 +1260,13 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &ParsedRelays,
+        exclude_exit: Option<&ExitExclusion>,
     ) -> Option<Relay> {
         // Filter among all valid relays
         let candidates = filter_matching_relay_list(query, parsed_relays, custom_lists);
         // Pick one of the valid relays.
-        helpers::pick_random_relay(&candidates).cloned()
+        helpers::pick_random_exit_relay(&candidates, parsed_relays.latency_cache(), exclude_exit)
+            .cloned()
     }
 }
 
@@ -18,8 +18,10 @@ use mullvad_types::{
     location::Location,
     relay_constraints::RelayOverride,
     relay_list::{Relay, RelayList},
+    speed_test::SpeedTestResult,
 };
 
+use super::latency::RelayLatencyCache;
 use crate::{constants::UDP2TCP_PORTS, error::Error};
 
 pub(crate) struct ParsedRelays {
@@ -30,6 +32,8 @@ pub(crate) struct ParsedRelays {
     /// The original list of relays, as returned by the Mullvad relays API.
     original_list: RelayList,
     overrides: Vec<RelayOverride>,
+    /// Results of previous speed tests, used to weigh relays.
+    latency_cache: RelayLatencyCache,
 }
 
 impl ParsedRelays {
@@ -41,7 +45,9 @@ impl ParsedRelays {
     /// Replace `self` with a new [`ParsedRelays`] based on [new_relays][`ParsedRelays`],
     /// bumping `self.last_updated` to the current system time.
     pub fn update(&mut self, new_relays: RelayList) {
+        let latency_cache = std::mem::take(&mut self.latency_cache);
         *self = Self::from_relay_list(new_relays, SystemTime::now(), &self.overrides);
+        self.latency_cache = latency_cache;
 
         log::info!(
             "Updated relay inventory has {} relays",
@@ -77,12 +83,26 @@ impl ParsedRelays {
         self.overrides = new_overrides.to_vec();
     }
 
+    /// Results of previous speed tests, used to weigh relays.
+    pub const fn latency_cache(&self) -> &RelayLatencyCache {
+        &self.latency_cache
+    }
+
+    pub(crate) fn set_latency_cache(&mut self, latency_cache: RelayLatencyCache) {
+        self.latency_cache = latency_cache;
+    }
+
+    pub(crate) fn record_speed_test(&mut self, result: SpeedTestResult) {
+        self.latency_cache.insert(result);
+    }
+
     pub(crate) fn empty() -> Self {
         ParsedRelays {
             last_updated: UNIX_EPOCH,
             parsed_list: RelayList::empty(),
             original_list: RelayList::empty(),
             overrides: vec![],
+            latency_cache: RelayLatencyCache::default(),
         }
     }
 
@@ -142,6 +162,7 @@ impl ParsedRelays {
             parsed_list: Self::parse_relay_list(&relay_list, overrides),
             original_list: relay_list,
             overrides: overrides.to_vec(),
+            latency_cache: RelayLatencyCache::default(),
         }
     }
 
@@ -15,8 +15,8 @@ use talpid_types::net::{
 
 use mullvad_relay_selector::{
     query::{builder::RelayQueryBuilder, BridgeQuery, ObfuscationQuery, OpenVpnRelayQuery},
-    Error, GetRelay, RelaySelector, RuntimeParameters, SelectedObfuscator, SelectorConfig,
-    WireguardConfig, RETRY_ORDER,
+    Error, ExitExclusion, GetRelay, RelaySelector, RuntimeParameters, SelectedObfuscator,
+    SelectorConfig, WireguardConfig, RETRY_ORDER,
 };
 use mullvad_types::{
     constraints::Constraint,
@@ -44,6 +44,7 @@ static DUMMY_LOCATION: LazyLock<Location> = LazyLock::new(|| Location {
 
 static RELAYS: LazyLock<RelayList> = LazyLock::new(|| RelayList {
     etag: None,
+    created: None,
     countries: vec![RelayListCountry {
         name: "Sweden".to_string(),
         code: "se".to_string(),
@@ -199,6 +200,7 @@ static RELAYS: LazyLock<RelayList> = LazyLock::new(|| RelayList {
                 protocol: Udp,
             },
         ],
+        domain_fronts: vec![],
     },
     wireguard: WireguardEndpointData {
         port_ranges: vec![
@@ -370,7 +372,13 @@ fn test_retry_order() {
     let relay_selector = default_relay_selector();
     for (retry_attempt, query) in RETRY_ORDER.iter().enumerate() {
         let relay = relay_selector
-            .get_relay(retry_attempt, RuntimeParameters { ipv6: true })
+            .get_relay(
+                retry_attempt,
+                RuntimeParameters {
+                    ipv6: true,
+                    ..Default::default()
+                },
+            )
             .unwrap_or_else(|_| panic!("Retry attempt {retry_attempt} did not yield any relay"));
         // For each relay, cross-check that the it has the expected tunnel protocol
         let tunnel_type = tunnel_type(&unwrap_relay(relay.clone()));
@@ -500,6 +508,7 @@ fn test_wireguard_entry() {
     // Define a relay list containing exactly two Wireguard relays in Gothenburg.
     let relays = RelayList {
         etag: None,
+        created: None,
         countries: vec![RelayListCountry {
             name: "Sweden".to_string(),
             code: "se".to_string(),
@@ -557,6 +566,7 @@ fn test_wireguard_entry() {
         openvpn: OpenVpnEndpointData { ports: vec![] },
         bridge: BridgeEndpointData {
             shadowsocks: vec![],
+            domain_fronts: vec![],
         },
         wireguard: WireguardEndpointData {
             port_ranges: vec![
@@ -1099,6 +1109,79 @@ fn test_load_balancing() {
     }
 }
 
+/// Verify that an excluded exit relay is never picked as long as there are other matching relays.
+#[test]
+fn test_exit_exclusion() {
+    const ATTEMPTS: usize = 100;
+    let relay_selector = default_relay_selector();
+    let excluded = unwrap_relay(
+        relay_selector
+            .get_relay(0, RuntimeParameters::default())
+            .unwrap(),
+    );
+
+    // All relays are in the same city, so asking for a different city must fall back to picking
+    // another relay in the same city.
+    for different_city in [false, true] {
+        let runtime_params = RuntimeParameters {
+            exclude_exit: Some(ExitExclusion {
+                relay: excluded.clone(),
+                different_city,
+            }),
+            ..Default::default()
+        };
+        for _ in 0..ATTEMPTS {
+            let relay = unwrap_relay(relay_selector.get_relay(0, runtime_params.clone()).unwrap());
+            assert_ne!(relay.hostname, excluded.hostname);
+        }
+    }
+}
+
+/// Verify that successive retry attempts alternate between IPv4 and IPv6 WireGuard endpoints,
+/// starting with the preferred IP version, when IPv6 is available.
+#[test]
+fn test_alternate_ip_version() {
+    let relay_selector = default_relay_selector();
+    let endpoint_ip_version = |retry_attempt, runtime_params| match relay_selector
+        .get_relay(retry_attempt, runtime_params)
+        .unwrap()
+    {
+        GetRelay::Wireguard { endpoint, .. } => IpVersion::from(endpoint.peer.endpoint.ip()),
+        wrong_relay => panic!(
+            "Relay selector should have picked a Wireguard relay, instead chose {wrong_relay:?}"
+        ),
+    };
+
+    for preferred_ip_version in [IpVersion::V4, IpVersion::V6] {
+        let runtime_params = RuntimeParameters {
+            ipv6: true,
+            preferred_ip_version,
+            ..Default::default()
+        };
+        let other_ip_version = match preferred_ip_version {
+            IpVersion::V4 => IpVersion::V6,
+            IpVersion::V6 => IpVersion::V4,
+        };
+        assert_eq!(
+            endpoint_ip_version(0, runtime_params.clone()),
+            preferred_ip_version
+        );
+        assert_eq!(endpoint_ip_version(1, runtime_params), other_ip_version);
+    }
+
+    // Never use IPv6 unless it is known to be available
+    let runtime_params = RuntimeParameters {
+        ipv6: false,
+        preferred_ip_version: IpVersion::V6,
+        ..Default::default()
+    };
+    assert_eq!(
+        endpoint_ip_version(0, runtime_params.clone()),
+        IpVersion::V4
+    );
+    assert_eq!(endpoint_ip_version(1, runtime_params), IpVersion::V4);
+}
+
 /// Construct a query for a relay with specific providers and verify that every chosen relay has
 /// the correct associated provider.
 #[test]
@@ -1172,6 +1255,7 @@ fn test_openvpn_auto_bridge() {
 fn test_include_in_country() {
     let mut relay_list = RelayList {
         etag: None,
+        created: None,
         countries: vec![RelayListCountry {
             name: "Sweden".to_string(),
             code: "se".to_string(),
@@ -1244,6 +1328,7 @@ fn test_include_in_country() {
         },
         bridge: BridgeEndpointData {
             shadowsocks: vec![],
+            domain_fronts: vec![],
         },
         wireguard: WireguardEndpointData {
             port_ranges: vec![53..=53, 4000..=33433, 33565..=51820, 52000..=60000],
@@ -0,0 +1,209 @@
+//! Local HTTP/JSON gateway for the management interface of the daemon. It serves
+//! `/v1/<RpcName>` endpoints on a Unix socket and forwards them to the daemon. See
+//! `docs/rest-gateway.md`.
+
+#[cfg(unix)]
+mod routes;
+
+#[cfg(unix)]
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    if let Err(error) = imp::run().await {
+        use talpid_types::ErrorExt;
+        eprintln!("{}", error.display_chain());
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("The REST gateway is only supported on Linux and macOS");
+    std::process::exit(1);
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::routes;
+    use bytes::Bytes;
+    use clap::Parser;
+    use http_body_util::{BodyExt, Limited};
+    use hyper::{body::Incoming, server::conn::http1, service::service_fn, Method, Request};
+    use hyper_util::rt::TokioIo;
+    use mullvad_management_interface::auth::{Role, RoleMapping};
+    use std::{convert::Infallible, path::PathBuf, sync::Arc};
+    use tokio::{
+        net::UnixListener,
+        signal::unix::{signal, SignalKind},
+    };
+
+    /// Environment variable that overrides the default socket path.
+    const SOCKET_PATH_VAR: &str = "MULLVAD_REST_SOCKET_PATH";
+    const DEFAULT_SOCKET_PATH: &str = "/var/run/mullvad-vpn-rest";
+
+    /// Prefix of all endpoints. Bumped if the JSON format changes incompatibly.
+    const PATH_PREFIX: &str = "/v1/";
+
+    /// Maximum size of a request body.
+    const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+    #[derive(thiserror::Error, Debug)]
+    pub enum Error {
+        #[error("Failed to read role groups")]
+        Roles(#[source] mullvad_management_interface::Error),
+
+        #[error(
+            "No role groups are configured. Set {} or {}",
+            mullvad_management_interface::auth::ADMIN_GROUP_VAR,
+            mullvad_management_interface::auth::OPERATOR_GROUP_VAR
+        )]
+        NoRoleGroups,
+
+        #[error("Failed to bind socket")]
+        Bind(#[source] std::io::Error),
+
+        #[error("Failed to set socket permissions")]
+        Permissions(#[source] mullvad_management_interface::Error),
+
+        #[error("Failed to listen for signals")]
+        Signal(#[source] std::io::Error),
+    }
+
+    #[derive(Debug, Parser)]
+    #[command(author, version = mullvad_version::VERSION, about, long_about = None)]
+    struct Args {
+        /// Path of the Unix socket to serve the gateway on. Defaults to the value of
+        /// MULLVAD_REST_SOCKET_PATH or /var/run/mullvad-vpn-rest
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    }
+
+    /// Removes the socket file when dropped.
+    struct SocketGuard(PathBuf);
+
+    impl Drop for SocketGuard {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    pub async fn run() -> Result<(), Error> {
+        let args = Args::parse();
+        let path = args
+            .socket
+            .or_else(|| std::env::var_os(SOCKET_PATH_VAR).map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_SOCKET_PATH));
+
+        let roles = RoleMapping::from_env().map_err(Error::Roles)?;
+        // Without role groups every client would be an admin. Since the gateway itself is an
+        // admin client of the daemon, that would hand admin access to anyone who can connect.
+        if !roles.is_enabled() {
+            return Err(Error::NoRoleGroups);
+        }
+        let roles = Arc::new(roles);
+
+        // Remove a socket left behind by a previous instance
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).map_err(Error::Bind)?;
+        let _guard = SocketGuard(path.clone());
+        mullvad_management_interface::set_socket_permissions(&path).map_err(Error::Permissions)?;
+
+        log::info!("Serving REST gateway on {}", path.display());
+
+        let mut sigterm = signal(SignalKind::terminate()).map_err(Error::Signal)?;
+        loop {
+            tokio::select! {
+                result = listener.accept() => match result {
+                    Ok((stream, _)) => {
+                        let role = roles.role(&stream);
+                        tokio::spawn(serve_connection(stream, role));
+                    }
+                    Err(error) => log::error!("Failed to accept connection: {error}"),
+                },
+                _ = tokio::signal::ctrl_c() => break,
+                _ = sigterm.recv() => break,
+            }
+        }
+
+        log::info!("Shutting down REST gateway");
+        Ok(())
+    }
+
+    async fn serve_connection(stream: tokio::net::UnixStream, role: Role) {
+        let service = service_fn(move |request| async move {
+            Ok::<_, Infallible>(handle_request(request, role).await)
+        });
+        if let Err(error) = http1::Builder::new()
+            .serve_connection(TokioIo::new(stream), service)
+            .await
+        {
+            log::debug!("Connection closed with error: {error}");
+        }
+    }
+
+    async fn handle_request(
+        request: Request<Incoming>,
+        role: Role,
+    ) -> hyper::Response<routes::Body> {
+        let Some(method) = rpc_name(request.uri().path()) else {
+            return error_response(hyper::StatusCode::NOT_FOUND, "Unknown endpoint");
+        };
+        let method = method.to_owned();
+
+        if !matches!(*request.method(), Method::GET | Method::POST) {
+            return error_response(hyper::StatusCode::METHOD_NOT_ALLOWED, "Use GET or POST");
+        }
+
+        let required = Role::required_for_method(&method);
+        if role < required {
+            log::warn!("Denied {method} to {role} client");
+            return error_response(
+                hyper::StatusCode::FORBIDDEN,
+                &format!("{method} requires the {required} role"),
+            );
+        }
+
+        let body: Bytes = match Limited::new(request.into_body(), MAX_BODY_SIZE)
+            .collect()
+            .await
+        {
+            Ok(body) => body.to_bytes(),
+            Err(_) => {
+                return error_response(
+                    hyper::StatusCode::PAYLOAD_TOO_LARGE,
+                    "Failed to read request body",
+                )
+            }
+        };
+
+        log::debug!("{method}");
+        match routes::handle(&method, &body).await {
+            Ok(response) => response,
+            Err(error) => error.into_response(),
+        }
+    }
+
+    /// Returns the RPC name of a path like `/v1/GetSettings`.
+    fn rpc_name(path: &str) -> Option<&str> {
+        path.strip_prefix(PATH_PREFIX)
+            .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric()))
+    }
+
+    fn error_response(status: hyper::StatusCode, message: &str) -> hyper::Response<routes::Body> {
+        routes::json_response(status, &serde_json::json!({ "error": message }))
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::rpc_name;
+
+        #[test]
+        fn test_rpc_name() {
+            assert_eq!(rpc_name("/v1/GetSettings"), Some("GetSettings"));
+            assert_eq!(rpc_name("/v1/"), None);
+            assert_eq!(rpc_name("/v1/Get/Settings"), None);
+            assert_eq!(rpc_name("/v2/GetSettings"), None);
+            assert_eq!(rpc_name("/GetSettings"), None);
+        }
+    }
+}
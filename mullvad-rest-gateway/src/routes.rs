@@ -0,0 +1,400 @@
+//! Maps the RPCs of the management interface to JSON endpoints. Each endpoint is named after the
+//! RPC that it calls, e.g. `POST /v1/SetAllowLan`. The request body is the JSON encoded argument
+//! of the RPC, if it takes one, and the response body is the JSON encoded result. The JSON format
+//! is the serde serialization of the types in `mullvad-types`.
+
+use bytes::Bytes;
+use futures::{channel::mpsc, SinkExt, StreamExt};
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::{
+    body::Frame,
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    Response, StatusCode,
+};
+use mullvad_management_interface::{client::DaemonEvent, Code, MullvadProxyClient};
+use mullvad_types::{
+    access_method::{self, AccessMethod},
+    account::AccountNumber,
+    device::DeviceId,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{convert::Infallible, time::Duration};
+
+pub type Body = http_body_util::combinators::UnsyncBoxBody<Bytes, Infallible>;
+
+/// Number of events that may be buffered for a slow `EventsListen` client.
+const EVENT_BUFFER_SIZE: usize = 16;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Unknown RPC: {0}")]
+    UnknownMethod(String),
+
+    #[error("Invalid request body")]
+    InvalidArgument(#[source] serde_json::Error),
+
+    #[error("Failed to connect to the daemon")]
+    Connect(#[source] mullvad_management_interface::Error),
+
+    #[error("RPC failed")]
+    Rpc(#[source] mullvad_management_interface::Error),
+}
+
+impl Error {
+    fn status_code(&self) -> StatusCode {
+        use mullvad_management_interface::Error as RpcError;
+
+        match self {
+            Error::UnknownMethod(_) => StatusCode::NOT_FOUND,
+            Error::InvalidArgument(_) => StatusCode::BAD_REQUEST,
+            Error::Connect(_) => StatusCode::BAD_GATEWAY,
+            Error::Rpc(RpcError::Rpc(status)) => match status.code() {
+                Code::InvalidArgument | Code::OutOfRange => StatusCode::BAD_REQUEST,
+                Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+                Code::PermissionDenied => StatusCode::FORBIDDEN,
+                Code::NotFound => StatusCode::NOT_FOUND,
+                Code::AlreadyExists | Code::FailedPrecondition | Code::Aborted => {
+                    StatusCode::CONFLICT
+                }
+                Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+                Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+                Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+                Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Error::Rpc(
+                RpcError::InvalidVoucher
+                | RpcError::UsedVoucher
+                | RpcError::InvalidAccount
+                | RpcError::DurationTooLarge,
+            ) => StatusCode::BAD_REQUEST,
+            Error::Rpc(
+                RpcError::DeviceNotFound
+                | RpcError::CustomListListNotFound
+                | RpcError::ApiAccessMethodNotFound,
+            ) => StatusCode::NOT_FOUND,
+            Error::Rpc(
+                RpcError::TooManyDevices | RpcError::AlreadyLoggedIn | RpcError::CustomListExists,
+            ) => StatusCode::CONFLICT,
+            Error::Rpc(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn message(&self) -> String {
+        use mullvad_management_interface::Error as RpcError;
+
+        match self {
+            Error::InvalidArgument(error) => format!("{self}: {error}"),
+            Error::Rpc(RpcError::Rpc(status)) => status.message().to_owned(),
+            Error::Rpc(error) => error.to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    pub fn into_response(self) -> Response<Body> {
+        #[derive(Serialize)]
+        struct ErrorBody {
+            error: String,
+        }
+        json_response(
+            self.status_code(),
+            &ErrorBody {
+                error: self.message(),
+            },
+        )
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RemoveDevice {
+    account_number: AccountNumber,
+    device_id: DeviceId,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewAccessMethod {
+    name: String,
+    #[serde(default = "default_true")]
+    enabled: bool,
+    access_method: AccessMethod,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpeedTest {
+    endpoint: String,
+    duration_secs: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Call the RPC named `method` with the JSON encoded argument in `body`.
+pub async fn handle(method: &str, body: &[u8]) -> Result<Response<Body>, Error> {
+    let mut rpc = MullvadProxyClient::new().await.map_err(Error::Connect)?;
+
+    match method {
+        "EventsListen" => Ok(events_listen(rpc)),
+
+        "ConnectTunnel" => reply(rpc.connect_tunnel().await),
+        "DisconnectTunnel" => reply(rpc.disconnect_tunnel().await),
+        "ReconnectTunnel" => reply(rpc.reconnect_tunnel().await),
+        "GetTunnelState" => reply(rpc.get_tunnel_state().await),
+        "FactoryReset" => reply(rpc.factory_reset().await),
+        "GetCurrentVersion" => reply(rpc.get_current_version().await),
+        "GetVersionInfo" => reply(rpc.get_version_info().await),
+        "GetFeatureIndicators" => reply(rpc.get_feature_indicators().await),
+
+        "UpdateRelayLocations" => reply(rpc.update_relay_locations().await),
+        "GetRelayLocations" => reply(rpc.get_relay_locations().await),
+        "ImportRelayList" => {
+            let signed_relay_list: serde_json::Value = arg(body)?;
+            reply(rpc.import_relay_list(signed_relay_list.to_string()).await)
+        }
+        "SetRelaySettings" => reply(rpc.set_relay_settings(arg(body)?).await),
+        "SetBridgeSettings" => reply(rpc.set_bridge_settings(arg(body)?).await),
+        "SetBridgeState" => reply(rpc.set_bridge_state(arg(body)?).await),
+        "SetObfuscationSettings" => reply(rpc.set_obfuscation_settings(arg(body)?).await),
+        "SetRelayOverride" => reply(rpc.set_relay_override(arg(body)?).await),
+        "ClearAllRelayOverrides" => reply(rpc.clear_all_relay_overrides().await),
+        "SetRelayRotationSettings" => reply(rpc.set_relay_rotation_settings(arg(body)?).await),
+
+        "GetSettings" => reply(rpc.get_settings().await),
+        "ResetSettings" => reply(rpc.reset_settings().await),
+        "SetAllowLan" => reply(rpc.set_allow_lan(arg(body)?).await),
+        "SetShowBetaReleases" => reply(rpc.set_show_beta_releases(arg(body)?).await),
+        "SetBlockWhenDisconnected" => reply(rpc.set_block_when_disconnected(arg(body)?).await),
+        "SetAutoConnect" => reply(rpc.set_auto_connect(arg(body)?).await),
+        "SetEnableIpv6" => reply(rpc.set_enable_ipv6(arg(body)?).await),
+        "SetQuantumResistantTunnel" => reply(rpc.set_quantum_resistant_tunnel(arg(body)?).await),
+        "SetWireguardMtu" => reply(rpc.set_wireguard_mtu(arg(body)?).await),
+        "SetWireguardRotationInterval" => {
+            reply(rpc.set_wireguard_rotation_interval(arg(body)?).await)
+        }
+        "ResetWireguardRotationInterval" => reply(rpc.reset_wireguard_rotation_interval().await),
+        "SetOpenvpnMssfix" => reply(rpc.set_openvpn_mssfix(arg(body)?).await),
+        "SetDnsOptions" => reply(rpc.set_dns_options(arg(body)?).await),
+        "SetNetworkRules" => reply(rpc.set_network_rules(arg(body)?).await),
+        "SetExpiryWarningSettings" => reply(rpc.set_expiry_warning_settings(arg(body)?).await),
+        "GetCurrentNetwork" => reply(rpc.get_current_network().await),
+
+        "CreateNewAccount" => reply(rpc.create_new_account().await),
+        "LoginAccount" => reply(rpc.login_account(arg(body)?).await),
+        "LogoutAccount" => reply(rpc.logout_account().await),
+        "GetAccountData" => reply(rpc.get_account_data(arg(body)?).await),
+        "GetAccountHistory" => reply(rpc.get_account_history().await),
+        "ClearAccountHistory" => reply(rpc.clear_account_history().await),
+        "GetVoucherHistory" => reply(rpc.get_voucher_history().await),
+        "SubmitVoucher" => reply(rpc.submit_voucher(arg(body)?).await),
+
+        "GetDevice" => reply(rpc.get_device().await),
+        "UpdateDevice" => reply(rpc.update_device().await),
+        "ListDevices" => reply(rpc.list_devices(arg(body)?).await),
+        "RemoveDevice" => {
+            let RemoveDevice {
+                account_number,
+                device_id,
+            } = arg(body)?;
+            reply(rpc.remove_device(account_number, device_id).await)
+        }
+        "SetDeviceLabel" => reply(rpc.set_device_label(arg(body)?).await),
+        "GetDeviceLabels" => reply(rpc.get_device_labels().await),
+        "RotateWireguardKey" => reply(rpc.rotate_wireguard_key().await),
+        "GetWireguardKey" => reply(rpc.get_wireguard_key().await),
+
+        "CreateCustomList" => reply(rpc.create_custom_list(arg(body)?).await),
+        "DeleteCustomList" => reply(rpc.delete_custom_list(arg(body)?).await),
+        "UpdateCustomList" => reply(rpc.update_custom_list(arg(body)?).await),
+        "ClearCustomLists" => reply(rpc.clear_custom_lists().await),
+
+        "AddApiAccessMethod" => {
+            let NewAccessMethod {
+                name,
+                enabled,
+                access_method,
+            } = arg(body)?;
+            reply(rpc.add_access_method(name, enabled, access_method).await)
+        }
+        "RemoveApiAccessMethod" => reply(
+            rpc.remove_access_method(arg::<access_method::Id>(body)?)
+                .await,
+        ),
+        "SetApiAccessMethod" => reply(rpc.set_access_method(arg(body)?).await),
+        "UpdateApiAccessMethod" => reply(rpc.update_access_method(arg(body)?).await),
+        "ClearCustomApiAccessMethods" => reply(rpc.clear_custom_access_methods().await),
+        "GetCurrentApiAccessMethod" => reply(rpc.get_current_api_access_method().await),
+        "GetApiAccessMethodStatistics" => reply(rpc.get_api_access_method_statistics().await),
+        "TestApiAccessMethodById" => reply(rpc.test_api_access_method(arg(body)?).await),
+        "TestCustomApiAccessMethod" => reply(rpc.test_custom_api_access_method(arg(body)?).await),
+        "SetSystemProxyPacUrl" => reply(rpc.set_system_proxy_pac_url(arg(body)?).await),
+        "SetEncryptedDnsProxyResolvers" => {
+            reply(rpc.set_encrypted_dns_proxy_resolvers(arg(body)?).await)
+        }
+        "GetEncryptedDnsProxyConfigs" => reply(rpc.get_encrypted_dns_proxy_configs().await),
+        "RefreshEncryptedDnsProxyConfigs" => reply(rpc.refresh_encrypted_dns_proxy_configs().await),
+        "SetTorSettings" => reply(rpc.set_tor_settings(arg(body)?).await),
+
+        "GetSplitTunnelProcesses" => reply(rpc.get_split_tunnel_processes().await),
+        "AddSplitTunnelProcess" => reply(rpc.add_split_tunnel_process(arg(body)?).await),
+        "RemoveSplitTunnelProcess" => reply(rpc.remove_split_tunnel_process(arg(body)?).await),
+        "ClearSplitTunnelProcesses" => reply(rpc.clear_split_tunnel_processes().await),
+        "SetSplitTunnelState" => reply(rpc.set_split_tunnel_state(arg(body)?).await),
+
+        "ApplyJsonSettings" => reply(rpc.apply_json_settings(arg(body)?).await),
+        "DiffJsonSettings" => reply(rpc.diff_json_settings(arg(body)?).await),
+        "ExportJsonSettings" => reply(rpc.export_json_settings().await),
+        "ExportSettingsBackup" => reply(rpc.export_settings_backup().await),
+        "ImportSettingsBackup" => reply(rpc.import_settings_backup(arg(body)?).await),
+        "CreateSettingsProfile" => reply(rpc.create_settings_profile(arg(body)?).await),
+        "GetSettingsProfiles" => reply(rpc.get_settings_profiles().await),
+        "ApplySettingsProfile" => reply(rpc.apply_settings_profile(arg(body)?).await),
+        "DeleteSettingsProfile" => reply(rpc.delete_settings_profile(arg(body)?).await),
+        "GetSettingsPolicy" => reply(rpc.get_settings_policy().await),
+        "GetEventHooks" => reply(rpc.get_event_hooks().await),
+
+        "RunSpeedTest" => {
+            let SpeedTest {
+                endpoint,
+                duration_secs,
+            } = arg(body)?;
+            reply(
+                rpc.run_speed_test(endpoint, Duration::from_secs(duration_secs))
+                    .await,
+            )
+        }
+
+        _ => Err(Error::UnknownMethod(method.to_owned())),
+    }
+}
+
+/// Parse the argument of an RPC from the request body.
+fn arg<T: DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+    serde_json::from_slice(body).map_err(Error::InvalidArgument)
+}
+
+/// Respond with the result of an RPC. RPCs that do not return anything result in `null`.
+fn reply<T: Serialize>(
+    result: mullvad_management_interface::client::Result<T>,
+) -> Result<Response<Body>, Error> {
+    let value = result.map_err(Error::Rpc)?;
+    Ok(json_response(StatusCode::OK, &value))
+}
+
+pub fn json_response<T: Serialize + ?Sized>(status: StatusCode, value: &T) -> Response<Body> {
+    // Serializing the types returned by the daemon does not fail
+    let json = serde_json::to_vec(value).expect("failed to serialize response");
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(json)).boxed_unsync())
+        .unwrap()
+}
+
+/// Stream daemon events as Server-Sent Events. The name of each event is the kind of event, e.g.
+/// `tunnel_state`, and the data is the JSON encoded event.
+fn events_listen(mut rpc: MullvadProxyClient) -> Response<Body> {
+    let (mut tx, rx) = mpsc::channel::<Bytes>(EVENT_BUFFER_SIZE);
+
+    tokio::spawn(async move {
+        let events = match rpc.events_listen().await {
+            Ok(events) => events,
+            Err(error) => {
+                let _ = tx.send(sse_error(&error)).await;
+                return;
+            }
+        };
+        futures::pin_mut!(events);
+        while let Some(event) = events.next().await {
+            let message = match event {
+                Ok(event) => sse_event(&event),
+                Err(error) => sse_error(&error),
+            };
+            // The client has disconnected if the receiver is gone
+            if tx.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let frames = rx.map(|message| Ok::<_, Infallible>(Frame::data(message)));
+    Response::builder()
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(StreamBody::new(frames).boxed_unsync())
+        .unwrap()
+}
+
+fn sse_event(event: &DaemonEvent) -> Bytes {
+    let (name, data) = match event {
+        DaemonEvent::TunnelState(state) => ("tunnel_state", serde_json::to_string(state)),
+        DaemonEvent::Settings(settings) => ("settings", serde_json::to_string(settings)),
+        DaemonEvent::RelayList(relay_list) => ("relay_list", serde_json::to_string(relay_list)),
+        DaemonEvent::AppVersionInfo(info) => ("app_version_info", serde_json::to_string(info)),
+        DaemonEvent::Device(device) => ("device", serde_json::to_string(device)),
+        DaemonEvent::RemoveDevice(device) => ("remove_device", serde_json::to_string(device)),
+        DaemonEvent::NewAccessMethod(access_method) => {
+            ("new_access_method", serde_json::to_string(access_method))
+        }
+        DaemonEvent::AccountExpiryWarning(warning) => {
+            ("account_expiry_warning", serde_json::to_string(warning))
+        }
+    };
+    let data = data.expect("failed to serialize event");
+    Bytes::from(format!("event: {name}\ndata: {data}\n\n"))
+}
+
+fn sse_error(error: &mullvad_management_interface::Error) -> Bytes {
+    let data = serde_json::json!({ "error": error.to_string() });
+    Bytes::from(format!("event: error\ndata: {data}\n\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_management_interface::Status;
+
+    #[test]
+    fn test_error_status_code() {
+        let rpc_error = |code| {
+            Error::Rpc(mullvad_management_interface::Error::Rpc(Status::new(
+                code, "",
+            )))
+        };
+
+        assert_eq!(
+            Error::UnknownMethod("Foo".to_owned()).status_code(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            arg::<bool>(b"\"yes\"").unwrap_err().status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            rpc_error(Code::PermissionDenied).status_code(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            rpc_error(Code::FailedPrecondition).status_code(),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            Error::Rpc(mullvad_management_interface::Error::TooManyDevices).status_code(),
+            StatusCode::CONFLICT
+        );
+    }
+
+    #[test]
+    fn test_parse_argument() {
+        assert!(arg::<bool>(b"true").unwrap());
+        assert_eq!(arg::<String>(b"\"1234\"").unwrap(), "1234");
+
+        let speed_test: SpeedTest =
+            arg(br#"{ "endpoint": "192.0.2.1:5201", "duration_secs": 5 }"#).unwrap();
+        assert_eq!(speed_test.endpoint, "192.0.2.1:5201");
+        assert_eq!(speed_test.duration_secs, 5);
+        arg::<SpeedTest>(br#"{ "duration_secs": 5, "foo": 1 }"#).unwrap_err();
+    }
+}
@@ -1,5 +1,11 @@
+use chrono::{offset::Utc, DateTime};
 use serde::{Deserialize, Serialize};
-use talpid_types::net::proxy::{CustomProxy, Shadowsocks, Socks5Local, Socks5Remote};
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+use talpid_types::net::proxy::{CustomProxy, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote};
 
 /// Settings for API access methods.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -10,22 +16,58 @@ pub struct Settings {
     mullvad_bridges: AccessMethodSetting,
     #[serde(default = "Settings::create_encrypted_dns_proxy")]
     encrypted_dns_proxy: AccessMethodSetting,
+    #[serde(default = "Settings::create_system_proxy")]
+    system_proxy: AccessMethodSetting,
+    #[serde(default = "Settings::create_domain_fronting")]
+    domain_fronting: AccessMethodSetting,
+    #[serde(default = "Settings::create_tor")]
+    tor: AccessMethodSetting,
     /// Custom API access methods.
     custom: Vec<AccessMethodSetting>,
+    /// URL of a PAC file to read proxies from when using the system proxy access method. If
+    /// unset, no PAC file is used.
+    #[serde(default)]
+    pac_url: Option<String>,
+    /// Hostnames of CDN fronts to use with the domain fronting access method, in addition to the
+    /// ones in the relay list.
+    #[serde(default)]
+    domain_fronts: Vec<String>,
+    /// DoH resolvers used to fetch Encrypted DNS proxy configurations, in addition to the
+    /// default ones.
+    #[serde(default)]
+    doh_resolvers: Vec<DohResolver>,
+    /// Bridge used by the Tor access method.
+    #[serde(default)]
+    tor_settings: TorSettings,
 }
 
 impl Settings {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         direct: AccessMethodSetting,
         mullvad_bridges: AccessMethodSetting,
         encrypted_dns_proxy: AccessMethodSetting,
+        system_proxy: AccessMethodSetting,
+        domain_fronting: AccessMethodSetting,
+        tor: AccessMethodSetting,
         custom: Vec<AccessMethodSetting>,
+        pac_url: Option<String>,
+        domain_fronts: Vec<String>,
+        doh_resolvers: Vec<DohResolver>,
+        tor_settings: TorSettings,
     ) -> Settings {
         Settings {
             direct,
             mullvad_bridges,
             encrypted_dns_proxy,
+            system_proxy,
+            domain_fronting,
+            tor,
             custom,
+            pac_url,
+            domain_fronts,
+            doh_resolvers,
+            tor_settings,
         }
     }
 
@@ -100,6 +142,9 @@ impl Settings {
         once(&self.direct)
             .chain(once(&self.mullvad_bridges))
             .chain(once(&self.encrypted_dns_proxy))
+            .chain(once(&self.system_proxy))
+            .chain(once(&self.domain_fronting))
+            .chain(once(&self.tor))
             .chain(&self.custom)
     }
 
@@ -109,6 +154,9 @@ impl Settings {
         once(&mut self.direct)
             .chain(once(&mut self.mullvad_bridges))
             .chain(once(&mut self.encrypted_dns_proxy))
+            .chain(once(&mut self.system_proxy))
+            .chain(once(&mut self.domain_fronting))
+            .chain(once(&mut self.tor))
             .chain(&mut self.custom)
     }
 
@@ -135,6 +183,52 @@ impl Settings {
         &self.encrypted_dns_proxy
     }
 
+    pub fn system_proxy(&self) -> &AccessMethodSetting {
+        &self.system_proxy
+    }
+
+    pub fn domain_fronting(&self) -> &AccessMethodSetting {
+        &self.domain_fronting
+    }
+
+    pub fn tor(&self) -> &AccessMethodSetting {
+        &self.tor
+    }
+
+    /// URL of the PAC file used by the system proxy access method, if one has been configured.
+    pub fn pac_url(&self) -> Option<&str> {
+        self.pac_url.as_deref()
+    }
+
+    /// Set or clear the URL of the PAC file used by the system proxy access method.
+    pub fn set_pac_url(&mut self, pac_url: Option<String>) {
+        self.pac_url = pac_url;
+    }
+
+    /// Hostnames of the CDN fronts configured for the domain fronting access method.
+    pub fn domain_fronts(&self) -> &[String] {
+        &self.domain_fronts
+    }
+
+    /// DoH resolvers configured for the Encrypted DNS proxy access method.
+    pub fn doh_resolvers(&self) -> &[DohResolver] {
+        &self.doh_resolvers
+    }
+
+    pub fn set_doh_resolvers(&mut self, doh_resolvers: Vec<DohResolver>) {
+        self.doh_resolvers = doh_resolvers;
+    }
+
+    /// Bridge configured for the Tor access method.
+    pub fn tor_settings(&self) -> &TorSettings {
+        &self.tor_settings
+    }
+
+    /// Set the bridge used by the Tor access method.
+    pub fn set_tor_settings(&mut self, tor_settings: TorSettings) {
+        self.tor_settings = tor_settings;
+    }
+
     fn create_direct() -> AccessMethodSetting {
         let method = BuiltInAccessMethod::Direct;
         AccessMethodSetting::new(method.canonical_name(), true, AccessMethod::from(method))
@@ -149,6 +243,26 @@ impl Settings {
         let method = BuiltInAccessMethod::EncryptedDnsProxy;
         AccessMethodSetting::new(method.canonical_name(), true, AccessMethod::from(method))
     }
+
+    /// The system proxy access method sends API traffic to proxies found in the environment or a
+    /// PAC file, so it is disabled by default.
+    fn create_system_proxy() -> AccessMethodSetting {
+        let method = BuiltInAccessMethod::SystemProxy;
+        AccessMethodSetting::new(method.canonical_name(), false, AccessMethod::from(method))
+    }
+
+    /// Domain fronting sends API traffic through a third-party CDN, so it is disabled by default.
+    fn create_domain_fronting() -> AccessMethodSetting {
+        let method = BuiltInAccessMethod::DomainFronting;
+        AccessMethodSetting::new(method.canonical_name(), false, AccessMethod::from(method))
+    }
+
+    /// The Tor access method is only available in builds with Tor support, so it is disabled by
+    /// default.
+    fn create_tor() -> AccessMethodSetting {
+        let method = BuiltInAccessMethod::Tor;
+        AccessMethodSetting::new(method.canonical_name(), false, AccessMethod::from(method))
+    }
 }
 
 impl Default for Settings {
@@ -157,7 +271,14 @@ impl Default for Settings {
             direct: Settings::create_direct(),
             mullvad_bridges: Settings::create_mullvad_bridges(),
             encrypted_dns_proxy: Settings::create_encrypted_dns_proxy(),
+            system_proxy: Settings::create_system_proxy(),
+            domain_fronting: Settings::create_domain_fronting(),
+            tor: Settings::create_tor(),
             custom: vec![],
+            pac_url: None,
+            domain_fronts: vec![],
+            doh_resolvers: vec![],
+            tor_settings: TorSettings::default(),
         }
     }
 }
@@ -181,7 +302,7 @@ pub struct AccessMethodSetting {
     pub access_method: AccessMethod,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Id(uuid::Uuid);
 
 impl Id {
@@ -203,6 +324,95 @@ impl std::fmt::Display for Id {
     }
 }
 
+/// How well an access method has worked on the current network.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessMethodStatistics {
+    /// Number of API requests that reached the API using the access method.
+    pub successes: u32,
+    /// Number of times that the access method failed to reach the API.
+    pub failures: u32,
+    /// Number of failures since the last success.
+    pub consecutive_failures: u32,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    /// Smoothed duration of successful API requests.
+    pub latency: Option<Duration>,
+}
+
+/// The statistics of an access method, as returned in the order that the access methods will be
+/// tried in.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccessMethodStatus {
+    pub id: Id,
+    pub name: String,
+    pub enabled: bool,
+    /// Whether this is the access method that is currently used.
+    pub active: bool,
+    pub statistics: AccessMethodStatistics,
+}
+
+/// A DNS-over-HTTPS resolver used to fetch Encrypted DNS proxy configurations.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DohResolver {
+    /// Hostname that the resolver's certificate is valid for.
+    pub name: String,
+    /// Addresses to reach the resolver on. Port 443 is always used.
+    pub addrs: Vec<IpAddr>,
+}
+
+/// An Encrypted DNS proxy configuration that is cached by the daemon.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EncryptedDnsProxyConfigInfo {
+    pub endpoint: SocketAddr,
+    /// Name of the obfuscation used by the proxy, if any.
+    pub obfuscation: Option<String>,
+    /// When the configuration was last received from a DoH resolver.
+    pub fetched: DateTime<Utc>,
+    /// When the API was last reached using this configuration.
+    pub last_success: Option<DateTime<Utc>>,
+    /// When the configuration is removed from the cache, unless it is fetched or used again.
+    pub expires: DateTime<Utc>,
+}
+
+/// Bridge used by the Tor access method.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TorSettings {
+    /// Bridge line, in the format used by Tor Browser, e.g.
+    /// `obfs4 192.0.2.1:443 <fingerprint> cert=<cert> iat-mode=0`. The Tor access method is not
+    /// used unless a bridge is set. Only one bridge is supported, since the firewall only lets
+    /// the daemon reach one endpoint while it is blocking traffic.
+    pub bridge: Option<String>,
+}
+
+/// A pluggable transport binary, such as lyrebird. Transports are only read from a file that may
+/// only be edited by root, since the daemon runs them with its own privileges.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TorTransport {
+    /// Names of the transports that the binary provides, e.g. `obfs4`.
+    pub protocols: Vec<String>,
+    /// Absolute path to the binary.
+    pub path: PathBuf,
+    /// Arguments to pass to the binary.
+    #[serde(default)]
+    pub arguments: Vec<String>,
+}
+
+impl TorSettings {
+    /// Address of the bridge, which is the only endpoint that the Tor client connects to.
+    pub fn bridge_addr(&self) -> Option<SocketAddr> {
+        self.bridge.as_deref().and_then(bridge_addr)
+    }
+}
+
+/// Return the address in a bridge line. The address is preceded by the transport name, if any,
+/// and an optional `Bridge` keyword, as used in torrc files.
+pub fn bridge_addr(line: &str) -> Option<SocketAddr> {
+    let mut words = line.split_whitespace().peekable();
+    words.next_if(|word| word.eq_ignore_ascii_case("bridge"));
+    words.take(2).find_map(|word| word.parse().ok())
+}
+
 /// Access Method datastructure.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Hash)]
 #[serde(rename_all = "snake_case")]
@@ -288,6 +498,14 @@ pub enum BuiltInAccessMethod {
     Direct,
     Bridge,
     EncryptedDnsProxy,
+    /// Proxies advertised by the system or the network, through environment variables, a PAC
+    /// file or WPAD.
+    SystemProxy,
+    /// Connect to a CDN front using its own hostname as SNI, while requesting the API host in
+    /// the HTTP `Host` header.
+    DomainFronting,
+    /// Tunnel API requests through the Tor network, using an embedded Tor client.
+    Tor,
 }
 
 impl AccessMethod {
@@ -305,6 +523,9 @@ impl BuiltInAccessMethod {
             BuiltInAccessMethod::Direct => "Direct".to_string(),
             BuiltInAccessMethod::Bridge => "Mullvad Bridges".to_string(),
             BuiltInAccessMethod::EncryptedDnsProxy => "Encrypted DNS proxy".to_string(),
+            BuiltInAccessMethod::SystemProxy => "System proxy".to_string(),
+            BuiltInAccessMethod::DomainFronting => "Domain fronting".to_string(),
+            BuiltInAccessMethod::Tor => "Tor".to_string(),
         }
     }
 }
@@ -344,3 +565,43 @@ impl From<Shadowsocks> for AccessMethod {
         CustomProxy::Shadowsocks(value).into()
     }
 }
+
+impl From<HttpConnect> for AccessMethod {
+    fn from(value: HttpConnect) -> Self {
+        CustomProxy::HttpConnect(value).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bridge_addr() {
+        let fingerprint = "0123456789ABCDEF0123456789ABCDEF01234567";
+        let lines = [
+            format!("192.0.2.1:443 {fingerprint}"),
+            format!("Bridge 192.0.2.1:443 {fingerprint}"),
+            format!("obfs4 192.0.2.1:443 {fingerprint} cert=abc iat-mode=0"),
+            format!("bridge obfs4 192.0.2.1:443 {fingerprint} cert=abc iat-mode=0"),
+        ];
+        for line in lines {
+            assert_eq!(bridge_addr(&line), Some("192.0.2.1:443".parse().unwrap()));
+        }
+        assert_eq!(
+            bridge_addr("obfs4 [2001:db8::1]:9001 fingerprint"),
+            Some("[2001:db8::1]:9001".parse().unwrap())
+        );
+        assert_eq!(bridge_addr("obfs4 bridge.example.com:443"), None);
+        assert_eq!(bridge_addr(""), None);
+    }
+
+    #[test]
+    fn test_tor_is_disabled_by_default() {
+        let settings = Settings::default();
+        assert!(settings.tor().disabled());
+        assert!(settings
+            .iter()
+            .any(|setting| setting.get_id() == settings.tor().get_id()));
+    }
+}
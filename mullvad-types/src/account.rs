@@ -20,6 +20,10 @@ pub type PlayPurchasePaymentToken = String;
 pub struct AccountData {
     pub id: AccountId,
     pub expiry: DateTime<Utc>,
+    /// Set if the API could not be reached and this is the last known data, in which case it
+    /// contains the time when the data was fetched.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_at: Option<DateTime<Utc>>,
 }
 
 impl AccountData {
@@ -29,6 +33,15 @@ impl AccountData {
     }
 }
 
+/// Emitted when the time left on the account drops below one of the thresholds in
+/// [`ExpiryWarningSettings`](crate::settings::ExpiryWarningSettings).
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AccountExpiryWarning {
+    pub expiry: DateTime<Utc>,
+    /// The threshold that was crossed.
+    pub threshold: std::time::Duration,
+}
+
 /// Data structure that's returned from successful invocation of the mullvad API's
 /// `/v1/submit-voucher` RPC.
 #[derive(Deserialize, Serialize, Debug)]
@@ -39,6 +52,17 @@ pub struct VoucherSubmission {
     pub new_expiry: DateTime<Utc>,
 }
 
+/// A voucher that was redeemed on this device.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RedeemedVoucher {
+    /// When the voucher was redeemed
+    pub redeemed: DateTime<Utc>,
+    /// Amount of time added to the account, in seconds
+    pub time_added: u64,
+    /// Expiry after the voucher was redeemed
+    pub new_expiry: DateTime<Utc>,
+}
+
 /// `PlayPurchase` is provided to google in order to verify that a google play purchase was
 /// acknowledged.
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -40,6 +40,24 @@ impl Device {
     }
 }
 
+/// A label and note for a device, stored locally by the daemon. Device names are assigned by the
+/// API, so this is how users can tell their devices apart.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DeviceLabel {
+    pub device_id: DeviceId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl DeviceLabel {
+    /// Return true if neither a label nor a note is set.
+    pub fn is_empty(&self) -> bool {
+        self.label.is_none() && self.note.is_none()
+    }
+}
+
 /// Contains a device state.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
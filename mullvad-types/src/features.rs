@@ -80,6 +80,7 @@ pub enum FeatureIndicator {
     CustomMtu,
     CustomMssFix,
     Daita,
+    RelayRotation,
 }
 
 impl FeatureIndicator {
@@ -99,6 +100,7 @@ impl FeatureIndicator {
             FeatureIndicator::CustomMtu => "Custom MTU",
             FeatureIndicator::CustomMssFix => "Custom MSS",
             FeatureIndicator::Daita => "DAITA",
+            FeatureIndicator::RelayRotation => "Relay Rotation",
         }
     }
 }
@@ -135,6 +137,7 @@ pub fn compute_feature_indicators(
         .default_options
         .any_blockers_enabled();
     let custom_dns = settings.tunnel_options.dns_options.state == DnsState::Custom;
+    let relay_rotation = settings.relay_rotation.is_enabled();
 
     let generic_features = [
         (split_tunneling, FeatureIndicator::SplitTunneling),
@@ -143,6 +146,7 @@ pub fn compute_feature_indicators(
         (dns_content_blockers, FeatureIndicator::DnsContentBlockers),
         (custom_dns, FeatureIndicator::CustomDns),
         (server_ip_override, FeatureIndicator::ServerIpOverride),
+        (relay_rotation, FeatureIndicator::RelayRotation),
     ];
 
     // Pick protocol-specific features and whether they are currently enabled.
@@ -224,6 +228,7 @@ mod tests {
             proxy: Default::default(),
             obfuscation: Default::default(),
             entry_endpoint: Default::default(),
+            ip_version: Default::default(),
             tunnel_interface: Default::default(),
             daita: Default::default(),
         };
@@ -418,6 +423,13 @@ mod tests {
             );
         }
 
+        settings.relay_rotation.interval = Some(std::time::Duration::from_secs(60 * 60));
+        expected_indicators.0.insert(FeatureIndicator::RelayRotation);
+        assert_eq!(
+            compute_feature_indicators(&settings, &endpoint, false),
+            expected_indicators
+        );
+
         // NOTE: If this match statement fails to compile, it means that a new feature indicator has
         // been added. Please update this test to include the new feature indicator.
         match FeatureIndicator::QuantumResistance {
@@ -435,6 +447,7 @@ mod tests {
             FeatureIndicator::CustomMtu => {}
             FeatureIndicator::CustomMssFix => {}
             FeatureIndicator::Daita => {}
+            FeatureIndicator::RelayRotation => {}
         }
     }
 }
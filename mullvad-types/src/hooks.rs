@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::PathBuf, time::Duration};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+
+/// An executable that the daemon runs when certain events happen. The event is written to its
+/// standard input as JSON.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct EventHook {
+    pub path: PathBuf,
+    /// Events that the hook is run for. If this is empty, it is run for every event.
+    #[serde(default)]
+    pub events: Vec<HookEvent>,
+    /// How long the hook may run before it is killed, in seconds.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+/// Kind of event that an [`EventHook`] may be run for.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    TunnelState,
+    Device,
+    Settings,
+    AccountExpiryWarning,
+}
+
+impl EventHook {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    /// Returns whether the hook should be run for `event`.
+    pub fn handles(&self, event: HookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookEvent::TunnelState => f.write_str("tunnel_state"),
+            HookEvent::Device => f.write_str("device"),
+            HookEvent::Settings => f.write_str("settings"),
+            HookEvent::AccountExpiryWarning => f.write_str("account_expiry_warning"),
+        }
+    }
+}
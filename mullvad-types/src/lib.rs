@@ -6,10 +6,15 @@ pub mod custom_list;
 pub mod device;
 pub mod endpoint;
 pub mod features;
+pub mod hooks;
 pub mod location;
+pub mod network_rules;
+pub mod policy;
+pub mod profile;
 pub mod relay_constraints;
 pub mod relay_list;
 pub mod settings;
+pub mod speed_test;
 pub mod states;
 pub mod version;
 pub mod wireguard;
@@ -0,0 +1,260 @@
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use talpid_types::net::NetworkIdentity;
+
+/// Rules for what the daemon should do when the host joins a network. Only networks that can be
+/// identified by the platform are matched, which currently means Linux.
+///
+/// None of the properties that networks are identified by are authenticated. Any network can use
+/// the SSID, gateway address or gateway MAC address of another network, and a NetworkManager
+/// connection may be activated for any network with the same SSID. Disconnecting therefore has to
+/// be allowed explicitly with [`NetworkRulesSettings::allow_disconnect`], since a hostile network
+/// could otherwise turn the tunnel off by posing as a trusted one.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct NetworkRulesSettings {
+    /// Rules that are matched against the current network, in order. The first matching rule
+    /// decides the action.
+    pub rules: Vec<NetworkRule>,
+    /// Networks that the user has saved as known, without a rule of their own. Networks that
+    /// match a rule are known as well.
+    pub known_networks: Vec<NetworkMatcher>,
+    /// Action for known networks that do not match any rule.
+    pub other_networks: Option<NetworkAction>,
+    /// Action for networks that the user has not saved and that do not match any rule.
+    pub unknown_networks: Option<NetworkAction>,
+    /// Whether [`NetworkAction::Disconnect`] is applied. If not, networks whose action is to
+    /// disconnect are treated as if no action was set.
+    pub allow_disconnect: bool,
+}
+
+impl NetworkRulesSettings {
+    /// Returns the action to take on `network`, or `None` if the current target state should be
+    /// left alone. This is [`NetworkRulesSettings::matching_action`], unless disconnecting has not
+    /// been allowed.
+    pub fn action_for(&self, network: &NetworkIdentity) -> Option<NetworkAction> {
+        self.matching_action(network)
+            .filter(|action| *action != NetworkAction::Disconnect || self.allow_disconnect)
+    }
+
+    /// Returns the action set for `network` by the first matching rule, or by the fallback
+    /// actions.
+    pub fn matching_action(&self, network: &NetworkIdentity) -> Option<NetworkAction> {
+        if !network.is_connected() {
+            return None;
+        }
+        if let Some(rule) = self.rules.iter().find(|rule| rule.matcher.matches(network)) {
+            return Some(rule.action);
+        }
+        if self.is_known(network) {
+            self.other_networks
+        } else {
+            self.unknown_networks
+        }
+    }
+
+    /// Returns whether `network` has been saved by the user, either as a known network or in a
+    /// rule.
+    pub fn is_known(&self, network: &NetworkIdentity) -> bool {
+        self.rules
+            .iter()
+            .map(|rule| &rule.matcher)
+            .chain(&self.known_networks)
+            .any(|matcher| matcher.matches(network))
+    }
+
+    /// Returns whether any rule or fallback action is set.
+    pub fn is_enabled(&self) -> bool {
+        !self.rules.is_empty() || self.other_networks.is_some() || self.unknown_networks.is_some()
+    }
+}
+
+/// Action to take on the networks matched by a [`NetworkRule`].
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum NetworkAction {
+    /// Connect the tunnel.
+    Connect,
+    /// Disconnect the tunnel.
+    Disconnect,
+    /// Block all traffic.
+    Block,
+}
+
+impl fmt::Display for NetworkAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkAction::Connect => f.write_str("connect"),
+            NetworkAction::Disconnect => f.write_str("disconnect"),
+            NetworkAction::Block => f.write_str("block"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct NetworkRule {
+    pub matcher: NetworkMatcher,
+    pub action: NetworkAction,
+}
+
+impl fmt::Display for NetworkRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on {}", self.action, self.matcher)
+    }
+}
+
+/// Property of a network that a [`NetworkRule`] applies to.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkMatcher {
+    /// UUID of a NetworkManager connection.
+    ConnectionId(String),
+    /// SSID of a Wi-Fi network.
+    Ssid(String),
+    /// Hardware address of the default gateway. Compared case-insensitively.
+    GatewayMac(String),
+    /// Subnet that the default gateway is in.
+    Subnet(IpNetwork),
+}
+
+impl NetworkMatcher {
+    pub fn matches(&self, network: &NetworkIdentity) -> bool {
+        match self {
+            NetworkMatcher::ConnectionId(id) => network.connection_id.as_ref() == Some(id),
+            NetworkMatcher::Ssid(ssid) => network.ssid.as_ref() == Some(ssid),
+            NetworkMatcher::GatewayMac(mac) => network
+                .gateway_mac
+                .as_ref()
+                .is_some_and(|gateway_mac| gateway_mac.eq_ignore_ascii_case(mac)),
+            NetworkMatcher::Subnet(subnet) => network
+                .gateway
+                .is_some_and(|gateway| subnet.contains(gateway)),
+        }
+    }
+}
+
+impl fmt::Display for NetworkMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkMatcher::ConnectionId(id) => write!(f, "connection {id}"),
+            NetworkMatcher::Ssid(ssid) => write!(f, "SSID {ssid}"),
+            NetworkMatcher::GatewayMac(mac) => write!(f, "gateway MAC {mac}"),
+            NetworkMatcher::Subnet(subnet) => write!(f, "gateway subnet {subnet}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn office_wifi() -> NetworkIdentity {
+        NetworkIdentity {
+            interface: Some("wlan0".to_owned()),
+            gateway: Some("10.1.0.1".parse().unwrap()),
+            gateway_mac: Some("aa:bb:cc:dd:ee:ff".to_owned()),
+            connection_id: Some("7c4c4b52-3a24-4ad4-9fb5-1f0b8f6f4f2e".to_owned()),
+            ssid: Some("Office".to_owned()),
+        }
+    }
+
+    fn unidentified_network() -> NetworkIdentity {
+        NetworkIdentity {
+            interface: Some("eth0".to_owned()),
+            gateway: Some("192.168.1.1".parse().unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn settings() -> NetworkRulesSettings {
+        NetworkRulesSettings {
+            rules: vec![NetworkRule {
+                matcher: NetworkMatcher::Ssid("Office".to_owned()),
+                action: NetworkAction::Disconnect,
+            }],
+            known_networks: vec![NetworkMatcher::Ssid("Home".to_owned())],
+            other_networks: Some(NetworkAction::Connect),
+            unknown_networks: Some(NetworkAction::Block),
+            allow_disconnect: true,
+        }
+    }
+
+    #[test]
+    fn test_action_for() {
+        let settings = settings();
+
+        assert_eq!(
+            settings.action_for(&office_wifi()),
+            Some(NetworkAction::Disconnect)
+        );
+
+        let home_wifi = NetworkIdentity {
+            ssid: Some("Home".to_owned()),
+            ..office_wifi()
+        };
+        assert_eq!(
+            settings.action_for(&home_wifi),
+            Some(NetworkAction::Connect)
+        );
+
+        assert_eq!(
+            settings.action_for(&unidentified_network()),
+            Some(NetworkAction::Block)
+        );
+
+        // Being offline should never change the target state.
+        assert_eq!(settings.action_for(&NetworkIdentity::default()), None);
+        assert_eq!(
+            NetworkRulesSettings::default().action_for(&office_wifi()),
+            None
+        );
+    }
+
+    /// A network posing as one with a disconnect rule must not be able to turn the tunnel off,
+    /// unless disconnecting has been allowed.
+    #[test]
+    fn test_disconnect_requires_opt_in() {
+        let settings = NetworkRulesSettings {
+            allow_disconnect: false,
+            ..settings()
+        };
+        assert_eq!(
+            settings.matching_action(&office_wifi()),
+            Some(NetworkAction::Disconnect)
+        );
+        assert_eq!(settings.action_for(&office_wifi()), None);
+
+        let settings = NetworkRulesSettings {
+            unknown_networks: Some(NetworkAction::Disconnect),
+            ..settings
+        };
+        assert_eq!(settings.action_for(&unidentified_network()), None);
+        assert!(!NetworkRulesSettings::default().allow_disconnect);
+    }
+
+    /// Having a gateway MAC address or an SSID does not make a network known.
+    #[test]
+    fn test_unsaved_network_is_unknown() {
+        let cafe_wifi = NetworkIdentity {
+            ssid: Some("Cafe".to_owned()),
+            gateway_mac: Some("11:22:33:44:55:66".to_owned()),
+            ..office_wifi()
+        };
+        let settings = settings();
+        assert!(!settings.is_known(&cafe_wifi));
+        assert_eq!(settings.action_for(&cafe_wifi), Some(NetworkAction::Block));
+    }
+
+    #[test]
+    fn test_matchers() {
+        let network = office_wifi();
+
+        assert!(NetworkMatcher::GatewayMac("AA:BB:CC:DD:EE:FF".to_owned()).matches(&network));
+        assert!(NetworkMatcher::Subnet("10.1.0.0/16".parse().unwrap()).matches(&network));
+        assert!(!NetworkMatcher::Subnet("10.2.0.0/16".parse().unwrap()).matches(&network));
+        assert!(!NetworkMatcher::ConnectionId("Office".to_owned()).matches(&network));
+        assert!(!NetworkMatcher::Ssid("Office".to_owned()).matches(&unidentified_network()));
+    }
+}
@@ -0,0 +1,287 @@
+use crate::{
+    constraints::Constraint,
+    custom_list::CustomListsSettings,
+    relay_constraints::{
+        GeographicLocationConstraint, LocationConstraint, RelayConstraints, RelaySettings,
+    },
+    settings::{DnsOptions, Settings},
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Settings that are pinned by an administrator and cannot be changed by users.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SettingsPolicy {
+    pub block_when_disconnected: Option<bool>,
+    pub allow_lan: Option<bool>,
+    pub dns_options: Option<DnsOptions>,
+    /// Locations that relays may be selected from. If this is empty, any location may be used.
+    pub allowed_locations: Vec<GeographicLocationConstraint>,
+}
+
+/// A setting that may be locked by a [`SettingsPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockedSetting {
+    LockdownMode,
+    AllowLan,
+    Dns,
+    RelayLocation,
+}
+
+impl fmt::Display for LockedSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockedSetting::LockdownMode => f.write_str("Lockdown mode"),
+            LockedSetting::AllowLan => f.write_str("Local network sharing"),
+            LockedSetting::Dns => f.write_str("DNS"),
+            LockedSetting::RelayLocation => f.write_str("Relay location"),
+        }
+    }
+}
+
+impl SettingsPolicy {
+    /// Policy to use when a policy exists but cannot be read. Lockdown mode is enabled, local
+    /// network sharing is disabled and the DNS settings in `settings` are kept, since the settings
+    /// that the unreadable policy would have allowed are unknown.
+    pub fn fail_closed(settings: &Settings) -> Self {
+        Self {
+            block_when_disconnected: Some(true),
+            allow_lan: Some(false),
+            dns_options: Some(settings.tunnel_options.dns_options.clone()),
+            allowed_locations: vec![],
+        }
+    }
+
+    /// Returns the settings that are locked by this policy.
+    pub fn locked_settings(&self) -> Vec<LockedSetting> {
+        let mut locked = vec![];
+        if self.block_when_disconnected.is_some() {
+            locked.push(LockedSetting::LockdownMode);
+        }
+        if self.allow_lan.is_some() {
+            locked.push(LockedSetting::AllowLan);
+        }
+        if self.dns_options.is_some() {
+            locked.push(LockedSetting::Dns);
+        }
+        if !self.allowed_locations.is_empty() {
+            locked.push(LockedSetting::RelayLocation);
+        }
+        locked
+    }
+
+    /// Returns a locked setting that `settings` do not comply with, if there is one.
+    pub fn violation(&self, settings: &Settings) -> Option<LockedSetting> {
+        if self
+            .block_when_disconnected
+            .is_some_and(|value| value != settings.block_when_disconnected)
+        {
+            return Some(LockedSetting::LockdownMode);
+        }
+        if self
+            .allow_lan
+            .is_some_and(|value| value != settings.allow_lan)
+        {
+            return Some(LockedSetting::AllowLan);
+        }
+        if self
+            .dns_options
+            .as_ref()
+            .is_some_and(|value| *value != settings.tunnel_options.dns_options)
+        {
+            return Some(LockedSetting::Dns);
+        }
+        if !self.permits_relay_settings(&settings.relay_settings, &settings.custom_lists) {
+            return Some(LockedSetting::RelayLocation);
+        }
+        None
+    }
+
+    /// Change `settings` so that they comply with the policy.
+    pub fn enforce(&self, settings: &mut Settings) {
+        if let Some(block_when_disconnected) = self.block_when_disconnected {
+            settings.block_when_disconnected = block_when_disconnected;
+        }
+        if let Some(allow_lan) = self.allow_lan {
+            settings.allow_lan = allow_lan;
+        }
+        if let Some(dns_options) = &self.dns_options {
+            settings.tunnel_options.dns_options = dns_options.clone();
+        }
+
+        let Some(fallback) = self.allowed_locations.first() else {
+            return;
+        };
+        let fallback = Constraint::Only(LocationConstraint::Location(fallback.clone()));
+        if !matches!(settings.relay_settings, RelaySettings::Normal(_)) {
+            settings.relay_settings = RelaySettings::Normal(RelayConstraints::default());
+        }
+        if let RelaySettings::Normal(constraints) = &mut settings.relay_settings {
+            if !self.permits_location(&constraints.location, &settings.custom_lists) {
+                constraints.location = fallback.clone();
+            }
+            let wireguard = &mut constraints.wireguard_constraints;
+            if wireguard.use_multihop
+                && !self.permits_location(&wireguard.entry_location, &settings.custom_lists)
+            {
+                wireguard.entry_location = fallback;
+            }
+        }
+    }
+
+    fn permits_relay_settings(
+        &self,
+        relay_settings: &RelaySettings,
+        custom_lists: &CustomListsSettings,
+    ) -> bool {
+        if self.allowed_locations.is_empty() {
+            return true;
+        }
+        match relay_settings {
+            RelaySettings::CustomTunnelEndpoint(_) => false,
+            RelaySettings::Normal(constraints) => {
+                let wireguard = &constraints.wireguard_constraints;
+                self.permits_location(&constraints.location, custom_lists)
+                    && (!wireguard.use_multihop
+                        || self.permits_location(&wireguard.entry_location, custom_lists))
+            }
+        }
+    }
+
+    /// Returns whether every relay matched by `location` is in an allowed location.
+    fn permits_location(
+        &self,
+        location: &Constraint<LocationConstraint>,
+        custom_lists: &CustomListsSettings,
+    ) -> bool {
+        if self.allowed_locations.is_empty() {
+            return true;
+        }
+        let is_allowed = |location: &GeographicLocationConstraint| {
+            self.allowed_locations
+                .iter()
+                .any(|allowed| location.is_within(allowed))
+        };
+        match location {
+            Constraint::Any => false,
+            Constraint::Only(LocationConstraint::Location(location)) => is_allowed(location),
+            Constraint::Only(LocationConstraint::CustomList { list_id }) => custom_lists
+                .iter()
+                .find(|list| list.id == *list_id)
+                .is_some_and(|list| {
+                    !list.locations.is_empty() && list.locations.iter().all(is_allowed)
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::custom_list::CustomList;
+
+    fn relay_location(settings: &mut Settings) -> &mut Constraint<LocationConstraint> {
+        match &mut settings.relay_settings {
+            RelaySettings::Normal(constraints) => &mut constraints.location,
+            RelaySettings::CustomTunnelEndpoint(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_pinned_values() {
+        let policy = SettingsPolicy {
+            block_when_disconnected: Some(true),
+            allow_lan: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.locked_settings(),
+            vec![LockedSetting::LockdownMode, LockedSetting::AllowLan]
+        );
+
+        let mut settings = Settings {
+            allow_lan: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.violation(&settings),
+            Some(LockedSetting::LockdownMode)
+        );
+        policy.enforce(&mut settings);
+        assert_eq!(policy.violation(&settings), None);
+        assert!(settings.block_when_disconnected);
+        assert!(!settings.allow_lan);
+    }
+
+    #[test]
+    fn test_allowed_locations() {
+        let policy = SettingsPolicy {
+            allowed_locations: vec![
+                GeographicLocationConstraint::country("se"),
+                GeographicLocationConstraint::city("de", "ber"),
+            ],
+            ..Default::default()
+        };
+
+        let mut settings = Settings::default();
+        *relay_location(&mut settings) = Constraint::Any;
+        assert_eq!(
+            policy.violation(&settings),
+            Some(LockedSetting::RelayLocation)
+        );
+
+        let allowed = [
+            GeographicLocationConstraint::country("se"),
+            GeographicLocationConstraint::city("se", "got"),
+            GeographicLocationConstraint::hostname("de", "ber", "de-ber-wg-001"),
+        ];
+        for location in allowed {
+            *relay_location(&mut settings) =
+                Constraint::Only(LocationConstraint::Location(location));
+            assert_eq!(policy.violation(&settings), None);
+        }
+
+        let prohibited = [
+            GeographicLocationConstraint::country("de"),
+            GeographicLocationConstraint::city("de", "fra"),
+        ];
+        for location in prohibited {
+            *relay_location(&mut settings) =
+                Constraint::Only(LocationConstraint::Location(location));
+            assert_eq!(
+                policy.violation(&settings),
+                Some(LockedSetting::RelayLocation)
+            );
+        }
+
+        policy.enforce(&mut settings);
+        assert_eq!(policy.violation(&settings), None);
+    }
+
+    #[test]
+    fn test_allowed_custom_list() {
+        let policy = SettingsPolicy {
+            allowed_locations: vec![GeographicLocationConstraint::country("se")],
+            ..Default::default()
+        };
+
+        let mut list = CustomList::new("nordic".to_owned()).unwrap();
+        list.locations
+            .insert(GeographicLocationConstraint::city("se", "got"));
+        let list_id = list.id;
+        let mut settings = Settings::default();
+        settings.custom_lists.add(list).unwrap();
+        *relay_location(&mut settings) =
+            Constraint::Only(LocationConstraint::CustomList { list_id });
+        assert_eq!(policy.violation(&settings), None);
+
+        settings.custom_lists[0]
+            .locations
+            .insert(GeographicLocationConstraint::country("no"));
+        assert_eq!(
+            policy.violation(&settings),
+            Some(LockedSetting::RelayLocation)
+        );
+    }
+}
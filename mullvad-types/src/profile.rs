@@ -0,0 +1,216 @@
+use crate::{
+    constraints::Constraint,
+    custom_list,
+    relay_constraints::{BridgeState, LocationConstraint, ObfuscationSettings, RelaySettings},
+    settings::{DnsOptions, Settings},
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A named set of settings that can be applied all at once. Settings that are not part of the
+/// profile are left alone when it is applied.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct SettingsProfile {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay_settings: Option<RelaySettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obfuscation_settings: Option<ObfuscationSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bridge_state: Option<BridgeState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_lan: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_when_disconnected: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns_options: Option<DnsOptions>,
+}
+
+/// Group of settings that may be part of a [`SettingsProfile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum ProfileField {
+    /// Relay constraints, including multihop
+    Relay,
+    Obfuscation,
+    Bridge,
+    /// Local network sharing
+    Lan,
+    /// Lockdown mode
+    Lockdown,
+    Dns,
+}
+
+impl ProfileField {
+    pub const ALL: [ProfileField; 6] = [
+        ProfileField::Relay,
+        ProfileField::Obfuscation,
+        ProfileField::Bridge,
+        ProfileField::Lan,
+        ProfileField::Lockdown,
+        ProfileField::Dns,
+    ];
+}
+
+impl fmt::Display for ProfileField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileField::Relay => f.write_str("relay"),
+            ProfileField::Obfuscation => f.write_str("obfuscation"),
+            ProfileField::Bridge => f.write_str("bridge"),
+            ProfileField::Lan => f.write_str("lan"),
+            ProfileField::Lockdown => f.write_str("lockdown"),
+            ProfileField::Dns => f.write_str("dns"),
+        }
+    }
+}
+
+impl SettingsProfile {
+    /// Create a profile from the current value of `fields` in `settings`.
+    pub fn capture(name: String, settings: &Settings, fields: &[ProfileField]) -> Self {
+        let has = |field| fields.contains(&field);
+        SettingsProfile {
+            name,
+            relay_settings: has(ProfileField::Relay).then(|| settings.relay_settings.clone()),
+            obfuscation_settings: has(ProfileField::Obfuscation)
+                .then(|| settings.obfuscation_settings.clone()),
+            bridge_state: has(ProfileField::Bridge).then_some(settings.bridge_state),
+            allow_lan: has(ProfileField::Lan).then_some(settings.allow_lan),
+            block_when_disconnected: has(ProfileField::Lockdown)
+                .then_some(settings.block_when_disconnected),
+            dns_options: has(ProfileField::Dns)
+                .then(|| settings.tunnel_options.dns_options.clone()),
+        }
+    }
+
+    /// Overwrite the settings that are part of this profile. Fails without changing anything if
+    /// the relay settings refer to a custom list that no longer exists.
+    pub fn apply(&self, settings: &mut Settings) -> Result<(), custom_list::Error> {
+        if let Some(RelaySettings::Normal(constraints)) = &self.relay_settings {
+            let locations = [
+                &constraints.location,
+                &constraints.wireguard_constraints.entry_location,
+            ];
+            for location in locations {
+                if let Constraint::Only(LocationConstraint::CustomList { list_id }) = location {
+                    if !settings.custom_lists.iter().any(|list| list.id == *list_id) {
+                        return Err(custom_list::Error::ListNotFound);
+                    }
+                }
+            }
+        }
+
+        if let Some(relay_settings) = &self.relay_settings {
+            settings.relay_settings = relay_settings.clone();
+        }
+        if let Some(obfuscation_settings) = &self.obfuscation_settings {
+            settings.obfuscation_settings = obfuscation_settings.clone();
+        }
+        if let Some(bridge_state) = self.bridge_state {
+            settings.bridge_state = bridge_state;
+        }
+        if let Some(allow_lan) = self.allow_lan {
+            settings.allow_lan = allow_lan;
+        }
+        if let Some(block_when_disconnected) = self.block_when_disconnected {
+            settings.block_when_disconnected = block_when_disconnected;
+        }
+        if let Some(dns_options) = &self.dns_options {
+            settings.tunnel_options.dns_options = dns_options.clone();
+        }
+        Ok(())
+    }
+
+    /// Returns the groups of settings that are part of this profile.
+    pub fn fields(&self) -> Vec<ProfileField> {
+        ProfileField::ALL
+            .into_iter()
+            .filter(|field| match field {
+                ProfileField::Relay => self.relay_settings.is_some(),
+                ProfileField::Obfuscation => self.obfuscation_settings.is_some(),
+                ProfileField::Bridge => self.bridge_state.is_some(),
+                ProfileField::Lan => self.allow_lan.is_some(),
+                ProfileField::Lockdown => self.block_when_disconnected.is_some(),
+                ProfileField::Dns => self.dns_options.is_some(),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for SettingsProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = self
+            .fields()
+            .iter()
+            .map(ProfileField::to_string)
+            .collect::<Vec<_>>();
+        write!(f, "{} ({})", self.name, fields.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_capture_apply() {
+        let travel = Settings {
+            allow_lan: false,
+            block_when_disconnected: true,
+            ..Default::default()
+        };
+        let profile = SettingsProfile::capture(
+            "travel".to_owned(),
+            &travel,
+            &[ProfileField::Lockdown, ProfileField::Lan],
+        );
+        assert_eq!(
+            profile.fields(),
+            vec![ProfileField::Lan, ProfileField::Lockdown]
+        );
+
+        let mut settings = Settings {
+            allow_lan: true,
+            auto_connect: true,
+            ..Default::default()
+        };
+        profile.apply(&mut settings).unwrap();
+        assert!(!settings.allow_lan);
+        assert!(settings.block_when_disconnected);
+        // Settings outside of the profile are not touched
+        assert!(settings.auto_connect);
+    }
+
+    /// A profile that refers to a deleted custom list is not applied at all.
+    #[test]
+    fn test_apply_deleted_custom_list() {
+        use crate::{custom_list::CustomList, relay_constraints::RelayConstraints};
+
+        let list = CustomList::new("favorites".to_owned()).unwrap();
+        let mut current = Settings::default();
+        current.custom_lists.add(list.clone()).unwrap();
+        current.relay_settings = RelaySettings::Normal(RelayConstraints {
+            location: Constraint::Only(LocationConstraint::CustomList { list_id: list.id }),
+            ..Default::default()
+        });
+        current.allow_lan = true;
+        let profile = SettingsProfile::capture(
+            "favorites".to_owned(),
+            &current,
+            &[ProfileField::Relay, ProfileField::Lan],
+        );
+
+        let mut settings = Settings::default();
+        let unchanged = settings.clone();
+        assert!(matches!(
+            profile.apply(&mut settings),
+            Err(custom_list::Error::ListNotFound)
+        ));
+        assert_eq!(settings, unchanged);
+
+        settings.custom_lists.add(list).unwrap();
+        profile.apply(&mut settings).unwrap();
+        assert_eq!(settings.relay_settings, current.relay_settings);
+        assert!(settings.allow_lan);
+    }
+}
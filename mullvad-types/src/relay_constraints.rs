@@ -205,6 +205,21 @@ impl GeographicLocationConstraint {
             _ => None,
         }
     }
+
+    /// Check if every relay in `self` is also in `other`.
+    pub fn is_within(&self, other: &GeographicLocationConstraint) -> bool {
+        use GeographicLocationConstraint::*;
+        match (self, other) {
+            (Country(country) | City(country, _) | Hostname(country, _, _), Country(other)) => {
+                country == other
+            }
+            (City(country, city) | Hostname(country, city, _), City(other_country, other_city)) => {
+                country == other_country && city == other_city
+            }
+            (Hostname(..), Hostname(..)) => self == other,
+            _ => false,
+        }
+    }
 }
 
 impl Match<Relay> for GeographicLocationConstraint {
@@ -1,4 +1,5 @@
 use crate::location::{CityCode, CountryCode, Location};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
@@ -14,6 +15,9 @@ use talpid_types::net::{
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct RelayList {
     pub etag: Option<String>,
+    /// When the API created the list, if it says so.
+    #[serde(default)]
+    pub created: Option<DateTime<Utc>>,
     pub countries: Vec<RelayListCountry>,
     #[serde(rename = "openvpn")]
     pub openvpn: OpenVpnEndpointData,
@@ -234,6 +238,9 @@ pub struct WireguardRelayEndpointData {
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct BridgeEndpointData {
     pub shadowsocks: Vec<ShadowsocksEndpointData>,
+    /// Hostnames of CDN fronts that forward requests for the API host to the API.
+    #[serde(default)]
+    pub domain_fronts: Vec<String>,
 }
 
 /// Data needed to connect to Shadowsocks endpoints.
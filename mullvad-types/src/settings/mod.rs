@@ -2,6 +2,7 @@ use crate::{
     access_method,
     constraints::Constraint,
     custom_list::CustomListsSettings,
+    network_rules::NetworkRulesSettings,
     relay_constraints::{
         BridgeSettings, BridgeState, GeographicLocationConstraint, LocationConstraint,
         ObfuscationSettings, RelayConstraints, RelayOverride, RelaySettings,
@@ -12,6 +13,7 @@ use crate::{
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 #[cfg(any(windows, target_os = "android", target_os = "macos"))]
 use std::collections::HashSet;
+use std::{fmt, time::Duration};
 use talpid_types::net::{openvpn, GenericTunnelOptions};
 
 mod dns;
@@ -91,6 +93,12 @@ pub struct Settings {
     pub tunnel_options: TunnelOptions,
     /// Overrides for relays
     pub relay_overrides: Vec<RelayOverride>,
+    /// When to automatically reconnect to a different exit relay.
+    pub relay_rotation: RelayRotationSettings,
+    /// What to do when joining specific networks.
+    pub network_rules: NetworkRulesSettings,
+    /// When to warn that the account is about to run out of time.
+    pub expiry_warnings: ExpiryWarningSettings,
     /// Whether to notify users of beta updates.
     pub show_beta_releases: bool,
     /// Split tunneling settings
@@ -100,6 +108,72 @@ pub struct Settings {
     pub settings_version: SettingsVersion,
 }
 
+/// Policy for periodically reconnecting to a different exit relay, so that the exit IP changes
+/// without any user interaction. Rotation is triggered by whichever limit is reached first.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RelayRotationSettings {
+    /// Rotate after having been connected to the same relay for this long.
+    pub interval: Option<Duration>,
+    /// Rotate after this many bytes have been sent and received through the tunnel.
+    pub traffic_limit: Option<u64>,
+    /// Require the new exit relay to be in a different city than the previous one.
+    pub change_city: bool,
+}
+
+impl RelayRotationSettings {
+    /// Returns whether any rotation limit is set.
+    pub fn is_enabled(&self) -> bool {
+        self.interval.is_some() || self.traffic_limit.is_some()
+    }
+}
+
+/// How long before the account expires that warnings are emitted. A warning is emitted once for
+/// each threshold that is crossed. No warnings are emitted if there are no thresholds.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ExpiryWarningSettings {
+    /// Invalid thresholds are dropped when the settings are loaded.
+    #[serde(deserialize_with = "deserialize_expiry_warning_thresholds")]
+    pub thresholds: Vec<Duration>,
+}
+
+impl ExpiryWarningSettings {
+    /// Longest threshold that is accepted.
+    pub const MAX_THRESHOLD: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+    /// Returns whether `threshold` is positive and at most [`Self::MAX_THRESHOLD`].
+    pub fn is_valid_threshold(threshold: Duration) -> bool {
+        !threshold.is_zero() && threshold <= Self::MAX_THRESHOLD
+    }
+}
+
+fn deserialize_expiry_warning_thresholds<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut thresholds = <Vec<Duration>>::deserialize(deserializer)?;
+    thresholds.retain(|threshold| {
+        let valid = ExpiryWarningSettings::is_valid_threshold(*threshold);
+        if !valid {
+            log::warn!("Ignoring invalid expiry warning threshold: {threshold:?}");
+        }
+        valid
+    });
+    Ok(thresholds)
+}
+
+impl Default for ExpiryWarningSettings {
+    fn default() -> Self {
+        const HOUR: Duration = Duration::from_secs(60 * 60);
+        Self {
+            thresholds: vec![7 * 24 * HOUR, 24 * HOUR, HOUR],
+        }
+    }
+}
+
 #[cfg(any(windows, target_os = "android", target_os = "macos"))]
 #[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
 pub struct SplitTunnelSettings {
@@ -204,6 +278,9 @@ impl Default for Settings {
             auto_connect: false,
             tunnel_options: TunnelOptions::default(),
             relay_overrides: vec![],
+            relay_rotation: RelayRotationSettings::default(),
+            network_rules: NetworkRulesSettings::default(),
+            expiry_warnings: ExpiryWarningSettings::default(),
             show_beta_releases: false,
             #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             split_tunnel: SplitTunnelSettings::default(),
@@ -258,6 +335,29 @@ impl Settings {
     }
 }
 
+/// A setting that would be changed by applying a settings patch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SettingsPatchChange {
+    /// Path to the changed value, e.g. `obfuscation_settings.selected_obfuscation`.
+    pub path: String,
+    /// JSON encoded value before applying the patch, or `None` if it was not set.
+    pub old_value: Option<String>,
+    /// JSON encoded value after applying the patch, or `None` if it is removed.
+    pub new_value: Option<String>,
+}
+
+impl fmt::Display for SettingsPatchChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path,
+            self.old_value.as_deref().unwrap_or("(not set)"),
+            self.new_value.as_deref().unwrap_or("(not set)"),
+        )
+    }
+}
+
 /// TunnelOptions holds configuration data that applies to all kinds of tunnels.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(default)]
@@ -0,0 +1,23 @@
+use chrono::{offset::Utc, DateTime};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Result of a throughput and latency test run through the tunnel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpeedTestResult {
+    /// Hostname of the exit relay that the test was run through. This is `None` if the tunnel
+    /// was not connected to a Mullvad relay, e.g. when using a custom tunnel endpoint.
+    pub relay: Option<String>,
+    /// The speed test server that was measured against.
+    pub endpoint: String,
+    /// Median round-trip time to `endpoint`.
+    pub latency: Duration,
+    /// Mean deviation between consecutive round-trip times.
+    pub jitter: Duration,
+    /// Download throughput in bits per second.
+    pub download_bps: u64,
+    /// Upload throughput in bits per second.
+    pub upload_bps: u64,
+    /// When the test finished.
+    pub timestamp: DateTime<Utc>,
+}
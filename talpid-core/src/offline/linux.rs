@@ -3,8 +3,12 @@ use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     sync::Arc,
 };
+use talpid_dbus::network_manager::NetworkManager;
 use talpid_routing::RouteManagerHandle;
-use talpid_types::{net::Connectivity, ErrorExt};
+use talpid_types::{
+    net::{Connectivity, NetworkIdentity},
+    ErrorExt,
+};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -25,6 +29,8 @@ const PUBLIC_INTERNET_ADDRESS_V4: IpAddr = IpAddr::V4(Ipv4Addr::new(193, 138, 21
 /// A non-local IPv6 address.
 const PUBLIC_INTERNET_ADDRESS_V6: IpAddr =
     IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6));
+/// Kernel ARP table, used to find the hardware address of the default gateway.
+const ARP_TABLE_PATH: &str = "/proc/net/arp";
 
 impl MonitorHandle {
     pub async fn connectivity(&self) -> Connectivity {
@@ -34,10 +40,31 @@ impl MonitorHandle {
 
 pub async fn spawn_monitor(
     notify_tx: UnboundedSender<Connectivity>,
+    identity_tx: UnboundedSender<NetworkIdentity>,
     route_manager: RouteManagerHandle,
     fwmark: Option<u32>,
 ) -> Result<MonitorHandle> {
     let mut connectivity = check_connectivity(&route_manager, fwmark).await;
+    let network_manager = tokio::task::spawn_blocking(NetworkManager::new)
+        .await
+        .ok()
+        .and_then(|result| {
+            result
+                .inspect_err(|error| {
+                    log::warn!(
+                        "{}",
+                        error.display_chain_with_msg(
+                            "Failed to connect to NetworkManager. Networks will not be \
+                             identified by connection or SSID"
+                        )
+                    )
+                })
+                .ok()
+        })
+        .map(Arc::new);
+    let mut identity =
+        network_identity(&route_manager, fwmark, network_manager.clone(), None).await;
+    let _ = identity_tx.unbounded_send(identity.clone());
 
     let mut listener = route_manager
         .change_listener()
@@ -61,6 +88,17 @@ pub async fn spawn_monitor(
                         connectivity = new_connectivity;
                         let _ = sender.unbounded_send(connectivity);
                     }
+                    let new_identity = network_identity(
+                        &route_manager,
+                        fwmark,
+                        network_manager.clone(),
+                        Some(&identity),
+                    )
+                    .await;
+                    if new_identity != identity {
+                        identity = new_identity;
+                        let _ = identity_tx.unbounded_send(identity.clone());
+                    }
                 }
                 None => return,
             }
@@ -103,3 +141,120 @@ async fn check_connectivity(handle: &RouteManagerHandle, fwmark: Option<u32>) ->
         }
     }
 }
+
+/// Identifies the network that the default route goes through. NetworkManager is only queried
+/// if the gateway or interface differs from `previous`, since route changes are frequent.
+async fn network_identity(
+    handle: &RouteManagerHandle,
+    fwmark: Option<u32>,
+    network_manager: Option<Arc<NetworkManager>>,
+    previous: Option<&NetworkIdentity>,
+) -> NetworkIdentity {
+    let route = match handle
+        .get_destination_route(PUBLIC_INTERNET_ADDRESS_V4, fwmark)
+        .await
+    {
+        Ok(Some(route)) => route,
+        Ok(None) => return NetworkIdentity::default(),
+        Err(error) => {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to obtain the default route")
+            );
+            return NetworkIdentity::default();
+        }
+    };
+    let interface = route.get_node().get_device().map(str::to_owned);
+    let gateway = route.get_node().get_address();
+
+    if let Some(previous) = previous {
+        if previous.interface == interface && previous.gateway == gateway {
+            return previous.clone();
+        }
+    }
+
+    let gateway_mac = match gateway {
+        Some(IpAddr::V4(gateway)) => tokio::fs::read_to_string(ARP_TABLE_PATH)
+            .await
+            .inspect_err(|error| log::debug!("Failed to read {ARP_TABLE_PATH}: {error}"))
+            .ok()
+            .and_then(|table| find_hardware_address(&table, gateway)),
+        _ => None,
+    };
+
+    let primary_connection = match network_manager {
+        Some(network_manager) => {
+            tokio::task::spawn_blocking(move || network_manager.primary_connection())
+                .await
+                .ok()
+        }
+        None => None,
+    }
+    .and_then(|result| {
+        result
+            .inspect_err(|error| {
+                log::debug!(
+                    "{}",
+                    error.display_chain_with_msg(
+                        "Failed to obtain the primary NetworkManager connection"
+                    )
+                )
+            })
+            .ok()
+    })
+    .flatten();
+    let (connection_id, ssid) = match primary_connection {
+        Some(connection) => (Some(connection.uuid), connection.ssid),
+        None => (None, None),
+    };
+
+    NetworkIdentity {
+        interface,
+        gateway,
+        gateway_mac,
+        connection_id,
+        ssid,
+    }
+}
+
+/// Looks up the hardware address of `address` in the contents of `/proc/net/arp`. Incomplete
+/// entries are ignored.
+fn find_hardware_address(arp_table: &str, address: Ipv4Addr) -> Option<String> {
+    const INCOMPLETE_ENTRY: &str = "00:00:00:00:00:00";
+
+    arp_table.lines().skip(1).find_map(|line| {
+        let columns: Vec<_> = line.split_whitespace().collect();
+        let (ip, mac) = (columns.first()?, columns.get(3)?);
+        if ip.parse::<Ipv4Addr>().ok()? == address && *mac != INCOMPLETE_ENTRY {
+            Some(mac.to_lowercase())
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_hardware_address() {
+        let table = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.1      0x1         0x2         AA:BB:CC:DD:EE:01     *        wlan0
+192.168.1.20     0x1         0x0         00:00:00:00:00:00     *        wlan0
+";
+        assert_eq!(
+            find_hardware_address(table, Ipv4Addr::new(192, 168, 1, 1)).as_deref(),
+            Some("aa:bb:cc:dd:ee:01")
+        );
+        assert_eq!(
+            find_hardware_address(table, Ipv4Addr::new(192, 168, 1, 20)),
+            None
+        );
+        assert_eq!(
+            find_hardware_address(table, Ipv4Addr::new(10, 0, 0, 1)),
+            None
+        );
+    }
+}
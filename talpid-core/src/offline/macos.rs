@@ -18,7 +18,10 @@ use std::{
     time::Duration,
 };
 use talpid_routing::{DefaultRouteEvent, RouteManagerHandle};
-use talpid_types::net::Connectivity;
+use talpid_types::{
+    net::{Connectivity, NetworkIdentity},
+    ErrorExt,
+};
 
 const SYNTHETIC_OFFLINE_DURATION: Duration = Duration::from_secs(1);
 
@@ -65,6 +68,7 @@ impl ConnectivityInner {
 
 pub async fn spawn_monitor(
     notify_tx: UnboundedSender<Connectivity>,
+    identity_tx: UnboundedSender<NetworkIdentity>,
     route_manager: RouteManagerHandle,
 ) -> Result<MonitorHandle, Error> {
     let notify_tx = Arc::new(notify_tx);
@@ -85,6 +89,9 @@ pub async fn spawn_monitor(
     let state = ConnectivityInner { ipv4, ipv6 };
     let mut real_state = state;
 
+    let mut identity = network_identity(&route_manager).await;
+    let _ = identity_tx.unbounded_send(identity.clone());
+
     let state = Arc::new(Mutex::new(state));
 
     let weak_state = Arc::downgrade(&state);
@@ -138,6 +145,12 @@ pub async fn spawn_monitor(
                         }
                     }
 
+                    let new_identity = network_identity(&route_manager).await;
+                    if new_identity != identity {
+                        identity = new_identity;
+                        let _ = identity_tx.unbounded_send(identity.clone());
+                    }
+
                     // Synthesize offline state
                     // Update shared state
                     let Some(state) = weak_state.upgrade() else {
@@ -171,3 +184,42 @@ pub async fn spawn_monitor(
         _notify_tx: notify_tx,
     })
 }
+
+/// Identifies the network that the non-tunnel default route goes through. The IPv4 default route
+/// is preferred, since IPv6 gateways are usually identified by link-local addresses.
+async fn network_identity(route_manager: &RouteManagerHandle) -> NetworkIdentity {
+    let route = match route_manager.get_default_routes().await {
+        Ok((Some(route), _)) | Ok((None, Some(route))) => route,
+        Ok((None, None)) => return NetworkIdentity::default(),
+        Err(error) => {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to obtain the default route")
+            );
+            return NetworkIdentity::default();
+        }
+    };
+
+    let gateway_mac = match route_manager.get_default_gateway().await {
+        Ok((v4_gateway, v6_gateway)) => [v4_gateway, v6_gateway]
+            .into_iter()
+            .flatten()
+            .find(|gateway| gateway.ip_address == route.router_ip)
+            .map(|gateway| super::format_hardware_address(&gateway.mac_address.into_bytes())),
+        Err(error) => {
+            log::debug!(
+                "{}",
+                error.display_chain_with_msg("Failed to obtain the default gateway")
+            );
+            None
+        }
+    };
+
+    NetworkIdentity {
+        interface: Some(route.interface),
+        gateway: Some(route.router_ip),
+        gateway_mac,
+        connection_id: None,
+        ssid: None,
+    }
+}
@@ -4,6 +4,8 @@ use std::sync::LazyLock;
 use talpid_routing::RouteManagerHandle;
 #[cfg(target_os = "android")]
 use talpid_types::android::AndroidContext;
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::NetworkIdentity;
 use talpid_types::{net::Connectivity, ErrorExt};
 
 #[cfg(target_os = "macos")]
@@ -40,8 +42,19 @@ impl MonitorHandle {
     }
 }
 
+/// Formats a hardware address as `aa:bb:cc:dd:ee:ff`, like [`NetworkIdentity::gateway_mac`].
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn format_hardware_address(address: &[u8]) -> String {
+    address
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
 pub async fn spawn_monitor(
     sender: UnboundedSender<Connectivity>,
+    #[cfg(not(target_os = "android"))] identity_sender: UnboundedSender<NetworkIdentity>,
     #[cfg(not(target_os = "android"))] route_manager: RouteManagerHandle,
     #[cfg(target_os = "linux")] fwmark: Option<u32>,
     #[cfg(target_os = "android")] android_context: AndroidContext,
@@ -52,6 +65,8 @@ pub async fn spawn_monitor(
         imp::spawn_monitor(
             sender,
             #[cfg(not(target_os = "android"))]
+            identity_sender,
+            #[cfg(not(target_os = "android"))]
             route_manager,
             #[cfg(target_os = "linux")]
             fwmark,
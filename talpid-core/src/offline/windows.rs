@@ -7,8 +7,11 @@ use std::{
     time::Duration,
 };
 use talpid_routing::{get_best_default_route, CallbackHandle, EventType, RouteManagerHandle};
-use talpid_types::{net::Connectivity, ErrorExt};
-use talpid_windows::net::AddressFamily;
+use talpid_types::{
+    net::{Connectivity, NetworkIdentity},
+    ErrorExt,
+};
+use talpid_windows::net::{alias_from_luid, get_neighbor_hardware_address, AddressFamily};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -29,11 +32,14 @@ unsafe impl Send for BroadcastListener {}
 impl BroadcastListener {
     pub async fn start(
         notify_tx: UnboundedSender<Connectivity>,
+        identity_tx: UnboundedSender<NetworkIdentity>,
         route_manager: RouteManagerHandle,
         mut power_mgmt_rx: PowerManagementListener,
     ) -> Result<Self, Error> {
         let notify_tx = Arc::new(notify_tx);
         let (ipv4, ipv6) = Self::check_initial_connectivity();
+        let identity = network_identity();
+        let _ = identity_tx.unbounded_send(identity.clone());
         let system_state = Arc::new(Mutex::new(SystemState {
             connectivity: ConnectivityInner {
                 ipv4,
@@ -41,6 +47,8 @@ impl BroadcastListener {
                 suspended: false,
             },
             notify_tx: Arc::downgrade(&notify_tx),
+            identity,
+            identity_tx,
         }));
 
         let state = system_state.clone();
@@ -124,6 +132,8 @@ impl BroadcastListener {
     ) {
         use talpid_routing::EventType::*;
 
+        state_lock.lock().update_identity();
+
         if matches!(event_type, UpdatedDetails(_)) {
             // ignore changes that don't affect the route
             return;
@@ -155,6 +165,8 @@ enum StateChange {
 struct SystemState {
     connectivity: ConnectivityInner,
     notify_tx: Weak<UnboundedSender<Connectivity>>,
+    identity: NetworkIdentity,
+    identity_tx: UnboundedSender<NetworkIdentity>,
 }
 
 impl SystemState {
@@ -186,6 +198,57 @@ impl SystemState {
     fn is_offline_currently(&self) -> bool {
         self.connectivity.is_offline()
     }
+
+    fn update_identity(&mut self) {
+        let identity = network_identity();
+        if identity != self.identity {
+            self.identity = identity;
+            let _ = self.identity_tx.unbounded_send(self.identity.clone());
+        }
+    }
+}
+
+/// Identifies the network that the best default route on a physical interface goes through. The
+/// IPv4 default route is preferred, since IPv6 gateways are usually identified by link-local
+/// addresses.
+fn network_identity() -> NetworkIdentity {
+    let route = [AddressFamily::Ipv4, AddressFamily::Ipv6]
+        .into_iter()
+        .find_map(|family| {
+            get_best_default_route(family)
+                .inspect_err(|error| {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to obtain the default route")
+                    );
+                })
+                .ok()
+                .flatten()
+        });
+    let Some(route) = route else {
+        return NetworkIdentity::default();
+    };
+    let gateway = route.gateway.ip();
+
+    let interface = alias_from_luid(&route.iface)
+        .inspect_err(|error| log::debug!("Failed to obtain the default interface alias: {error}"))
+        .ok()
+        .map(|alias| alias.to_string_lossy().into_owned());
+    let gateway_mac = get_neighbor_hardware_address(&route.iface, gateway)
+        .inspect_err(|error| {
+            log::debug!("Failed to obtain the hardware address of the gateway: {error}")
+        })
+        .ok()
+        .flatten()
+        .map(|address| super::format_hardware_address(&address));
+
+    NetworkIdentity {
+        interface,
+        gateway: Some(gateway),
+        gateway_mac,
+        connection_id: None,
+        ssid: None,
+    }
 }
 
 // If `offline` is true, return "Offline". Otherwise, return "Connected".
@@ -201,10 +264,11 @@ pub type MonitorHandle = BroadcastListener;
 
 pub async fn spawn_monitor(
     sender: UnboundedSender<Connectivity>,
+    identity_sender: UnboundedSender<NetworkIdentity>,
     route_manager: RouteManagerHandle,
 ) -> Result<MonitorHandle, Error> {
     let power_mgmt_rx = crate::window::PowerManagementListener::new();
-    BroadcastListener::start(sender, route_manager, power_mgmt_rx).await
+    BroadcastListener::start(sender, identity_sender, route_manager, power_mgmt_rx).await
 }
 
 fn apply_system_state_change(state: Arc<Mutex<SystemState>>, change: StateChange) {
@@ -8,6 +8,7 @@ pub use talpid_tunnel::{TunnelArgs, TunnelEvent, TunnelMetadata};
 #[cfg(not(target_os = "android"))]
 use talpid_types::net::openvpn as openvpn_types;
 use talpid_types::net::{wireguard as wireguard_types, TunnelParameters};
+pub use talpid_wireguard::{HandoverError, Stats, TunnelHandoverHandle, TunnelStatsHandle};
 
 const OPENVPN_LOG_FILENAME: &str = "openvpn.log";
 const WIREGUARD_LOG_FILENAME: &str = "wireguard.log";
@@ -123,8 +124,13 @@ impl TunnelMonitor {
         match params {
             TunnelParameters::OpenVpn(params) => match &params.proxy {
                 Some(CustomProxy::Shadowsocks(_)) => Some(std::env::current_exe().unwrap()),
+                Some(CustomProxy::HttpConnect(http)) if http.uses_tls() => {
+                    Some(std::env::current_exe().unwrap())
+                }
                 Some(CustomProxy::Socks5Local(_)) => None,
-                Some(CustomProxy::Socks5Remote(_)) | None => Some(resource_dir.join("openvpn.exe")),
+                Some(CustomProxy::Socks5Remote(_)) | Some(CustomProxy::HttpConnect(_)) | None => {
+                    Some(resource_dir.join("openvpn.exe"))
+                }
             },
             _ => Some(std::env::current_exe().unwrap()),
         }
@@ -237,6 +243,26 @@ impl TunnelMonitor {
         }
     }
 
+    /// Returns a handle for reading the traffic counters of the tunnel. Only WireGuard tunnels
+    /// provide traffic counters.
+    pub fn stats_handle(&self) -> Option<TunnelStatsHandle> {
+        match &self.monitor {
+            #[cfg(not(target_os = "android"))]
+            InternalTunnelMonitor::OpenVpn(_) => None,
+            InternalTunnelMonitor::Wireguard(monitor) => Some(monitor.stats_handle()),
+        }
+    }
+
+    /// Returns a handle for switching the tunnel over to a new peer without recreating it. Only
+    /// WireGuard tunnels support this.
+    pub fn handover_handle(&self) -> Option<TunnelHandoverHandle> {
+        match &self.monitor {
+            #[cfg(not(target_os = "android"))]
+            InternalTunnelMonitor::OpenVpn(_) => None,
+            InternalTunnelMonitor::Wireguard(monitor) => Some(monitor.handover_handle()),
+        }
+    }
+
     /// Consumes the monitor and blocks until the tunnel exits or there is an error.
     pub fn wait(self) -> Result<()> {
         self.monitor.wait().map_err(Error::from)
@@ -6,13 +6,14 @@ use super::{
 use crate::{
     dns::ResolvedDnsConfig,
     firewall::FirewallPolicy,
-    tunnel::{TunnelEvent, TunnelMetadata},
+    tunnel::{TunnelEvent, TunnelHandoverHandle, TunnelMetadata, TunnelStatsHandle},
 };
 use futures::{
     channel::{mpsc, oneshot},
     stream::Fuse,
     StreamExt,
 };
+use std::sync::{Arc, Mutex};
 use talpid_types::{
     net::{AllowedClients, AllowedEndpoint, TunnelParameters},
     tunnel::{ErrorStateCause, FirewallPolicyError},
@@ -34,6 +35,8 @@ pub struct ConnectedState {
     tunnel_parameters: TunnelParameters,
     tunnel_close_event: TunnelCloseEvent,
     tunnel_close_tx: oneshot::Sender<()>,
+    tunnel_stats: Arc<Mutex<Option<TunnelStatsHandle>>>,
+    tunnel_handover: Arc<Mutex<Option<TunnelHandoverHandle>>>,
 }
 
 impl ConnectedState {
@@ -45,6 +48,8 @@ impl ConnectedState {
         tunnel_parameters: TunnelParameters,
         tunnel_close_event: TunnelCloseEvent,
         tunnel_close_tx: oneshot::Sender<()>,
+        tunnel_stats: Arc<Mutex<Option<TunnelStatsHandle>>>,
+        tunnel_handover: Arc<Mutex<Option<TunnelHandoverHandle>>>,
     ) -> (Box<dyn TunnelState>, TunnelStateTransition) {
         let connected_state = ConnectedState {
             metadata,
@@ -52,6 +57,8 @@ impl ConnectedState {
             tunnel_parameters,
             tunnel_close_event,
             tunnel_close_tx,
+            tunnel_stats,
+            tunnel_handover,
         };
 
         let tunnel_interface = Some(connected_state.metadata.interface.clone());
@@ -225,6 +232,80 @@ impl ConnectedState {
         ))
     }
 
+    /// Connects using newly generated tunnel parameters. If possible, the running tunnel is
+    /// switched over to the new peer instead of being torn down. Otherwise, the parameters are
+    /// kept for the next connecting state so that they are not generated twice.
+    fn reconnect(mut self, shared_values: &mut SharedTunnelStateValues) -> EventConsequence {
+        let Some(handover) = self.tunnel_handover.lock().unwrap().clone() else {
+            return self.disconnect(shared_values, AfterDisconnect::Reconnect(0));
+        };
+
+        let new_parameters = match shared_values.runtime.block_on(
+            shared_values
+                .tunnel_parameters_generator
+                .generate(0, shared_values.connectivity.has_ipv6()),
+        ) {
+            Ok(TunnelParameters::Wireguard(new_parameters)) => new_parameters,
+            result => {
+                shared_values.pending_tunnel_parameters = result.ok();
+                return self.disconnect(shared_values, AfterDisconnect::Reconnect(0));
+            }
+        };
+
+        // Nothing has been changed yet, so the parameters are still valid for a new tunnel.
+        if let Err(error) = shared_values
+            .runtime
+            .block_on(handover.check(&new_parameters))
+        {
+            log::debug!("{}", error);
+            shared_values.pending_tunnel_parameters =
+                Some(TunnelParameters::Wireguard(new_parameters));
+            return self.disconnect(shared_values, AfterDisconnect::Reconnect(0));
+        }
+
+        // Allow traffic to the new peer before switching over to it. Traffic to the previous
+        // peer is blocked from here on.
+        self.tunnel_parameters = TunnelParameters::Wireguard(new_parameters.clone());
+        // A key rotation also changes the tunnel addresses.
+        self.metadata.ips = new_parameters.connection.tunnel.addresses.clone();
+        if let Err(error) = self.set_firewall_policy(shared_values) {
+            return self.disconnect(
+                shared_values,
+                AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+            );
+        }
+
+        // Only report the tunnel as connected once a handshake with the new peer has been
+        // completed.
+        if let Err(error) = shared_values
+            .runtime
+            .block_on(handover.replace_peer(&new_parameters))
+        {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to hand over tunnel to new relay")
+            );
+            // The new peer may be unreachable, so new parameters are generated for the next
+            // attempt.
+            shared_values.pending_tunnel_parameters = None;
+            return self.disconnect(shared_values, AfterDisconnect::Reconnect(0));
+        }
+
+        log::info!(
+            "Handed over tunnel to {}",
+            new_parameters.connection.peer.endpoint
+        );
+
+        let tunnel_endpoint = talpid_types::net::TunnelEndpoint {
+            tunnel_interface: Some(self.metadata.interface.clone()),
+            ..self.tunnel_parameters.get_tunnel_endpoint()
+        };
+        EventConsequence::NewState((
+            Box::new(self),
+            TunnelStateTransition::Connected(tunnel_endpoint),
+        ))
+    }
+
     fn handle_commands(
         self: Box<Self>,
         command: Option<TunnelCommand>,
@@ -330,15 +411,24 @@ impl ConnectedState {
                     SameState(self)
                 }
             }
-            Some(TunnelCommand::Connect) => {
-                self.disconnect(shared_values, AfterDisconnect::Reconnect(0))
-            }
+            Some(TunnelCommand::Connect) => self.reconnect(shared_values),
             Some(TunnelCommand::Disconnect) | None => {
                 self.disconnect(shared_values, AfterDisconnect::Nothing)
             }
             Some(TunnelCommand::Block(reason)) => {
                 self.disconnect(shared_values, AfterDisconnect::Block(reason))
             }
+            Some(TunnelCommand::GetTunnelStats(tx)) => {
+                let handle = self.tunnel_stats.lock().unwrap().clone();
+                shared_values.runtime.spawn(async move {
+                    let stats = match handle {
+                        Some(handle) => handle.get().await,
+                        None => None,
+                    };
+                    let _ = tx.send(stats);
+                });
+                SameState(self)
+            }
             #[cfg(target_os = "android")]
             Some(TunnelCommand::BypassSocket(fd, done_tx)) => {
                 shared_values.bypass_socket(fd, done_tx);
@@ -5,7 +5,7 @@ use super::{
 };
 use crate::{
     firewall::FirewallPolicy,
-    tunnel::{self, TunnelMonitor},
+    tunnel::{self, TunnelHandoverHandle, TunnelMonitor, TunnelStatsHandle},
 };
 use futures::{
     channel::{mpsc, oneshot},
@@ -49,6 +49,8 @@ pub struct ConnectingState {
     allowed_tunnel_traffic: AllowedTunnelTraffic,
     tunnel_close_event: TunnelCloseEvent,
     tunnel_close_tx: oneshot::Sender<()>,
+    tunnel_stats: Arc<Mutex<Option<TunnelStatsHandle>>>,
+    tunnel_handover: Arc<Mutex<Option<TunnelHandoverHandle>>>,
     retry_attempt: u32,
 }
 
@@ -77,11 +79,16 @@ impl ConnectingState {
             }
             return ErrorState::enter(shared_values, ErrorStateCause::IsOffline);
         }
-        match shared_values.runtime.block_on(
-            shared_values
-                .tunnel_parameters_generator
-                .generate(retry_attempt, shared_values.connectivity.has_ipv6()),
-        ) {
+        // Parameters may already have been generated for a handover that was not possible
+        let tunnel_parameters = match shared_values.pending_tunnel_parameters.take() {
+            Some(tunnel_parameters) => Ok(tunnel_parameters),
+            None => shared_values.runtime.block_on(
+                shared_values
+                    .tunnel_parameters_generator
+                    .generate(retry_attempt, shared_values.connectivity.has_ipv6()),
+            ),
+        };
+        match tunnel_parameters {
             Err(err) => {
                 ErrorState::enter(shared_values, ErrorStateCause::TunnelParameterError(err))
             }
@@ -231,6 +238,10 @@ impl ConnectingState {
         let (tunnel_close_event_tx, tunnel_close_event_rx) = oneshot::channel();
 
         let tunnel_parameters = parameters.clone();
+        let tunnel_stats = Arc::new(Mutex::new(None));
+        let monitor_tunnel_stats = tunnel_stats.clone();
+        let tunnel_handover = Arc::new(Mutex::new(None));
+        let monitor_tunnel_handover = tunnel_handover.clone();
 
         tokio::task::spawn_blocking(move || {
             let start = Instant::now();
@@ -247,6 +258,8 @@ impl ConnectingState {
 
             let block_reason = match TunnelMonitor::start(&tunnel_parameters, &log_dir, args) {
                 Ok(monitor) => {
+                    *monitor_tunnel_stats.lock().unwrap() = monitor.stats_handle();
+                    *monitor_tunnel_handover.lock().unwrap() = monitor.handover_handle();
                     let reason = Self::wait_for_tunnel_monitor(monitor, retry_attempt);
                     log::debug!("Tunnel monitor exited with block reason: {:?}", reason);
                     reason
@@ -314,6 +327,8 @@ impl ConnectingState {
             allowed_tunnel_traffic: INITIAL_ALLOWED_TUNNEL_TRAFFIC,
             tunnel_close_event: tunnel_close_event_rx.fuse(),
             tunnel_close_tx,
+            tunnel_stats,
+            tunnel_handover,
             retry_attempt,
         }
     }
@@ -498,6 +513,10 @@ impl ConnectingState {
             Some(TunnelCommand::Block(reason)) => {
                 self.disconnect(shared_values, AfterDisconnect::Block(reason))
             }
+            Some(TunnelCommand::GetTunnelStats(tx)) => {
+                let _ = tx.send(None);
+                SameState(self)
+            }
             #[cfg(target_os = "android")]
             Some(TunnelCommand::BypassSocket(fd, done_tx)) => {
                 shared_values.bypass_socket(fd, done_tx);
@@ -618,6 +637,8 @@ impl ConnectingState {
                 self.tunnel_parameters,
                 self.tunnel_close_event,
                 self.tunnel_close_tx,
+                self.tunnel_stats,
+                self.tunnel_handover,
             )),
             Some((TunnelEvent::Down, _)) => {
                 // It is important to reset this before the tunnel device is down,
@@ -20,6 +20,9 @@ impl DisconnectedState {
         shared_values: &mut SharedTunnelStateValues,
         should_reset_firewall: bool,
     ) -> (Box<dyn TunnelState>, TunnelStateTransition) {
+        // Parameters generated for a previous connection attempt must not outlive it
+        shared_values.pending_tunnel_parameters = None;
+
         #[cfg(target_os = "macos")]
         if let Err(err) = shared_values
             .runtime
@@ -213,6 +216,10 @@ impl TunnelState for DisconnectedState {
             }
             Some(TunnelCommand::Connect) => NewState(ConnectingState::enter(shared_values, 0)),
             Some(TunnelCommand::Block(_reason)) => SameState(self),
+            Some(TunnelCommand::GetTunnelStats(tx)) => {
+                let _ = tx.send(None);
+                SameState(self)
+            }
             #[cfg(target_os = "android")]
             Some(TunnelCommand::BypassSocket(fd, done_tx)) => {
                 shared_values.bypass_socket(fd, done_tx);
@@ -71,6 +71,10 @@ impl DisconnectingState {
                 Some(TunnelCommand::Disconnect) | Some(TunnelCommand::Block(_)) | None => {
                     AfterDisconnect::Nothing
                 }
+                Some(TunnelCommand::GetTunnelStats(tx)) => {
+                    let _ = tx.send(None);
+                    AfterDisconnect::Nothing
+                }
                 #[cfg(target_os = "android")]
                 Some(TunnelCommand::BypassSocket(fd, done_tx)) => {
                     shared_values.bypass_socket(fd, done_tx);
@@ -128,6 +132,10 @@ impl DisconnectingState {
                 Some(TunnelCommand::Connect) => AfterDisconnect::Reconnect(0),
                 Some(TunnelCommand::Disconnect) => AfterDisconnect::Nothing,
                 Some(TunnelCommand::Block(new_reason)) => AfterDisconnect::Block(new_reason),
+                Some(TunnelCommand::GetTunnelStats(tx)) => {
+                    let _ = tx.send(None);
+                    AfterDisconnect::Block(reason)
+                }
                 #[cfg(target_os = "android")]
                 Some(TunnelCommand::BypassSocket(fd, done_tx)) => {
                     shared_values.bypass_socket(fd, done_tx);
@@ -177,15 +185,25 @@ impl DisconnectingState {
                 }
                 Some(TunnelCommand::Connectivity(connectivity)) => {
                     shared_values.connectivity = connectivity;
+                    // Pending parameters may use an IP version that is no longer available
+                    shared_values.pending_tunnel_parameters = None;
                     if connectivity.is_offline() {
                         AfterDisconnect::Block(ErrorStateCause::IsOffline)
                     } else {
                         AfterDisconnect::Reconnect(retry_attempt)
                     }
                 }
-                Some(TunnelCommand::Connect) => AfterDisconnect::Reconnect(retry_attempt),
+                Some(TunnelCommand::Connect) => {
+                    // Connecting again is requested when the relay constraints have changed
+                    shared_values.pending_tunnel_parameters = None;
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
                 Some(TunnelCommand::Disconnect) | None => AfterDisconnect::Nothing,
                 Some(TunnelCommand::Block(reason)) => AfterDisconnect::Block(reason),
+                Some(TunnelCommand::GetTunnelStats(tx)) => {
+                    let _ = tx.send(None);
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
                 #[cfg(target_os = "android")]
                 Some(TunnelCommand::BypassSocket(fd, done_tx)) => {
                     shared_values.bypass_socket(fd, done_tx);
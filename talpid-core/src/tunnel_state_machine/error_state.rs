@@ -23,6 +23,9 @@ impl ErrorState {
         shared_values: &mut SharedTunnelStateValues,
         block_reason: ErrorStateCause,
     ) -> (Box<dyn TunnelState>, TunnelStateTransition) {
+        // Parameters generated for a previous connection attempt must not outlive it
+        shared_values.pending_tunnel_parameters = None;
+
         #[cfg(windows)]
         if let Err(error) = shared_values.split_tunnel.set_tunnel_addresses(None) {
             log::error!(
@@ -208,6 +211,10 @@ impl TunnelState for ErrorState {
             Some(TunnelCommand::Block(reason)) => {
                 NewState(ErrorState::enter(shared_values, reason))
             }
+            Some(TunnelCommand::GetTunnelStats(tx)) => {
+                let _ = tx.send(None);
+                SameState(self)
+            }
             #[cfg(target_os = "android")]
             Some(TunnelCommand::BypassSocket(fd, done_tx)) => {
                 shared_values.bypass_socket(fd, done_tx);
@@ -42,6 +42,8 @@ use std::{
     sync::{Arc, Mutex},
     time::Duration,
 };
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::NetworkIdentity;
 #[cfg(target_os = "android")]
 use talpid_types::{android::AndroidContext, ErrorExt};
 use talpid_types::{
@@ -125,6 +127,9 @@ pub async fn spawn(
     resource_dir: PathBuf,
     state_change_listener: impl Sender<TunnelStateTransition> + Send + 'static,
     offline_state_listener: mpsc::UnboundedSender<Connectivity>,
+    #[cfg(not(target_os = "android"))] network_identity_listener: mpsc::UnboundedSender<
+        NetworkIdentity,
+    >,
     #[cfg(target_os = "windows")] volume_update_rx: mpsc::UnboundedReceiver<()>,
     #[cfg(target_os = "android")] android_context: AndroidContext,
     #[cfg(target_os = "linux")] linux_ids: LinuxNetworkingIdentifiers,
@@ -146,6 +151,8 @@ pub async fn spawn(
         settings: initial_settings,
         command_tx: weak_command_tx,
         offline_state_tx: offline_state_listener,
+        #[cfg(not(target_os = "android"))]
+        network_identity_tx: network_identity_listener,
         tunnel_parameters_generator,
         tun_provider,
         log_dir,
@@ -197,6 +204,9 @@ pub enum TunnelCommand {
     Connect,
     /// Close tunnel connection.
     Disconnect,
+    /// Request the traffic counters of the tunnel. `None` is sent to the channel unless the
+    /// tunnel is connected and provides traffic counters.
+    GetTunnelStats(oneshot::Sender<Option<crate::tunnel::Stats>>),
     /// Block all network access unless tunnel is disconnecting or disconnected
     Block(ErrorStateCause),
     /// Bypass a socket, allowing traffic to flow through outside the tunnel.
@@ -241,6 +251,8 @@ struct TunnelStateMachineInitArgs<G: TunnelParametersGenerator> {
     settings: InitialTunnelState,
     command_tx: std::sync::Weak<mpsc::UnboundedSender<TunnelCommand>>,
     offline_state_tx: mpsc::UnboundedSender<Connectivity>,
+    #[cfg(not(target_os = "android"))]
+    network_identity_tx: mpsc::UnboundedSender<NetworkIdentity>,
     tunnel_parameters_generator: G,
     tun_provider: TunProvider,
     log_dir: Option<PathBuf>,
@@ -328,6 +340,8 @@ impl TunnelStateMachine {
         let offline_monitor = offline::spawn_monitor(
             offline_tx,
             #[cfg(not(target_os = "android"))]
+            args.network_identity_tx,
+            #[cfg(not(target_os = "android"))]
             route_manager.clone(),
             #[cfg(target_os = "linux")]
             Some(args.linux_ids.fwmark),
@@ -376,6 +390,7 @@ impl TunnelStateMachine {
             dns_config: args.settings.dns_config,
             allowed_endpoint: args.settings.allowed_endpoint,
             tunnel_parameters_generator: Box::new(args.tunnel_parameters_generator),
+            pending_tunnel_parameters: None,
             tun_provider: Arc::new(Mutex::new(args.tun_provider)),
             log_dir: args.log_dir,
             resource_dir: args.resource_dir,
@@ -472,6 +487,9 @@ struct SharedTunnelStateValues {
     allowed_endpoint: AllowedEndpoint,
     /// The generator of new `TunnelParameter`s
     tunnel_parameters_generator: Box<dyn TunnelParametersGenerator>,
+    /// Parameters that were generated for a handover that could not be performed. These are
+    /// used by the next connecting state instead of generating new ones.
+    pending_tunnel_parameters: Option<TunnelParameters>,
     /// The provider of tunnel devices.
     tun_provider: Arc<Mutex<TunProvider>>,
     /// Directory to store tunnel log file.
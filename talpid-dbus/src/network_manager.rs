@@ -43,6 +43,11 @@ const NM_SETTINGS_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings";
 const NM_SETTINGS_CONNECTION_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings.Connection";
 const NM_SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
 const NM_CONNECTION_ACTIVE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+const NM_DEVICE_WIRELESS: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+const NM_ACCESS_POINT: &str = "org.freedesktop.NetworkManager.AccessPoint";
+const NM_WIRELESS_CONNECTION_TYPE: &str = "802-11-wireless";
+/// Object path used by NetworkManager to represent the absence of an object.
+const NM_NULL_PATH: &str = "/";
 
 const NM_ADD_CONNECTION_VOLATILE: u32 = 0x2;
 
@@ -108,6 +113,15 @@ pub enum Error {
     ObtainDevices,
 }
 
+/// The active connection that provides the default route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimaryConnection {
+    /// UUID of the connection profile.
+    pub uuid: String,
+    /// SSID of the network, if this is a Wi-Fi connection.
+    pub ssid: Option<String>,
+}
+
 pub type VariantRefArg = Variant<Box<dyn RefArg>>;
 pub type VariantMap = HashMap<String, VariantRefArg>;
 // settings are a{sa{sv}}
@@ -643,6 +657,42 @@ impl NetworkManager {
         Err(Error::DeviceNotFound)
     }
 
+    /// Returns the active connection that currently provides the default route, if any.
+    pub fn primary_connection(&self) -> Result<Option<PrimaryConnection>> {
+        let connection_path: dbus::Path<'static> =
+            self.as_manager().get(NM_MANAGER, "PrimaryConnection")?;
+        if &*connection_path == NM_NULL_PATH {
+            return Ok(None);
+        }
+        let connection = self.as_path(&connection_path);
+        let uuid: String = connection.get(NM_CONNECTION_ACTIVE, "Uuid")?;
+        let connection_type: String = connection.get(NM_CONNECTION_ACTIVE, "Type")?;
+
+        let ssid = if connection_type == NM_WIRELESS_CONNECTION_TYPE {
+            let devices: Vec<dbus::Path<'static>> =
+                connection.get(NM_CONNECTION_ACTIVE, "Devices")?;
+            devices
+                .iter()
+                .find_map(|device| self.active_ssid(device).transpose())
+                .transpose()?
+        } else {
+            None
+        };
+
+        Ok(Some(PrimaryConnection { uuid, ssid }))
+    }
+
+    fn active_ssid(&self, device: &dbus::Path<'_>) -> Result<Option<String>> {
+        let access_point: dbus::Path<'static> = self
+            .as_path(device)
+            .get(NM_DEVICE_WIRELESS, "ActiveAccessPoint")?;
+        if &*access_point == NM_NULL_PATH {
+            return Ok(None);
+        }
+        let ssid: Vec<u8> = self.as_path(&access_point).get(NM_ACCESS_POINT, "Ssid")?;
+        Ok(Some(String::from_utf8_lossy(&ssid).into_owned()))
+    }
+
     pub fn convert_address_to_dbus(address: &IpAddr) -> VariantMap {
         let mut map: VariantMap = HashMap::new();
         map.insert(
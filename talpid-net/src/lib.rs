@@ -1,2 +1,3 @@
+pub mod tls;
 #[cfg(unix)]
 pub mod unix;
@@ -0,0 +1,22 @@
+//! TLS configuration shared by the connections that the app makes to servers that it does not
+//! operate, such as HTTPS proxies.
+
+use std::sync::{Arc, LazyLock};
+use tokio_rustls::rustls::{self, ClientConfig};
+
+/// TLS configuration that trusts the public web PKI. Unlike the API, proxies are not pinned to a
+/// specific root certificate.
+pub fn public_tls_config() -> Arc<ClientConfig> {
+    static TLS_CONFIG: LazyLock<Arc<ClientConfig>> = LazyLock::new(|| {
+        let mut root_store = rustls::RootCertStore::empty();
+        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .expect("ring crypto-provider should support the default protocol versions")
+                .with_root_certificates(root_store)
+                .with_no_client_auth();
+        Arc::new(config)
+    });
+    TLS_CONFIG.clone()
+}
@@ -548,13 +548,24 @@ impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
     fn create_proxy_auth_file(
         proxy_settings: &Option<CustomProxy>,
     ) -> std::result::Result<Option<mktemp::TempFile>, io::Error> {
-        if let Some(CustomProxy::Socks5Remote(ref remote_proxy)) = proxy_settings {
-            if let Some(ref proxy_auth) = remote_proxy.auth {
-                return Ok(Some(Self::create_credentials_file(
-                    proxy_auth.username(),
-                    proxy_auth.password(),
-                )?));
+        match proxy_settings {
+            Some(CustomProxy::Socks5Remote(ref remote_proxy)) => {
+                if let Some(ref proxy_auth) = remote_proxy.auth {
+                    return Ok(Some(Self::create_credentials_file(
+                        proxy_auth.username(),
+                        proxy_auth.password(),
+                    )?));
+                }
+            }
+            Some(CustomProxy::HttpConnect(ref http_proxy)) => {
+                if let Some(ref proxy_auth) = http_proxy.auth {
+                    return Ok(Some(Self::create_credentials_file(
+                        proxy_auth.username(),
+                        proxy_auth.password(),
+                    )?));
+                }
             }
+            _ => (),
         }
         Ok(None)
     }
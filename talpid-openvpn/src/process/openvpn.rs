@@ -343,6 +343,35 @@ impl OpenVpnCommand {
                 args.push("255.255.255.255".to_owned());
                 args.push("net_gateway".to_owned());
             }
+            Some(CustomProxy::HttpConnect(ref http_proxy)) => {
+                args.push("--http-proxy".to_owned());
+                if http_proxy.uses_tls() {
+                    // OpenVPN talks plain HTTP to a local forwarder, which adds TLS
+                    args.push("127.0.0.1".to_owned());
+                    if let Some(ref proxy_port) = self.proxy_port {
+                        args.push(proxy_port.to_string());
+                    } else {
+                        panic!("Dynamic proxy port was not registered with OpenVpnCommand");
+                    }
+                } else {
+                    args.push(http_proxy.endpoint.ip().to_string());
+                    args.push(http_proxy.endpoint.port().to_string());
+                }
+
+                if http_proxy.auth.is_some() {
+                    if let Some(ref auth_file) = self.proxy_auth_path {
+                        args.push(auth_file.to_string_lossy().to_string());
+                        args.push("basic".to_owned());
+                    } else {
+                        log::error!("Proxy credentials present but credentials file missing");
+                    }
+                }
+
+                args.push("--route".to_owned());
+                args.push(http_proxy.endpoint.ip().to_string());
+                args.push("255.255.255.255".to_owned());
+                args.push("net_gateway".to_owned());
+            }
             None => {}
         };
         args
@@ -457,7 +486,10 @@ impl OpenVpnProcHandle {
 mod tests {
     use super::OpenVpnCommand;
     use std::{ffi::OsString, net::Ipv4Addr};
-    use talpid_types::net::{Endpoint, TransportProtocol};
+    use talpid_types::net::{
+        proxy::{CustomProxy, HttpAuth, HttpConnect},
+        Endpoint, TransportProtocol,
+    };
 
     #[test]
     fn passes_one_remote() {
@@ -484,4 +516,43 @@ mod tests {
         assert!(testee_args.contains(&OsString::from("123")));
         assert!(testee_args.contains(&OsString::from("cde")));
     }
+
+    #[test]
+    fn passes_http_proxy() {
+        let auth = HttpAuth::new("user".to_owned(), "pass".to_owned()).unwrap();
+        let proxy = HttpConnect::new((Ipv4Addr::new(192, 0, 2, 1), 3128), None, Some(auth));
+        let testee_args = OpenVpnCommand::new("")
+            .proxy_settings(CustomProxy::HttpConnect(proxy))
+            .proxy_auth("./auth")
+            .get_arguments();
+
+        let expected: Vec<OsString> = ["--http-proxy", "192.0.2.1", "3128", "./auth", "basic"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        assert!(testee_args
+            .windows(expected.len())
+            .any(|window| window == expected.as_slice()));
+    }
+
+    #[test]
+    fn passes_local_forwarder_for_https_proxy() {
+        let proxy = HttpConnect::new(
+            (Ipv4Addr::new(192, 0, 2, 1), 443),
+            Some("proxy.example.com".to_owned()),
+            None,
+        );
+        let testee_args = OpenVpnCommand::new("")
+            .proxy_settings(CustomProxy::HttpConnect(proxy))
+            .proxy_port(1234)
+            .get_arguments();
+
+        let expected: Vec<OsString> = ["--http-proxy", "127.0.0.1", "1234"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        assert!(testee_args
+            .windows(expected.len())
+            .any(|window| window == expected.as_slice()));
+    }
 }
@@ -0,0 +1,322 @@
+//! Local forwarder for HTTP proxies that must be reached over TLS. OpenVPN only speaks plain HTTP
+//! to its proxy, so it connects to a local port and each connection is wrapped in TLS here.
+use async_trait::async_trait;
+use futures::future::{abortable, AbortHandle, Aborted};
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+use tokio::{
+    net::{TcpListener, TcpSocket, TcpStream},
+    task::JoinHandle,
+};
+use tokio_rustls::{
+    rustls::{pki_types::ServerName, ClientConfig},
+    TlsConnector,
+};
+
+use super::{Error, ProxyMonitor, ProxyMonitorCloseHandle};
+use talpid_types::{net::proxy::HttpConnect, ErrorExt};
+
+pub struct HttpsProxyMonitor {
+    port: u16,
+    server_join_handle: Option<JoinHandle<Result<io::Result<()>, Aborted>>>,
+    server_abort_handle: AbortHandle,
+}
+
+impl HttpsProxyMonitor {
+    pub async fn start(
+        settings: &HttpConnect,
+        server_name: &str,
+        #[cfg(target_os = "linux")] fwmark: u32,
+    ) -> super::Result<Self> {
+        Self::start_inner(
+            settings,
+            server_name,
+            talpid_net::tls::public_tls_config(),
+            #[cfg(target_os = "linux")]
+            fwmark,
+        )
+        .await
+        .map_err(Error::Io)
+    }
+
+    /// Start forwarding to the proxy, verifying its certificate using `tls_config`.
+    async fn start_inner(
+        settings: &HttpConnect,
+        server_name: &str,
+        tls_config: Arc<ClientConfig>,
+        #[cfg(target_os = "linux")] fwmark: u32,
+    ) -> io::Result<Self> {
+        let server_name = ServerName::try_from(server_name.to_owned()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid proxy hostname: {server_name}"),
+            )
+        })?;
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let port = listener.local_addr()?.port();
+        let endpoint = settings.endpoint;
+
+        let (fut, server_abort_handle) = abortable(serve(
+            listener,
+            endpoint,
+            server_name,
+            TlsConnector::from(tls_config),
+            #[cfg(target_os = "linux")]
+            fwmark,
+        ));
+        let server_join_handle = tokio::spawn(fut);
+
+        Ok(Self {
+            port,
+            server_join_handle: Some(server_join_handle),
+            server_abort_handle,
+        })
+    }
+}
+
+/// Accept connections from OpenVPN and forward each of them to the proxy.
+async fn serve(
+    listener: TcpListener,
+    endpoint: SocketAddr,
+    server_name: ServerName<'static>,
+    connector: TlsConnector,
+    #[cfg(target_os = "linux")] fwmark: u32,
+) -> io::Result<()> {
+    loop {
+        let (client, _) = listener.accept().await?;
+        let server_name = server_name.clone();
+        let connector = connector.clone();
+        tokio::spawn(async move {
+            if let Err(error) = forward(
+                client,
+                endpoint,
+                server_name,
+                connector,
+                #[cfg(target_os = "linux")]
+                fwmark,
+            )
+            .await
+            {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to forward to HTTPS proxy")
+                );
+            }
+        });
+    }
+}
+
+/// Connect to the proxy over TLS and copy data between it and `client` until either side
+/// closes the connection.
+async fn forward(
+    mut client: TcpStream,
+    endpoint: SocketAddr,
+    server_name: ServerName<'static>,
+    connector: TlsConnector,
+    #[cfg(target_os = "linux")] fwmark: u32,
+) -> io::Result<()> {
+    let socket = match endpoint {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+
+    #[cfg(target_os = "linux")]
+    {
+        use nix::sys::socket::{setsockopt, sockopt};
+        use std::os::fd::AsRawFd;
+
+        setsockopt(socket.as_raw_fd(), sockopt::Mark, &fwmark)
+            .map_err(|error| io::Error::from_raw_os_error(error as i32))?;
+    }
+
+    let server = socket.connect(endpoint).await?;
+    let mut server = connector.connect(server_name, server).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut server).await?;
+    Ok(())
+}
+
+impl Drop for HttpsProxyMonitor {
+    fn drop(&mut self) {
+        self.server_abort_handle.abort();
+    }
+}
+
+#[async_trait]
+impl ProxyMonitor for HttpsProxyMonitor {
+    fn close_handle(&mut self) -> Box<dyn ProxyMonitorCloseHandle> {
+        Box::new(HttpsProxyMonitorCloseHandle {
+            server_abort_handle: self.server_abort_handle.clone(),
+        })
+    }
+
+    async fn wait(mut self: Box<Self>) -> super::Result<()> {
+        if let Some(join_handle) = self.server_join_handle.take() {
+            match join_handle.await {
+                Ok(Err(Aborted)) => Ok(()),
+
+                Err(join_err) if join_err.is_cancelled() => Ok(()),
+                Err(_) => Err(Error::UnexpectedExit(
+                    "HTTPS proxy task panicked".to_string(),
+                )),
+
+                Ok(Ok(result)) => match result {
+                    Ok(()) => Err(Error::UnexpectedExit("Exited without error".to_string())),
+                    Err(error) => Err(Error::UnexpectedExit(format!(
+                        "Error: {}",
+                        error.display_chain()
+                    ))),
+                },
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+struct HttpsProxyMonitorCloseHandle {
+    server_abort_handle: AbortHandle,
+}
+
+impl ProxyMonitorCloseHandle for HttpsProxyMonitorCloseHandle {
+    fn close(self: Box<Self>) -> super::Result<()> {
+        self.server_abort_handle.abort();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::{
+        rustls::{self, ServerConfig},
+        TlsAcceptor,
+    };
+
+    const CA_CERT: &[u8] = include_bytes!("../../test-data/proxy-ca.pem");
+    const PROXY_CERT: &[u8] = include_bytes!("../../test-data/proxy.example.com.pem");
+    const PROXY_KEY: &[u8] = include_bytes!("../../test-data/proxy.example.com.key");
+    const REQUEST: &[u8] = b"CONNECT 192.0.2.1:1194 HTTP/1.1\r\n\r\n";
+
+    fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+        Arc::new(rustls::crypto::ring::default_provider())
+    }
+
+    /// Client configuration that only trusts the test CA.
+    fn client_config() -> Arc<ClientConfig> {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in rustls_pemfile::certs(&mut &CA_CERT[..]) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let config = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Arc::new(config)
+    }
+
+    /// Accept one TLS connection as `proxy.example.com` and echo what it receives.
+    async fn spawn_tls_echo_server() -> SocketAddr {
+        let certs = rustls_pemfile::certs(&mut &PROXY_CERT[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = rustls_pemfile::private_key(&mut &PROXY_KEY[..])
+            .unwrap()
+            .unwrap();
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            if let Ok(stream) = acceptor.accept(stream).await {
+                let (mut reader, mut writer) = tokio::io::split(stream);
+                let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            }
+        });
+        addr
+    }
+
+    fn settings(endpoint: SocketAddr) -> HttpConnect {
+        HttpConnect {
+            endpoint,
+            tls_server_name: Some("proxy.example.com".to_owned()),
+            auth: None,
+        }
+    }
+
+    /// Setting the fwmark requires `CAP_NET_ADMIN`.
+    fn can_forward() -> bool {
+        #[cfg(target_os = "linux")]
+        if !nix::unistd::Uid::effective().is_root() {
+            eprintln!("Skipping test since setting the fwmark requires root");
+            return false;
+        }
+        true
+    }
+
+    /// Plain data from OpenVPN is sent to the proxy over TLS.
+    #[tokio::test]
+    async fn test_forwards_over_tls() {
+        if !can_forward() {
+            return;
+        }
+        let proxy = spawn_tls_echo_server().await;
+        let monitor = HttpsProxyMonitor::start_inner(
+            &settings(proxy),
+            "proxy.example.com",
+            client_config(),
+            #[cfg(target_os = "linux")]
+            0,
+        )
+        .await
+        .unwrap();
+
+        let mut client = TcpStream::connect((Ipv4Addr::LOCALHOST, monitor.port()))
+            .await
+            .unwrap();
+        client.write_all(REQUEST).await.unwrap();
+        let mut echoed = vec![0u8; REQUEST.len()];
+        client.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(echoed, REQUEST);
+    }
+
+    /// A proxy whose certificate is not valid for the configured name is not used.
+    #[tokio::test]
+    async fn test_rejects_wrong_server_name() {
+        if !can_forward() {
+            return;
+        }
+        let proxy = spawn_tls_echo_server().await;
+        let monitor = HttpsProxyMonitor::start_inner(
+            &settings(proxy),
+            "other.example.com",
+            client_config(),
+            #[cfg(target_os = "linux")]
+            0,
+        )
+        .await
+        .unwrap();
+
+        let mut client = TcpStream::connect((Ipv4Addr::LOCALHOST, monitor.port()))
+            .await
+            .unwrap();
+        let _ = client.write_all(REQUEST).await;
+        let mut buf = [0u8; 1];
+        assert!(matches!(client.read(&mut buf).await, Ok(0) | Err(_)));
+    }
+}
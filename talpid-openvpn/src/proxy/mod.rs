@@ -1,7 +1,8 @@
+mod https;
 mod noop;
 mod shadowsocks;
 
-use self::shadowsocks::ShadowsocksProxyMonitor;
+use self::{https::HttpsProxyMonitor, shadowsocks::ShadowsocksProxyMonitor};
 use async_trait::async_trait;
 use std::{fmt, io};
 use talpid_types::net::proxy::CustomProxy;
@@ -64,5 +65,21 @@ pub async fn start_proxy(
             )
             .await?,
         )),
+        CustomProxy::HttpConnect(http_settings) => match http_settings.tls_server_name {
+            // OpenVPN connects to a local forwarder that wraps the connection in TLS.
+            Some(ref server_name) => Ok(Box::new(
+                HttpsProxyMonitor::start(
+                    http_settings,
+                    server_name,
+                    #[cfg(target_os = "linux")]
+                    fwmark,
+                )
+                .await?,
+            )),
+            // These are generic proxy settings with the proxy client not managed by us.
+            None => Ok(Box::new(noop::NoopProxyMonitor::start(
+                http_settings.endpoint.port(),
+            )?)),
+        },
     }
 }
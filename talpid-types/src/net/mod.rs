@@ -72,6 +72,7 @@ impl TunnelParameters {
                     .map(|proxy| proxy.get_remote_endpoint()),
                 obfuscation: None,
                 entry_endpoint: None,
+                ip_version: IpVersion::from(self.get_next_hop_endpoint().address.ip()),
                 tunnel_interface: None,
                 #[cfg(daita)]
                 daita: false,
@@ -89,6 +90,7 @@ impl TunnelParameters {
                     .connection
                     .get_exit_endpoint()
                     .map(|_| params.connection.get_endpoint()),
+                ip_version: IpVersion::from(self.get_next_hop_endpoint().address.ip()),
                 tunnel_interface: None,
                 #[cfg(daita)]
                 daita: params.options.daita,
@@ -217,6 +219,9 @@ pub struct TunnelEndpoint {
     pub proxy: Option<proxy::ProxyEndpoint>,
     pub obfuscation: Option<ObfuscationEndpoint>,
     pub entry_endpoint: Option<Endpoint>,
+    /// IP version used to reach the first hop, i.e. the relay, entry relay, bridge or
+    /// obfuscator.
+    pub ip_version: IpVersion,
     pub tunnel_interface: Option<String>,
     #[cfg(daita)]
     pub daita: bool,
@@ -465,7 +470,7 @@ impl fmt::Display for AllowedTunnelTraffic {
 }
 
 /// IP protocol version.
-#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum IpVersion {
     #[default]
@@ -631,3 +636,58 @@ impl Connectivity {
         matches!(self, Connectivity::Status { connected: false })
     }
 }
+
+/// Identifies the physical network that the host is connected to, as far as it can be
+/// determined. All fields are `None` if the host has no default route.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkIdentity {
+    /// Interface that the default route goes through.
+    pub interface: Option<String>,
+    /// Address of the default gateway.
+    pub gateway: Option<IpAddr>,
+    /// Hardware address of the default gateway, formatted as `aa:bb:cc:dd:ee:ff`.
+    pub gateway_mac: Option<String>,
+    /// UUID of the NetworkManager connection that provides the default route.
+    pub connection_id: Option<String>,
+    /// SSID of the Wi-Fi network that provides the default route.
+    pub ssid: Option<String>,
+}
+
+impl NetworkIdentity {
+    /// Returns whether the host has a default route at all.
+    pub fn is_connected(&self) -> bool {
+        self.interface.is_some() || self.gateway.is_some()
+    }
+
+    /// Returns a value that identifies the network when it is joined again, if one is known.
+    pub fn key(&self) -> Option<&str> {
+        self.connection_id
+            .as_deref()
+            .or(self.gateway_mac.as_deref())
+    }
+}
+
+impl fmt::Display for NetworkIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_connected() {
+            return write!(f, "no network");
+        }
+        let mut parts = vec![];
+        if let Some(ssid) = &self.ssid {
+            parts.push(format!("SSID {ssid}"));
+        }
+        if let Some(connection_id) = &self.connection_id {
+            parts.push(format!("connection {connection_id}"));
+        }
+        if let Some(interface) = &self.interface {
+            parts.push(format!("interface {interface}"));
+        }
+        if let Some(gateway) = &self.gateway {
+            parts.push(format!("gateway {gateway}"));
+        }
+        if let Some(gateway_mac) = &self.gateway_mac {
+            parts.push(format!("gateway MAC {gateway_mac}"));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
@@ -9,6 +9,10 @@ pub enum Error {
     /// Validation of SOCKS5 username or password failed.
     #[error("Invalid SOCKS5 authentication credentials: {0}")]
     InvalidSocksAuthValues(&'static str),
+
+    /// Validation of HTTP proxy username or password failed.
+    #[error("Invalid HTTP proxy authentication credentials: {0}")]
+    InvalidHttpAuthValues(&'static str),
 }
 
 /// Types of bridges that can be used to proxy a connection to a tunnel
@@ -44,6 +48,7 @@ pub enum CustomProxy {
     Shadowsocks(Shadowsocks),
     Socks5Local(Socks5Local),
     Socks5Remote(Socks5Remote),
+    HttpConnect(HttpConnect),
 }
 
 impl CustomProxy {
@@ -61,6 +66,10 @@ impl CustomProxy {
                 endpoint: Endpoint::from_socket_address(settings.endpoint, TransportProtocol::Tcp),
                 proxy_type: ProxyType::Shadowsocks,
             },
+            CustomProxy::HttpConnect(settings) => ProxyEndpoint {
+                endpoint: Endpoint::from_socket_address(settings.endpoint, TransportProtocol::Tcp),
+                proxy_type: ProxyType::Custom,
+            },
         }
     }
 }
@@ -83,6 +92,12 @@ impl From<Shadowsocks> for CustomProxy {
     }
 }
 
+impl From<HttpConnect> for CustomProxy {
+    fn from(value: HttpConnect) -> Self {
+        CustomProxy::HttpConnect(value)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Shadowsocks {
     pub endpoint: SocketAddr,
@@ -105,6 +120,87 @@ pub struct Socks5Remote {
     pub auth: Option<SocksAuth>,
 }
 
+/// HTTP proxy that tunnels connections using the `CONNECT` method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct HttpConnect {
+    pub endpoint: SocketAddr,
+    /// If set, the connection to the proxy itself uses TLS, and the certificate of the proxy is
+    /// verified against this name.
+    #[serde(default)]
+    pub tls_server_name: Option<String>,
+    pub auth: Option<HttpAuth>,
+}
+
+/// Credentials for HTTP Basic authentication with a proxy, sent in the `Proxy-Authorization`
+/// header. Deserialized credentials are validated by [`HttpAuth::new`].
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(try_from = "UncheckedHttpAuth")]
+pub struct HttpAuth {
+    username: String,
+    password: String,
+}
+
+/// [`HttpAuth`] as it is serialized, before it has been validated.
+#[derive(Deserialize)]
+struct UncheckedHttpAuth {
+    username: String,
+    password: String,
+}
+
+impl TryFrom<UncheckedHttpAuth> for HttpAuth {
+    type Error = Error;
+
+    fn try_from(auth: UncheckedHttpAuth) -> Result<Self, Self::Error> {
+        HttpAuth::new(auth.username, auth.password)
+    }
+}
+
+impl HttpAuth {
+    /// Validate HTTP Basic authentication credentials.
+    ///
+    /// The username must not be empty and must not contain a colon, since it is separated from
+    /// the password by a colon. Neither may contain control characters.
+    ///
+    /// ```
+    /// use talpid_types::net::proxy::HttpAuth;
+    ///
+    /// assert!(HttpAuth::new("FooBar".to_string(), "hunter2".to_string()).is_ok());
+    /// assert!(HttpAuth::new("".to_string(), "hunter2".to_string()).is_err());
+    /// assert!(HttpAuth::new("Foo:Bar".to_string(), "hunter2".to_string()).is_err());
+    /// assert!(HttpAuth::new("FooBar".to_string(), "hunter\n2".to_string()).is_err());
+    /// ```
+    pub fn new(username: String, password: String) -> Result<Self, Error> {
+        if username.is_empty() {
+            return Err(Error::InvalidHttpAuthValues("Username must not be empty"));
+        }
+        if username.contains(':') {
+            return Err(Error::InvalidHttpAuthValues(
+                "Username must not contain a colon",
+            ));
+        }
+        if username
+            .chars()
+            .chain(password.chars())
+            .any(char::is_control)
+        {
+            return Err(Error::InvalidHttpAuthValues(
+                "Username and password must not contain control characters",
+            ));
+        }
+        Ok(HttpAuth { username, password })
+    }
+
+    /// Read the username.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Read the password.
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+}
+
 /// A valid SOCKS5 username/password authentication according to
 /// RFC 1929: <https://datatracker.ietf.org/doc/html/rfc1929>.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -214,6 +310,25 @@ impl Socks5Local {
     }
 }
 
+impl HttpConnect {
+    pub fn new<I: Into<SocketAddr>>(
+        endpoint: I,
+        tls_server_name: Option<String>,
+        auth: Option<HttpAuth>,
+    ) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            tls_server_name,
+            auth,
+        }
+    }
+
+    /// Whether the connection to the proxy uses TLS.
+    pub fn uses_tls(&self) -> bool {
+        self.tls_server_name.is_some()
+    }
+}
+
 impl Socks5Remote {
     pub fn new<I: Into<SocketAddr>>(endpoint: I) -> Self {
         Self {
@@ -257,3 +372,24 @@ pub const SHADOWSOCKS_CIPHERS: [&str; 19] = [
     "aes-128-pmac-siv",
     "aes-256-pmac-siv",
 ];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_http_auth() {
+        let auth: HttpAuth =
+            serde_json::from_str(r#"{ "username": "FooBar", "password": "hunter2" }"#).unwrap();
+        assert_eq!(auth.username(), "FooBar");
+        assert_eq!(auth.password(), "hunter2");
+
+        for invalid in [
+            r#"{ "username": "", "password": "hunter2" }"#,
+            r#"{ "username": "Foo:Bar", "password": "hunter2" }"#,
+            r#"{ "username": "FooBar", "password": "hunter\n2" }"#,
+        ] {
+            serde_json::from_str::<HttpAuth>(invalid).unwrap_err();
+        }
+    }
+}
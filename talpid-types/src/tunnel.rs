@@ -92,6 +92,8 @@ pub enum ErrorStateCause {
     TunnelParameterError(ParameterGenerationError),
     /// This device is offline, no tunnels can be established.
     IsOffline,
+    /// A network rule says that all traffic should be blocked on the current network.
+    BlockedByNetworkRule,
     /// The Android VPN permission was denied.
     #[cfg(target_os = "android")]
     VpnPermissionDenied,
@@ -200,6 +202,7 @@ impl fmt::Display for ErrorStateCause {
                 return write!(f, "Failure to generate tunnel parameters: {err}");
             }
             IsOffline => "This device is offline, no tunnels can be established",
+            BlockedByNetworkRule => "Blocked by the rule for the current network",
             #[cfg(target_os = "android")]
             VpnPermissionDenied => "The Android VPN permission was denied when creating the tunnel",
             #[cfg(any(target_os = "windows", target_os = "macos", target_os = "android"))]
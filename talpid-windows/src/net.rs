@@ -17,19 +17,19 @@ use windows_sys::{
             IpHelper::{
                 CancelMibChangeNotify2, ConvertInterfaceAliasToLuid, ConvertInterfaceLuidToAlias,
                 ConvertInterfaceLuidToGuid, ConvertInterfaceLuidToIndex,
-                CreateUnicastIpAddressEntry, FreeMibTable, GetIpInterfaceEntry,
+                CreateUnicastIpAddressEntry, FreeMibTable, GetIpInterfaceEntry, GetIpNetEntry2,
                 GetUnicastIpAddressEntry, GetUnicastIpAddressTable,
                 InitializeUnicastIpAddressEntry, MibAddInstance, NotifyIpInterfaceChange,
-                SetIpInterfaceEntry, MIB_IPINTERFACE_ROW, MIB_UNICASTIPADDRESS_ROW,
+                SetIpInterfaceEntry, MIB_IPINTERFACE_ROW, MIB_IPNET_ROW2, MIB_UNICASTIPADDRESS_ROW,
                 MIB_UNICASTIPADDRESS_TABLE,
             },
             Ndis::{IF_MAX_STRING_SIZE, NET_LUID_LH},
         },
         Networking::WinSock::{
             IpDadStateDeprecated, IpDadStateDuplicate, IpDadStateInvalid, IpDadStatePreferred,
-            IpDadStateTentative, AF_INET, AF_INET6, AF_UNSPEC, IN6_ADDR, IN_ADDR, NL_DAD_STATE,
-            SOCKADDR_IN as sockaddr_in, SOCKADDR_IN6 as sockaddr_in6, SOCKADDR_INET,
-            SOCKADDR_STORAGE as sockaddr_storage,
+            IpDadStateTentative, NlnsIncomplete, NlnsUnreachable, AF_INET, AF_INET6, AF_UNSPEC,
+            IN6_ADDR, IN_ADDR, NL_DAD_STATE, SOCKADDR_IN as sockaddr_in,
+            SOCKADDR_IN6 as sockaddr_in6, SOCKADDR_INET, SOCKADDR_STORAGE as sockaddr_storage,
         },
     },
 };
@@ -396,6 +396,28 @@ pub fn alias_from_luid(luid: &NET_LUID_LH) -> io::Result<OsString> {
     Ok(OsString::from_wide(&buffer[0..nul]))
 }
 
+/// Returns the hardware address of the neighbor `address` on the interface `luid`, or `None` if
+/// it has not been resolved.
+pub fn get_neighbor_hardware_address(
+    luid: &NET_LUID_LH,
+    address: IpAddr,
+) -> io::Result<Option<Vec<u8>>> {
+    let mut row: MIB_IPNET_ROW2 = unsafe { mem::zeroed() };
+    row.InterfaceLuid = *luid;
+    row.Address = inet_sockaddr_from_socketaddr(SocketAddr::new(address, 0));
+
+    match win32_err!(unsafe { GetIpNetEntry2(&mut row) }) {
+        Ok(()) => (),
+        Err(error) if error.raw_os_error() == Some(ERROR_NOT_FOUND as i32) => return Ok(None),
+        Err(error) => return Err(error),
+    }
+    if row.State == NlnsIncomplete || row.State == NlnsUnreachable {
+        return Ok(None);
+    }
+    let length = (row.PhysicalAddressLength as usize).min(row.PhysicalAddress.len());
+    Ok(Some(row.PhysicalAddress[..length].to_vec()))
+}
+
 fn af_family_from_family(family: Option<AddressFamily>) -> u16 {
     family.map(|family| family as u16).unwrap_or(AF_UNSPEC)
 }
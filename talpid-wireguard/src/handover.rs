@@ -0,0 +1,561 @@
+//! Switching a running tunnel to a different peer without recreating the tunnel interface.
+//!
+//! This is only possible when the interface itself would be configured identically for the new
+//! peer, which rules out obfuscation, multihop and anything that requires negotiating an
+//! ephemeral peer. The tunnel addresses may change, as they do after a key rotation, if the
+//! tunnel implementation can replace them on the running interface. Connections through the
+//! tunnel survive the handover if the exit IP does not change. The handover is only complete once
+//! a handshake with the new peer has been observed.
+
+use crate::{
+    config::Config,
+    ping_monitor::{new_pinger, Pinger},
+    Tunnel, TunnelError,
+};
+use std::{
+    net::IpAddr,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+#[cfg(not(target_os = "android"))]
+use talpid_routing::RouteManagerHandle;
+use talpid_types::net::wireguard::TunnelParameters;
+use tokio::{sync::Mutex as AsyncMutex, time::Instant};
+
+/// Time to wait for a handshake with the new peer before the handover is considered failed.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Interval at which the tunnel stats are read while waiting for a handshake.
+const HANDSHAKE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Interval at which pings are sent through the tunnel to prod WireGuard into a handshake.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Errors that can happen when handing over a tunnel to a new peer.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The new parameters cannot be applied to the running tunnel.
+    #[error("Handover is not possible: {0}")]
+    Unsupported(&'static str),
+
+    /// The tunnel has been stopped.
+    #[error("The tunnel is not running")]
+    TunnelDown,
+
+    /// Failed to create a WireGuard config from the new parameters.
+    #[error("Invalid WireGuard configuration")]
+    Config(#[from] crate::config::Error),
+
+    /// Failed to route traffic to the new peer.
+    #[cfg(not(target_os = "android"))]
+    #[error("Failed to add route to the new peer")]
+    Routing(#[source] talpid_routing::Error),
+
+    /// Failed to replace the addresses of the tunnel interface.
+    #[error("Failed to replace the tunnel addresses")]
+    SetAddresses(#[source] TunnelError),
+
+    /// Failed to apply the new config to the tunnel.
+    #[error("Failed to apply the new WireGuard configuration")]
+    SetConfig(#[source] TunnelError),
+
+    /// Failed to read the tunnel stats while waiting for a handshake.
+    #[error("Failed to read the tunnel stats")]
+    Stats(#[source] TunnelError),
+
+    /// Failed to send traffic through the tunnel while waiting for a handshake.
+    #[error("Failed to ping the tunnel gateway")]
+    Ping(#[source] crate::ping_monitor::Error),
+
+    /// No handshake with the new peer was observed in time.
+    #[error("Timed out waiting for a handshake with the new peer")]
+    HandshakeTimeout,
+}
+
+/// Handle for switching a running tunnel to a new peer.
+#[derive(Clone)]
+pub struct TunnelHandoverHandle {
+    tunnel: Weak<AsyncMutex<Option<Box<dyn Tunnel>>>>,
+    /// Parameters that the tunnel is currently configured with.
+    parameters: Arc<Mutex<TunnelParameters>>,
+    /// MTU of the tunnel interface, which is kept as is.
+    mtu: u16,
+    #[cfg(not(target_os = "android"))]
+    route_manager: RouteManagerHandle,
+}
+
+impl TunnelHandoverHandle {
+    pub(crate) fn new(
+        tunnel: &Arc<AsyncMutex<Option<Box<dyn Tunnel>>>>,
+        parameters: &TunnelParameters,
+        mtu: u16,
+        #[cfg(not(target_os = "android"))] route_manager: RouteManagerHandle,
+    ) -> Self {
+        Self {
+            tunnel: Arc::downgrade(tunnel),
+            parameters: Arc::new(Mutex::new(parameters.clone())),
+            mtu,
+            #[cfg(not(target_os = "android"))]
+            route_manager,
+        }
+    }
+
+    /// Returns an error describing why `new_parameters` cannot be applied to the running tunnel,
+    /// if that is the case. This does not change the tunnel, and should be called before traffic
+    /// to the new peer is allowed.
+    pub async fn check(&self, new_parameters: &TunnelParameters) -> Result<(), Error> {
+        let current = self.parameters.lock().unwrap().clone();
+        let tunnel = self.tunnel.upgrade().ok_or(Error::TunnelDown)?;
+        let tunnel = tunnel.lock().await;
+        let tunnel = tunnel.as_ref().ok_or(Error::TunnelDown)?;
+        check_tunnel(tunnel.as_ref(), &current, new_parameters)
+    }
+
+    /// Replaces the peer of the running tunnel with the one in `new_parameters`, and waits for a
+    /// handshake with it. The firewall must already allow traffic to the new peer.
+    pub async fn replace_peer(&self, new_parameters: &TunnelParameters) -> Result<(), Error> {
+        let config = Config::from_parameters(new_parameters, self.mtu)?;
+
+        #[cfg(not(target_os = "android"))]
+        {
+            // Routes to the previous peer are removed along with all other routes when the
+            // tunnel is eventually torn down.
+            let endpoints: Vec<_> = config.peers().map(|peer| peer.endpoint.ip()).collect();
+            self.route_manager
+                .add_routes(crate::WireguardMonitor::get_endpoint_routes(&endpoints).collect())
+                .await
+                .map_err(Error::Routing)?;
+        }
+
+        let current = self.parameters.lock().unwrap().clone();
+        let tunnel = self.tunnel.upgrade().ok_or(Error::TunnelDown)?;
+
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        let interface_name = tunnel
+            .lock()
+            .await
+            .as_ref()
+            .ok_or(Error::TunnelDown)?
+            .get_interface_name();
+        let mut pinger = new_pinger(
+            new_parameters.connection.ipv4_gateway,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            interface_name,
+        )
+        .map_err(Error::Ping)?;
+
+        switch_peer(
+            &tunnel,
+            &current,
+            new_parameters,
+            config,
+            pinger.as_mut(),
+            HANDSHAKE_TIMEOUT,
+        )
+        .await?;
+
+        *self.parameters.lock().unwrap() = new_parameters.clone();
+        Ok(())
+    }
+}
+
+/// Returns why a running `tunnel` configured with `current` cannot be switched over to `new`, if
+/// that is the case.
+fn check_tunnel(
+    tunnel: &dyn Tunnel,
+    current: &TunnelParameters,
+    new: &TunnelParameters,
+) -> Result<(), Error> {
+    check_compatible(current, new).map_err(Error::Unsupported)?;
+    if current.connection.tunnel.addresses != new.connection.tunnel.addresses
+        && !tunnel.can_set_addresses()
+    {
+        return Err(Error::Unsupported(
+            "the tunnel addresses cannot be changed while the tunnel is running",
+        ));
+    }
+    Ok(())
+}
+
+/// Applies `config`, which is created from `new`, to a running tunnel configured with `current`
+/// and waits for a handshake with the new peer.
+async fn switch_peer(
+    tunnel: &AsyncMutex<Option<Box<dyn Tunnel>>>,
+    current: &TunnelParameters,
+    new: &TunnelParameters,
+    config: Config,
+    pinger: &mut dyn Pinger,
+    timeout: Duration,
+) -> Result<(), Error> {
+    let peer = *config.entry_peer.public_key.as_bytes();
+
+    let initial_rx_bytes = {
+        let mut tunnel = tunnel.lock().await;
+        let tunnel = tunnel.as_mut().ok_or(Error::TunnelDown)?;
+        check_tunnel(tunnel.as_ref(), current, new)?;
+        apply_config(
+            tunnel.as_mut(),
+            &current.connection.tunnel.addresses,
+            config,
+        )
+        .await?;
+        peer_rx_bytes(tunnel.as_ref(), &peer)?
+    };
+
+    // Data can only be received from the new peer once a handshake has been completed with the
+    // new key.
+    let start = Instant::now();
+    let mut last_ping: Option<Instant> = None;
+    loop {
+        {
+            let tunnel = tunnel.lock().await;
+            let tunnel = tunnel.as_ref().ok_or(Error::TunnelDown)?;
+            if peer_rx_bytes(tunnel.as_ref(), &peer)? > initial_rx_bytes {
+                return Ok(());
+            }
+        }
+        if start.elapsed() >= timeout {
+            return Err(Error::HandshakeTimeout);
+        }
+        if last_ping.map_or(true, |last_ping| last_ping.elapsed() >= PING_INTERVAL) {
+            pinger.send_icmp().map_err(Error::Ping)?;
+            last_ping = Some(Instant::now());
+        }
+        tokio::time::sleep(HANDSHAKE_POLL_INTERVAL).await;
+    }
+}
+
+/// Returns the number of bytes that have been received from `peer`.
+fn peer_rx_bytes(tunnel: &dyn Tunnel, peer: &[u8; 32]) -> Result<u64, Error> {
+    let stats = tunnel.get_tunnel_stats().map_err(Error::Stats)?;
+    Ok(stats.get(peer).map(|stats| stats.rx_bytes).unwrap_or(0))
+}
+
+/// Applies `config` to a running tunnel whose interface currently has `current_addresses`.
+async fn apply_config(
+    tunnel: &mut dyn Tunnel,
+    current_addresses: &[IpAddr],
+    config: Config,
+) -> Result<(), Error> {
+    if current_addresses != config.tunnel.addresses {
+        tunnel
+            .set_addresses(current_addresses, &config.tunnel.addresses)
+            .await
+            .map_err(Error::SetAddresses)?;
+    }
+    tunnel.set_config(config).await.map_err(Error::SetConfig)
+}
+
+/// Returns why a tunnel configured with `current` cannot be switched over to `new`, if that is
+/// the case.
+fn check_compatible(
+    current: &TunnelParameters,
+    new: &TunnelParameters,
+) -> Result<(), &'static str> {
+    for params in [current, new] {
+        if params.obfuscation.is_some() {
+            return Err("obfuscation is used");
+        }
+        if params.connection.exit_peer.is_some() {
+            return Err("multihop is used");
+        }
+        if params.options.quantum_resistant {
+            return Err("quantum-resistant tunnels require a new ephemeral peer");
+        }
+        #[cfg(daita)]
+        if params.options.daita {
+            return Err("DAITA requires a new ephemeral peer");
+        }
+    }
+    if current.options != new.options || current.generic_options != new.generic_options {
+        return Err("the tunnel options have changed");
+    }
+    if current.connection.ipv4_gateway != new.connection.ipv4_gateway
+        || current.connection.ipv6_gateway != new.connection.ipv6_gateway
+    {
+        return Err("the tunnel gateway has changed");
+    }
+    if current.connection.peer.allowed_ips != new.connection.peer.allowed_ips {
+        return Err("the allowed IPs have changed");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stats::{Stats, StatsMap};
+    use futures::Future;
+    use std::pin::Pin;
+    use talpid_types::net::{
+        obfuscation::ObfuscatorConfig,
+        wireguard::{ConnectionConfig, PeerConfig, PrivateKey, TunnelConfig, TunnelOptions},
+        GenericTunnelOptions,
+    };
+
+    fn parameters(endpoint: &str, private_key: PrivateKey) -> TunnelParameters {
+        TunnelParameters {
+            connection: ConnectionConfig {
+                tunnel: TunnelConfig {
+                    private_key,
+                    addresses: vec!["10.64.0.2".parse().unwrap()],
+                },
+                peer: PeerConfig {
+                    public_key: PrivateKey::new_from_random().public_key(),
+                    allowed_ips: vec!["0.0.0.0/0".parse().unwrap()],
+                    endpoint: endpoint.parse().unwrap(),
+                    psk: None,
+                    #[cfg(daita)]
+                    constant_packet_size: false,
+                },
+                exit_peer: None,
+                ipv4_gateway: "10.64.0.1".parse().unwrap(),
+                ipv6_gateway: None,
+                #[cfg(target_os = "linux")]
+                fwmark: None,
+            },
+            options: TunnelOptions {
+                mtu: None,
+                quantum_resistant: false,
+                #[cfg(daita)]
+                daita: false,
+            },
+            generic_options: GenericTunnelOptions { enable_ipv6: false },
+            obfuscation: None,
+        }
+    }
+
+    #[test]
+    fn test_relay_switch_and_key_rotation() {
+        let current = parameters("1.2.3.4:51820", PrivateKey::new_from_random());
+
+        let other_relay = parameters(
+            "5.6.7.8:51820",
+            current.connection.tunnel.private_key.clone(),
+        );
+        assert_eq!(check_compatible(&current, &other_relay), Ok(()));
+
+        let rotated_key = TunnelParameters {
+            connection: ConnectionConfig {
+                tunnel: TunnelConfig {
+                    private_key: PrivateKey::new_from_random(),
+                    addresses: vec!["10.64.0.3".parse().unwrap()],
+                },
+                ..current.connection.clone()
+            },
+            ..current.clone()
+        };
+        assert_eq!(check_compatible(&current, &rotated_key), Ok(()));
+    }
+
+    #[test]
+    fn test_incompatible_parameters() {
+        let current = parameters("1.2.3.4:51820", PrivateKey::new_from_random());
+
+        let mut new = current.clone();
+        new.obfuscation = Some(ObfuscatorConfig::Udp2Tcp {
+            endpoint: "5.6.7.8:443".parse().unwrap(),
+        });
+        assert!(check_compatible(&current, &new).is_err());
+
+        let mut new = current.clone();
+        new.options.quantum_resistant = true;
+        assert!(check_compatible(&current, &new).is_err());
+
+        let mut new = current.clone();
+        new.connection.peer.allowed_ips = vec!["10.0.0.0/8".parse().unwrap()];
+        assert!(check_compatible(&current, &new).is_err());
+
+        let mut new = current.clone();
+        new.generic_options.enable_ipv6 = true;
+        assert!(check_compatible(&current, &new).is_err());
+    }
+
+    /// What a [`MockTunnel`] has been configured with, and what it has received since.
+    #[derive(Default)]
+    struct MockState {
+        addresses: Vec<IpAddr>,
+        private_key: Option<PrivateKey>,
+        peer: Option<[u8; 32]>,
+        rx_bytes: u64,
+    }
+
+    /// Tunnel that records how it is configured, and reports the received bytes in its state as
+    /// having been received from the configured peer.
+    struct MockTunnel {
+        can_set_addresses: bool,
+        state: Arc<Mutex<MockState>>,
+    }
+
+    impl Tunnel for MockTunnel {
+        fn get_interface_name(&self) -> String {
+            "mock-tunnel".to_string()
+        }
+
+        fn stop(self: Box<Self>) -> Result<(), TunnelError> {
+            Ok(())
+        }
+
+        fn get_tunnel_stats(&self) -> Result<StatsMap, TunnelError> {
+            let state = self.state.lock().unwrap();
+            let stats = Stats {
+                tx_bytes: 0,
+                rx_bytes: state.rx_bytes,
+            };
+            Ok(state.peer.into_iter().map(|peer| (peer, stats)).collect())
+        }
+
+        fn set_config(
+            &mut self,
+            config: Config,
+        ) -> Pin<Box<dyn Future<Output = Result<(), TunnelError>> + Send + '_>> {
+            Box::pin(async move {
+                let mut state = self.state.lock().unwrap();
+                state.private_key = Some(config.tunnel.private_key);
+                state.peer = Some(*config.entry_peer.public_key.as_bytes());
+                Ok(())
+            })
+        }
+
+        fn can_set_addresses(&self) -> bool {
+            self.can_set_addresses
+        }
+
+        fn set_addresses<'a>(
+            &'a mut self,
+            current: &'a [IpAddr],
+            new: &'a [IpAddr],
+        ) -> Pin<Box<dyn Future<Output = Result<(), TunnelError>> + Send + 'a>> {
+            Box::pin(async move {
+                assert!(self.can_set_addresses);
+                let mut state = self.state.lock().unwrap();
+                assert_eq!(state.addresses, current);
+                state.addresses = new.to_vec();
+                Ok(())
+            })
+        }
+
+        #[cfg(daita)]
+        fn start_daita(&mut self) -> Result<(), TunnelError> {
+            Ok(())
+        }
+    }
+
+    /// Pinger whose pings are answered through the tunnel if `reply_to` is set.
+    struct MockPinger {
+        reply_to: Option<Arc<Mutex<MockState>>>,
+    }
+
+    impl Pinger for MockPinger {
+        fn send_icmp(&mut self) -> Result<(), crate::ping_monitor::Error> {
+            if let Some(state) = &self.reply_to {
+                state.lock().unwrap().rx_bytes += 92;
+            }
+            Ok(())
+        }
+    }
+
+    /// Parameters for the same relay after a key rotation, which assigns new tunnel addresses.
+    fn rotate_key(current: &TunnelParameters) -> TunnelParameters {
+        let mut rotated = current.clone();
+        rotated.connection.tunnel = TunnelConfig {
+            private_key: PrivateKey::new_from_random(),
+            addresses: vec!["10.64.0.3".parse().unwrap()],
+        };
+        rotated
+    }
+
+    /// Performs the same steps as [`TunnelHandoverHandle`] does on a tunnel that is configured
+    /// with `current`, except for adding routes.
+    async fn hand_over(
+        current: &TunnelParameters,
+        new: &TunnelParameters,
+        can_set_addresses: bool,
+        peer_replies: bool,
+    ) -> (Result<(), Error>, Arc<Mutex<MockState>>) {
+        let state = Arc::new(Mutex::new(MockState {
+            addresses: current.connection.tunnel.addresses.clone(),
+            ..Default::default()
+        }));
+        let tunnel: AsyncMutex<Option<Box<dyn Tunnel>>> =
+            AsyncMutex::new(Some(Box::new(MockTunnel {
+                can_set_addresses,
+                state: state.clone(),
+            })));
+        let mut pinger = MockPinger {
+            reply_to: peer_replies.then(|| state.clone()),
+        };
+
+        let checked = check_tunnel(tunnel.lock().await.as_deref().unwrap(), current, new);
+        let result = match checked {
+            Ok(()) => {
+                let config = Config::from_parameters(new, 1380).unwrap();
+                switch_peer(
+                    &tunnel,
+                    current,
+                    new,
+                    config,
+                    &mut pinger,
+                    HANDSHAKE_TIMEOUT,
+                )
+                .await
+            }
+            Err(error) => Err(error),
+        };
+        (result, state)
+    }
+
+    /// A key rotation comes with new tunnel addresses, which must be moved over to the running
+    /// interface along with the new key.
+    #[tokio::test(start_paused = true)]
+    async fn test_handover_across_key_rotation() {
+        let current = parameters("1.2.3.4:51820", PrivateKey::new_from_random());
+        let rotated = rotate_key(&current);
+
+        let (result, state) = hand_over(&current, &rotated, true, true).await;
+        result.unwrap();
+
+        let state = state.lock().unwrap();
+        assert_eq!(state.addresses, rotated.connection.tunnel.addresses);
+        assert_eq!(
+            state.private_key.as_ref().map(PrivateKey::public_key),
+            Some(rotated.connection.tunnel.private_key.public_key())
+        );
+    }
+
+    /// Tunnels that cannot change their addresses must be rejected before they are touched, so
+    /// that the firewall is never opened for a handover that cannot happen.
+    #[tokio::test(start_paused = true)]
+    async fn test_handover_rejected_without_set_addresses() {
+        let current = parameters("1.2.3.4:51820", PrivateKey::new_from_random());
+        let rotated = rotate_key(&current);
+
+        let (result, state) = hand_over(&current, &rotated, false, true).await;
+        assert!(matches!(result, Err(Error::Unsupported(_))));
+
+        let state = state.lock().unwrap();
+        assert_eq!(state.addresses, current.connection.tunnel.addresses);
+        assert!(state.private_key.is_none());
+
+        // Switching relays without changing the addresses does not require it.
+        let other_relay = parameters(
+            "5.6.7.8:51820",
+            current.connection.tunnel.private_key.clone(),
+        );
+        let (result, _) = hand_over(&current, &other_relay, false, true).await;
+        result.unwrap();
+    }
+
+    /// The handover must not succeed until data has been received from the new peer.
+    #[tokio::test(start_paused = true)]
+    async fn test_handover_requires_handshake() {
+        let current = parameters("1.2.3.4:51820", PrivateKey::new_from_random());
+        let other_relay = parameters(
+            "5.6.7.8:51820",
+            current.connection.tunnel.private_key.clone(),
+        );
+
+        let start = Instant::now();
+        let (result, _) = hand_over(&current, &other_relay, true, false).await;
+        assert!(matches!(result, Err(Error::HandshakeTimeout)));
+        assert!(start.elapsed() >= HANDSHAKE_TIMEOUT);
+    }
+}
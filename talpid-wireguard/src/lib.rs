@@ -16,7 +16,7 @@ use std::{
     net::IpAddr,
     path::Path,
     pin::Pin,
-    sync::{mpsc as sync_mpsc, Arc, Mutex},
+    sync::{mpsc as sync_mpsc, Arc, Mutex, Weak},
 };
 #[cfg(target_os = "linux")]
 use std::{env, sync::LazyLock};
@@ -37,6 +37,7 @@ use tokio::sync::Mutex as AsyncMutex;
 pub mod config;
 mod connectivity_check;
 mod ephemeral;
+mod handover;
 mod logging;
 mod obfuscation;
 mod ping_monitor;
@@ -54,6 +55,9 @@ mod mtu_detection;
 #[cfg(wireguard_go)]
 use self::wireguard_go::WgGoTunnel;
 
+pub use handover::{Error as HandoverError, TunnelHandoverHandle};
+pub use stats::Stats;
+
 type Result<T> = std::result::Result<T, Error>;
 type EventCallback = Box<dyn (Fn(TunnelEvent) -> BoxFuture<'static, ()>) + Send + Sync + 'static>;
 
@@ -140,6 +144,7 @@ pub struct WireguardMonitor {
     close_msg_receiver: sync_mpsc::Receiver<CloseMsg>,
     pinger_stop_sender: sync_mpsc::Sender<()>,
     obfuscator: Arc<AsyncMutex<Option<ObfuscatorHandle>>>,
+    handover: TunnelHandoverHandle,
 }
 
 #[cfg(target_os = "linux")]
@@ -209,16 +214,20 @@ impl WireguardMonitor {
         let iface_name = tunnel.get_interface_name();
 
         let obfuscator = Arc::new(AsyncMutex::new(obfuscator));
+        let tunnel = Arc::new(AsyncMutex::new(Some(tunnel)));
+        let handover =
+            TunnelHandoverHandle::new(&tunnel, params, config.mtu, args.route_manager.clone());
 
         let event_callback = Box::new(on_event.clone());
         let (pinger_tx, pinger_rx) = sync_mpsc::channel();
         let monitor = WireguardMonitor {
             runtime: args.runtime.clone(),
-            tunnel: Arc::new(AsyncMutex::new(Some(tunnel))),
+            tunnel,
             event_callback,
             close_msg_receiver: close_obfs_listener,
             pinger_stop_sender: pinger_tx,
             obfuscator,
+            handover,
         };
 
         let gateway = config.ipv4_gateway;
@@ -431,14 +440,18 @@ impl WireguardMonitor {
 
         let iface_name = tunnel.get_interface_name();
 
+        let tunnel = Arc::new(AsyncMutex::new(Some(tunnel)));
+        let handover = TunnelHandoverHandle::new(&tunnel, params, config.mtu);
+
         let (pinger_tx, pinger_rx) = sync_mpsc::channel();
         let monitor = WireguardMonitor {
             runtime: args.runtime.clone(),
-            tunnel: Arc::new(AsyncMutex::new(Some(tunnel))),
+            tunnel,
             event_callback: Box::new(args.on_event.clone()),
             close_msg_receiver: close_obfs_listener,
             pinger_stop_sender: pinger_tx,
             obfuscator: Arc::new(AsyncMutex::new(obfuscator)),
+            handover,
         };
 
         let gateway = config.ipv4_gateway;
@@ -763,6 +776,20 @@ impl WireguardMonitor {
         Ok(tunnel)
     }
 
+    /// Returns a handle that can be used to read the traffic counters of the tunnel for as long
+    /// as it is running.
+    pub fn stats_handle(&self) -> TunnelStatsHandle {
+        TunnelStatsHandle {
+            tunnel: Arc::downgrade(&self.tunnel),
+        }
+    }
+
+    /// Returns a handle that can be used to switch the tunnel over to a new peer without
+    /// recreating it.
+    pub fn handover_handle(&self) -> TunnelHandoverHandle {
+        self.handover.clone()
+    }
+
     /// Blocks the current thread until tunnel disconnects
     pub fn wait(mut self) -> Result<()> {
         let wait_result = match self.close_msg_receiver.recv() {
@@ -963,6 +990,35 @@ impl WireguardMonitor {
     }
 }
 
+/// Handle for reading the traffic counters of a running tunnel.
+#[derive(Clone)]
+pub struct TunnelStatsHandle {
+    tunnel: Weak<AsyncMutex<Option<Box<dyn Tunnel>>>>,
+}
+
+impl TunnelStatsHandle {
+    /// Returns the total number of bytes sent and received by all peers of the tunnel, or `None`
+    /// if the tunnel is no longer running.
+    pub async fn get(&self) -> Option<Stats> {
+        let tunnel = self.tunnel.upgrade()?;
+        let tunnel = tunnel.lock().await;
+        let stats = match tunnel.as_ref()?.get_tunnel_stats() {
+            Ok(stats) => stats,
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read tunnel stats")
+                );
+                return None;
+            }
+        };
+        Some(stats.values().fold(Stats::default(), |total, peer| Stats {
+            tx_bytes: total.tx_bytes + peer.tx_bytes,
+            rx_bytes: total.rx_bytes + peer.rx_bytes,
+        }))
+    }
+}
+
 #[derive(Debug)]
 enum CloseMsg {
     Stop,
@@ -981,6 +1037,18 @@ pub(crate) trait Tunnel: Send {
         &'a mut self,
         _config: Config,
     ) -> Pin<Box<dyn Future<Output = std::result::Result<(), TunnelError>> + Send + 'a>>;
+    /// Returns whether [`Tunnel::set_addresses`] is supported by this tunnel.
+    fn can_set_addresses(&self) -> bool {
+        false
+    }
+    /// Replaces the IP addresses assigned to the tunnel interface.
+    fn set_addresses<'a>(
+        &'a mut self,
+        _current: &'a [IpAddr],
+        _new: &'a [IpAddr],
+    ) -> Pin<Box<dyn Future<Output = std::result::Result<(), TunnelError>> + Send + 'a>> {
+        Box::pin(async { Err(TunnelError::SetAddressesUnsupported) })
+    }
     #[cfg(daita)]
     /// A [`Tunnel`] capable of using DAITA.
     fn start_daita(&mut self) -> std::result::Result<(), TunnelError>;
@@ -1020,6 +1088,14 @@ pub enum TunnelError {
     #[error("Failed to set config of WireGuard tunnel")]
     SetConfigError,
 
+    /// Failed to replace the IP addresses of the tunnel interface
+    #[error("Failed to set the addresses of the WireGuard tunnel")]
+    SetAddressesError,
+
+    /// This tunnel does not support changing its addresses while running.
+    #[error("The tunnel implementation does not support changing its addresses")]
+    SetAddressesUnsupported,
+
     /// Failed to duplicate tunnel file descriptor for wireguard-go
     #[cfg(any(target_os = "linux", target_os = "macos", target_os = "android"))]
     #[error("Failed to duplicate tunnel file descriptor for wireguard-go")]
@@ -1,7 +1,9 @@
 /// Contains bytes sent and received through a tunnel
 #[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Stats {
+    /// Number of bytes sent through the tunnel
     pub tx_bytes: u64,
+    /// Number of bytes received through the tunnel
     pub rx_bytes: u64,
 }
 
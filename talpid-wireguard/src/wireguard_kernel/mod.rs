@@ -77,6 +77,9 @@ pub enum Error {
     #[error("Add IP to device error")]
     NetlinkSetIp(rtnetlink::Error),
 
+    #[error("Remove IP from device error")]
+    NetlinkDeleteIp(rtnetlink::Error),
+
     #[error("Failed to delete device")]
     DeleteDevice(#[source] rtnetlink::Error),
 
@@ -214,6 +217,22 @@ impl Handle {
         Ok(())
     }
 
+    pub async fn delete_ip_address(&mut self, index: u32, addr: IpAddr) -> Result<(), Error> {
+        let address_message = add_ip_addr_message(index, addr);
+        let mut request = NetlinkMessage::from(RtnlMessage::DelAddress(address_message));
+        request.header.flags = NLM_F_REQUEST | NLM_F_ACK;
+
+        let mut response = self
+            .route_handle
+            .request(request)
+            .map_err(Error::NetlinkDeleteIp)?;
+        while let Some(response_message) = response.next().await {
+            consume_netlink_error(response_message, Error::NetlinkDeleteIp)?;
+        }
+
+        Ok(())
+    }
+
     pub async fn delete_device(&mut self, index: u32) -> Result<(), Error> {
         let mut link_message = LinkMessage::default();
         link_message.header.index = index;
@@ -1,4 +1,4 @@
-use std::pin::Pin;
+use std::{net::IpAddr, pin::Pin};
 
 use futures::Future;
 
@@ -130,6 +130,41 @@ impl Tunnel for NetlinkTunnel {
         })
     }
 
+    fn can_set_addresses(&self) -> bool {
+        true
+    }
+
+    fn set_addresses<'a>(
+        &'a mut self,
+        current: &'a [IpAddr],
+        new: &'a [IpAddr],
+    ) -> Pin<Box<dyn Future<Output = std::result::Result<(), TunnelError>> + Send + 'a>> {
+        let interface_index = self.interface_index;
+        Box::pin(async move {
+            // Add the new addresses before removing the old ones so that the interface is never
+            // left without an address.
+            for address in new.iter().filter(|address| !current.contains(address)) {
+                self.netlink_connections
+                    .set_ip_address(interface_index, *address)
+                    .await
+                    .map_err(|err| {
+                        log::error!("Failed to add tunnel address {}: {}", address, err);
+                        TunnelError::SetAddressesError
+                    })?;
+            }
+            for address in current.iter().filter(|address| !new.contains(address)) {
+                self.netlink_connections
+                    .delete_ip_address(interface_index, *address)
+                    .await
+                    .map_err(|err| {
+                        log::error!("Failed to remove tunnel address {}: {}", address, err);
+                        TunnelError::SetAddressesError
+                    })?;
+            }
+            Ok(())
+        })
+    }
+
     /// Outright fail to start - this tunnel type does not support DAITA.
     fn start_daita(&mut self) -> std::result::Result<(), TunnelError> {
         Err(TunnelError::DaitaNotSupported)
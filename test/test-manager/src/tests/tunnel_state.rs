@@ -369,6 +369,7 @@ pub async fn test_connected_state(
                     proxy: None,
                     obfuscation: _,
                     entry_endpoint: None,
+                    ip_version: _,
                     tunnel_interface: _,
                     daita: _,
                 },